@@ -16,6 +16,7 @@ mod entity;
 use entity::Entity;
 
 const NUM_ENTITIES: usize = 250;
+const NUM_PARTICLES: usize = 400;
 
 #[repr(C)]
 #[derive(Default)]
@@ -69,6 +70,7 @@ struct ApplicationData {
     logo_ofs_x: f32,
     logo_ofs_y: f32,
     quad_queue: QuadQueue,
+    particles: ParticleSystem,
     shader_params: Uniform::<ShaderParams>,
     push_params: PushConstants::<PushParams>,
     sprite: Sprite<Explosion>,
@@ -77,6 +79,7 @@ struct ApplicationData {
 impl Disposable for ApplicationData {
     fn dispose(&mut self) {
         self.quad_queue.dispose();
+        self.particles.dispose();
         self.logo.dispose();
     }
 }
@@ -150,6 +153,9 @@ impl ApplicationData {
             entities.push(entity);
         }
 
+        let mut particles = ParticleSystem::new(NUM_PARTICLES);
+        particles.play("fountain", screen_size.0 / 2.0, screen_size.1);
+
         /////
 
         let renderer = crate::api::renderer_mut();
@@ -170,6 +176,7 @@ impl ApplicationData {
             logo_ofs_x: 0.0,
             logo_ofs_y: 0.0,
             quad_queue,
+            particles,
             shader_params,
             push_params,
             sprite
@@ -249,6 +256,8 @@ impl Application for App {
                 );
             }
             quad_queue.end();
+
+            appdata.particles.update(tm.step);
         }
 
         {
@@ -295,6 +304,8 @@ impl Application for App {
 
             let quad_queue = &mut appdata.quad_queue;
             quad_queue.draw();
+
+            appdata.particles.draw();
         }
 
         {
@@ -86,9 +86,6 @@ impl ApplicationData {
             .add_push_constants(&push_params)
             .add_uniform(&shader_params);
 
-        let renderer = crate::api::renderer_mut();
-
-        renderer.generate_sprite_sheet(448, 32, 32, 32);
         let mut sprite: Sprite<SpriteAttributes> = Sprite::default();
         sprite.set_position(0.0, 0.0);
         sprite.set_size(32.0, 32.0);
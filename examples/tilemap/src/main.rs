@@ -80,11 +80,14 @@ struct ApplicationData {
     scroll_pos: Vec2,
     scroll_speed: Vec2,
     background: Background,
+    lighting: Lighting,
+    torch: u32,
     counter: usize
 }
 
 impl Disposable for ApplicationData {
     fn dispose(&mut self) {
+        self.lighting.dispose();
     }
 }
 
@@ -100,14 +103,22 @@ impl ApplicationData {
         let mut scroll_range = Vec2::new(0.0, 0.0);
         let mut scroll_limits = Vec2::new(0.0, 0.0);
 
+        let mut lighting = Lighting::with_default_capacity()?;
+        lighting.set_ambient(&Color::rgb(0.3, 0.3, 0.45));
+
         if let Ok(map) = map.lock() {
             let level = map.get_level(level).unwrap();
             scroll_range.x = level.width as f32;
             scroll_range.y = level.height as f32;
             scroll_limits.x = scroll_range.x - metrics.view_width;
             scroll_limits.y = scroll_range.y - metrics.view_height;
+
+            // terrain casts shadows
+            lighting.add_layer_occluders(level.get_layer(1)?, 0.0, 0.0);
         };
 
+        let torch = lighting.add_light(Light::point(0.0, 0.0, 140.0, &Color::rgb(1.0, 0.85, 0.6)));
+
         let scroll_pos = Vec2::new(0.0, scroll_limits.y);
         let scroll_speed = Vec2::new(0.0, 0.0);
 
@@ -121,6 +132,8 @@ impl ApplicationData {
             scroll_pos,
             scroll_speed,
             background,
+            lighting,
+            torch,
             counter: 0
         })
     }
@@ -233,7 +246,17 @@ impl Application for App {
             appdata.scroll_speed.y = 0.0;
         };
 
+        // torch at the view center, light map is rendered before drawing
+        let metrics = crate::api::metrics();
+        appdata.lighting.set_light_position(
+            appdata.torch,
+            appdata.scroll_pos.x + metrics.view_width * 0.5,
+            appdata.scroll_pos.y + metrics.view_height * 0.5
+        );
 
+        if let Err(e) = appdata.lighting.render(-appdata.scroll_pos.x, -appdata.scroll_pos.y) {
+            error!("failed to render lighting: {e:?}");
+        }
     }
 
     fn on_draw(&mut self) {
@@ -273,6 +296,8 @@ impl Application for App {
             );
         }
 
+        appdata.lighting.draw();
+
         // reset clipping
         renderer.reset_scissor();
     }
@@ -0,0 +1,215 @@
+//!
+//! Aseprite
+//!
+
+/*
+
+Aseprite sprite sheet JSON export, both "hash" and "array" frame layouts.
+
+*/
+
+use serde::{Deserialize, Deserializer, de::{MapAccess, SeqAccess, Visitor}};
+
+#[derive(Default, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(default)]
+pub struct AsepriteRect {
+    pub x: i32,
+    pub y: i32,
+    pub w: u32,
+    pub h: u32
+}
+
+#[derive(Default, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(default)]
+pub struct AsepriteSize {
+    pub w: u32,
+    pub h: u32
+}
+
+#[derive(Default, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(default)]
+pub struct AsepritePoint {
+    pub x: i32,
+    pub y: i32
+}
+
+#[derive(Default, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct AsepriteFrame {
+    pub filename: String,
+    pub frame: AsepriteRect,
+    pub rotated: bool,
+    pub trimmed: bool,
+    #[serde(rename = "spriteSourceSize")]
+    pub sprite_source_size: AsepriteRect,
+    #[serde(rename = "sourceSize")]
+    pub source_size: AsepriteSize,
+    pub duration: u32
+}
+
+/// Frames are exported either as array or as map with the file name as key
+#[derive(Default, Debug, PartialEq)]
+pub struct AsepriteFrames(pub Vec<AsepriteFrame>);
+
+impl<'de> Deserialize<'de> for AsepriteFrames {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+
+        struct FramesVisitor;
+
+        impl<'de> Visitor<'de> for FramesVisitor {
+            type Value = AsepriteFrames;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("frame array or frame map")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut frames = Vec::new();
+                while let Some(frame) = seq.next_element::<AsepriteFrame>()? {
+                    frames.push(frame);
+                }
+                Ok(AsepriteFrames(frames))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                // keep document order, frame indices depend on it
+                let mut frames = Vec::new();
+                while let Some((filename, mut frame)) = map.next_entry::<String, AsepriteFrame>()? {
+                    frame.filename = filename;
+                    frames.push(frame);
+                }
+                Ok(AsepriteFrames(frames))
+            }
+        }
+
+        deserializer.deserialize_any(FramesVisitor)
+    }
+}
+
+/// Tag repeat count, exported as string by Aseprite
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum AsepriteRepeat {
+    Number(u32),
+    Text(String)
+}
+
+impl Default for AsepriteRepeat {
+    fn default() -> Self {
+        AsepriteRepeat::Number(0)
+    }
+}
+
+impl AsepriteRepeat {
+    /// Repeat count, 0 = infinite
+    pub fn count(&self) -> u32 {
+        match self {
+            AsepriteRepeat::Number(n) => *n,
+            AsepriteRepeat::Text(s) => s.trim().parse::<u32>().unwrap_or(0)
+        }
+    }
+}
+
+#[derive(Default, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct AsepriteTag {
+    pub name: String,
+    pub from: u32,
+    pub to: u32,
+    pub direction: String,  // forward, reverse, pingpong, pingpong_reverse
+    pub repeat: AsepriteRepeat
+}
+
+#[derive(Default, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct AsepriteSliceKey {
+    pub frame: u32,
+    pub bounds: AsepriteRect,
+    pub pivot: Option<AsepritePoint>,
+    pub center: Option<AsepriteRect>
+}
+
+#[derive(Default, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct AsepriteSlice {
+    pub name: String,
+    pub keys: Vec<AsepriteSliceKey>
+}
+
+#[derive(Default, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct AsepriteMeta {
+    pub image: String,
+    pub size: AsepriteSize,
+    #[serde(rename = "frameTags")]
+    pub frame_tags: Vec<AsepriteTag>,
+    pub slices: Vec<AsepriteSlice>
+}
+
+#[derive(Default, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct AsepriteJson {
+    pub frames: AsepriteFrames,
+    pub meta: AsepriteMeta
+}
+
+impl AsepriteJson {
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let obj: AsepriteJson = match json5::from_str(json) {
+            Ok(obj) => obj,
+            Err(e) => {
+                return Err(format!("failed to parse aseprite json: {}", e));
+            }
+        };
+
+        if obj.frames.0.iter().any(|frame| frame.rotated) {
+            return Err("rotated aseprite frames are not supported".to_string());
+        }
+
+        Ok(obj)
+    }
+
+    pub fn frames(&self) -> &[AsepriteFrame] {
+        &self.frames.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_layouts() {
+
+        let hash = r#"{
+            "frames": {
+                "b.png": { "frame": { "x": 32, "y": 0, "w": 16, "h": 16 }, "duration": 200 },
+                "a.png": { "frame": { "x": 0, "y": 0, "w": 32, "h": 32 }, "duration": 100 }
+            },
+            "meta": {
+                "size": { "w": 64, "h": 32 },
+                "frameTags": [ { "name": "walk", "from": 0, "to": 1, "direction": "pingpong", "repeat": "2" } ],
+                "slices": [ { "name": "pivot", "keys": [ { "frame": 0, "bounds": { "x": 0, "y": 0, "w": 32, "h": 32 }, "pivot": { "x": 16, "y": 30 } } ] } ]
+            }
+        }"#;
+
+        let obj = AsepriteJson::parse(hash).unwrap();
+        assert_eq!(obj.frames().len(), 2);
+        assert_eq!(obj.frames()[0].filename, "b.png");
+        assert_eq!(obj.frames()[1].duration, 100);
+        assert_eq!(obj.meta.frame_tags[0].repeat.count(), 2);
+        assert_eq!(obj.meta.slices[0].keys[0].pivot, Some(AsepritePoint { x: 16, y: 30 }));
+
+        let array = r#"{
+            "frames": [
+                { "filename": "a.png", "frame": { "x": 0, "y": 0, "w": 32, "h": 32 }, "duration": 100 }
+            ],
+            "meta": { "size": { "w": 32, "h": 32 } }
+        }"#;
+
+        let obj = AsepriteJson::parse(array).unwrap();
+        assert_eq!(obj.frames().len(), 1);
+        assert_eq!(obj.frames()[0].frame.w, 32);
+        assert_eq!(obj.meta.frame_tags.len(), 0);
+    }
+}
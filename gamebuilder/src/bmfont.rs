@@ -0,0 +1,355 @@
+//!
+//! BMFont
+//!
+
+/*
+
+AngelCode BMFont descriptor files (.fnt) in text, XML and binary (version 3) format.
+
+*/
+
+#[derive(Default, Debug, PartialEq, Clone, Copy)]
+pub struct BmFontChar {
+    pub id: u32,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub xoffset: i32,
+    pub yoffset: i32,
+    pub xadvance: i32,
+    pub page: u32
+}
+
+#[derive(Default, Debug, PartialEq, Clone, Copy)]
+pub struct BmFontKerning {
+    pub first: u32,
+    pub second: u32,
+    pub amount: i32
+}
+
+#[derive(Default, Debug, PartialEq)]
+pub struct BmFont {
+    pub face: String,
+    pub size: i32,
+    pub line_height: u32,
+    pub base: u32,
+    pub scale_w: u32,
+    pub scale_h: u32,
+    pub pages: Vec<String>,
+    pub chars: Vec<BmFontChar>,
+    pub kernings: Vec<BmFontKerning>
+}
+
+impl BmFont {
+    /// Parse text, XML or binary format, detected from the content
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+
+        let font = if data.starts_with(b"BMF") {
+            Self::parse_binary(data)?
+        } else {
+            let text = match std::str::from_utf8(data) {
+                Ok(text) => text.trim_start_matches('\u{feff}'),
+                Err(_) => {
+                    return Err("failed to parse bmfont: invalid utf-8".to_string());
+                }
+            };
+
+            if text.trim_start().starts_with('<') {
+                Self::parse_xml(text)?
+            } else {
+                Self::parse_text(text)?
+            }
+        };
+
+        if font.scale_w == 0 || font.scale_h == 0 {
+            return Err("failed to parse bmfont: missing texture size".to_string());
+        }
+
+        if font.chars.iter().any(|c| c.page as usize >= font.pages.len().max(1)) {
+            return Err("failed to parse bmfont: invalid page index".to_string());
+        }
+
+        Ok(font)
+    }
+
+    /// Split "key=value key=\"quoted value\"" into pairs
+    fn attributes(line: &str) -> Vec<(&str, &str)> {
+        let mut attributes = Vec::new();
+        let mut rest = line.trim();
+
+        while let Some(eq) = rest.find('=') {
+            let key = rest[..eq].trim();
+            let value_start = rest[eq + 1..].trim_start();
+
+            let (value, remainder) = if let Some(quoted) = value_start.strip_prefix('"') {
+                let end = quoted.find('"').unwrap_or(quoted.len());
+                (&quoted[..end], &quoted[(end + 1).min(quoted.len())..])
+            } else {
+                let end = value_start.find(char::is_whitespace).unwrap_or(value_start.len());
+                (&value_start[..end], &value_start[end..])
+            };
+
+            // keys are preceded by the previous value or the tag
+            let key = key.rsplit(char::is_whitespace).next().unwrap_or_default();
+            attributes.push((key, value));
+            rest = remainder.trim_start();
+        }
+
+        attributes
+    }
+
+    fn add_element(&mut self, tag: &str, attributes: &[(&str, &str)]) {
+
+        let int = |key: &str| -> i32 {
+            attributes.iter()
+                .find(|(k, _)| *k == key)
+                .and_then(|(_, v)| v.trim().parse::<i32>().ok())
+                .unwrap_or_default()
+        };
+
+        let uint = |key: &str| -> u32 { int(key).max(0) as u32 };
+
+        match tag {
+            "info" => {
+                self.face = attributes.iter().find(|(k, _)| *k == "face").map(|(_, v)| v.to_string()).unwrap_or_default();
+                self.size = int("size");
+            },
+            "common" => {
+                self.line_height = uint("lineHeight");
+                self.base = uint("base");
+                self.scale_w = uint("scaleW");
+                self.scale_h = uint("scaleH");
+            },
+            "page" => {
+                let id = uint("id") as usize;
+                let file = attributes.iter().find(|(k, _)| *k == "file").map(|(_, v)| v.to_string()).unwrap_or_default();
+                if self.pages.len() <= id {
+                    self.pages.resize(id + 1, String::new());
+                }
+                self.pages[id] = file;
+            },
+            "char" => {
+                self.chars.push(BmFontChar {
+                    id: uint("id"),
+                    x: uint("x"),
+                    y: uint("y"),
+                    width: uint("width"),
+                    height: uint("height"),
+                    xoffset: int("xoffset"),
+                    yoffset: int("yoffset"),
+                    xadvance: int("xadvance"),
+                    page: uint("page")
+                });
+            },
+            "kerning" => {
+                self.kernings.push(BmFontKerning {
+                    first: uint("first"),
+                    second: uint("second"),
+                    amount: int("amount")
+                });
+            },
+            _ => {}
+        }
+    }
+
+    fn parse_text(text: &str) -> Result<Self, String> {
+        let mut font = Self::default();
+
+        for line in text.lines() {
+            let line = line.trim();
+            let tag = line.split_whitespace().next().unwrap_or_default();
+            let attributes = Self::attributes(&line[tag.len()..]);
+            font.add_element(tag, &attributes);
+        }
+
+        Ok(font)
+    }
+
+    fn parse_xml(text: &str) -> Result<Self, String> {
+        let mut font = Self::default();
+
+        let mut rest = text;
+        while let Some(start) = rest.find('<') {
+            let end = match rest[start..].find('>') {
+                Some(end) => start + end,
+                None => {
+                    return Err("failed to parse bmfont: unterminated xml element".to_string());
+                }
+            };
+
+            let element = rest[start + 1..end].trim_end_matches('/').trim();
+            rest = &rest[end + 1..];
+
+            if element.starts_with(['?', '!', '/']) {
+                continue;
+            }
+
+            let tag = element.split_whitespace().next().unwrap_or_default();
+            let attributes = Self::attributes(&element[tag.len()..]);
+            font.add_element(tag, &attributes);
+        }
+
+        Ok(font)
+    }
+
+    fn parse_binary(data: &[u8]) -> Result<Self, String> {
+
+        if data.len() < 4 || data[3] != 3 {
+            return Err("failed to parse bmfont: unsupported binary version".to_string());
+        }
+
+        let u16_at = |b: &[u8], i: usize| u16::from_le_bytes([b[i], b[i + 1]]);
+        let i16_at = |b: &[u8], i: usize| i16::from_le_bytes([b[i], b[i + 1]]);
+        let u32_at = |b: &[u8], i: usize| u32::from_le_bytes([b[i], b[i + 1], b[i + 2], b[i + 3]]);
+
+        let c_string = |b: &[u8]| -> String {
+            let end = b.iter().position(|&c| c == 0).unwrap_or(b.len());
+            String::from_utf8_lossy(&b[..end]).into_owned()
+        };
+
+        let mut font = Self::default();
+        let mut pos = 4;
+
+        while pos + 5 <= data.len() {
+            let block_type = data[pos];
+            let block_size = u32_at(data, pos + 1) as usize;
+            pos += 5;
+
+            if pos + block_size > data.len() {
+                return Err("failed to parse bmfont: truncated binary block".to_string());
+            }
+
+            let block = &data[pos..pos + block_size];
+            pos += block_size;
+
+            match block_type {
+                1 if block.len() >= 14 => {
+                    font.size = i16_at(block, 0) as i32;
+                    font.face = c_string(&block[14..]);
+                },
+                2 if block.len() >= 10 => {
+                    font.line_height = u16_at(block, 0) as u32;
+                    font.base = u16_at(block, 2) as u32;
+                    font.scale_w = u16_at(block, 4) as u32;
+                    font.scale_h = u16_at(block, 6) as u32;
+                },
+                3 => {
+                    font.pages = block.split(|&c| c == 0)
+                        .filter(|s| !s.is_empty())
+                        .map(|s| String::from_utf8_lossy(s).into_owned())
+                        .collect();
+                },
+                4 => {
+                    for c in block.chunks_exact(20) {
+                        font.chars.push(BmFontChar {
+                            id: u32_at(c, 0),
+                            x: u16_at(c, 4) as u32,
+                            y: u16_at(c, 6) as u32,
+                            width: u16_at(c, 8) as u32,
+                            height: u16_at(c, 10) as u32,
+                            xoffset: i16_at(c, 12) as i32,
+                            yoffset: i16_at(c, 14) as i32,
+                            xadvance: i16_at(c, 16) as i32,
+                            page: c[18] as u32
+                        });
+                    }
+                },
+                5 => {
+                    for k in block.chunks_exact(10) {
+                        font.kernings.push(BmFontKerning {
+                            first: u32_at(k, 0),
+                            second: u32_at(k, 4),
+                            amount: i16_at(k, 8) as i32
+                        });
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        Ok(font)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formats() {
+
+        let text = r#"info face="Sans Serif" size=16 bold=0
+common lineHeight=18 base=14 scaleW=128 scaleH=64 pages=2 packed=0
+page id=0 file="font_0.png"
+page id=1 file="font_1.png"
+chars count=2
+char id=65 x=0 y=0 width=10 height=12 xoffset=0 yoffset=2 xadvance=11 page=0 chnl=15
+char id=86 x=10 y=0 width=10 height=12 xoffset=-1 yoffset=2 xadvance=10 page=1 chnl=15
+kernings count=1
+kerning first=65 second=86 amount=-2
+"#;
+
+        let font = BmFont::parse(text.as_bytes()).unwrap();
+        assert_eq!(font.face, "Sans Serif");
+        assert_eq!(font.line_height, 18);
+        assert_eq!(font.pages, vec!["font_0.png", "font_1.png"]);
+        assert_eq!(font.chars[1], BmFontChar { id: 86, x: 10, y: 0, width: 10, height: 12, xoffset: -1, yoffset: 2, xadvance: 10, page: 1 });
+        assert_eq!(font.kernings[0], BmFontKerning { first: 65, second: 86, amount: -2 });
+
+        let xml = r#"<?xml version="1.0"?>
+<font>
+  <info face="Sans Serif" size="16"/>
+  <common lineHeight="18" base="14" scaleW="128" scaleH="64" pages="2"/>
+  <pages>
+    <page id="0" file="font_0.png" />
+    <page id="1" file="font_1.png" />
+  </pages>
+  <chars count="2">
+    <char id="65" x="0" y="0" width="10" height="12" xoffset="0" yoffset="2" xadvance="11" page="0" chnl="15" />
+    <char id="86" x="10" y="0" width="10" height="12" xoffset="-1" yoffset="2" xadvance="10" page="1" chnl="15" />
+  </chars>
+  <kernings count="1">
+    <kerning first="65" second="86" amount="-2" />
+  </kernings>
+</font>
+"#;
+
+        assert_eq!(BmFont::parse(xml.as_bytes()).unwrap(), font);
+
+        let mut binary: Vec<u8> = b"BMF\x03".to_vec();
+        let mut block = |block_type: u8, data: &[u8]| {
+            binary.push(block_type);
+            binary.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            binary.extend_from_slice(data);
+        };
+
+        let mut info = vec![16, 0, 0, 0, 100, 0, 1, 0, 0, 0, 0, 1, 1, 0];
+        info.extend_from_slice(b"Sans Serif\0");
+        block(1, &info);
+        block(2, &[18, 0, 14, 0, 128, 0, 64, 0, 2, 0, 0, 0, 0, 0, 0]);
+        block(3, b"font_0.png\0font_1.png\0");
+
+        let mut chars = Vec::new();
+        for (id, x, xoffset, xadvance, page) in [(65u32, 0u16, 0i16, 11i16, 0u8), (86, 10, -1, 10, 1)] {
+            chars.extend_from_slice(&id.to_le_bytes());
+            chars.extend_from_slice(&x.to_le_bytes());
+            chars.extend_from_slice(&0u16.to_le_bytes());
+            chars.extend_from_slice(&10u16.to_le_bytes());
+            chars.extend_from_slice(&12u16.to_le_bytes());
+            chars.extend_from_slice(&xoffset.to_le_bytes());
+            chars.extend_from_slice(&2i16.to_le_bytes());
+            chars.extend_from_slice(&xadvance.to_le_bytes());
+            chars.extend_from_slice(&[page, 15]);
+        }
+        block(4, &chars);
+
+        let mut kernings = Vec::new();
+        kernings.extend_from_slice(&65u32.to_le_bytes());
+        kernings.extend_from_slice(&86u32.to_le_bytes());
+        kernings.extend_from_slice(&(-2i16).to_le_bytes());
+        block(5, &kernings);
+
+        assert_eq!(BmFont::parse(&binary).unwrap(), font);
+    }
+}
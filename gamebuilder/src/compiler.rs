@@ -10,7 +10,10 @@ use std::process::Command;
 
 //use json5;
 
-use crate::manifest::Manifest;
+use crate::aseprite::AsepriteJson;
+use crate::bmfont::BmFont;
+use crate::truetype::{TrueTypeAtlas, TrueTypeOptions};
+use crate::manifest::{FontDescriptor, Manifest, ShaderDescriptor, name_from_path};
 
 const LOG: bool = false;
 
@@ -29,12 +32,15 @@ use gamekit::api::StaticOptionsDescriptor;
 use gamekit::api::StaticDataDescriptor;
 use gamekit::api::StaticBitmapDescriptor;
 use gamekit::api::StaticTextureDescriptor;
+use gamekit::api::StaticNineSliceDescriptor;
 use gamekit::api::StaticFontDescriptor;
+use gamekit::api::StaticSpriteSheetDescriptor;
 use gamekit::api::StaticShaderDescriptor;
 use gamekit::api::StaticMaterialDescriptor;
 use gamekit::api::StaticTaskDescriptor;
 use gamekit::api::StaticSampleDescriptor;
 use gamekit::api::StaticMapDescriptor;
+use gamekit::api::StaticParticleEffectDescriptor;
 
 "#;
 
@@ -44,14 +50,17 @@ pub static DESCRIPTOR_TABLE: &'static ApplicationDescriptorTable = &ApplicationD
     options: OPTIONS_DESCRIPTOR,
     bitmaps: BITMAP_DESCRIPTORS,
     textures: TEXTURE_DESCRIPTORS,
+    nine_slices: NINE_SLICE_DESCRIPTORS,
     fonts: FONT_DESCRIPTORS,
+    sprite_sheets: SPRITE_SHEET_DESCRIPTORS,
     shaders: SHADER_DESCRIPTORS,
     data: DATA_DESCRIPTORS,
     materials: MATERIAL_DESCRIPTORS,
     tasks: TASK_DESCRIPTORS,
     music: MUSIC_DESCRIPTORS,
     samples: SAMPLE_DESCRIPTORS,
-    maps: MAP_DESCRIPTORS
+    maps: MAP_DESCRIPTORS,
+    particle_effects: PARTICLE_EFFECT_DESCRIPTORS
 };
 "#;
 
@@ -129,6 +138,7 @@ struct CompileOptions {
     pub base_path: PathBuf,
     pub out_path: PathBuf,
     pub is_debug: bool,
+    pub debug_assertions: bool,
     pub optimization_level: String,
     pub disable_checks: bool,
     pub use_stdout: bool
@@ -222,6 +232,8 @@ pub fn compile() -> Result<(), CompileError> {
         None => false
     };
 
+    let debug_assertions = env::var_os("CARGO_CFG_DEBUG_ASSERTIONS").is_some();
+
     let opt_level = match env::var_os("OPT_LEVEL") {
         Some(level) => level.into_string().unwrap_or_default(),
         None => String::from("0")
@@ -234,6 +246,7 @@ pub fn compile() -> Result<(), CompileError> {
         base_path,
         out_path,
         is_debug,
+        debug_assertions,
         optimization_level: opt_level,
         disable_checks,
         use_stdout
@@ -287,9 +300,16 @@ pub fn compile() -> Result<(), CompileError> {
     Ok(())
 }
 
+/// Shaders to embed, debug-only shaders are left out without debug assertions
+fn included_shaders<'a>(manifest: &'a Manifest, options: &CompileOptions) -> Vec<&'a ShaderDescriptor> {
+    manifest.shaders.iter()
+        .filter(|shader| options.debug_assertions || !shader.is_debug())
+        .collect()
+}
+
 fn process_manifest(manifest: &Manifest, options: &CompileOptions) -> u8 {
     let shader_path = options.base_path.join("resources").join("shaders");
-    for shader in &manifest.shaders {
+    for shader in included_shaders(manifest, options) {
         let file_path = PathBuf::from(shader.path());
         let file_spec = FileSpec::new(&file_path, &shader_path);
         let compile_spec = CompileSpec::new(file_spec, &options.base_path, &options.out_path);
@@ -328,7 +348,7 @@ fn compile_file(manifest: &Manifest, compile_spec: &CompileSpec, options: &Compi
 
     if compile_spec.src.name == MANIFEST_FILENAME {
         res = compile_manifest(manifest, compile_spec, options);
-    } else if compile_spec.src.extension == "frag" || compile_spec.src.extension == "vert" {
+    } else if compile_spec.src.extension == "frag" || compile_spec.src.extension == "vert" || compile_spec.src.extension == "comp" {
         res = compile_shader(input_file, output_file, options);
     }
 
@@ -360,7 +380,8 @@ fn compile_manifest(manifest: &Manifest, compile_spec: &CompileSpec, options: &C
         manifest_str.push_str(format!("    queue_size: {},\n", o.queue_size).as_str());
         manifest_str.push_str(format!("    headless: {},\n", o.headless).as_str());
         manifest_str.push_str(format!("    enable_validation_layer: {},\n", o.enable_validation_layer).as_str());
-        manifest_str.push_str(format!("    enable_api_dump_layer: {}\n", o.enable_api_dump_layer).as_str());
+        manifest_str.push_str(format!("    enable_api_dump_layer: {},\n", o.enable_api_dump_layer).as_str());
+        manifest_str.push_str(format!("    deferred_render_pass: {}\n", o.deferred_render_pass).as_str());
 
         manifest_str.push_str("};\n");
     }
@@ -384,10 +405,36 @@ fn compile_manifest(manifest: &Manifest, compile_spec: &CompileSpec, options: &C
     }
     manifest_str.push_str("];\n\n");
 
+    // fonts are loaded first, rasterized atlases are added to the textures
+    let mut fonts = Vec::new();
+    for font in manifest.fonts.iter() {
+        match load_font(font, compile_spec, options) {
+            Ok(obj) => fonts.push(obj),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                return 1;
+            }
+        }
+    }
+
     manifest_str.push_str("/// Texture descriptors\n");
     for (idx, texture) in manifest.textures.iter().enumerate() {
         manifest_str.push_str(format!("static TEX_{}: &'static[u8] = gamekit::include_resource!(concat!(env!(\"CARGO_MANIFEST_DIR\"), \"/resources/textures/{}\"));\n", idx, texture.path()).as_str());
     }
+    for (idx, font) in fonts.iter().enumerate() {
+        if let Some(atlas_path) = &font.atlas_path {
+            manifest_str.push_str(format!("static FONT_TEX_{}: &'static[u8] = gamekit::include_resource!(concat!(env!(\"OUT_DIR\"), \"/{}\"));\n", idx, atlas_path).as_str());
+        }
+    }
+
+    // BMFont pages not declared as textures
+    let font_pages: Vec<(usize, usize, &(String, String))> = fonts.iter().enumerate()
+        .flat_map(|(idx, font)| font.page_textures.iter().enumerate().map(move |(page_idx, page)| (idx, page_idx, page)))
+        .filter(|(_, _, (name, _))| !manifest.textures.iter().any(|texture| texture.name() == *name))
+        .collect();
+    for (idx, page_idx, (_, path)) in &font_pages {
+        manifest_str.push_str(format!("static FONT_PAGE_{}_{}: &'static[u8] = gamekit::include_resource!(concat!(env!(\"CARGO_MANIFEST_DIR\"), \"/resources/textures/{}\"));\n", idx, page_idx, path).as_str());
+    }
     manifest_str.push_str("static TEXTURE_DESCRIPTORS: &'static [StaticTextureDescriptor] = &[\n");
     for (idx, texture) in manifest.textures.iter().enumerate() {
         let abs_path = Path::new(&compile_spec.src.dir_path).join("resources/textures").join(texture.path());
@@ -399,12 +446,113 @@ fn compile_manifest(manifest: &Manifest, compile_spec: &CompileSpec, options: &C
         let format = if ext == "bin" { "charmem" } else { "bitmap" };
         manifest_str.push_str(format!("    StaticTextureDescriptor::new(\"{}\", TEX_{}, \"{}\"),\n", texture.name(), idx, format).as_str());
     }
+    for (idx, font) in fonts.iter().enumerate() {
+        if font.atlas_path.is_some() {
+            manifest_str.push_str(format!("    StaticTextureDescriptor::new(\"{}\", FONT_TEX_{}, \"rgba\"),\n", font.texture, idx).as_str());
+        }
+    }
+    for (idx, page_idx, (name, path)) in &font_pages {
+        let abs_path = Path::new(&compile_spec.src.dir_path).join("resources/textures").join(path);
+        if !options.disable_checks && !abs_path.is_file() {
+            eprintln!("error: font page file does not exist: {}", abs_path.to_str().unwrap());
+            return 1;
+        }
+        manifest_str.push_str(format!("    StaticTextureDescriptor::new(\"{}\", FONT_PAGE_{}_{}, \"bitmap\"),\n", name, idx, page_idx).as_str());
+    }
+    manifest_str.push_str("];\n\n");
+
+    manifest_str.push_str("/// Nine-slice descriptors\n");
+    manifest_str.push_str("static NINE_SLICE_DESCRIPTORS: &'static [StaticNineSliceDescriptor] = &[\n");
+    for texture in manifest.textures.iter() {
+        for s in texture.slices() {
+            manifest_str.push_str(format!("    StaticNineSliceDescriptor::new(\"{}\", \"{}\", {}, {}, {}, {}, {}, {}, {}, {}, \"{}\", \"{}\"),\n",
+                s.name, texture.name(), s.x, s.y, s.w, s.h, s.left, s.top, s.right, s.bottom, s.edge_mode, s.center_mode).as_str());
+        }
+    }
     manifest_str.push_str("];\n\n");
 
     manifest_str.push_str("/// Font descriptors\n");
     manifest_str.push_str("static FONT_DESCRIPTORS: &'static [StaticFontDescriptor] = &[\n");
-    for font in manifest.fonts.iter() {
-        manifest_str.push_str(format!("    StaticFontDescriptor::new(\"{}\", r##\"{}\"##, {}, {}, \"{}\"),\n", font.name(), font.charset(), font.char_width(), font.char_height(), font.texture()).as_str());
+    for (font, obj) in manifest.fonts.iter().zip(fonts.iter()) {
+
+        let bmfont = &obj.metrics;
+        let pages = &obj.pages;
+        let texture = &obj.texture;
+
+        manifest_str.push_str(format!("    StaticFontDescriptor::new(\"{}\", r##\"{}\"##, {}, {}, \"{}\", {}, {}, {}, {}, &[",
+            font.name(), font.charset(), font.char_width(), font.char_height(), texture,
+            bmfont.line_height, bmfont.base, bmfont.scale_w, bmfont.scale_h).as_str());
+        for page in pages {
+            manifest_str.push_str(format!("\"{}\", ", page).as_str());
+        }
+        manifest_str.push_str("], &[\n");
+        for c in &bmfont.chars {
+            manifest_str.push_str(format!("        gamekit::api::StaticGlyphDescriptor::new({}, {}, {}, {}, {}, {}, {}, {}, {}),\n",
+                c.id, c.x, c.y, c.width, c.height, c.xoffset, c.yoffset, c.xadvance, c.page).as_str());
+        }
+        manifest_str.push_str("    ], &[\n");
+        for k in &bmfont.kernings {
+            manifest_str.push_str(format!("        gamekit::api::StaticKerningDescriptor::new({}, {}, {}),\n",
+                k.first, k.second, k.amount).as_str());
+        }
+        manifest_str.push_str(format!("    ], r##\"{}\"##),\n", font.fallback()).as_str());
+    }
+    manifest_str.push_str("];\n\n");
+
+    manifest_str.push_str("/// Sprite sheet descriptors\n");
+    manifest_str.push_str("static SPRITE_SHEET_DESCRIPTORS: &'static [StaticSpriteSheetDescriptor] = &[\n");
+    for sheet in manifest.sprite_sheets.iter() {
+
+        let mut aseprite = AsepriteJson::default();
+
+        if !sheet.path.is_empty() {
+            let abs_path = Path::new(&compile_spec.src.dir_path).join("resources/textures").join(&sheet.path);
+            println!("cargo:rerun-if-changed={}", abs_path.to_str().unwrap());
+
+            if !abs_path.is_file() {
+                if !options.disable_checks {
+                    eprintln!("error: sprite sheet file does not exist: {}", abs_path.to_str().unwrap());
+                    return 1;
+                }
+            } else {
+                let json = fs::read_to_string(&abs_path).unwrap_or_default();
+                aseprite = match AsepriteJson::parse(&json) {
+                    Ok(obj) => obj,
+                    Err(e) => {
+                        eprintln!("error: {}: {}", abs_path.to_str().unwrap(), e);
+                        return 1;
+                    }
+                };
+            }
+        }
+
+        manifest_str.push_str(format!("    StaticSpriteSheetDescriptor::new(\"{}\", \"{}\", {}, {}, &[\n", sheet.name(), sheet.texture, sheet.tile_width, sheet.tile_height).as_str());
+        for f in aseprite.frames() {
+            manifest_str.push_str(format!("        gamekit::api::StaticSpriteFrameDescriptor::new(r##\"{}\"##, {}, {}, {}, {}, {}, {}, {}, {}, {}),\n",
+                f.filename, f.frame.x, f.frame.y, f.frame.w, f.frame.h, f.sprite_source_size.x, f.sprite_source_size.y, f.source_size.w, f.source_size.h, f.duration).as_str());
+        }
+        for f in &sheet.frames {
+            manifest_str.push_str(format!("        gamekit::api::StaticSpriteFrameDescriptor::new(r##\"{}\"##, {}, {}, {}, {}, {}, {}, {}, {}, {}),\n",
+                f.name, f.x, f.y, f.w, f.h, f.offset_x, f.offset_y, f.source_width, f.source_height, f.duration).as_str());
+        }
+        manifest_str.push_str("    ], &[\n");
+        for t in &aseprite.meta.frame_tags {
+            manifest_str.push_str(format!("        gamekit::api::StaticSpriteTagDescriptor::new(r##\"{}\"##, {}, {}, \"{}\", {}),\n",
+                t.name, t.from, t.to, t.direction, t.repeat.count()).as_str());
+        }
+        for t in &sheet.tags {
+            manifest_str.push_str(format!("        gamekit::api::StaticSpriteTagDescriptor::new(r##\"{}\"##, {}, {}, \"{}\", {}),\n",
+                t.name, t.from, t.to, t.direction, t.repeat).as_str());
+        }
+        manifest_str.push_str("    ], &[\n");
+        for slice in &aseprite.meta.slices {
+            for key in &slice.keys {
+                let pivot = key.pivot.unwrap_or_default();
+                manifest_str.push_str(format!("        gamekit::api::StaticSpriteSliceDescriptor::new(r##\"{}\"##, {}, {}, {}, {}, {}, {}, {}, {}),\n",
+                    slice.name, key.frame, key.bounds.x, key.bounds.y, key.bounds.w, key.bounds.h, key.pivot.is_some(), pivot.x, pivot.y).as_str());
+            }
+        }
+        manifest_str.push_str("    ]),\n");
     }
     manifest_str.push_str("];\n\n");
 
@@ -424,18 +572,19 @@ fn compile_manifest(manifest: &Manifest, compile_spec: &CompileSpec, options: &C
     manifest_str.push_str("];\n\n");
 
     manifest_str.push_str("/// Shader descriptors\n");
-    for (idx, shader) in manifest.shaders.iter().enumerate() {
+    let shaders = included_shaders(manifest, options);
+    for (idx, shader) in shaders.iter().enumerate() {
         manifest_str.push_str(format!("static SHD_{}: &'static[u8] = gamekit::include_resource!(concat!(env!(\"OUT_DIR\"), \"/resources/shaders/{}\"));\n", idx, shader.path()).as_str());
     }
     manifest_str.push_str("static SHADER_DESCRIPTORS: &'static [StaticShaderDescriptor] = &[\n");
-    for (idx, shader) in manifest.shaders.iter().enumerate() {
+    for (idx, shader) in shaders.iter().enumerate() {
         let abs_path = Path::new(&compile_spec.src.dir_path).join("resources/shaders").join(shader.path());
         if !options.disable_checks && !abs_path.is_file() {
             eprintln!("error: shader file does not exist: {}", abs_path.to_str().unwrap());
             return 1;
         }
         let ext = abs_path.extension().unwrap();
-        let format = if ext == "vert" { "vertex" } else { "fragment" };
+        let format = if ext == "vert" { "vertex" } else if ext == "comp" { "compute" } else { "fragment" };
         manifest_str.push_str(format!("    StaticShaderDescriptor::new(\"{}\", SHD_{}, \"{}\"),\n", shader.name(), idx, format).as_str());
     }
     manifest_str.push_str("];\n\n");
@@ -447,8 +596,8 @@ fn compile_manifest(manifest: &Manifest, compile_spec: &CompileSpec, options: &C
     for m in &manifest.materials {
         manifest_str.push_str("    StaticMaterialDescriptor::new(");
         manifest_str.push_str(format!(
-            "\"{}\", \"{}\", \"{}\", {}, {}, \"{}\", \"{}\", \"{}\", {}, \"{}\", {}, {}, {}, {}, \"{}\"",
-            m.name, m.font, m.texture, m.texture_binding, m.texture_filtering, m.vertex_shader, m.fragment_shader, m.shader_input_type, m.blending, m.blend_mode, m.backface_culling, m.frontface_clockwise, m.depth_testing, m.depth_writing, m.topology
+            "\"{}\", \"{}\", \"{}\", \"{}\", {}, {}, \"{}\", \"{}\", \"{}\", {}, \"{}\", {}, {}, {}, {}, \"{}\", {:?}",
+            m.name, m.font, m.texture, m.sprite_sheet, m.texture_binding, m.texture_filtering, m.vertex_shader, m.fragment_shader, m.shader_input_type, m.blending, m.blend_mode, m.backface_culling, m.frontface_clockwise, m.depth_testing, m.depth_writing, m.topology, m.line_width
         ).as_str());
         manifest_str.push_str("    ),\n");
     }
@@ -510,6 +659,38 @@ fn compile_manifest(manifest: &Manifest, compile_spec: &CompileSpec, options: &C
     manifest_str.push_str("/// Map descriptors\n");
     for (idx, map) in manifest.maps.iter().enumerate() {
         manifest_str.push_str(format!("static MAP_{}: &'static[u8] = gamekit::include_resource!(concat!(env!(\"CARGO_MANIFEST_DIR\"), \"/resources/maps/{}\"));\n", idx, map.path()).as_str());
+
+        // levels and tilesets saved to separate files are embedded as well
+        let abs_path = Path::new(&compile_spec.src.dir_path).join("resources/maps").join(map.path());
+        let level_paths = if abs_path.is_file() {
+            let external_files = if map.is_tiled() { find_external_tilesets(&abs_path) } else { find_external_levels(&abs_path) };
+            match external_files {
+                Ok(level_paths) => level_paths,
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    return 1;
+                }
+            }
+        } else {
+            Vec::new()
+        };
+
+        let map_dir = Path::new(map.path()).parent().unwrap_or(Path::new(""));
+        for (level_idx, level_path) in level_paths.iter().enumerate() {
+            let resource_path = map_dir.join(level_path).to_str().unwrap().replace('\\', "/");
+            let abs_level_path = Path::new(&compile_spec.src.dir_path).join("resources/maps").join(&resource_path);
+            if !options.disable_checks && !abs_level_path.is_file() {
+                eprintln!("error: map level file does not exist: {}", abs_level_path.to_str().unwrap());
+                return 1;
+            }
+            manifest_str.push_str(format!("static MAP_{}_LEVEL_{}: &'static[u8] = gamekit::include_resource!(concat!(env!(\"CARGO_MANIFEST_DIR\"), \"/resources/maps/{}\"));\n", idx, level_idx, resource_path).as_str());
+        }
+
+        manifest_str.push_str(format!("static MAP_{}_LEVELS: &'static [gamekit::api::StaticMapLevelDescriptor] = &[\n", idx).as_str());
+        for (level_idx, level_path) in level_paths.iter().enumerate() {
+            manifest_str.push_str(format!("    gamekit::api::StaticMapLevelDescriptor::new(\"{}\", MAP_{}_LEVEL_{}),\n", level_path, idx, level_idx).as_str());
+        }
+        manifest_str.push_str("];\n");
     }
     manifest_str.push_str("static MAP_DESCRIPTORS: &'static [StaticMapDescriptor] = &[\n");
     for (idx, map) in manifest.maps.iter().enumerate() {
@@ -518,11 +699,31 @@ fn compile_manifest(manifest: &Manifest, compile_spec: &CompileSpec, options: &C
             eprintln!("error: map file does not exist: {}", abs_path.to_str().unwrap());
             return 1;
         }
-        manifest_str.push_str(format!("    StaticMapDescriptor::new(\"{}\", MAP_{}, \"{}\", &[{}]),\n", 
+        let format = if map.is_tiled() { "tiled" } else { "ldtk" };
+        manifest_str.push_str(format!("    StaticMapDescriptor::new(\"{}\", MAP_{}, \"{}\", \"{}\", \"{}\", &[{}], MAP_{}_LEVELS),\n", 
             map.name(),
             idx,
+            format,
             map.material,
-            map.uncompressed_layers.iter().map(|s| format!("\"{}\"", s)).collect::<Vec<_>>().join(", ")
+            map.normal_material,
+            map.uncompressed_layers.iter().map(|s| format!("\"{}\"", s)).collect::<Vec<_>>().join(", "),
+            idx
+        ).as_str());
+    }
+    manifest_str.push_str("];\n");
+
+
+    manifest_str.push_str("/// Particle effect descriptors\n");
+    manifest_str.push_str("static PARTICLE_EFFECT_DESCRIPTORS: &'static [StaticParticleEffectDescriptor] = &[\n");
+    for p in &manifest.particles {
+        manifest_str.push_str(format!(
+            "    StaticParticleEffectDescriptor::new(\"{}\", \"{}\", {:?}, {:?}, {:?}, {:?}, {}, {:?}, {}, {:?}, {:?}, {:?}, {:?}, {:?}, {:?}, {:?}, {:?}, {:?}, &[{}], &{:?}, &{:?}, {:?}, {:?}, {:?}, {:?}, {}, {}),\n",
+            p.name, p.shape, p.width, p.height, p.radius, p.rate, p.burst, p.duration, p.looping,
+            p.lifetime_min, p.lifetime_max, p.speed_min, p.speed_max, p.direction, p.spread,
+            p.gravity_x, p.gravity_y, p.drag,
+            p.colors.iter().map(|s| format!("\"{}\"", s)).collect::<Vec<_>>().join(", "),
+            p.sizes, p.velocities,
+            p.texture_x, p.texture_y, p.texture_w, p.texture_h, p.texture_mask, p.flags
         ).as_str());
     }
     manifest_str.push_str("];\n");
@@ -539,6 +740,145 @@ fn compile_manifest(manifest: &Manifest, compile_spec: &CompileSpec, options: &C
     0u8
 }
 
+struct CompiledFont {
+    metrics: BmFont,
+    pages: Vec<String>,
+    texture: String,
+    atlas_path: Option<String>,             // generated atlas, relative to the output dir
+    page_textures: Vec<(String, String)>    // embedded page textures (name, path in resources/textures)
+}
+
+fn load_font(font: &FontDescriptor, compile_spec: &CompileSpec, options: &CompileOptions) -> Result<CompiledFont, String> {
+
+    let mut compiled = CompiledFont {
+        metrics: BmFont::default(),
+        pages: font.pages().to_vec(),
+        texture: font.texture().to_owned(),
+        atlas_path: None,
+        page_textures: Vec::new()
+    };
+
+    if font.path().is_empty() {
+        return Ok(compiled);
+    }
+
+    let abs_path = Path::new(&compile_spec.src.dir_path).join("resources/textures").join(font.path());
+    println!("cargo:rerun-if-changed={}", abs_path.to_str().unwrap());
+
+    if !abs_path.is_file() {
+        if !options.disable_checks {
+            return Err(format!("font file does not exist: {}", abs_path.to_str().unwrap()));
+        }
+        return Ok(compiled);
+    }
+
+    let data = fs::read(&abs_path).unwrap_or_default();
+
+    if font.is_truetype() {
+        let truetype_options = TrueTypeOptions {
+            size: font.size(),
+            chars: TrueTypeOptions::parse_chars(font.ranges(), font.charset())
+                .map_err(|e| format!("{}: {}", abs_path.to_str().unwrap(), e))?,
+            outline: font.outline(),
+            shadow_x: font.shadow().0,
+            shadow_y: font.shadow().1
+        };
+
+        let atlas = TrueTypeAtlas::rasterize(&data, &truetype_options)
+            .map_err(|e| format!("{}: {}", abs_path.to_str().unwrap(), e))?;
+
+        // the atlas texture is named after the font unless given
+        if compiled.texture.is_empty() {
+            compiled.texture = font.name();
+        }
+
+        let atlas_path = format!("fonts/{}.rgba", font.name());
+        let out_file = options.out_path.join(&atlas_path);
+        check_output_dir(out_file.parent().unwrap());
+        if let Err(e) = fs::write(&out_file, atlas.to_rgba()) {
+            return Err(format!("{}: {}", out_file.to_str().unwrap(), e));
+        }
+
+        compiled.pages = vec![compiled.texture.clone()];
+        compiled.metrics = atlas.font;
+        compiled.atlas_path = Some(atlas_path);
+
+    } else {
+        compiled.metrics = BmFont::parse(&data).map_err(|e| format!("{}: {}", abs_path.to_str().unwrap(), e))?;
+
+        // page files are embedded as textures named after the files, unless
+        // the page textures are given
+        if compiled.pages.is_empty() {
+            let font_dir = Path::new(font.path()).parent().unwrap_or(Path::new(""));
+            for page in &compiled.metrics.pages {
+                let name = name_from_path("", page);
+                let page_path = font_dir.join(page).to_str().unwrap().replace('\\', "/");
+                compiled.pages.push(name.clone());
+                compiled.page_textures.push((name, page_path));
+            }
+        }
+
+        if compiled.texture.is_empty() {
+            compiled.texture = compiled.pages.first().cloned().unwrap_or_default();
+        }
+    }
+
+    Ok(compiled)
+}
+
+/// Level files of an LDtk project saved with "separate level files"
+fn find_external_levels(abs_path: &Path) -> Result<Vec<String>, String> {
+
+    let data = fs::read(abs_path).map_err(|e| format!("{}: {}", abs_path.to_str().unwrap(), e))?;
+    let project: serde_json::Value = serde_json::from_slice(&data)
+        .map_err(|e| format!("{}: {}", abs_path.to_str().unwrap(), e))?;
+
+    let mut levels: Vec<&serde_json::Value> = Vec::new();
+
+    if let Some(project_levels) = project["levels"].as_array() {
+        levels.extend(project_levels);
+    }
+
+    if let Some(worlds) = project["worlds"].as_array() {
+        for world in worlds {
+            if let Some(world_levels) = world["levels"].as_array() {
+                levels.extend(world_levels);
+            }
+        }
+    }
+
+    Ok(levels.iter()
+        .filter_map(|level| level["externalRelPath"].as_str())
+        .map(|path| path.to_owned())
+        .collect())
+}
+
+/// Tilesets of a Tiled map saved to separate files (.tsx, .tsj)
+fn find_external_tilesets(abs_path: &Path) -> Result<Vec<String>, String> {
+
+    let data = fs::read(abs_path).map_err(|e| format!("{}: {}", abs_path.to_str().unwrap(), e))?;
+    let text = String::from_utf8_lossy(&data);
+
+    if text.trim_start().starts_with('<') {
+        let document = roxmltree::Document::parse(&text)
+            .map_err(|e| format!("{}: {}", abs_path.to_str().unwrap(), e))?;
+
+        return Ok(document.root_element().children()
+            .filter(|node| node.has_tag_name("tileset"))
+            .filter_map(|node| node.attribute("source"))
+            .map(|path| path.to_owned())
+            .collect());
+    }
+
+    let map: serde_json::Value = serde_json::from_str(&text)
+        .map_err(|e| format!("{}: {}", abs_path.to_str().unwrap(), e))?;
+
+    Ok(map["tilesets"].as_array().map(Vec::as_slice).unwrap_or_default().iter()
+        .filter_map(|tileset| tileset["source"].as_str())
+        .map(|path| path.to_owned())
+        .collect())
+}
+
 fn check_output_dir(out_dir: &Path) {
     //println!("create output directory: {}", out_dir.to_str().unwrap());
     let _ = fs::create_dir_all(out_dir);
@@ -610,4 +950,120 @@ mod tests {
 
         let _ = compile();
     }
+
+    #[test]
+    fn test_load_font() {
+
+        let base_path = env::temp_dir().join("gamebuilder_test_load_font");
+        let out_path = base_path.join("out");
+        fs::create_dir_all(base_path.join("resources/textures")).unwrap();
+
+        let spec = CompileSpec::from_path(Path::new(MANIFEST_FILENAME), &base_path, &out_path);
+        let options = CompileOptions {
+            base_path: base_path.clone(),
+            out_path: out_path.clone(),
+            is_debug: true,
+            debug_assertions: true,
+            optimization_level: "0".to_owned(),
+            disable_checks: false,
+            use_stdout: true
+        };
+
+        let single_page = "common lineHeight=18 base=14 scaleW=128 scaleH=64 pages=1\n\
+            page id=0 file=\"sans_0.png\"\n\
+            char id=65 x=0 y=0 width=10 height=12 xoffset=0 yoffset=2 xadvance=11 page=0\n";
+        fs::write(base_path.join("resources/textures/sans.fnt"), single_page).unwrap();
+
+        let font: FontDescriptor = serde_json::from_str(r#"{ "name": "sans", "path": "sans.fnt" }"#).unwrap();
+        let compiled = load_font(&font, &spec, &options).unwrap();
+        assert_eq!(compiled.pages, vec!["sans_0"]);
+        assert_eq!(compiled.texture, "sans_0");
+        assert_eq!(compiled.metrics.chars.len(), 1);
+
+        assert_eq!(compiled.page_textures, vec![("sans_0".to_owned(), "sans_0.png".to_owned())]);
+
+        // every page becomes a texture, relative to the font file
+        let two_pages = "common lineHeight=18 base=14 scaleW=128 scaleH=64 pages=2\n\
+            page id=0 file=\"sans_0.png\"\n\
+            page id=1 file=\"sans_1.png\"\n\
+            char id=65 x=0 y=0 width=10 height=12 xoffset=0 yoffset=2 xadvance=11 page=1\n";
+        fs::create_dir_all(base_path.join("resources/textures/fonts")).unwrap();
+        fs::write(base_path.join("resources/textures/fonts/sans.fnt"), two_pages).unwrap();
+
+        let font: FontDescriptor = serde_json::from_str(r#"{ "name": "sans", "path": "fonts/sans.fnt" }"#).unwrap();
+        let compiled = load_font(&font, &spec, &options).unwrap();
+        assert_eq!(compiled.pages, vec!["sans_0", "sans_1"]);
+        assert_eq!(compiled.texture, "sans_0");
+        assert_eq!(compiled.page_textures, vec![
+            ("sans_0".to_owned(), "fonts/sans_0.png".to_owned()),
+            ("sans_1".to_owned(), "fonts/sans_1.png".to_owned())
+        ]);
+        assert_eq!(compiled.metrics.chars[0].page, 1);
+
+        // given page textures are declared by the manifest
+        let font: FontDescriptor = serde_json::from_str(r#"{ "name": "sans", "path": "fonts/sans.fnt", "pages": ["a", "b"] }"#).unwrap();
+        let compiled = load_font(&font, &spec, &options).unwrap();
+        assert_eq!(compiled.pages, vec!["a", "b"]);
+        assert!(compiled.page_textures.is_empty());
+
+        fs::remove_dir_all(&base_path).unwrap();
+    }
+
+    #[test]
+    fn test_included_shaders() {
+
+        let manifest: Manifest = json5::from_str(r#"{
+            shaders: [
+                { name: "sprite", path: "sprite.vert" },
+                { name: "debug", path: "debug.vert", debug: true }
+            ]
+        }"#).unwrap();
+
+        let mut options = CompileOptions {
+            base_path: PathBuf::new(),
+            out_path: PathBuf::new(),
+            is_debug: false,
+            debug_assertions: true,
+            optimization_level: "3".to_owned(),
+            disable_checks: true,
+            use_stdout: true
+        };
+
+        let names = |options: &CompileOptions| included_shaders(&manifest, options).iter().map(|shader| shader.name()).collect::<Vec<_>>();
+
+        assert_eq!(names(&options), vec!["sprite", "debug"]);
+
+        options.debug_assertions = false;
+        assert_eq!(names(&options), vec!["sprite"]);
+    }
+
+    #[test]
+    fn test_find_external_levels() {
+
+        let base_path = env::temp_dir().join("gamebuilder_test_external_levels");
+        fs::create_dir_all(&base_path).unwrap();
+
+        let project = r#"{
+            "levels": [
+                { "identifier": "Level_0", "externalRelPath": "world/Level_0.ldtkl" },
+                { "identifier": "Level_1", "externalRelPath": null }
+            ],
+            "worlds": [
+                { "levels": [ { "identifier": "Level_2", "externalRelPath": "world/Level_2.ldtkl" } ] }
+            ]
+        }"#;
+
+        let project_path = base_path.join("world.ldtk");
+        fs::write(&project_path, project).unwrap();
+
+        let levels = find_external_levels(&project_path).unwrap();
+        assert_eq!(levels, vec!["world/Level_0.ldtkl", "world/Level_2.ldtkl"]);
+
+        assert!(find_external_levels(&base_path.join("missing.ldtk")).is_err());
+
+        fs::write(&project_path, "{ \"levels\": [").unwrap();
+        assert!(find_external_levels(&project_path).is_err());
+
+        fs::remove_dir_all(&base_path).unwrap();
+    }
 }
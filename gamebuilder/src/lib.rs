@@ -38,6 +38,9 @@
 
 mod compiler;
 pub mod manifest;
+pub mod aseprite;
+pub mod bmfont;
+pub mod truetype;
 
 use std::process::ExitCode;
 use std::env;
@@ -9,7 +9,7 @@ Note: include the generated files like this:
 
 */
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use serde::Deserialize;
 
 pub fn name_from_path(name: &str, path: &str) -> String {
@@ -39,6 +39,10 @@ fn default_title() -> String { "gamekit".to_string() }
 fn default_validation_layer() -> bool{ false }
 fn default_api_dump_layer() -> bool { false }
 fn default_topology() -> String { "trianglelist".to_string() }
+fn default_line_width() -> f32 { 1.0 }
+fn default_lifetime() -> f32 { 1.0 }
+fn default_spread() -> f32 { 360.0 }
+fn default_font_size() -> f32 { 16.0 }
 
 #[derive(Default, Deserialize, Debug, PartialEq)]
 #[serde(default, rename = "options", deny_unknown_fields)]
@@ -76,7 +80,9 @@ pub struct OptionsDescriptor {
     pub enable_validation_layer: bool,
 
     #[serde(default = "default_api_dump_layer")]
-    pub enable_api_dump_layer: bool
+    pub enable_api_dump_layer: bool,
+
+    pub deferred_render_pass: bool
 }
 
 #[derive(Default, Deserialize, Debug, PartialEq)]
@@ -94,7 +100,8 @@ pub struct StaticOptionsDescriptor {
     pub queue_size: usize,
     pub headless: bool,
     pub enable_validation_layer: bool,
-    pub enable_api_dump_layer: bool
+    pub enable_api_dump_layer: bool,
+    pub deferred_render_pass: bool
 }
 
 #[derive(Default, Deserialize, Debug, PartialEq)]
@@ -130,7 +137,8 @@ impl StaticDataDescriptor {
 #[serde(default, rename = "texture", deny_unknown_fields)]
 pub struct TextureDescriptor {
     name: String,
-    path: String
+    path: String,
+    slices: Vec<NineSliceDescriptor>
 }
 
 impl TextureDescriptor {
@@ -141,6 +149,10 @@ impl TextureDescriptor {
     pub fn path(&self) -> &str {
         &self.path
     }
+
+    pub fn slices(&self) -> &[NineSliceDescriptor] {
+        &self.slices
+    }
 }
 
 pub struct StaticTextureDescriptor {
@@ -156,11 +168,177 @@ impl StaticTextureDescriptor {
     }
 }
 
+#[derive(Default, Deserialize, Debug, PartialEq)]
+#[serde(default, rename = "slice", deny_unknown_fields)]
+pub struct NineSliceDescriptor {
+    pub name: String,
+    pub x: u32,             // texture region, w/h 0 = whole texture
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+    pub left: u32,          // insets (pixels)
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+    pub edge_mode: String,  // stretch (default), tile
+    pub center_mode: String
+}
+
+pub struct StaticNineSliceDescriptor {
+    pub name: &'static str,
+    pub texture: &'static str,
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+    pub left: u32,
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+    pub edge_mode: &'static str,
+    pub center_mode: &'static str
+}
+
+#[allow(clippy::too_many_arguments)]
+impl StaticNineSliceDescriptor {
+    pub const fn new(name: &'static str, texture: &'static str, x: u32, y: u32, w: u32, h: u32, left: u32, top: u32, right: u32, bottom: u32, edge_mode: &'static str, center_mode: &'static str) -> Self {
+        Self { name, texture, x, y, w, h, left, top, right, bottom, edge_mode, center_mode }
+    }
+}
+
+#[derive(Default, Deserialize, Debug, PartialEq)]
+#[serde(default, rename = "frame", deny_unknown_fields)]
+pub struct SpriteFrameDescriptor {
+    pub name: String,
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+    pub offset_x: u32,      // trim offset within the source frame
+    pub offset_y: u32,
+    pub source_width: u32,  // untrimmed frame size, 0 = same as w/h
+    pub source_height: u32,
+    pub duration: u32       // milliseconds, 0 = default
+}
+
+pub struct StaticSpriteFrameDescriptor {
+    pub name: &'static str,
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+    pub offset_x: u32,
+    pub offset_y: u32,
+    pub source_width: u32,
+    pub source_height: u32,
+    pub duration: u32
+}
+
+#[allow(clippy::too_many_arguments)]
+impl StaticSpriteFrameDescriptor {
+    pub const fn new(name: &'static str, x: u32, y: u32, w: u32, h: u32, offset_x: u32, offset_y: u32, source_width: u32, source_height: u32, duration: u32) -> Self {
+        Self { name, x, y, w, h, offset_x, offset_y, source_width, source_height, duration }
+    }
+}
+
+#[derive(Default, Deserialize, Debug, PartialEq)]
+#[serde(default, rename = "tag", deny_unknown_fields)]
+pub struct SpriteTagDescriptor {
+    pub name: String,
+    pub from: u32,
+    pub to: u32,
+    pub direction: String,  // forward, reverse, pingpong, pingpong_reverse
+    pub repeat: u32         // 0 = infinite
+}
+
+pub struct StaticSpriteTagDescriptor {
+    pub name: &'static str,
+    pub from: u32,
+    pub to: u32,
+    pub direction: &'static str,
+    pub repeat: u32
+}
+
+impl StaticSpriteTagDescriptor {
+    pub const fn new(name: &'static str, from: u32, to: u32, direction: &'static str, repeat: u32) -> Self {
+        Self { name, from, to, direction, repeat }
+    }
+}
+
+pub struct StaticSpriteSliceDescriptor {
+    pub name: &'static str,
+    pub frame: u32,
+    pub x: i32,
+    pub y: i32,
+    pub w: u32,
+    pub h: u32,
+    pub has_pivot: bool,
+    pub pivot_x: i32,
+    pub pivot_y: i32
+}
+
+#[allow(clippy::too_many_arguments)]
+impl StaticSpriteSliceDescriptor {
+    pub const fn new(name: &'static str, frame: u32, x: i32, y: i32, w: u32, h: u32, has_pivot: bool, pivot_x: i32, pivot_y: i32) -> Self {
+        Self { name, frame, x, y, w, h, has_pivot, pivot_x, pivot_y }
+    }
+}
+
+#[derive(Default, Deserialize, Debug, PartialEq)]
+#[serde(default, rename = "sprite_sheet", deny_unknown_fields)]
+pub struct SpriteSheetDescriptor {
+    pub name: String,
+    pub path: String,       // optional aseprite json export (resources/textures)
+    pub texture: String,
+    pub tile_width: u32,    // optional uniform grid, 0 = no grid
+    pub tile_height: u32,
+    pub frames: Vec<SpriteFrameDescriptor>,
+    pub tags: Vec<SpriteTagDescriptor>
+}
+
+impl SpriteSheetDescriptor {
+    pub fn name(&self) -> String {
+        if !self.name.is_empty() || self.path.is_empty() {
+            return self.name.to_owned();
+        }
+
+        // strip double extensions such as "sheet.aseprite.json"
+        let name = name_from_path("", &self.path);
+        name.strip_suffix(".aseprite").or(name.strip_suffix(".json")).unwrap_or(&name).to_owned()
+    }
+}
+
+pub struct StaticSpriteSheetDescriptor {
+    pub name: &'static str,
+    pub texture: &'static str,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub frames: &'static [StaticSpriteFrameDescriptor],
+    pub tags: &'static [StaticSpriteTagDescriptor],
+    pub slices: &'static [StaticSpriteSliceDescriptor]
+}
+
+impl StaticSpriteSheetDescriptor {
+    pub const fn new(
+        name: &'static str,
+        texture: &'static str,
+        tile_width: u32,
+        tile_height: u32,
+        frames: &'static [StaticSpriteFrameDescriptor],
+        tags: &'static [StaticSpriteTagDescriptor],
+        slices: &'static [StaticSpriteSliceDescriptor]
+    ) -> Self {
+        Self { name, texture, tile_width, tile_height, frames, tags, slices }
+    }
+}
+
 #[derive(Default, Deserialize, Debug, PartialEq)]
 #[serde(default, rename = "shader", deny_unknown_fields)]
 pub struct ShaderDescriptor {
     name: String,
-    path: String
+    path: String,
+    /// Only embedded in builds with debug assertions
+    debug: bool
 }
 
 impl ShaderDescriptor {
@@ -171,6 +349,10 @@ impl ShaderDescriptor {
     pub fn path(&self) -> &str {
         &self.path
     }
+
+    pub fn is_debug(&self) -> bool {
+        self.debug
+    }
 }
 
 pub struct StaticShaderDescriptor {
@@ -190,15 +372,32 @@ impl StaticShaderDescriptor {
 #[serde(default, rename = "font", deny_unknown_fields)]
 pub struct FontDescriptor {
     name: String,
+    path: String,           // optional BMFont or TrueType file (resources/textures)
+    pages: Vec<String>,     // BMFont page textures, default: embedded page files
     charset: String,
     texture: String,
     char_width: u32,
-    char_height: u32
+    char_height: u32,
+    fallback: String,       // glyph for missing characters
+    #[serde(default = "default_font_size")]
+    size: f32,              // TrueType pixel size, default: 16
+    ranges: Vec<String>,    // TrueType character ranges, e.g. "32-126", "0x400-0x4ff"
+    outline: u32,           // TrueType outline thickness (pixels)
+    shadow_x: i32,          // TrueType shadow offset (pixels)
+    shadow_y: i32
 }
 
 impl FontDescriptor {
-    pub fn name(&self) -> &str {
-        &self.name
+    pub fn name(&self) -> String {
+        name_from_path(&self.name, &self.path)
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn pages(&self) -> &[String] {
+        &self.pages
     }
 
     pub fn charset(&self) -> &str {
@@ -216,6 +415,62 @@ impl FontDescriptor {
     pub fn texture(&self) -> &str {
         &self.texture
     }
+
+    pub fn fallback(&self) -> &str {
+        &self.fallback
+    }
+
+    pub fn size(&self) -> f32 {
+        self.size
+    }
+
+    pub fn ranges(&self) -> &[String] {
+        &self.ranges
+    }
+
+    pub fn outline(&self) -> u32 {
+        self.outline
+    }
+
+    pub fn shadow(&self) -> (i32, i32) {
+        (self.shadow_x, self.shadow_y)
+    }
+
+    pub fn is_truetype(&self) -> bool {
+        let path = self.path.to_lowercase();
+        path.ends_with(".ttf") || path.ends_with(".otf")
+    }
+}
+
+pub struct StaticGlyphDescriptor {
+    pub id: u32,
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+    pub offset_x: i32,
+    pub offset_y: i32,
+    pub advance: i32,
+    pub page: u32
+}
+
+impl StaticGlyphDescriptor {
+    #[allow(clippy::too_many_arguments)]
+    pub const fn new(id: u32, x: u32, y: u32, w: u32, h: u32, offset_x: i32, offset_y: i32, advance: i32, page: u32) -> Self {
+        Self { id, x, y, w, h, offset_x, offset_y, advance, page }
+    }
+}
+
+pub struct StaticKerningDescriptor {
+    pub first: u32,
+    pub second: u32,
+    pub amount: i32
+}
+
+impl StaticKerningDescriptor {
+    pub const fn new(first: u32, second: u32, amount: i32) -> Self {
+        Self { first, second, amount }
+    }
 }
 
 pub struct StaticFontDescriptor {
@@ -223,12 +478,35 @@ pub struct StaticFontDescriptor {
     pub charset: &'static str,
     pub char_width: u32,
     pub char_height: u32,
-    pub texture: &'static str
+    pub texture: &'static str,
+    pub line_height: u32,
+    pub base: u32,
+    pub texture_width: u32,
+    pub texture_height: u32,
+    pub pages: &'static [&'static str],
+    pub glyphs: &'static [StaticGlyphDescriptor],
+    pub kernings: &'static [StaticKerningDescriptor],
+    pub fallback: &'static str
 }
 
 impl StaticFontDescriptor {
-    pub const fn new(name: &'static str, charset: &'static str, char_width: u32, char_height: u32, texture: &'static str) -> Self {
-        Self { name, charset, char_width, char_height, texture }
+    #[allow(clippy::too_many_arguments)]
+    pub const fn new(
+        name: &'static str,
+        charset: &'static str,
+        char_width: u32,
+        char_height: u32,
+        texture: &'static str,
+        line_height: u32,
+        base: u32,
+        texture_width: u32,
+        texture_height: u32,
+        pages: &'static [&'static str],
+        glyphs: &'static [StaticGlyphDescriptor],
+        kernings: &'static [StaticKerningDescriptor],
+        fallback: &'static str
+    ) -> Self {
+        Self { name, charset, char_width, char_height, texture, line_height, base, texture_width, texture_height, pages, glyphs, kernings, fallback }
     }
 }
 
@@ -268,6 +546,7 @@ pub struct MaterialDescriptor {
     pub name: String,
     pub font: String,
     pub texture: String,
+    pub sprite_sheet: String,
 
     #[serde(default = "default_1")]
     pub texture_binding: u32,
@@ -293,7 +572,10 @@ pub struct MaterialDescriptor {
     pub depth_writing: bool,
 
     #[serde(default = "default_topology")]
-    pub topology: String
+    pub topology: String,
+
+    #[serde(default = "default_line_width")]
+    pub line_width: f32
 }
 
 
@@ -301,6 +583,7 @@ pub struct StaticMaterialDescriptor {
     pub name: &'static str,
     pub font: &'static str,
     pub texture: &'static str,
+    pub sprite_sheet: &'static str,
     pub texture_binding: u32,
     pub texture_filtering: bool,
     pub vertex_shader: &'static str,
@@ -312,7 +595,8 @@ pub struct StaticMaterialDescriptor {
     pub frontface_clockwise: bool,
     pub depth_testing: bool,
     pub depth_writing: bool,
-    pub topology: &'static str
+    pub topology: &'static str,
+    pub line_width: f32
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -321,6 +605,7 @@ impl StaticMaterialDescriptor {
         name: &'static str,
         font: &'static str,
         texture: &'static str,
+        sprite_sheet: &'static str,
         texture_binding: u32,
         texture_filtering: bool,
         vertex_shader: &'static str,
@@ -332,12 +617,14 @@ impl StaticMaterialDescriptor {
         frontface_clockwise: bool,
         depth_testing: bool,
         depth_writing: bool,
-        topology: &'static str
+        topology: &'static str,
+        line_width: f32
     ) -> Self {
         Self {
             name,
             font,
             texture,
+            sprite_sheet,
             texture_binding,
             texture_filtering,
             vertex_shader,
@@ -349,7 +636,8 @@ impl StaticMaterialDescriptor {
             frontface_clockwise,
             depth_testing,
             depth_writing,
-            topology
+            topology,
+            line_width
         }
     }
 }
@@ -442,6 +730,7 @@ pub struct MapDescriptor {
     name: String,
     path: String,
     pub material: String,
+    pub normal_material: String,            // normal map tileset for lighting
     pub uncompressed_layers: Vec<String>    // don't create a sparse map, even create empty tiles
 }
 
@@ -453,19 +742,175 @@ impl MapDescriptor {
     pub fn path(&self) -> &str {
         &self.path
     }
+
+    /// Tiled maps (.tmx, .tmj), LDtk projects otherwise
+    pub fn is_tiled(&self) -> bool {
+        let extension = Path::new(&self.path).extension().and_then(|e| e.to_str()).unwrap_or_default();
+        extension.eq_ignore_ascii_case("tmx") || extension.eq_ignore_ascii_case("tmj")
+    }
 }
 
 pub struct StaticMapDescriptor {
     pub name: &'static str,
     pub data: &'static [u8],
     pub size: usize,
+    pub format: &'static str,
     pub material: &'static str,
-    pub uncompressed_layers: &'static [&'static str]
+    pub normal_material: &'static str,
+    pub uncompressed_layers: &'static [&'static str],
+    pub levels: &'static [StaticMapLevelDescriptor]
 }
 
 impl StaticMapDescriptor {
-    pub const fn new(name: &'static str, data: &'static [u8], material: &'static str, uncompressed_layers: &'static [&'static str]) -> Self {
-        Self { name, data, size: data.len(), material, uncompressed_layers }
+    pub const fn new(name: &'static str, data: &'static [u8], format: &'static str, material: &'static str, normal_material: &'static str, uncompressed_layers: &'static [&'static str], levels: &'static [StaticMapLevelDescriptor]) -> Self {
+        Self { name, data, size: data.len(), format, material, normal_material, uncompressed_layers, levels }
+    }
+}
+
+/// LDtk level or Tiled tileset saved to a separate file, path
+/// relative to the map file
+pub struct StaticMapLevelDescriptor {
+    pub path: &'static str,
+    pub data: &'static [u8]
+}
+
+impl StaticMapLevelDescriptor {
+    pub const fn new(path: &'static str, data: &'static [u8]) -> Self {
+        Self { path, data }
+    }
+}
+
+#[derive(Default, Deserialize, Debug, PartialEq)]
+#[serde(default, rename = "particle", deny_unknown_fields)]
+pub struct ParticleEffectDescriptor {
+    pub name: String,
+    pub shape: String,          // point (default), line, circle, rect
+    pub width: f32,             // line extent or rect size
+    pub height: f32,
+    pub radius: f32,            // circle
+    pub rate: f32,              // particles per second
+    pub burst: u32,             // particles at start (and per loop)
+    pub duration: f32,          // seconds, 0 = infinite
+    pub looping: bool,
+
+    #[serde(default = "default_lifetime")]
+    pub lifetime_min: f32,      // seconds
+
+    pub lifetime_max: f32,      // 0 = lifetime_min
+    pub speed_min: f32,         // pixels per second
+    pub speed_max: f32,         // 0 = speed_min
+    pub direction: f32,         // degrees, 0 = right, 90 = down
+
+    #[serde(default = "default_spread")]
+    pub spread: f32,            // degrees
+
+    pub gravity_x: f32,
+    pub gravity_y: f32,
+    pub drag: f32,
+    pub colors: Vec<String>,    // over lifetime, "#rrggbb(aa)" or name
+    pub sizes: Vec<f32>,        // over lifetime (pixels)
+    pub velocities: Vec<f32>,   // speed factor over lifetime
+    pub texture_x: f32,         // normalized texture region, w/h 0 = 1
+    pub texture_y: f32,
+    pub texture_w: f32,
+    pub texture_h: f32,
+
+    #[serde(default = "default_1")]
+    pub texture_mask: u32,
+
+    pub flags: u32
+}
+
+pub struct StaticParticleEffectDescriptor {
+    pub name: &'static str,
+    pub shape: &'static str,
+    pub width: f32,
+    pub height: f32,
+    pub radius: f32,
+    pub rate: f32,
+    pub burst: u32,
+    pub duration: f32,
+    pub looping: bool,
+    pub lifetime_min: f32,
+    pub lifetime_max: f32,
+    pub speed_min: f32,
+    pub speed_max: f32,
+    pub direction: f32,
+    pub spread: f32,
+    pub gravity_x: f32,
+    pub gravity_y: f32,
+    pub drag: f32,
+    pub colors: &'static [&'static str],
+    pub sizes: &'static [f32],
+    pub velocities: &'static [f32],
+    pub texture_x: f32,
+    pub texture_y: f32,
+    pub texture_w: f32,
+    pub texture_h: f32,
+    pub texture_mask: u32,
+    pub flags: u32
+}
+
+#[allow(clippy::too_many_arguments)]
+impl StaticParticleEffectDescriptor {
+    pub const fn new(
+        name: &'static str,
+        shape: &'static str,
+        width: f32,
+        height: f32,
+        radius: f32,
+        rate: f32,
+        burst: u32,
+        duration: f32,
+        looping: bool,
+        lifetime_min: f32,
+        lifetime_max: f32,
+        speed_min: f32,
+        speed_max: f32,
+        direction: f32,
+        spread: f32,
+        gravity_x: f32,
+        gravity_y: f32,
+        drag: f32,
+        colors: &'static [&'static str],
+        sizes: &'static [f32],
+        velocities: &'static [f32],
+        texture_x: f32,
+        texture_y: f32,
+        texture_w: f32,
+        texture_h: f32,
+        texture_mask: u32,
+        flags: u32
+    ) -> Self {
+        Self {
+            name,
+            shape,
+            width,
+            height,
+            radius,
+            rate,
+            burst,
+            duration,
+            looping,
+            lifetime_min,
+            lifetime_max,
+            speed_min,
+            speed_max,
+            direction,
+            spread,
+            gravity_x,
+            gravity_y,
+            drag,
+            colors,
+            sizes,
+            velocities,
+            texture_x,
+            texture_y,
+            texture_w,
+            texture_h,
+            texture_mask,
+            flags
+        }
     }
 }
 
@@ -475,13 +920,16 @@ pub struct ApplicationDescriptorTable {
     pub data: &'static [StaticDataDescriptor],
     pub bitmaps: &'static [StaticBitmapDescriptor],
     pub textures: &'static [StaticTextureDescriptor],
+    pub nine_slices: &'static [StaticNineSliceDescriptor],
     pub fonts: &'static [StaticFontDescriptor],
+    pub sprite_sheets: &'static [StaticSpriteSheetDescriptor],
     pub shaders: &'static [StaticShaderDescriptor],
     pub materials: &'static [StaticMaterialDescriptor],
     pub tasks: &'static [StaticTaskDescriptor],
     pub music: &'static [StaticSampleDescriptor],
     pub samples: &'static [StaticSampleDescriptor],
     pub maps: &'static [StaticMapDescriptor],
+    pub particle_effects: &'static [StaticParticleEffectDescriptor],
 }
 
 #[derive(Default, Deserialize, Debug, PartialEq)]
@@ -492,10 +940,37 @@ pub struct Manifest {
     pub bitmaps: Vec<BitmapDescriptor>,
     pub textures: Vec<TextureDescriptor>,
     pub fonts: Vec<FontDescriptor>,
+    pub sprite_sheets: Vec<SpriteSheetDescriptor>,
     pub shaders: Vec<ShaderDescriptor>,
     pub materials: Vec<MaterialDescriptor>,
     pub tasks: Vec<TaskDescriptor>,
     pub music: Vec<SampleDescriptor>,
     pub samples: Vec<SampleDescriptor>,
-    pub maps: Vec<MapDescriptor>
+    pub maps: Vec<MapDescriptor>,
+    pub particles: Vec<ParticleEffectDescriptor>
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sprite_sheet_name() {
+        let sheet = |name: &str, path: &str| SpriteSheetDescriptor { name: name.to_string(), path: path.to_string(), ..Default::default() };
+
+        assert_eq!(sheet("", "hero.aseprite.json").name(), "hero");
+        assert_eq!(sheet("", "hero.json").name(), "hero");
+        assert_eq!(sheet("", "sprites/hero.v2.json").name(), "hero.v2");
+        assert_eq!(sheet("hero.v2", "hero.aseprite.json").name(), "hero.v2");
+        assert_eq!(sheet("hero", "").name(), "hero");
+    }
+
+    #[test]
+    fn test_font_size() {
+        let font: FontDescriptor = serde_json::from_str(r#"{ "path": "sans.ttf" }"#).unwrap();
+        assert_eq!(font.size(), 16.0);
+
+        let font: FontDescriptor = serde_json::from_str(r#"{ "path": "sans.ttf", "size": 24 }"#).unwrap();
+        assert_eq!(font.size(), 24.0);
+    }
 }
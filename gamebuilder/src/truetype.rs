@@ -0,0 +1,365 @@
+//!
+//! TrueType
+//!
+
+/*
+
+Rasterize TrueType and OpenType fonts into a glyph atlas at build time.
+Glyph metrics follow the BMFont conventions, so the result is emitted
+like a loaded BMFont descriptor. The atlas is stored as raw RGBA pixels
+(8 byte header with width and height, little endian).
+
+*/
+
+use crate::bmfont::{BmFont, BmFontChar, BmFontKerning};
+
+const PADDING: usize = 1;
+const MAX_ATLAS_SIZE: usize = 4096;
+const SHADOW_ALPHA: f32 = 0.5;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TrueTypeOptions {
+    pub size: f32,              // pixel height (em size)
+    pub chars: Vec<char>,
+    pub outline: u32,           // outline thickness (pixels)
+    pub shadow_x: i32,          // shadow offset (pixels), 0/0 = no shadow
+    pub shadow_y: i32
+}
+
+impl TrueTypeOptions {
+    /// Characters from ranges such as "32-126", "0x400-0x4ff" or "65"
+    /// and from a literal charset, defaults to printable ASCII
+    pub fn parse_chars(ranges: &[String], charset: &str) -> Result<Vec<char>, String> {
+
+        let parse_code = |s: &str| -> Result<u32, String> {
+            let s = s.trim();
+            let res = if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+                u32::from_str_radix(hex, 16)
+            } else {
+                s.parse::<u32>()
+            };
+            res.map_err(|_| format!("invalid character code \"{}\"", s))
+        };
+
+        let mut chars: Vec<char> = charset.chars().collect();
+
+        for range in ranges {
+            let (first, last) = match range.split_once('-') {
+                Some((first, last)) => (parse_code(first)?, parse_code(last)?),
+                None => {
+                    let code = parse_code(range)?;
+                    (code, code)
+                }
+            };
+
+            if first > last {
+                return Err(format!("invalid character range \"{}\"", range));
+            }
+
+            chars.extend((first..=last).filter_map(char::from_u32));
+        }
+
+        if chars.is_empty() {
+            chars.extend((32u32..=126).filter_map(char::from_u32));
+        }
+
+        chars.sort_unstable();
+        chars.dedup();
+
+        Ok(chars)
+    }
+}
+
+/// Rasterized font with atlas pixels
+#[derive(Debug, Default)]
+pub struct TrueTypeAtlas {
+    pub font: BmFont,
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>     // rgba
+}
+
+/// Glyph bitmap with effects applied
+struct GlyphCell {
+    c: char,
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,        // rgba
+    xoffset: i32,
+    yoffset: i32,
+    xadvance: i32,
+    x: usize,
+    y: usize
+}
+
+impl TrueTypeAtlas {
+    pub fn rasterize(data: &[u8], options: &TrueTypeOptions) -> Result<Self, String> {
+
+        if options.size <= 0.0 {
+            return Err("font size must be greater than zero".to_string());
+        }
+
+        let settings = fontdue::FontSettings { scale: options.size, ..Default::default() };
+        let font = match fontdue::Font::from_bytes(data, settings) {
+            Ok(font) => font,
+            Err(e) => {
+                return Err(format!("failed to load font: {}", e));
+            }
+        };
+
+        let size = options.size;
+        let line_metrics = match font.horizontal_line_metrics(size) {
+            Some(line_metrics) => line_metrics,
+            None => {
+                return Err("font has no horizontal metrics".to_string());
+            }
+        };
+
+        let base = line_metrics.ascent.round() as i32;
+
+        // effects extend the glyph bitmap
+        let outline = options.outline as usize;
+        let left = outline + (-options.shadow_x).max(0) as usize;
+        let top = outline + (-options.shadow_y).max(0) as usize;
+        let extra_w = 2 * outline + options.shadow_x.unsigned_abs() as usize;
+        let extra_h = 2 * outline + options.shadow_y.unsigned_abs() as usize;
+
+        let mut cells = Vec::new();
+
+        for &c in &options.chars {
+
+            // skip characters missing from the font
+            if font.lookup_glyph_index(c) == 0 {
+                continue;
+            }
+
+            let (metrics, coverage) = font.rasterize(c, size);
+            let has_pixels = metrics.width > 0 && metrics.height > 0;
+
+            let (width, height, pixels) = if has_pixels {
+                let w = metrics.width + extra_w;
+                let h = metrics.height + extra_h;
+                (w, h, Self::apply_effects(&coverage, metrics.width, metrics.height, options, left, top, w, h))
+            } else {
+                (0, 0, Vec::new())
+            };
+
+            cells.push(GlyphCell {
+                c,
+                width,
+                height,
+                pixels,
+                xoffset: metrics.xmin - left as i32,
+                yoffset: base - (metrics.ymin + metrics.height as i32) - top as i32,
+                xadvance: metrics.advance_width.round() as i32,
+                x: 0,
+                y: 0
+            });
+        }
+
+        if cells.is_empty() {
+            return Err("font contains none of the requested characters".to_string());
+        }
+
+        let (width, height) = Self::pack(&mut cells)?;
+
+        let mut pixels = vec![0u8; width * height * 4];
+        for cell in &cells {
+            for row in 0..cell.height {
+                let src = row * cell.width * 4;
+                let dest = ((cell.y + row) * width + cell.x) * 4;
+                pixels[dest..dest + cell.width * 4].copy_from_slice(&cell.pixels[src..src + cell.width * 4]);
+            }
+        }
+
+        let mut kernings = Vec::new();
+        for first in &cells {
+            for second in &cells {
+                let amount = font.horizontal_kern(first.c, second.c, size).unwrap_or_default().round() as i32;
+                if amount != 0 {
+                    kernings.push(BmFontKerning { first: first.c as u32, second: second.c as u32, amount });
+                }
+            }
+        }
+
+        let chars = cells.iter().map(|cell| BmFontChar {
+            id: cell.c as u32,
+            x: cell.x as u32,
+            y: cell.y as u32,
+            width: cell.width as u32,
+            height: cell.height as u32,
+            xoffset: cell.xoffset,
+            yoffset: cell.yoffset,
+            xadvance: cell.xadvance,
+            page: 0
+        }).collect();
+
+        let line_height = (line_metrics.ascent - line_metrics.descent + line_metrics.line_gap).round();
+
+        let bmfont = BmFont {
+            size: size.round() as i32,
+            line_height: line_height.max(0.0) as u32,
+            base: base.max(0) as u32,
+            scale_w: width as u32,
+            scale_h: height as u32,
+            chars,
+            kernings,
+            ..Default::default()
+        };
+
+        Ok(Self { font: bmfont, width, height, pixels })
+    }
+
+    /// Compose shadow, outline and white glyph into an rgba cell
+    #[allow(clippy::too_many_arguments)]
+    fn apply_effects(coverage: &[u8], w: usize, h: usize, options: &TrueTypeOptions, left: usize, top: usize, cell_w: usize, cell_h: usize) -> Vec<u8> {
+
+        let fill_at = |x: i64, y: i64| -> f32 {
+            let gx = x - left as i64;
+            let gy = y - top as i64;
+            if gx < 0 || gy < 0 || gx >= w as i64 || gy >= h as i64 {
+                0.0
+            } else {
+                coverage[gy as usize * w + gx as usize] as f32 / 255.0
+            }
+        };
+
+        // outline is the fill dilated by a disc
+        let radius = options.outline as i64;
+        let outline_at = |x: i64, y: i64| -> f32 {
+            let mut alpha = fill_at(x, y);
+            for dy in -radius..=radius {
+                for dx in -radius..=radius {
+                    if dx * dx + dy * dy <= radius * radius {
+                        alpha = alpha.max(fill_at(x + dx, y + dy));
+                    }
+                }
+            }
+            alpha
+        };
+
+        let has_shadow = options.shadow_x != 0 || options.shadow_y != 0;
+        let sx = options.shadow_x as i64;
+        let sy = options.shadow_y as i64;
+
+        let mut pixels = vec![0u8; cell_w * cell_h * 4];
+
+        for y in 0..cell_h as i64 {
+            for x in 0..cell_w as i64 {
+
+                let fill = fill_at(x, y);
+                let outline = if radius > 0 { outline_at(x, y) } else { 0.0 };
+                let shadow = if has_shadow {
+                    SHADOW_ALPHA * if radius > 0 { outline_at(x - sx, y - sy) } else { fill_at(x - sx, y - sy) }
+                } else {
+                    0.0
+                };
+
+                // white fill over black outline over black shadow
+                let below = outline + shadow * (1.0 - outline);
+                let alpha = fill + below * (1.0 - fill);
+                let color = if alpha > 0.0 { fill / alpha } else { 1.0 };
+
+                let index = (y as usize * cell_w + x as usize) * 4;
+                let value = (color * 255.0).round() as u8;
+                pixels[index] = value;
+                pixels[index + 1] = value;
+                pixels[index + 2] = value;
+                pixels[index + 3] = (alpha * 255.0).round() as u8;
+            }
+        }
+
+        pixels
+    }
+
+    /// Shelf packing into the smallest square power of two width
+    fn pack(cells: &mut [GlyphCell]) -> Result<(usize, usize), String> {
+
+        let mut order: Vec<usize> = (0..cells.len()).collect();
+        order.sort_by_key(|&index| std::cmp::Reverse(cells[index].height));
+
+        let mut width = 64;
+        while width <= MAX_ATLAS_SIZE {
+
+            let mut x = PADDING;
+            let mut y = PADDING;
+            let mut shelf_height = 0;
+            let mut fits = true;
+
+            for &index in &order {
+                let cell = &mut cells[index];
+                if cell.width == 0 || cell.height == 0 {
+                    continue;
+                }
+
+                if cell.width + 2 * PADDING > width {
+                    fits = false;
+                    break;
+                }
+
+                if x + cell.width + PADDING > width {
+                    x = PADDING;
+                    y += shelf_height + PADDING;
+                    shelf_height = 0;
+                }
+
+                cell.x = x;
+                cell.y = y;
+                x += cell.width + PADDING;
+                shelf_height = shelf_height.max(cell.height);
+            }
+
+            let height = (y + shelf_height + PADDING).next_power_of_two();
+            if fits && height <= width {
+                return Ok((width, height));
+            }
+
+            width *= 2;
+        }
+
+        Err(format!("glyphs do not fit into a {}x{} atlas", MAX_ATLAS_SIZE, MAX_ATLAS_SIZE))
+    }
+
+    /// Raw rgba texture data
+    pub fn to_rgba(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(8 + self.pixels.len());
+        data.extend_from_slice(&(self.width as u32).to_le_bytes());
+        data.extend_from_slice(&(self.height as u32).to_le_bytes());
+        data.extend_from_slice(&self.pixels);
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chars_and_packing() {
+
+        let chars = TrueTypeOptions::parse_chars(&["0x41-0x43".to_string(), "97".to_string()], "Ab\u{e4}").unwrap();
+        assert_eq!(chars, vec!['A', 'B', 'C', 'a', 'b', '\u{e4}']);
+        assert_eq!(TrueTypeOptions::parse_chars(&[], "").unwrap().len(), 95);
+        assert!(TrueTypeOptions::parse_chars(&["z".to_string()], "").is_err());
+
+        let cell = |c: char, width: usize, height: usize| GlyphCell {
+            c, width, height, pixels: Vec::new(), xoffset: 0, yoffset: 0, xadvance: 0, x: 0, y: 0
+        };
+
+        let mut cells = vec![cell('a', 30, 10), cell(' ', 0, 0), cell('b', 30, 20), cell('c', 30, 10)];
+        assert_eq!(TrueTypeAtlas::pack(&mut cells).unwrap(), (64, 64));
+
+        // tallest first, two per shelf
+        assert_eq!((cells[2].x, cells[2].y), (1, 1));
+        assert_eq!((cells[0].x, cells[0].y), (32, 1));
+        assert_eq!((cells[3].x, cells[3].y), (1, 22));
+
+        let options = TrueTypeOptions { size: 8.0, chars: Vec::new(), outline: 1, shadow_x: 1, shadow_y: 1 };
+        let pixels = TrueTypeAtlas::apply_effects(&[255], 1, 1, &options, 1, 1, 4, 4);
+
+        // white fill, black outline and half transparent shadow
+        assert_eq!(&pixels[(4 + 1) * 4..(4 + 1) * 4 + 4], &[255, 255, 255, 255]);
+        assert_eq!(&pixels[(4 + 2) * 4..(4 + 2) * 4 + 4], &[0, 0, 0, 255]);
+        assert_eq!(&pixels[(2 * 4 + 2) * 4..(2 * 4 + 2) * 4 + 4], &[0, 0, 0, 128]);
+    }
+}
@@ -0,0 +1,15 @@
+//
+// Builtin Debug Draw Fragment Shader
+//
+
+#version 450
+
+layout (location = 0) in vertex_data {
+    vec4 color;
+} inputs;
+
+layout (location = 0) out vec4 oColor;
+
+void main() {
+    oColor = inputs.color;
+}
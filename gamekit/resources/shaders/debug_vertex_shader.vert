@@ -0,0 +1,47 @@
+//
+// Builtin Debug Draw Vertex Shader
+//
+
+#version 450
+
+// dynamic shader parameters
+layout(std140, set=0, binding=0) uniform shader_params {
+    float window_width;
+    float window_height;
+    float view_width;
+    float view_height;
+    float view_x;
+    float view_y;
+    float view_scaling;
+} params;
+
+// world space: camera offset, screen space: zero
+layout(push_constant) uniform constants {
+    float offset_x;
+    float offset_y;
+} push;
+
+layout (location = 0) in vec3 iPosition;
+layout (location = 1) in vec4 iColor;
+
+layout (location = 0) out vertex_data {
+    vec4 color;
+} outputs;
+
+void main() {
+
+    float window_width = params.window_width > 0.0 ? params.window_width : 1.0;
+    float window_height = params.window_height > 0.0 ? params.window_height : 1.0;
+
+    vec2 screen_coords = vec2(
+        (iPosition.x + push.offset_x) * params.view_scaling + params.view_x,
+        (iPosition.y + push.offset_y) * params.view_scaling + params.view_y
+    );
+
+    float x = -1.0 + 2.0 * screen_coords.x / window_width;
+    float y = -1.0 + 2.0 * screen_coords.y / window_height;
+
+    outputs.color = iColor;
+
+    gl_Position = vec4(x, y, 0.0, 1.0);
+}
@@ -0,0 +1,18 @@
+//
+// Builtin Instanced Sprite Fragment Shader
+//
+
+#version 450
+
+layout (binding = 1) uniform sampler2D iTexture;
+
+layout (location = 0) in vertex_data {
+    vec2 textureCoord;
+    vec4 color;
+} inputs;
+
+layout (location = 0) out vec4 oColor;
+
+void main() {
+    oColor = texture(iTexture, inputs.textureCoord) * inputs.color;
+}
@@ -0,0 +1,70 @@
+//
+// Builtin Instanced Sprite Vertex Shader
+//
+
+#version 450
+
+// dynamic shader parameters
+layout(std140, set=0, binding=0) uniform shader_params {
+    float offset_x;
+    float offset_y;
+    float window_width;
+    float window_height;
+    float view_width;
+    float view_height;
+    float view_x;
+    float view_y;
+    float view_scaling;
+} params;
+
+// sprite sheet frame rects (x, y, w, h in texture space)
+layout(std430, set=0, binding=2) readonly buffer shader_frame_lookup_buffer {
+    vec4 rects[];
+} frame_lookup;
+
+// per-vertex unit quad corner
+layout (location = 0) in vec3 iCorner;
+
+// per-instance data
+layout (location = 1) in vec2 iPosition;
+layout (location = 2) in vec2 iSize;
+layout (location = 3) in vec2 iPivot;
+layout (location = 4) in float iRotation;
+layout (location = 5) in vec4 iColor;
+layout (location = 6) in uint iFrame;
+
+layout (location = 0) out vertex_data {
+    vec2 textureCoord;
+    vec4 color;
+} outputs;
+
+void main() {
+
+    float window_width = params.window_width > 0.0 ? params.window_width : 1.0;
+    float window_height = params.window_height > 0.0 ? params.window_height : 1.0;
+
+    // rotate around pivot, then move pivot to sprite position
+    vec2 local_coords = iCorner.xy * iSize - iPivot;
+
+    float s = sin(iRotation);
+    float c = cos(iRotation);
+
+    vec2 rotated_coords = vec2(
+        local_coords.x * c - local_coords.y * s,
+        local_coords.x * s + local_coords.y * c
+    );
+
+    vec2 sprite_coords = iPosition + rotated_coords;
+
+    float x = -1.0 + 2.0 * (params.offset_x + params.view_x + sprite_coords.x * params.view_scaling) / window_width;
+    float y = -1.0 + 2.0 * (params.offset_y + params.view_y + sprite_coords.y * params.view_scaling) / window_height;
+
+    // out of range frames fall back to the first frame
+    uint frame_index = iFrame < uint(frame_lookup.rects.length()) ? iFrame : 0u;
+    vec4 rect = frame_lookup.rects[frame_index];
+
+    outputs.textureCoord = rect.xy + iCorner.xy * rect.zw;
+    outputs.color = iColor;
+
+    gl_Position = vec4(x, y, 0.0, 1.0);
+}
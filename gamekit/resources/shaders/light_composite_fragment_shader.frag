@@ -0,0 +1,14 @@
+//
+// Builtin Light Composite Fragment Shader
+//
+
+#version 450
+
+// accumulated light, same size as the frame buffer
+layout (binding = 1) uniform sampler2D iLightMap;
+
+layout (location = 0) out vec4 oColor;
+
+void main() {
+    oColor = vec4(texelFetch(iLightMap, ivec2(gl_FragCoord.xy), 0).rgb, 1.0);
+}
@@ -0,0 +1,11 @@
+//
+// Builtin Light Composite Vertex Shader
+//
+
+#version 450
+
+// full screen triangle, no vertex input
+void main() {
+    vec2 uv = vec2(float((gl_VertexIndex << 1) & 2), float(gl_VertexIndex & 2));
+    gl_Position = vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+}
@@ -0,0 +1,123 @@
+//
+// Builtin Light Fragment Shader
+//
+
+#version 450
+
+const uint LIGHT_FLAG_SPOT = 0x1;
+const uint LIGHT_FLAG_SHADOWS = 0x2;
+
+layout(std140, set=0, binding=0) uniform shader_params {
+    float window_width;
+    float window_height;
+    float view_width;
+    float view_height;
+    float view_x;
+    float view_y;
+    float view_scaling;
+    float offset_x;
+    float offset_y;
+    uint normal_mapping;
+    uint num_lights;
+} params;
+
+struct Light {
+    float x;
+    float y;
+    float radius;
+    float falloff;
+    float r;
+    float g;
+    float b;
+    float intensity;
+    float direction_x;
+    float direction_y;
+    float cos_outer;
+    float cos_inner;
+    float height;
+    uint first_occluder;
+    uint num_occluders;
+    uint flags;
+};
+
+layout(std430, set=0, binding=1) readonly buffer light_buffer {
+    Light lights[];
+};
+
+// occluder segments (x0, y0, x1, y1), grouped per light
+layout(std430, set=0, binding=2) readonly buffer occluder_buffer {
+    vec4 occluders[];
+};
+
+// normal buffer, flat normals where nothing was drawn
+layout (binding = 3) uniform sampler2D iNormals;
+
+layout (location = 0) in vertex_data {
+    vec2 position;
+    flat uint light_index;
+} inputs;
+
+layout (location = 0) out vec4 oColor;
+
+vec3 linear_to_srgb(vec3 c) {
+    return mix(c * 12.92, 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, step(vec3(0.0031308), c));
+}
+
+bool is_occluded(vec2 p, vec2 q, vec4 segment) {
+    vec2 r = q - p;
+    vec2 s = segment.zw - segment.xy;
+
+    float denom = r.x * s.y - r.y * s.x;
+    if (abs(denom) < 1e-6) {
+        return false;
+    }
+
+    vec2 ap = segment.xy - p;
+    float t = (ap.x * s.y - ap.y * s.x) / denom;
+    float u = (ap.x * r.y - ap.y * r.x) / denom;
+
+    return t > 0.0 && t < 1.0 && u >= 0.0 && u <= 1.0;
+}
+
+void main() {
+
+    Light light = lights[inputs.light_index];
+
+    vec2 light_pos = vec2(light.x, light.y);
+    vec2 to_light = light_pos - inputs.position;
+    float dist = length(to_light);
+
+    if (dist >= light.radius) {
+        discard;
+    }
+
+    float attenuation = pow(1.0 - dist / light.radius, light.falloff);
+
+    if ((light.flags & LIGHT_FLAG_SPOT) != 0) {
+        vec2 dir = dist > 0.0 ? -to_light / dist : vec2(light.direction_x, light.direction_y);
+        float c = dot(dir, vec2(light.direction_x, light.direction_y));
+        attenuation *= smoothstep(light.cos_outer, light.cos_inner, c);
+    }
+
+    if (params.normal_mapping != 0) {
+        // normal buffer holds the raw normal map texels, y axis points up
+        vec3 encoded = linear_to_srgb(texelFetch(iNormals, ivec2(gl_FragCoord.xy), 0).xyz);
+        vec3 normal = normalize(encoded * 2.0 - 1.0);
+        vec3 l = normalize(vec3(to_light.x, -to_light.y, light.height));
+        attenuation *= max(dot(normal, l), 0.0);
+    }
+
+    if (attenuation <= 0.0) {
+        discard;
+    }
+
+    if ((light.flags & LIGHT_FLAG_SHADOWS) != 0) {
+        for (uint i = 0; i < light.num_occluders; i++) {
+            if (is_occluded(inputs.position, light_pos, occluders[light.first_occluder + i])) {
+                discard;
+            }
+        }
+    }
+
+    oColor = vec4(vec3(light.r, light.g, light.b) * light.intensity * attenuation, 1.0);
+}
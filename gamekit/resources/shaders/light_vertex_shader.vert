@@ -0,0 +1,48 @@
+//
+// Builtin Light Vertex Shader
+//
+
+#version 450
+
+// dynamic shader parameters
+layout(std140, set=0, binding=0) uniform shader_params {
+    float window_width;
+    float window_height;
+    float view_width;
+    float view_height;
+    float view_x;
+    float view_y;
+    float view_scaling;
+    float offset_x;
+    float offset_y;
+    uint normal_mapping;
+    uint num_lights;
+} params;
+
+// light quad in world coordinates, flags hold the light index
+layout (location = 0) in vec3 iPosition;
+layout (location = 4) in uint iFlags;
+
+layout (location = 0) out vertex_data {
+    vec2 position;
+    flat uint light_index;
+} outputs;
+
+void main() {
+
+    float window_width = params.window_width > 0.0 ? params.window_width : 1.0;
+    float window_height = params.window_height > 0.0 ? params.window_height : 1.0;
+
+    vec2 screen_coords = vec2(
+        (iPosition.x + params.offset_x) * params.view_scaling + params.view_x,
+        (iPosition.y + params.offset_y) * params.view_scaling + params.view_y
+    );
+
+    float x = -1.0 + 2.0 * screen_coords.x / window_width;
+    float y = -1.0 + 2.0 * screen_coords.y / window_height;
+
+    outputs.position = iPosition.xy;
+    outputs.light_index = iFlags;
+
+    gl_Position = vec4(x, y, 0.0, 1.0);
+}
@@ -21,27 +21,65 @@ layout(std140, set=0, binding=0) uniform shader_params {
     uint cols;
 } params;
 
-layout (binding = 1) uniform sampler2D iTexture;
+// per-layer parameters
+layout(push_constant) uniform layer_params {
+    float offset_x;
+    float offset_y;
+    uint texture_width;
+    uint texture_height;
+    uint tile_size;
+    uint grid_size;
+    uint cols;
+    uint tileset;
+    vec4 tint;
+    uint padding;
+    uint spacing;
+} layer;
+
+// tilesets of the map, selected by the layer
+layout (binding = 1) uniform sampler2D iTileset0;
+layout (binding = 2) uniform sampler2D iTileset1;
+layout (binding = 3) uniform sampler2D iTileset2;
+layout (binding = 4) uniform sampler2D iTileset3;
 
 layout (location = 0) in vertex_data {
     vec2 textureCoord;
     vec4 color;
+    flat uint flags;
 } inputs;
 
 layout (location = 0) out vec4 oColor;
 
+vec4 sampleTileset(vec2 coords) {
+    if (layer.tileset == 1) return texture(iTileset1, coords);
+    if (layer.tileset == 2) return texture(iTileset2, coords);
+    if (layer.tileset == 3) return texture(iTileset3, coords);
+    return texture(iTileset0, coords);
+}
+
+// rotate clockwise by quarter turns, then mirror
+vec2 transformTileCoord(vec2 uv, uint flags) {
+    uint rotation = (flags >> 2) & 0x3u;
+    if (rotation == 1u) uv = vec2(uv.y, 1.0 - uv.x);
+    else if (rotation == 2u) uv = vec2(1.0 - uv.x, 1.0 - uv.y);
+    else if (rotation == 3u) uv = vec2(1.0 - uv.y, uv.x);
+    if ((flags & 0x1u) != 0u) uv.x = 1.0 - uv.x;
+    if ((flags & 0x2u) != 0u) uv.y = 1.0 - uv.y;
+    return uv;
+}
+
 void main() {
 
-    float grid_size_f = float(params.grid_size);
+    float tile_size_f = float(layer.tile_size);
 
     vec2 scale = vec2(
-        grid_size_f / params.texture_width,
-        grid_size_f / params.texture_height
+        tile_size_f / layer.texture_width,
+        tile_size_f / layer.texture_height
     );
 
     if (inputs.color.w == 0.0) {
         oColor = vec4(1.0, 0.0, 1.0, 0.0);
     } else {
-        oColor = texture(iTexture, inputs.textureCoord + gl_PointCoord * scale) * inputs.color;
+        oColor = sampleTileset(inputs.textureCoord + transformTileCoord(gl_PointCoord, inputs.flags) * scale) * inputs.color * layer.tint;
     }
 }
@@ -3,7 +3,6 @@
 //
 
 #version 450
-#extension GL_EXT_scalar_block_layout : require
 
 const int INT32_MAX = 0x7FFFFFFF;
 const int TRANSPARENT_TILE_ID = INT32_MAX;
@@ -26,18 +25,36 @@ layout(std140, set=0, binding=0) uniform shader_params {
     uint cols;
 } params;
 
-// dynamic lookup table for animated tiles
-layout(std140, set=0, binding=2, scalar) uniform shader_tile_lookup_buffer {
-    uint animation_frames[256];
-} tile_lookup;
+// per-layer parameters
+layout(push_constant) uniform layer_params {
+    float offset_x;
+    float offset_y;
+    uint texture_width;
+    uint texture_height;
+    uint tile_size;
+    uint grid_size;
+    uint cols;
+    uint tileset;
+    vec4 tint;
+    uint padding;
+    uint spacing;
+} layer;
+
+// current frame of the animated tiles, sized from the tilesets
+layout(std430, set=0, binding=5) readonly buffer tile_animation_buffer {
+    uint animation_frames[];
+} tile_animations;
 
 // 16 bit position index (hiword) + 16 bit tileset index (loword)
 layout (location = 0) in uint map_index;
 layout (location = 1) in int tileset_index;
+// flip x (bit 0), flip y (bit 1), quarter turns (bits 2-3), alpha (bits 8-15)
+layout (location = 2) in uint tile_flags;
 
 layout (location = 0) out vertex_data {
     vec2 textureCoord;
     vec4 color;
+    flat uint flags;
 } outputs;
 
 void main() {
@@ -50,22 +67,25 @@ void main() {
     int rel_index = -(tileset_index+1);
 
     uint resolved_tileset_index = (tileset_index >= 0) ? 
-        uint(tileset_index) : tile_lookup.animation_frames[rel_index];
+        uint(tileset_index) :
+        (rel_index < tile_animations.animation_frames.length() ?
+            tile_animations.animation_frames[rel_index] : uint(TRANSPARENT_TILE_ID));
 
-    // use grid size as scale factor
-    float grid_size_f = float(params.grid_size);
+    // use grid size of the layer as scale factor
+    float grid_size_f = float(layer.grid_size);
+    float tile_size_f = float(layer.tile_size);
 
     // calculate vertex coords
-    uint map_row = map_index / params.cols;
-    uint map_col = map_index % params.cols;
+    uint map_row = map_index / layer.cols;
+    uint map_col = map_index % layer.cols;
     vec2 map_coords = vec2(
         float(map_col) * grid_size_f,
         float(map_row) * grid_size_f
     );
 
     vec2 screen_coords = vec2(
-        (map_coords.x + params.offset_left + grid_size_f / 2.0) * params.view_scaling + params.view_x,
-        (map_coords.y + params.offset_top + grid_size_f / 2.0) * params.view_scaling + params.view_y
+        (map_coords.x + layer.offset_x + grid_size_f / 2.0) * params.view_scaling + params.view_x,
+        (map_coords.y + layer.offset_y + grid_size_f / 2.0) * params.view_scaling + params.view_y
     );
 
     vec2 logical_coords = vec2(
@@ -75,20 +95,25 @@ void main() {
 
     vec4 vertex_coords = vec4(logical_coords.x, logical_coords.y, 0.0, 1.0);
 
-    // calculate texture coords
-    uint tileset_cols = params.texture_width / params.grid_size;
+    // calculate texture coords, tiles start after the padding and are
+    // separated by the spacing
+    uint tile_stride = layer.tile_size + layer.spacing;
+    uint tileset_width = layer.texture_width + layer.spacing - min(2u * layer.padding, layer.texture_width);
+    uint tileset_cols = max(tileset_width / tile_stride, 1u);
     uint tileset_row = resolved_tileset_index / tileset_cols;
     uint tileset_col = resolved_tileset_index % tileset_cols;
 
     vec2 texture_coords = vec2(
-        float(tileset_col) * grid_size_f / params.texture_width,
-        float(tileset_row) * grid_size_f / params.texture_height
+        float(layer.padding + tileset_col * tile_stride) / layer.texture_width,
+        float(layer.padding + tileset_row * tile_stride) / layer.texture_height
     );
 
     // set outputs for fragment shader
     //outputs.position = vertex_coords;
     outputs.textureCoord = texture_coords;
-    outputs.color = resolved_tileset_index != TRANSPARENT_TILE_ID ? vec4(1.0, 1.0, 1.0, 1.0) : vec4(1.0, 1.0, 1.0, 0.0);
+    float alpha = float((tile_flags >> 8) & 0xffu) / 255.0;
+    outputs.color = resolved_tileset_index != TRANSPARENT_TILE_ID ? vec4(1.0, 1.0, 1.0, alpha) : vec4(1.0, 1.0, 1.0, 0.0);
+    outputs.flags = tile_flags;
 
     gl_Position = vertex_coords;
     gl_PointSize = grid_size_f * params.view_scaling;
@@ -0,0 +1,671 @@
+//!
+//! Animation
+//!
+
+use std::collections::VecDeque;
+
+use crate::{animator::{Animator, AnimatorMode}, api::SpriteMeta, sprite::{SpriteData, SpriteSheet}};
+
+/// Named sequence of sprite sheet frames with individual frame durations.
+///
+/// Playback direction is given by the animator mode. Looping modes repeat
+/// infinitely, non-looping modes play `repeat` cycles (at least one) and
+/// stop at the end frame.
+#[derive(Clone, Debug)]
+pub struct AnimationClip {
+    name: String,
+    frames: Vec<usize>,
+    durations: Vec<f32>,
+    mode: AnimatorMode,
+    repeat: u32,
+    events: Vec<(usize, String)>
+}
+
+impl Default for AnimationClip {
+    fn default() -> Self {
+        Self::new("", AnimatorMode::ForwardLoop)
+    }
+}
+
+impl AnimationClip {
+
+    pub const DEFAULT_FRAME_DURATION: f32 = 0.1;
+
+    pub fn new(name: &str, mode: AnimatorMode) -> Self {
+        Self {
+            name: name.to_string(),
+            frames: Vec::new(),
+            durations: Vec::new(),
+            mode,
+            repeat: 0,
+            events: Vec::new()
+        }
+    }
+
+    /// Create clip from Aseprite tag direction (forward, reverse, pingpong,
+    /// pingpong_reverse) and repeat count (0 = infinite).
+    pub fn from_direction(name: &str, direction: &str, repeat: u32) -> Self {
+        let looping = repeat == 0;
+
+        let mode = match direction {
+            "reverse" => if looping { AnimatorMode::BackwardLoop } else { AnimatorMode::Backward },
+            "pingpong" | "pingpong_reverse" => if looping { AnimatorMode::PingPongLoop } else { AnimatorMode::PingPong },
+            _ => if looping { AnimatorMode::ForwardLoop } else { AnimatorMode::Forward }
+        };
+
+        let mut clip = Self::new(name, mode);
+        clip.repeat = repeat;
+        clip
+    }
+
+    pub fn add_frame(&mut self, frame: usize, duration: f32) -> &mut Self {
+        self.frames.push(frame);
+        self.durations.push(if duration > 0.0 { duration } else { Self::DEFAULT_FRAME_DURATION });
+        self
+    }
+
+    /// Add named event fired when playback enters the given clip frame position
+    pub fn add_event(&mut self, position: usize, name: &str) -> &mut Self {
+        self.events.push((position, name.to_string()));
+        self
+    }
+
+    /// Reverse frame order, used for "pingpong_reverse" tags
+    pub fn reverse(&mut self) -> &mut Self {
+        self.frames.reverse();
+        self.durations.reverse();
+        self
+    }
+
+    pub fn set_mode(&mut self, mode: AnimatorMode) -> &mut Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn set_repeat(&mut self, repeat: u32) -> &mut Self {
+        self.repeat = repeat;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn mode(&self) -> AnimatorMode {
+        self.mode
+    }
+
+    pub fn repeat(&self) -> u32 {
+        self.repeat
+    }
+
+    pub fn frames(&self) -> &[usize] {
+        &self.frames
+    }
+
+    pub fn durations(&self) -> &[f32] {
+        &self.durations
+    }
+
+    pub fn events(&self) -> &[(usize, String)] {
+        &self.events
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn is_looping(&self) -> bool {
+        matches!(self.mode, AnimatorMode::ForwardLoop | AnimatorMode::BackwardLoop | AnimatorMode::PingPongLoop)
+    }
+
+    /// Clip frame positions of a single playback cycle
+    pub fn sequence(&self) -> Vec<usize> {
+        let n = self.frames.len();
+        match self.mode {
+            AnimatorMode::Backward | AnimatorMode::BackwardLoop => (0..n).rev().collect(),
+            AnimatorMode::PingPong | AnimatorMode::PingPongLoop => {
+                // bounce without repeating the end frames
+                (0..n).chain((1..n.saturating_sub(1)).rev()).collect()
+            },
+            _ => (0..n).collect()
+        }
+    }
+
+    /// Duration of a single playback cycle in seconds
+    pub fn cycle_duration(&self) -> f32 {
+        self.sequence().iter().map(|&pos| self.durations[pos]).sum()
+    }
+
+    /// Total duration in seconds, infinite for looping clips
+    pub fn duration(&self) -> f32 {
+        if self.is_looping() {
+            f32::INFINITY
+        } else {
+            self.cycle_duration() * (self.repeat.max(1) as f32)
+        }
+    }
+
+    pub fn is_finished(&self, time: f32) -> bool {
+        !self.is_looping() && time >= self.duration()
+    }
+
+    /// Number of frame steps until the clip finishes, `None` for looping clips
+    pub fn num_steps(&self) -> Option<usize> {
+        if self.is_looping() {
+            None
+        } else {
+            Some(self.sequence().len() * (self.repeat.max(1) as usize))
+        }
+    }
+
+    /// Absolute frame step (counted over all cycles) at the given playback time
+    pub fn step_at(&self, time: f32) -> usize {
+
+        let sequence = self.sequence();
+        let cycle = self.cycle_duration();
+
+        if sequence.is_empty() || cycle <= 0.0 {
+            return 0;
+        }
+
+        if self.is_finished(time) {
+            return self.num_steps().unwrap_or(1) - 1;
+        }
+
+        let time = time.max(0.0);
+        let cycles = (time / cycle).floor();
+        let mut t = time - cycles * cycle;
+        let mut step = (cycles as usize) * sequence.len();
+
+        for &pos in &sequence[..sequence.len() - 1] {
+            let duration = self.durations[pos];
+            if t < duration {
+                break;
+            }
+            t -= duration;
+            step += 1;
+        }
+
+        step
+    }
+
+    /// Clip frame position of an absolute frame step
+    pub fn position_of_step(&self, step: usize) -> usize {
+        let sequence = self.sequence();
+        if sequence.is_empty() {
+            return 0;
+        }
+
+        sequence[step % sequence.len()]
+    }
+
+    /// Clip frame position at the given playback time
+    pub fn position_at(&self, time: f32) -> usize {
+
+        if self.frames.is_empty() {
+            return 0;
+        }
+
+        let sequence = self.sequence();
+
+        if self.is_finished(time) {
+            return match self.mode {
+                AnimatorMode::Forward => self.frames.len() - 1,
+                _ => 0
+            };
+        }
+
+        let cycle = self.cycle_duration();
+        let mut t = if cycle > 0.0 { time.max(0.0) % cycle } else { 0.0 };
+
+        for &pos in &sequence {
+            let duration = self.durations[pos];
+            if t < duration {
+                return pos;
+            }
+            t -= duration;
+        }
+
+        *sequence.last().unwrap()
+    }
+
+    /// Sprite sheet frame index at the given playback time
+    pub fn frame_at(&self, time: f32) -> usize {
+        if self.frames.is_empty() {
+            return 0;
+        }
+
+        self.frames[self.position_at(time)]
+    }
+
+}
+
+/// Event emitted by the animation player
+#[derive(Clone, Debug, PartialEq)]
+pub enum AnimationEvent {
+    /// Clip frame event (clip name, event name)
+    Frame(String, String),
+    /// Clip finished playing (clip name)
+    Complete(String)
+}
+
+type AnimationCallback = Box<dyn FnMut(&AnimationEvent) + Send>;
+
+#[derive(Default)]
+struct AnimationState {
+    clip: usize,
+    time: f32,
+    step: usize
+}
+
+/// Plays animation clips on a sprite.
+///
+/// Supports clip queueing, crossfading and frame and completion events.
+/// Events are collected and can be polled with `poll_event()`, or handled
+/// directly by a callback set with `on_event()`. The player implements
+/// `SpriteMeta`, so `Sprite<AnimationPlayer>` animates itself on `update()`.
+pub struct AnimationPlayer {
+    clips: Vec<AnimationClip>,
+    current: Option<AnimationState>,
+    previous: Option<AnimationState>,
+    fade: Animator,
+    queue: VecDeque<(usize, f32)>,
+    events: VecDeque<AnimationEvent>,
+    callback: Option<AnimationCallback>,
+    speed: f32,
+    paused: bool,
+    finished: bool
+}
+
+impl Default for AnimationPlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SpriteMeta for AnimationPlayer {
+    fn update(&mut self, data: &mut SpriteData, step: f32) {
+        self.update(step);
+        self.apply(data);
+    }
+}
+
+impl AnimationPlayer {
+    pub fn new() -> Self {
+        let mut fade = Animator::new(0.0, 1.0, 1.0, 1.0, AnimatorMode::Forward);
+        fade.active = false;
+
+        Self {
+            clips: Vec::new(),
+            current: None,
+            previous: None,
+            fade,
+            queue: VecDeque::new(),
+            events: VecDeque::new(),
+            callback: None,
+            speed: 1.0,
+            paused: false,
+            finished: false
+        }
+    }
+
+    /// Create player with all clips of a sprite sheet
+    pub fn from_sprite_sheet(sprite_sheet: &SpriteSheet) -> Self {
+        let mut player = Self::new();
+        for clip in sprite_sheet.clips() {
+            player.add_clip(clip.clone());
+        }
+        player
+    }
+
+    pub fn add_clip(&mut self, clip: AnimationClip) -> &mut Self {
+        match self.find(clip.name()) {
+            Some(index) => { self.clips[index] = clip; },
+            None => { self.clips.push(clip); }
+        }
+        self
+    }
+
+    pub fn clip(&self, name: &str) -> Option<&AnimationClip> {
+        self.clips.iter().find(|clip| clip.name() == name)
+    }
+
+    pub fn clip_mut(&mut self, name: &str) -> Option<&mut AnimationClip> {
+        self.clips.iter_mut().find(|clip| clip.name() == name)
+    }
+
+    fn find(&self, name: &str) -> Option<usize> {
+        self.clips.iter().position(|clip| clip.name() == name)
+    }
+
+    /// Set callback invoked for every frame and completion event
+    pub fn on_event<F: FnMut(&AnimationEvent) + Send + 'static>(&mut self, callback: F) -> &mut Self {
+        self.callback = Some(Box::new(callback));
+        self
+    }
+
+    /// Play clip immediately, clears the queue
+    pub fn play(&mut self, name: &str) -> bool {
+        self.crossfade(name, 0.0)
+    }
+
+    /// Blend from the current clip to the given clip, clears the queue.
+    /// The fading frame is passed to the blitter through `apply()`.
+    pub fn crossfade(&mut self, name: &str, duration: f32) -> bool {
+        let Some(index) = self.find(name) else {
+            return false;
+        };
+
+        self.queue.clear();
+        self.start(index, duration);
+        true
+    }
+
+    /// Queue clip to start after the current clip finishes, looping clips
+    /// are left at the end of the current cycle.
+    pub fn enqueue(&mut self, name: &str, fade_duration: f32) -> bool {
+        let Some(index) = self.find(name) else {
+            return false;
+        };
+
+        if self.current.is_none() || self.finished {
+            self.start(index, fade_duration);
+        } else {
+            self.queue.push_back((index, fade_duration));
+        }
+
+        true
+    }
+
+    pub fn stop(&mut self) {
+        self.current = None;
+        self.previous = None;
+        self.queue.clear();
+        self.finished = false;
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Set playback speed factor
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.max(0.0);
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.current.is_some() && !self.finished
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Name of the current clip
+    pub fn current(&self) -> Option<&str> {
+        self.current.as_ref().map(|state| self.clips[state.clip].name())
+    }
+
+    /// Playback time of the current clip in seconds
+    pub fn time(&self) -> f32 {
+        self.current.as_ref().map(|state| state.time).unwrap_or(0.0)
+    }
+
+    /// Current sprite sheet frame
+    pub fn frame(&self) -> usize {
+        match &self.current {
+            Some(state) => self.clips[state.clip].frame_at(state.time),
+            None => 0
+        }
+    }
+
+    /// Sprite sheet frame of the clip fading out and its remaining weight
+    pub fn fading(&self) -> Option<(usize, f32)> {
+        let state = self.previous.as_ref()?;
+        Some((self.clips[state.clip].frame_at(state.time), 1.0 - self.fade.value))
+    }
+
+    /// Weight of the current clip, below 1.0 while crossfading
+    pub fn weight(&self) -> f32 {
+        if self.previous.is_some() { self.fade.value } else { 1.0 }
+    }
+
+    /// Take next pending event
+    pub fn poll_event(&mut self) -> Option<AnimationEvent> {
+        self.events.pop_front()
+    }
+
+    /// Write current frame and the crossfade state to sprite data
+    pub fn apply(&self, data: &mut SpriteData) {
+        if self.current.is_some() {
+            data.frame = self.frame() as f32;
+        }
+
+        match self.fading() {
+            Some((frame, weight)) => data.set_fade(frame as f32, weight),
+            None => data.set_fade(0.0, 0.0)
+        }
+    }
+
+    fn start(&mut self, index: usize, fade_duration: f32) {
+        let previous = self.current.take();
+
+        if fade_duration > 0.0 && previous.is_some() {
+            self.previous = previous;
+            self.fade.set(0.0, 1.0, 0.0, 1.0 / fade_duration, AnimatorMode::Forward);
+            self.fade.active = true;
+        } else {
+            self.previous = None;
+            self.fade.active = false;
+        }
+
+        self.current = Some(AnimationState { clip: index, time: 0.0, step: 0 });
+        self.finished = false;
+        self.fire_frame_events(index, 0, 0);
+    }
+
+    pub fn update(&mut self, step: f32) {
+
+        if self.paused {
+            return;
+        }
+
+        let delta = step * self.speed;
+
+        if let Some(previous) = &mut self.previous {
+            previous.time += delta;
+            self.fade.update(delta);
+            if !self.fade.active {
+                self.previous = None;
+            }
+        }
+
+        let Some(state) = &mut self.current else {
+            return;
+        };
+
+        if self.finished {
+            return;
+        }
+
+        let index = state.clip;
+        let clip = &self.clips[index];
+
+        let last_step = state.step;
+        state.time += delta;
+        let time = state.time;
+        let step = clip.step_at(time);
+        state.step = step;
+
+        let cycle_len = clip.sequence().len().max(1);
+        let finished = clip.is_finished(time);
+        let cycle_ended = clip.is_looping() && step / cycle_len > last_step / cycle_len;
+
+        if step > last_step {
+            self.fire_frame_events(index, last_step + 1, step);
+        }
+
+        if finished {
+            self.finished = true;
+            let name = self.clips[index].name().to_string();
+            self.emit(AnimationEvent::Complete(name));
+        }
+
+        let next = if finished || cycle_ended { self.queue.pop_front() } else { None };
+        if let Some((next, fade_duration)) = next {
+            self.start(next, fade_duration);
+        }
+    }
+
+    fn fire_frame_events(&mut self, index: usize, first_step: usize, last_step: usize) {
+        let clip = &self.clips[index];
+        if clip.events().is_empty() {
+            return;
+        }
+
+        let mut fired = Vec::new();
+        for step in first_step..=last_step {
+            let position = clip.position_of_step(step);
+            for (event_position, event_name) in clip.events() {
+                if *event_position == position {
+                    fired.push(AnimationEvent::Frame(clip.name().to_string(), event_name.clone()));
+                }
+            }
+        }
+
+        for event in fired {
+            self.emit(event);
+        }
+    }
+
+    fn emit(&mut self, event: AnimationEvent) {
+        match &mut self.callback {
+            Some(callback) => callback(&event),
+            None => self.events.push_back(event)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_at() {
+
+        let mut clip = AnimationClip::from_direction("walk", "forward", 0);
+        clip.add_frame(4, 0.1).add_frame(5, 0.3).add_frame(6, 0.1);
+
+        assert_eq!(clip.frame_at(0.05), 4);
+        assert_eq!(clip.frame_at(0.2), 5);
+        assert_eq!(clip.frame_at(0.45), 6);
+        assert_eq!(clip.frame_at(0.55), 4);
+        assert!(!clip.is_finished(100.0));
+
+        let mut clip = AnimationClip::from_direction("bounce", "pingpong", 1);
+        clip.add_frame(0, 0.1).add_frame(1, 0.1).add_frame(2, 0.1);
+
+        assert_eq!(clip.sequence(), vec![0, 1, 2, 1]);
+        assert_eq!(clip.frame_at(0.35), 1);
+        assert!(clip.is_finished(0.45));
+        assert_eq!(clip.frame_at(0.45), 0);
+
+        let mut clip = AnimationClip::from_direction("back", "reverse", 2);
+        clip.add_frame(0, 0.0).add_frame(1, 0.0);
+
+        assert_eq!(clip.frame_at(0.0), 1);
+        assert_eq!(clip.frame_at(0.15), 0);
+        assert_eq!(clip.duration(), 0.4);
+    }
+
+    #[test]
+    fn test_player() {
+
+        let mut walk = AnimationClip::from_direction("walk", "forward", 0);
+        walk.add_frame(0, 0.1).add_frame(1, 0.1).add_event(1, "step");
+
+        let mut jump = AnimationClip::from_direction("jump", "forward", 1);
+        jump.add_frame(2, 0.1).add_frame(3, 0.1);
+
+        let mut player = AnimationPlayer::new();
+        player.add_clip(walk).add_clip(jump);
+
+        assert!(player.play("walk"));
+        assert_eq!(player.frame(), 0);
+
+        player.update(0.25);
+        assert_eq!(player.frame(), 0);
+        assert_eq!(player.poll_event(), Some(AnimationEvent::Frame("walk".to_string(), "step".to_string())));
+        assert_eq!(player.poll_event(), None);
+
+        player.enqueue("jump", 0.0);
+        player.update(0.1);
+        assert_eq!(player.current(), Some("walk"));
+        player.update(0.1);
+        assert_eq!(player.current(), Some("jump"));
+        assert_eq!(player.frame(), 2);
+        assert_eq!(player.poll_event(), Some(AnimationEvent::Frame("walk".to_string(), "step".to_string())));
+
+        player.update(0.25);
+        assert!(player.is_finished());
+        assert_eq!(player.frame(), 3);
+        assert_eq!(player.poll_event(), Some(AnimationEvent::Complete("jump".to_string())));
+
+        player.crossfade("walk", 0.5);
+        assert!(player.fading().is_some());
+        player.update(0.25);
+        assert!((player.weight() - 0.5).abs() < 0.001);
+        player.update(0.5);
+        assert!(player.fading().is_none());
+    }
+
+    #[test]
+    fn test_crossfade() {
+
+        let mut idle = AnimationClip::from_direction("idle", "forward", 0);
+        idle.add_frame(7, 1.0);
+
+        let mut run = AnimationClip::from_direction("run", "forward", 1);
+        run.add_frame(3, 1.0);
+
+        let mut player = AnimationPlayer::new();
+        player.add_clip(idle).add_clip(run);
+        player.play("idle");
+
+        let mut data = SpriteData::default();
+        player.apply(&mut data);
+        assert_eq!(data.frame, 7.0);
+        assert_eq!(data.fade_weight, 0.0);
+
+        // the previous clip frame fades out over the new one
+        player.crossfade("run", 0.4);
+        player.update(0.1);
+        player.apply(&mut data);
+        assert_eq!(data.frame, 3.0);
+        assert_eq!(data.fade_frame, 7.0);
+        assert!((data.fade_weight - 0.75).abs() < 0.001);
+
+        player.update(0.4);
+        player.apply(&mut data);
+        assert_eq!(data.frame, 3.0);
+        assert_eq!(data.fade_weight, 0.0);
+
+        // play cuts without fading
+        player.play("idle");
+        player.apply(&mut data);
+        assert_eq!(data.fade_weight, 0.0);
+    }
+}
@@ -2,7 +2,7 @@
 //! Animator
 //!
 
-#[derive(PartialEq, Default, Debug)]
+#[derive(PartialEq, Default, Debug, Clone, Copy)]
 pub enum AnimatorMode {
     #[default]
     ForwardLoop,
@@ -83,27 +83,48 @@ pub type Vertex = crate::primitives::Vertex;
 /// Vertex queue
 pub type VertexQueue = crate::primitives::VertexQueue;
 
+/// Thick anti-aliased line queue
+pub type LineQueue = crate::primitives::LineQueue;
+
 /// Quadric
 pub type Quad = crate::primitives::Quad;
 
 /// Quad queue
 pub type QuadQueue = crate::primitives::QuadQueue;
 
+/// Quad instance
+pub type QuadInstance = crate::primitives::QuadInstance;
+
+/// Instance queue
+pub type InstanceQueue = crate::primitives::InstanceQueue;
+
 /// Tile (index)
 pub type Tile = crate::primitives::Tile;
 
 /// Tile queue
 pub type TileQueue = crate::primitives::TileQueue;
 
+/// RGBA color
+pub type Color = crate::primitives::Color;
+
 /// Texture
 pub type Texture = crate::texture::Texture;
 
 /// Typed uniform buffer
 pub type Uniform<T> = crate::buffer::Uniform<T>;
 
+/// Typed shader storage buffer
+pub type ShaderStorage<T> = crate::buffer::ShaderStorage<T>;
+
 /// Typed push constants
 pub type PushConstants<T> = crate::buffer::PushConstants<T>;
 
+/// Compute program
+pub type ComputeProgram = crate::compute::ComputeProgram;
+
+/// Compute program lock reference
+pub type ComputeProgramLockRef = crate::compute::ComputeProgramLockRef;
+
 /// Random number generator
 pub type Random = crate::random::Random;
 
@@ -122,18 +143,147 @@ pub type SpriteData = crate::sprite::SpriteData;
 /// Typed sprite
 pub type Sprite<T=crate::sprite::DefaultSpriteMeta> = crate::sprite::Sprite<T>;
 
+/// Sprite sheet
+pub type SpriteSheet = crate::sprite::SpriteSheet;
+
+/// Shared sprite sheet reference
+pub type SpriteSheetLockRef = crate::sprite::SpriteSheetLockRef;
+
+/// Sprite sheet frame
+pub type SpriteFrame = crate::sprite::SpriteFrame;
+
+/// Sprite sheet slice
+pub type SpriteSlice = crate::sprite::SpriteSlice;
+
+/// Nine-slice panel
+pub type NineSlice = crate::nineslice::NineSlice;
+
+/// Shared nine-slice reference
+pub type NineSliceLockRef = crate::nineslice::NineSliceLockRef;
+
+/// Nine-slice edge and centre mode
+pub type NineSliceMode = crate::nineslice::NineSliceMode;
+
+/// Nine-slice quad (view rect and texture coordinates)
+pub type NineSlicePatch = crate::nineslice::NineSlicePatch;
+
+/// Instanced sprite batch
+pub type SpriteBatch = crate::batch::SpriteBatch;
+
+/// Shared sprite batch reference
+pub type SpriteBatchLockRef = crate::batch::SpriteBatchLockRef;
+
+/// Debug shape drawing
+pub type DebugDraw = crate::debug::DebugDraw;
+
+/// Shared debug draw reference
+pub type DebugDrawLockRef = crate::debug::DebugDrawLockRef;
+
+/// Debug shape coordinate space
+pub type DebugDrawSpace = crate::debug::DebugDrawSpace;
+
 /// Animator mode
 pub type AnimatorMode = crate::animator::AnimatorMode;
 
 /// Animator
 pub type Animator = crate::animator::Animator;
 
+/// Animation clip
+pub type AnimationClip = crate::animation::AnimationClip;
+
+/// Animation clip player
+pub type AnimationPlayer = crate::animation::AnimationPlayer;
+
+/// Animation player event
+pub type AnimationEvent = crate::animation::AnimationEvent;
+
+/// Easing curve
+pub type Easing = crate::tween::Easing;
+
+/// Value interpolation
+pub type Tween<T> = crate::tween::Tween<T>;
+
+/// Tween delay
+pub type TweenDelay = crate::tween::TweenDelay;
+
+/// Tween sequence or parallel group
+pub type TweenGroup = crate::tween::TweenGroup;
+
+/// Tween group mode
+pub type TweenGroupMode = crate::tween::TweenGroupMode;
+
+/// Tween runner
+pub type Tweens = crate::tween::Tweens;
+
+/// Tween interpolation trait
+pub use crate::tween::Tweenable;
+
+/// Tween playback trait
+pub use crate::tween::Tweening;
+
+/// Infinite tween repeat count
+pub const TWEEN_REPEAT_INFINITE: i32 = crate::tween::REPEAT_INFINITE;
+
+/// Particle system
+pub type ParticleSystem = crate::particles::ParticleSystem;
+
+/// Particle emitter settings
+pub type ParticleEffect = crate::particles::ParticleEffect;
+
+/// Shared particle effect reference
+pub type ParticleEffectLockRef = crate::particles::ParticleEffectLockRef;
+
+/// Particle value over lifetime
+pub type ParticleCurve<T> = crate::particles::ParticleCurve<T>;
+
+/// Particle attractor
+pub type ParticleAttractor = crate::particles::ParticleAttractor;
+
+/// Particle emitter shape
+pub type EmitterShape = crate::particles::EmitterShape;
+
+/// 2D lighting
+pub type Lighting = crate::lighting::Lighting;
+
+/// Shared lighting reference
+pub type LightingLockRef = crate::lighting::LightingLockRef;
+
+/// Point or spot light
+pub type Light = crate::lighting::Light;
+
+/// Light type
+pub type LightType = crate::lighting::LightType;
+
+/// Shadow casting line segment
+pub type Occluder = crate::lighting::Occluder;
+
+/// Offscreen render target
+pub type RenderTarget = crate::target::RenderTarget;
+
+/// Shared render target reference
+pub type RenderTargetLockRef = crate::target::RenderTargetLockRef;
+
 /// Font
 pub type Font = crate::font::Font;
 
 /// Shared font reference
 pub type FontLockRef = crate::font::FontLockRef;
 
+/// Font glyph metrics
+pub type Glyph = crate::font::Glyph;
+
+/// Wrapped and aligned text with markup
+pub type TextLayout = crate::text::TextLayout;
+
+/// Laid out text character
+pub type TextGlyph = crate::text::TextGlyph;
+
+/// Text alignment
+pub type TextAlign = crate::text::TextAlign;
+
+/// Animated text effect
+pub type TextEffect = crate::text::TextEffect;
+
 /// Bitmap
 pub type Bitmap = crate::bitmap::Bitmap;
 
@@ -173,6 +323,45 @@ pub type MapLevel = crate::maps::MapLevel;
 /// Map layer
 pub type MapLayer = crate::maps::MapLayer;
 
+/// Tileset of a map built at runtime
+pub type MapTileset = crate::maps::MapTileset;
+
+/// Arrangement of the levels of a map
+pub type WorldLayout = crate::maps::WorldLayout;
+
+/// Neighbour level of a map level
+pub type LevelNeighbour = crate::maps::LevelNeighbour;
+
+/// Direction of a neighbour level
+pub type NeighbourDirection = crate::maps::NeighbourDirection;
+
+/// Map entity
+pub type MapEntity = crate::entities::MapEntity;
+
+/// Custom field of a map entity
+pub type EntityField = crate::entities::EntityField;
+
+/// Typed value of an entity field
+pub type FieldValue = crate::entities::FieldValue;
+
+/// Reference to a map entity
+pub type EntityRef = crate::entities::EntityRef;
+
+/// Tileset rectangle of a map entity
+pub type EntityTile = crate::entities::EntityTile;
+
+/// IntGrid values of a map layer with collision queries
+pub type IntGrid = crate::collision::IntGrid;
+
+/// Collision behaviour of an IntGrid value
+pub type CellKind = crate::collision::CellKind;
+
+/// Raycast result
+pub type RayHit = crate::collision::RayHit;
+
+/// Move-and-slide result
+pub type MoveResult = crate::collision::MoveResult;
+
 /// Input
 pub type Input = crate::input::Input;
 
@@ -184,11 +373,20 @@ pub type StaticDataDescriptor = gamebuilder::manifest::StaticDataDescriptor;
 pub type StaticBitmapDescriptor = gamebuilder::manifest::StaticBitmapDescriptor;
 pub type StaticTextureDescriptor = gamebuilder::manifest::StaticTextureDescriptor;
 pub type StaticFontDescriptor = gamebuilder::manifest::StaticFontDescriptor;
+pub type StaticGlyphDescriptor = gamebuilder::manifest::StaticGlyphDescriptor;
+pub type StaticKerningDescriptor = gamebuilder::manifest::StaticKerningDescriptor;
+pub type StaticSpriteSheetDescriptor = gamebuilder::manifest::StaticSpriteSheetDescriptor;
+pub type StaticNineSliceDescriptor = gamebuilder::manifest::StaticNineSliceDescriptor;
+pub type StaticSpriteFrameDescriptor = gamebuilder::manifest::StaticSpriteFrameDescriptor;
+pub type StaticSpriteTagDescriptor = gamebuilder::manifest::StaticSpriteTagDescriptor;
+pub type StaticSpriteSliceDescriptor = gamebuilder::manifest::StaticSpriteSliceDescriptor;
 pub type StaticShaderDescriptor = gamebuilder::manifest::StaticShaderDescriptor;
 pub type StaticMaterialDescriptor = gamebuilder::manifest::StaticMaterialDescriptor;
 pub type StaticTaskDescriptor = gamebuilder::manifest::StaticTaskDescriptor;
 pub type StaticSampleDescriptor = gamebuilder::manifest::StaticSampleDescriptor;
 pub type StaticMapDescriptor = gamebuilder::manifest::StaticMapDescriptor;
+pub type StaticMapLevelDescriptor = gamebuilder::manifest::StaticMapLevelDescriptor;
+pub type StaticParticleEffectDescriptor = gamebuilder::manifest::StaticParticleEffectDescriptor;
 
 /// Sprite meta data encoder
 pub trait SpriteMeta {
@@ -0,0 +1,326 @@
+//!
+//! Batch
+//!
+
+use std::sync::{Arc, Mutex};
+
+use crate::{api::{Disposable, LockRef, SpriteMeta}, buffer::{ShaderStorage, Uniform}, constants::Constants, error::Error, material::MaterialLockRef, math::Vec2, primitives::InstanceQueue, sprite::{Sprite, SpriteData, SpriteFrame, SpriteSheet, SpriteSheetLockRef, find_frame, sprite_rect}};
+
+const SHADER_PARAMS_BINDING: u32 = 0;
+const FRAME_LOOKUP_BINDING: u32 = 2;
+
+#[repr(C)]
+#[derive(Default)]
+struct SpriteBatchShaderParams {
+    offset_x: f32,
+    offset_y: f32,
+    window_width: f32,
+    window_height: f32,
+    view_width: f32,
+    view_height: f32,
+    view_x: f32,
+    view_y: f32,
+    view_scaling: f32
+}
+
+#[repr(C)]
+#[derive(Default, Clone, Debug, PartialEq)]
+struct SpriteBatchFrameRect {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32
+}
+
+impl From<&SpriteFrame> for SpriteBatchFrameRect {
+    fn from(frame: &SpriteFrame) -> Self {
+        let rect = &frame.rect;
+        Self { x: rect.x, y: rect.y, w: rect.z, h: rect.w }
+    }
+}
+
+/// Frame and opacity of each instance drawn for a sprite, while
+/// crossfading the fade frame comes first
+fn sprite_layers(data: &SpriteData) -> impl Iterator<Item = (u32, f32)> {
+    let alpha = data.color.a;
+    let fading = data.fade_weight > 0.0;
+
+    let layers = if fading {
+        [(data.fade_frame as u32, alpha * data.fade_weight), (data.frame as u32, alpha * (1.0 - data.fade_weight))]
+    } else {
+        [(data.frame as u32, alpha), (0, 0.0)]
+    };
+
+    layers.into_iter().take(if fading { 2 } else { 1 })
+}
+
+/// Instance position, size and pivot of a sprite frame. Trimmed frames
+/// are placed as by `Blitter`, rotation stays around the sprite pivot.
+fn sprite_instance(data: &SpriteData, frame: &SpriteFrame) -> (Vec2, Vec2, Vec2) {
+    let rect = sprite_rect(data, frame);
+    let size = Vec2::new(rect.z, rect.w);
+    let pivot = Vec2::new(data.position.x - rect.x, data.position.y - rect.y);
+
+    (data.position, size, pivot)
+}
+
+/// Sprite batch using GPU instancing. Each sprite is a single
+/// instance of a unit quad, sprite sheet frames are resolved
+/// in the vertex shader. A batch draws from a single sprite sheet,
+/// sprites with a sheet of their own must use that sheet.
+pub struct SpriteBatch {
+    instance_queue: InstanceQueue,
+    material: Option<MaterialLockRef>,
+    frames: Vec<SpriteFrame>,
+    sprite_sheet: Option<SpriteSheetLockRef>,
+    stale_frames: Vec<bool>,    // per frame in flight, frame lookup not uploaded yet
+    shader_params: Uniform::<SpriteBatchShaderParams>,
+    frame_lookup: ShaderStorage::<SpriteBatchFrameRect>
+}
+
+pub type SpriteBatchRef = std::sync::Arc<SpriteBatch>;
+pub type SpriteBatchLockRef = LockRef<SpriteBatch>;
+
+impl Disposable for SpriteBatch {
+    fn dispose(&mut self) {
+        self.instance_queue.dispose();
+    }
+}
+
+impl SpriteBatch {
+    pub fn new(capacity: usize) -> Result<Self, Error> {
+
+        let instance_queue = InstanceQueue::new(capacity);
+        let shader_params = Uniform::<SpriteBatchShaderParams>::new(SHADER_PARAMS_BINDING, 0)?;
+        let frame_lookup = ShaderStorage::<SpriteBatchFrameRect>::new(FRAME_LOOKUP_BINDING, 1)?;
+
+        let mut batch = Self {
+            instance_queue,
+            material: None,
+            frames: Vec::new(),
+            sprite_sheet: None,
+            stale_frames: vec![true; crate::globals::pipeline().frame_count()],
+            shader_params,
+            frame_lookup
+        };
+
+        batch.set_sprite_sheet(&SpriteSheet::default())?;
+
+        Ok(batch)
+    }
+
+    pub fn with_default_capacity() -> Result<Self, Error> {
+        Self::new(Constants::DEFAULT_BLITTER_BATCH_CAPACITY)
+    }
+
+    pub fn to_lockref(batch: Self) -> SpriteBatchLockRef {
+        Arc::new(Mutex::new(batch))
+    }
+
+    pub fn set_material(&mut self, material_ref: &MaterialLockRef) {
+        material_ref.lock().unwrap().add_uniform(&self.shader_params);
+        material_ref.lock().unwrap().add_storage(&self.frame_lookup);
+        self.material = Some(material_ref.clone());
+    }
+
+    /// Set the sheet of the frame lookup, also used by sprites with
+    /// this sheet assigned
+    pub fn set_sprite_sheet_ref(&mut self, sprite_sheet_ref: &SpriteSheetLockRef) -> Result<(), Error> {
+        self.set_sprite_sheet(&sprite_sheet_ref.lock().unwrap())?;
+        self.sprite_sheet = Some(sprite_sheet_ref.clone());
+        Ok(())
+    }
+
+    /// Set the sheet of the frame lookup. The lookup buffer of each frame
+    /// in flight is updated when drawing that frame, resizing waits for
+    /// the device to be idle.
+    pub fn set_sprite_sheet(&mut self, sprite_sheet: &SpriteSheet) -> Result<(), Error> {
+
+        self.sprite_sheet = None;
+        self.frames = sprite_sheet.frames().to_vec();
+
+        let num_frames = sprite_sheet.len().max(1);
+
+        if num_frames != self.frame_lookup.len() {
+            // the buffers might still be in use by frames in flight
+            let device = crate::globals::device();
+            unsafe { device.obj.device_wait_idle() }.map_err(|e| Error::from(format!("failed to wait for device: {e}")))?;
+
+            self.frame_lookup.resize(num_frames);
+            if let Some(material) = &self.material {
+                material.lock().unwrap().invalidate();
+            }
+        }
+
+        let rects = self.frame_lookup.data_mut();
+        for (index, frame) in sprite_sheet.frames().iter().enumerate() {
+            rects[index] = SpriteBatchFrameRect::from(frame);
+        }
+
+        self.stale_frames.fill(true);
+
+        Ok(())
+    }
+
+    pub fn generate_sprite_sheet(&mut self, width: usize, height: usize, tile_width: usize, tile_height: usize) -> Result<(), Error> {
+        let sprite_sheet = SpriteSheet::new(width, height, tile_width, tile_height);
+        self.set_sprite_sheet(&sprite_sheet)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.instance_queue.capacity()
+    }
+
+    pub fn count(&self) -> usize {
+        self.instance_queue.count()
+    }
+
+    pub fn begin(&mut self) {
+        self.instance_queue.begin();
+    }
+
+    pub fn end(&mut self) {
+        self.instance_queue.end();
+    }
+
+    pub fn clear(&mut self) {
+        self.instance_queue.clear();
+    }
+
+    /// Push sprite instance, while crossfading the fade frame is pushed
+    /// as a second instance
+    pub fn push_sprite(&mut self, data: &SpriteData) {
+
+        // frames are looked up in the batch sheet only
+        debug_assert!(
+            data.sheet.as_ref().is_none_or(|sheet| self.sprite_sheet.as_ref().is_some_and(|own| Arc::ptr_eq(sheet, own))),
+            "sprite sheet differs from the sprite batch sheet"
+        );
+
+        for (frame, alpha) in sprite_layers(data) {
+            self.push_sprite_frame(data, frame, alpha);
+        }
+    }
+
+    fn push_sprite_frame(&mut self, data: &SpriteData, frame: u32, alpha: f32) {
+
+        let (position, size, pivot) = sprite_instance(data, find_frame(&self.frames, frame as usize));
+        let color = &data.color;
+
+        self.instance_queue.push(
+            position.x, position.y,
+            size.x, size.y,
+            pivot.x, pivot.y,
+            data.rotation,
+            color.r, color.g, color.b, alpha,
+            frame
+        );
+    }
+
+    pub fn draw_sprite<T: Default + SpriteMeta>(&mut self, sprite: &mut Sprite<T>) {
+        let data = sprite.encode();
+        self.push_sprite(data);
+    }
+
+    pub fn draw(&mut self, offset_x: f32, offset_y: f32) {
+
+        let renderer = crate::api::renderer_mut();
+
+        if let Some(material) = &self.material {
+            renderer.set_material(material);
+        }
+
+        let shader_params = self.shader_params.data_mut();
+        shader_params.offset_x = offset_x;
+        shader_params.offset_y = offset_y;
+
+        // window metrics
+        let metrics = crate::api::metrics();
+        shader_params.window_width = metrics.window_width;
+        shader_params.window_height = metrics.window_height;
+        shader_params.view_width = metrics.view_width;
+        shader_params.view_height = metrics.view_height;
+        shader_params.view_x = metrics.view_x;
+        shader_params.view_y = metrics.view_y;
+        shader_params.view_scaling = metrics.view_scaling;
+
+        self.shader_params.update().unwrap();
+
+        // frame lookup of the current frame, the others might still be in use
+        let frame_index = crate::globals::pipeline().current_frame().index as usize;
+        if let Some(stale) = self.stale_frames.get_mut(frame_index).filter(|stale| **stale) {
+            self.frame_lookup.update().unwrap();
+            *stale = false;
+        }
+
+        if let Some(material) = &self.material {
+            material.lock().unwrap().bind_uniforms();
+        }
+
+        self.instance_queue.draw();
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::Color;
+
+    #[test]
+    fn test_sprite_layers() {
+        let mut data = SpriteData {
+            frame: 4.0,
+            color: Color::rgba(1.0, 1.0, 1.0, 0.5),
+            ..Default::default()
+        };
+
+        assert_eq!(sprite_layers(&data).collect::<Vec<_>>(), vec![(4, 0.5)]);
+
+        // crossfade draws the fading frame below the current one
+        data.fade_frame = 9.0;
+        data.fade_weight = 0.25;
+        assert_eq!(sprite_layers(&data).collect::<Vec<_>>(), vec![(9, 0.125), (4, 0.375)]);
+
+        data.fade_weight = 0.0;
+        assert_eq!(sprite_layers(&data).count(), 1);
+    }
+
+    #[test]
+    fn test_sprite_instance() {
+        let mut sheet = SpriteSheet::with_size(128, 64);
+
+        // 20x24 pixels trimmed from a 32x32 source frame at (6, 4)
+        let trimmed = sheet.add_trimmed_frame("", 0.0, 0.0, 20.0, 24.0, 6.0, 4.0, 32.0, 32.0);
+        let frame = sheet.frame(trimmed);
+
+        let mut data = SpriteData::default();
+        data.set_position(100.0, 50.0);
+        data.set_pivot(16.0, 16.0);
+
+        // zero size uses the source size, the quad covers the trimmed pixels
+        let (position, size, pivot) = sprite_instance(&data, frame);
+        assert_eq!(position, Vec2::new(100.0, 50.0));
+        assert_eq!(size, Vec2::new(20.0, 24.0));
+        assert_eq!(pivot, Vec2::new(10.0, 12.0));
+
+        // unrotated, the instance matches the blitter quad
+        let rect = sprite_rect(&data, frame);
+        assert_eq!((position.x - pivot.x, position.y - pivot.y), (rect.x, rect.y));
+
+        data.set_size(64.0, 16.0);
+        let (_, size, pivot) = sprite_instance(&data, frame);
+        assert_eq!(size, Vec2::new(40.0, 12.0));
+        assert_eq!(pivot, Vec2::new(4.0, 14.0));
+    }
+
+    #[test]
+    fn test_frame_lookup() {
+        let sheet = SpriteSheet::new(64, 32, 16, 16);
+        let rects = sheet.frames().iter().map(SpriteBatchFrameRect::from).collect::<Vec<_>>();
+
+        assert_eq!(rects.len(), 8);
+        assert_eq!(rects[0], SpriteBatchFrameRect { x: 0.0, y: 0.0, w: 0.25, h: 0.5 });
+        assert_eq!(rects[5], SpriteBatchFrameRect { x: 0.25, y: 0.5, w: 0.25, h: 0.5 });
+    }
+}
@@ -92,6 +92,8 @@ impl Bitmap {
     pub fn from_memory(data: &[u8], format: &str) -> Result<Self, Error> {
         if format == "charmem" {
             Self::from_charmem(data)
+        } else if format == "rgba" {
+            Self::from_rgba(data)
         } else {
             Self::from_image_memory(data)
         }
@@ -138,6 +140,26 @@ impl Bitmap {
         })
     }
 
+    pub fn from_rgba(data: &[u8]) -> Result<Self, Error> {
+
+        // Raw 32 bit pixels with a header of width and height (u32, little endian),
+        // as written for generated font atlases
+
+        if data.len() < 8 {
+            return Err(Error::from("invalid rgba data"));
+        }
+
+        let width = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        let height = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+        let size = (width as usize) * (height as usize) * 4;
+
+        if width == 0 || height == 0 || data.len() < 8 + size {
+            return Err(Error::from("invalid rgba data"));
+        }
+
+        Self::from_data(width, height, 32, data[8..8 + size].to_vec())
+    }
+
     pub fn from_charmem(data: &[u8]) -> Result<Self, Error> {
 
         // Decode commodore character set format
@@ -4,13 +4,14 @@
 
 use std::sync::{Arc, Mutex};
 
-use crate::{api::{Disposable, LockRef, SpriteMeta}, constants::Constants, font::Font, math::Vec4, primitives::QuadQueue, sprite::{Sprite, SpriteData, SpriteSheet}};
+use crate::{api::{Disposable, LockRef, SpriteMeta}, constants::Constants, font::{Font, Glyph}, math::Vec4, nineslice::NineSlice, primitives::{Color, QuadQueue}, sprite::{Sprite, SpriteData, SpriteSheet, SpriteSheetLockRef, sprite_rect}, text::TextLayout};
 
 pub struct Blitter {
     capacity: usize,
     usage: usize,
     quad_queue: QuadQueue,
-    sprite_sheet: SpriteSheet
+    default_sprite_sheet: SpriteSheetLockRef,
+    sprite_sheet: SpriteSheetLockRef
 }
 
 pub type BlitterRef = std::sync::Arc<Blitter>;
@@ -30,18 +31,33 @@ impl Default for Blitter {
 impl Blitter {
     pub fn new(capacity: usize) -> Self {
         let quad_queue = QuadQueue::new(capacity);
-        let sprite_sheet = SpriteSheet::default();
+        let sprite_sheet = SpriteSheet::to_lockref(SpriteSheet::default());
 
         Self {
             capacity,
             usage: 0,
             quad_queue,
+            default_sprite_sheet: sprite_sheet.clone(),
             sprite_sheet
         }
     }
 
     pub fn generate_sprite_sheet(&mut self, width: usize, height: usize, tile_width: usize, tile_height: usize) {
-        self.sprite_sheet.alloc(width, height, tile_width, tile_height);
+        let sprite_sheet = SpriteSheet::new(width, height, tile_width, tile_height);
+        self.default_sprite_sheet = SpriteSheet::to_lockref(sprite_sheet);
+        self.sprite_sheet = self.default_sprite_sheet.clone();
+    }
+
+    /// Set active sprite sheet, None selects the generated default sheet
+    pub fn set_sprite_sheet(&mut self, sprite_sheet: Option<&SpriteSheetLockRef>) {
+        self.sprite_sheet = match sprite_sheet {
+            Some(sprite_sheet) => sprite_sheet.clone(),
+            None => self.default_sprite_sheet.clone()
+        };
+    }
+
+    pub fn sprite_sheet(&self) -> &SpriteSheetLockRef {
+        &self.sprite_sheet
     }
 
     pub fn to_lockref(blitter: Self) -> BlitterLockRef {
@@ -61,20 +77,31 @@ impl Blitter {
         self.quad_queue.clear();
     }
 
+    /// Push sprite quad, while crossfading the fade frame is pushed
+    /// first and both frames share the sprite opacity
     pub fn push_sprite(&mut self, data: &SpriteData) {
 
-        let q = &mut self.quad_queue;
+        if data.fade_weight > 0.0 {
+            self.push_sprite_frame(data, data.fade_frame as usize, data.color.a * data.fade_weight);
+            self.push_sprite_frame(data, data.frame as usize, data.color.a * (1.0 - data.fade_weight));
+        } else {
+            self.push_sprite_frame(data, data.frame as usize, data.color.a);
+        }
+    }
+
+    fn push_sprite_frame(&mut self, data: &SpriteData, frame_index: usize, alpha: f32) {
 
-        let position = &data.position;
-        let pivot = &data.pivot;
-        let size = &data.size;
+        let q = &mut self.quad_queue;
         let color = &data.color;
-        let texcoords = self.sprite_sheet.rect(data.frame as usize);
+
+        let sheet = data.sheet.as_ref().unwrap_or(&self.sprite_sheet).lock().unwrap();
+        let frame = sheet.frame(frame_index);
+        let texcoords = &frame.rect;
+        let rect = sprite_rect(data, frame);
 
         q.push(
-            position.x - pivot.x, position.y - pivot.x,
-            size.x, size.y,
-            color.r, color.g, color.b, color.a,
+            rect.x, rect.y, rect.z, rect.w,
+            color.r, color.g, color.b, alpha,
             texcoords.x, texcoords.y, texcoords.z, texcoords.w,
             0x0, 0x0
         )
@@ -85,6 +112,21 @@ impl Blitter {
         self.push_sprite(data);
     }
 
+    pub fn draw_nine_slice(&mut self, nine_slice: &NineSlice, rect: &Vec4, color: &Color) {
+        let q = &mut self.quad_queue;
+
+        for patch in nine_slice.patches(rect) {
+            let r = &patch.rect;
+            let t = &patch.texcoords;
+            q.push(
+                r.x, r.y, r.z, r.w,
+                color.r, color.g, color.b, color.a,
+                t.x, t.y, t.z, t.w,
+                0x0, 0x0
+            );
+        }
+    }
+
     fn draw_char_by_index_impl(&mut self, font: &Font, x: f32, y: f32, w: f32, h: f32, idx: u32) {
         let q = &mut self.quad_queue;
 
@@ -102,60 +144,123 @@ impl Blitter {
         self.draw_char_by_index_impl(font, x, y, font.char_width() as f32, font.char_height() as f32, idx);
     }
 
-    fn draw_char_impl(&mut self, font: &Font, x: f32, y: f32, w: f32, h: f32, c: char) {
-        let idx = font.charset().find(c).unwrap_or_default();
-        self.draw_char_by_index_impl(font, x, y, w, h, idx as u32);
+    #[allow(clippy::too_many_arguments)]
+    fn push_glyph(&mut self, font: &Font, x: f32, y: f32, scale_x: f32, scale_y: f32, c: char, color: &Color) -> f32 {
+
+        let Some(glyph) = font.glyph(c) else {
+            return 0.0;
+        };
+
+        let r = &glyph.rect;
+
+        // the texture mask selects the atlas page
+        if let Some(rect) = glyph_rect(&glyph, x, y, scale_x, scale_y) {
+            self.quad_queue.push(
+                rect.x, rect.y, rect.z, rect.w,
+                color.r, color.g, color.b, color.a,
+                r.x, r.y, r.z, r.w,
+                glyph.texture_mask(), 0x0
+            );
+        }
+
+        glyph.advance * scale_x
     }
 
     pub fn draw_char(&mut self, font: &Font, x: f32, y: f32, c: char) {
-        self.draw_char_impl(font, x, y, font.char_width() as f32, font.char_height() as f32, c);
+        self.push_glyph(font, x, y, 1.0, 1.0, c, &Color::white());
     }
 
     pub fn draw_text(&mut self, font: &Font, x: f32, y: f32, text: &str) {
+        self.draw_text_scaled(font, x, y, 1.0, 1.0, text);
+    }
+
+    /// Draw text with kerning, lines are separated by '\n'
+    pub fn draw_text_scaled(&mut self, font: &Font, x: f32, y: f32, scale_x: f32, scale_y: f32, text: &str) {
 
         if text.is_empty() { return; }
 
         let mut xpos = x;
-        let ypos = y;
-        let w = font.char_width() as f32;
-        let h = font.char_height() as f32;
+        let mut ypos = y;
+        let line_height = scale_y * font.line_height() as f32;
+        let mut prev: Option<char> = None;
+        let color = Color::white();
 
         for c in text.chars() {
-            self.draw_char_impl(font, xpos, ypos, w, h, c);
-            xpos += w;
+            if c == '\n' {
+                xpos = x;
+                ypos += line_height;
+                prev = None;
+                continue;
+            }
+
+            if let Some(p) = prev {
+                xpos += font.kerning(p, c) * scale_x;
+            }
+
+            xpos += self.push_glyph(font, xpos, ypos, scale_x, scale_y, c, &color);
+            prev = Some(c);
         }
-
     }
 
-    pub fn draw_text_scaled(&mut self, font: &Font, x: f32, y: f32, scale_x: f32, scale_y: f32, text: &str) {
+    /// Draw laid out text with markup colors, effects and typewriter reveal
+    pub fn draw_text_layout(&mut self, font: &Font, layout: &mut TextLayout, x: f32, y: f32) {
 
-        if text.is_empty() { return; }
+        if layout.is_dirty() {
+            layout.layout(font);
+        }
 
-        let mut xpos = x;
-        let ypos = y;
-        let w = scale_x * font.char_width() as f32;
-        let h = scale_y * font.char_height() as f32;
+        let time = layout.time();
+        let scale = layout.scale();
+        let line_height = font.line_height() as f32 * scale;
 
-        for c in text.chars() {
-            self.draw_char_impl(font, xpos, ypos, w, h, c);
-            xpos += w;
+        for glyph in layout.glyphs().iter().take(layout.visible_count()) {
+            let offset = glyph.effect.offset(time, glyph.index, line_height);
+            self.push_glyph(font, x + glyph.x + offset.x, y + glyph.y + offset.y, scale, scale, glyph.c, &glyph.color);
         }
     }
 
+    /// Draw text scaled to fill the rectangle
     pub fn draw_text_rect(&mut self, font: &Font, rect: &Vec4, text: &str) {
 
-        if text.is_empty() { return; }
+        let extent = font.get_text_extent(text);
+        if extent.x <= 0.0 || extent.y <= 0.0 { return; }
+
+        self.draw_text_scaled(font, rect.x, rect.y, rect.z / extent.x, rect.w / extent.y, text);
+    }
 
-        let mut xpos = rect.x;
-        let ypos = rect.y;
-        let w = rect.z / (text.len() as f32);
-        let h = rect.w;
+}
 
-        for c in text.chars() {
-            self.draw_char_impl(font, xpos, ypos, w, h, c);
-            xpos += w;
-        }
+/// Screen rectangle (x, y, w, h) of a glyph at the pen position,
+/// None for glyphs without pixels (e.g. space)
+fn glyph_rect(glyph: &Glyph, x: f32, y: f32, scale_x: f32, scale_y: f32) -> Option<Vec4> {
 
+    if glyph.size.x <= 0.0 || glyph.size.y <= 0.0 {
+        return None;
     }
 
+    Some(Vec4::new(
+        x + glyph.offset.x * scale_x, y + glyph.offset.y * scale_y,
+        glyph.size.x * scale_x, glyph.size.y * scale_y
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glyph_rect() {
+        let fnt = "common lineHeight=18 base=14 scaleW=128 scaleH=64 pages=2\n\
+            page id=0 file=\"sans_0.png\"\n\
+            page id=1 file=\"sans_1.png\"\n\
+            char id=32 x=0 y=0 width=0 height=0 xoffset=0 yoffset=0 xadvance=4 page=0\n\
+            char id=86 x=10 y=0 width=10 height=12 xoffset=-1 yoffset=2 xadvance=10 page=1\n";
+
+        let font = Font::from_bmfont(fnt.as_bytes(), &[]).unwrap();
+        assert!(glyph_rect(&font.glyph(' ').unwrap(), 0.0, 0.0, 1.0, 1.0).is_none());
+
+        let glyph = font.glyph('V').unwrap();
+        assert_eq!(glyph_rect(&glyph, 20.0, 10.0, 2.0, 2.0), Some(Vec4::new(18.0, 14.0, 20.0, 24.0)));
+        assert_eq!(glyph.texture_mask(), 0x2);
+    }
 }
@@ -23,6 +23,7 @@ impl BufferType {
     pub const SHADER_STORAGE: u32 = 0x4;
     pub const STAGING: u32 = 0x5;
     pub const DYNAMIC_UNIFORM: u32 = 0x6;
+    pub const SHARED_SHADER_STORAGE: u32 = 0x7;
 }
 
 /// Buffer Object
@@ -80,6 +81,10 @@ impl BufferObject {
     }
 
     pub fn bind(&self, frame: &Frame) -> Result<(), Error> {
+        self.bind_to(frame, 0)
+    }
+
+    pub fn bind_to(&self, frame: &Frame, binding: u32) -> Result<(), Error> {
 
         let device = crate::globals::device();
         let command_buffer = &frame.command_buffer;
@@ -89,7 +94,7 @@ impl BufferObject {
                 let offsets = [0u64];
                 let buffers = [self.obj];
                 unsafe { device.obj.cmd_bind_vertex_buffers(
-                    command_buffer.obj, 0, &buffers, &offsets
+                    command_buffer.obj, binding, &buffers, &offsets
                 )};
             },
             BufferType::INDEX => {
@@ -234,7 +239,21 @@ impl BufferObjects {
                 for _ in 0..num_frames {
                     buffer_objects.add_uniform_buffer();
                 }
-            }
+            },
+            BufferType::SHADER_STORAGE => {
+
+                let pipeline = crate::globals::pipeline();
+
+                // create buffer object per frame
+                let num_frames = pipeline.frame_count();
+                for _ in 0..num_frames {
+                    buffer_objects.add_shader_storage_buffer();
+                }
+            },
+            BufferType::SHARED_SHADER_STORAGE => {
+                // single buffer for all frames, written by compute shaders
+                buffer_objects.add_shader_storage_buffer();
+            },
             _ => {}
         }
 
@@ -295,7 +314,7 @@ impl BufferObjects {
         self.add(BufferObject::new(
             self.buffer_type,
             self.size,
-            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::TRANSFER_SRC,
             DeviceMemory::HOST_VISIBLE | DeviceMemory::HOST_COHERENT));
 
         self
@@ -340,6 +359,10 @@ impl VertexBuffer {
         self.buffer_objects.buffer_objects[0].bind(frame)
     }
 
+    pub fn bind_to(&self, frame: &Frame, binding: u32) -> Result<(), Error> {
+        self.buffer_objects.buffer_objects[0].bind_to(frame, binding)
+    }
+
 }
 
 
@@ -384,43 +407,235 @@ impl IndexBuffer {
 }
 
 pub struct ShaderStorageBuffer {
-    buffer_objects: BufferObjects
+    buffer_objects: BufferObjects,
+    pub size: usize
 }
 
+pub type ShaderStorageBufferRef = std::sync::Arc<ShaderStorageBuffer>;
+pub type ShaderStorageBufferLockRef = LockRef<ShaderStorageBuffer>;
+
 impl Disposable for ShaderStorageBuffer {
     fn dispose(&mut self) {
         self.buffer_objects.dispose();
+        self.size = 0;
     }
 }
 
 impl ShaderStorageBuffer {
-    pub fn new(size: usize) -> Self {
+    pub fn new(binding: u32, size: usize) -> Self {
 
         let buffer_objects = BufferObjects::new(
-            0,
+            binding,
             BufferType::SHADER_STORAGE,
             size
         );
 
         Self {
-            buffer_objects
+            buffer_objects,
+            size
         }
     }
 
-    pub fn alloc_frame_buffer(&mut self) {
-        self.buffer_objects.add_shader_storage_buffer();
+    /// Single buffer shared by all frames in flight, for data that
+    /// stays on the GPU (e.g. written by compute shaders)
+    pub fn new_shared(binding: u32, size: usize) -> Self {
+
+        let buffer_objects = BufferObjects::new(
+            binding,
+            BufferType::SHARED_SHADER_STORAGE,
+            size
+        );
+
+        Self {
+            buffer_objects,
+            size
+        }
+    }
+
+    pub fn realloc(&mut self, size: usize) {
+
+        // buffers might still be in use by frames in flight
+        let device = crate::globals::device();
+        unsafe { let _ = device.obj.device_wait_idle(); }
+
+        let binding = self.binding();
+        let buffer_type = self.buffer_objects.buffer_type;
+        self.dispose();
+
+        self.buffer_objects = BufferObjects::new(
+            binding,
+            buffer_type,
+            size
+        );
+
+        self.size = size;
+    }
+
+    pub fn binding(&self) -> u32 {
+        self.buffer_objects.binding
+    }
+
+    pub fn is_shared(&self) -> bool {
+        self.buffer_objects.buffer_type == BufferType::SHARED_SHADER_STORAGE
+    }
+
+    fn buffer_index(&self, frame_index: usize) -> usize {
+        if self.is_shared() { 0 } else { frame_index }
+    }
+
+    /// Bind as vertex buffer
+    pub fn bind_to(&self, frame: &Frame, binding: u32) -> Result<(), Error> {
+        let buffer_object = self.buffer_objects.get(self.buffer_index(frame.index as usize));
+
+        let device = crate::globals::device();
+        let offsets = [0u64];
+        let buffers = [buffer_object.obj];
+        unsafe { device.obj.cmd_bind_vertex_buffers(
+            frame.command_buffer.obj, binding, &buffers, &offsets
+        )};
+
+        Ok(())
     }
 
     pub fn copy(&self, frame: &Frame, source_ptr: *const std::ffi::c_void) -> Result<(), Error> {
-        let buffer_object = &self.buffer_objects.buffer_objects[frame.index as usize];
-        buffer_object.copy_raw(source_ptr)
+        self.copy_region(frame, source_ptr, 0, 0, self.size)
     }
 
-    pub fn bind(&self, frame: &Frame) -> Result<(), Error> {
-        let buffer_object = &self.buffer_objects.buffer_objects[frame.index as usize];
-        buffer_object.bind(frame)
+    pub fn copy_region(&self, frame: &Frame, source_ptr: *const std::ffi::c_void, src_ofs: usize, dest_ofs: usize, len: usize) -> Result<(), Error> {
+        let buffer_object = self.buffer_objects.get(self.buffer_index(frame.index as usize));
+        buffer_object.copy_region_raw(source_ptr, src_ofs, dest_ofs, len)
+    }
+
+    pub fn get_buffer_info(&self, frame_index: usize) -> vk::DescriptorBufferInfo {
+        let buffer_object = self.buffer_objects.get(self.buffer_index(frame_index));
+
+        vk::DescriptorBufferInfo::default()
+            .buffer(buffer_object.obj)
+            .offset(0)
+            .range(self.size as u64)
+    }
+
+}
+
+/// Typed shader storage buffer holding an array of elements
+pub struct ShaderStorage<T> {
+    data: Vec<T>,
+    buffer_ref: ShaderStorageBufferLockRef
+}
+
+impl <T: Default + Clone> Disposable for ShaderStorage<T> {
+    fn dispose(&mut self) {
+        self.buffer_ref.lock().unwrap().dispose();
+        self.data.clear();
     }
+}
+
+impl <T: Default + Clone> ShaderStorage<T> {
+
+    pub fn new(index: u32, num_elements: usize) -> Result<Self, Error> {
+
+        let num_elements = num_elements.max(1);
+
+        let data = vec![T::default(); num_elements];
+        let data_size = num_elements * core::mem::size_of::<T>();
+
+        let buffer = ShaderStorageBuffer::new(index, data_size);
+        let buffer_ref = std::sync::Arc::new(std::sync::Mutex::new(buffer));
+
+        Ok(Self {
+            data,
+            buffer_ref
+        })
+    }
+
+    /// Storage with a single buffer for all frames. The data is uploaded
+    /// with `update()` and then owned by the GPU (compute shaders).
+    pub fn new_shared(index: u32, num_elements: usize) -> Result<Self, Error> {
+
+        let num_elements = num_elements.max(1);
+
+        let data = vec![T::default(); num_elements];
+        let data_size = num_elements * core::mem::size_of::<T>();
 
+        let buffer = ShaderStorageBuffer::new_shared(index, data_size);
+        let buffer_ref = std::sync::Arc::new(std::sync::Mutex::new(buffer));
+
+        Ok(Self {
+            data,
+            buffer_ref
+        })
+    }
+
+    pub fn get_buffer_ref(&self) -> ShaderStorageBufferLockRef {
+        self.buffer_ref.clone()
+    }
+
+    /// Draw one vertex per element with the current material, the buffer
+    /// is bound as vertex input (e.g. points rendered from compute output)
+    pub fn draw(&self, num_elements: usize) {
+        let num_elements = num_elements.min(self.data.len());
+        if 0 == num_elements {
+            return;
+        }
+
+        let pipeline = crate::globals::pipeline();
+        let frame = pipeline.current_frame();
+        self.buffer_ref.lock().unwrap().bind_to(frame, 0).unwrap();
+
+        let device = crate::globals::device();
+        unsafe { device.obj.cmd_draw(
+            frame.command_buffer.obj,
+            num_elements as u32,
+            1, 0, 0
+        ) };
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Resize the storage. Materials using the storage need to be invalidated
+    /// afterwards as the underlying buffer objects are re-created.
+    pub fn resize(&mut self, num_elements: usize) {
+        let num_elements = num_elements.max(1);
+        if num_elements == self.data.len() {
+            return;
+        }
+
+        self.data.resize(num_elements, T::default());
+        self.buffer_ref.lock().unwrap().realloc(num_elements * core::mem::size_of::<T>());
+    }
+
+    fn copy(&self, frame: &Frame) -> Result<(), Error> {
+        let raw_ptr = self.data.as_ptr() as *const std::ffi::c_void;
+        self.buffer_ref.lock().unwrap().copy(frame, raw_ptr)
+    }
+
+    pub fn update(&self) -> Result<(), Error> {
+        let pipeline = crate::globals::pipeline();
+        let frame = pipeline.current_frame();
+        self.copy(frame)
+    }
+
+    pub fn update_all(&self) -> Result<(), Error> {
+        let pipeline = crate::globals::pipeline();
+        for frame in &pipeline.frames {
+            self.copy(frame)?;
+        }
+        Ok(())
+    }
+
+    pub fn data(&self) -> &[T] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [T] {
+        &mut self.data
+    }
 
 }
 
@@ -457,6 +672,13 @@ impl <T: Default> PushConstants<T> {
         self.data_size
     }
 
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(
+            &self.data as *const T as *const u8,
+            self.data_size
+        ) }
+    }
+
     pub fn update(&self) -> Result<(), Error> {
         let pipeline = crate::globals::pipeline();
         let frame = pipeline.current_frame();
@@ -475,10 +697,7 @@ impl <T: Default> PushConstants<T> {
 
     fn update_frame(&self, frame: &Frame) -> Result<(), Error> {
 
-        let data_ptr = unsafe { std::slice::from_raw_parts(
-            &self.data as *const T as *const u8,
-            std::mem::size_of::<T>()
-        ) };
+        let data_ptr = self.as_bytes();
 
         let material_ref = crate::globals::renderer().material();
         let material = material_ref.lock().unwrap();
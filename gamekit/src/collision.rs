@@ -0,0 +1,572 @@
+//!
+//! Collision
+//! IntGrid queries and tile collision
+//!
+
+use std::collections::HashMap;
+
+use crate::math::Rect;
+
+const EPSILON: f32 = 0.001;
+
+/// Collision behaviour of an IntGrid value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellKind {
+    Empty,
+    Solid,
+    /// Blocks only when landing from above
+    OneWay,
+    /// 45 degree floor rising to the right
+    SlopeUp,
+    /// 45 degree floor falling to the right
+    SlopeDown
+}
+
+impl CellKind {
+    /// Convention for IntGrid value identifiers of the LDtk layer
+    /// definition, values with other identifiers are solid
+    pub fn from_identifier(identifier: &str) -> Self {
+        match identifier.to_ascii_lowercase().as_str() {
+            "empty" | "none" => CellKind::Empty,
+            "one_way" | "oneway" | "platform" => CellKind::OneWay,
+            "slope_up" => CellKind::SlopeUp,
+            "slope_down" => CellKind::SlopeDown,
+            _ => CellKind::Solid
+        }
+    }
+
+    fn is_slope(self) -> bool {
+        matches!(self, CellKind::SlopeUp | CellKind::SlopeDown)
+    }
+}
+
+/// First solid cell along a ray
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub x: f32,
+    pub y: f32,
+    pub col: usize,
+    pub row: usize,
+    pub value: i32,
+    pub distance: f32,
+    pub normal_x: f32,
+    pub normal_y: f32
+}
+
+/// Outcome of `IntGrid::move_and_slide`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MoveResult {
+    /// Resolved position (top left)
+    pub x: f32,
+    pub y: f32,
+    /// Distance actually moved
+    pub dx: f32,
+    pub dy: f32,
+    pub hit_x: bool,
+    pub hit_y: bool,
+    pub on_ground: bool,
+    pub on_ceiling: bool,
+    pub on_slope: bool
+}
+
+/// Raw IntGrid values of a layer. Positions are pixels relative to
+/// the level, cells outside the grid are empty.
+#[derive(Debug, Clone, Default)]
+pub struct IntGrid {
+    cols: usize,
+    rows: usize,
+    grid_size: f32,
+    values: Vec<i32>,
+    kinds: HashMap<i32, CellKind>
+}
+
+impl IntGrid {
+
+    pub fn new(cols: usize, rows: usize, grid_size: f32, values: Vec<i32>) -> Self {
+        Self {
+            cols,
+            rows,
+            grid_size,
+            values,
+            kinds: HashMap::new()
+        }
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn grid_size(&self) -> f32 {
+        self.grid_size
+    }
+
+    /// Values row by row, empty for layers without IntGrid data
+    pub fn values(&self) -> &[i32] {
+        &self.values
+    }
+
+    pub fn value(&self, col: usize, row: usize) -> i32 {
+        if col >= self.cols || row >= self.rows {
+            return 0;
+        }
+
+        self.values.get(row * self.cols + col).copied().unwrap_or(0)
+    }
+
+    pub fn set_value(&mut self, col: usize, row: usize, value: i32) {
+        if col >= self.cols || row >= self.rows {
+            return;
+        }
+
+        if self.values.len() < self.cols * self.rows {
+            self.values.resize(self.cols * self.rows, 0);
+        }
+
+        self.values[row * self.cols + col] = value;
+    }
+
+    /// Change the grid size, values of the remaining cells are kept
+    pub fn resize(&mut self, cols: usize, rows: usize) {
+        if !self.values.is_empty() {
+            let mut values = vec![0; cols * rows];
+
+            for row in 0..rows.min(self.rows) {
+                for col in 0..cols.min(self.cols) {
+                    values[row * cols + col] = self.value(col, row);
+                }
+            }
+
+            self.values = values;
+        }
+
+        self.cols = cols;
+        self.rows = rows;
+    }
+
+    /// Set all cells to empty, cell kinds are kept
+    pub fn clear(&mut self) {
+        self.values.fill(0);
+    }
+
+    /// Cell containing a position
+    pub fn cell_at(&self, x: f32, y: f32) -> Option<(usize, usize)> {
+        let col = self.cell_coord(x);
+        let row = self.cell_coord(y);
+
+        if col < 0 || row < 0 || col as usize >= self.cols || row as usize >= self.rows {
+            return None;
+        }
+
+        Some((col as usize, row as usize))
+    }
+
+    /// Top left position of a cell
+    pub fn cell_pos(&self, col: usize, row: usize) -> (f32, f32) {
+        (col as f32 * self.grid_size, row as f32 * self.grid_size)
+    }
+
+    pub fn value_at(&self, x: f32, y: f32) -> i32 {
+        match self.cell_at(x, y) {
+            Some((col, row)) => self.value(col, row),
+            None => 0
+        }
+    }
+
+    /// Override the collision behaviour of a value
+    pub fn set_cell_kind(&mut self, value: i32, kind: CellKind) {
+        self.kinds.insert(value, kind);
+    }
+
+    /// Value 0 is empty, other values are solid unless configured
+    pub fn cell_kind(&self, value: i32) -> CellKind {
+        if value == 0 {
+            return CellKind::Empty;
+        }
+
+        self.kinds.get(&value).copied().unwrap_or(CellKind::Solid)
+    }
+
+    pub fn kind(&self, col: usize, row: usize) -> CellKind {
+        self.cell_kind(self.value(col, row))
+    }
+
+    pub fn is_solid(&self, col: usize, row: usize) -> bool {
+        self.kind(col, row) == CellKind::Solid
+    }
+
+    pub fn is_solid_at(&self, x: f32, y: f32) -> bool {
+        self.cell_kind(self.value_at(x, y)) == CellKind::Solid
+    }
+
+    /// Non-empty cells overlapping a rectangle as (col, row, value)
+    pub fn query_rect(&self, rect: &Rect) -> Vec<(usize, usize, i32)> {
+        let mut cells = Vec::new();
+
+        let (col0, col1) = self.cell_span(rect.pos.x, rect.size.x);
+        let (row0, row1) = self.cell_span(rect.pos.y, rect.size.y);
+
+        for row in row0.max(0)..=row1.min(self.rows as i64 - 1) {
+            for col in col0.max(0)..=col1.min(self.cols as i64 - 1) {
+                let value = self.value(col as usize, row as usize);
+                if value != 0 {
+                    cells.push((col as usize, row as usize, value));
+                }
+            }
+        }
+
+        cells
+    }
+
+    /// True if a rectangle overlaps any solid cell
+    pub fn overlaps_solid(&self, rect: &Rect) -> bool {
+        self.query_rect(rect).iter().any(|(_, _, value)| self.cell_kind(*value) == CellKind::Solid)
+    }
+
+    /// Trace a segment through the grid and return the first solid cell
+    pub fn raycast(&self, x0: f32, y0: f32, x1: f32, y1: f32) -> Option<RayHit> {
+
+        let g = self.grid_size;
+        let length = ((x1 - x0) * (x1 - x0) + (y1 - y0) * (y1 - y0)).sqrt();
+
+        let mut col = self.cell_coord(x0);
+        let mut row = self.cell_coord(y0);
+
+        if self.kind_i(col, row) == CellKind::Solid {
+            return Some(self.ray_hit(col, row, x0, y0, 0.0, 0.0, 0.0));
+        }
+
+        if length <= 0.0 {
+            return None;
+        }
+
+        let dir_x = (x1 - x0) / length;
+        let dir_y = (y1 - y0) / length;
+
+        let step_x: i64 = if dir_x > 0.0 { 1 } else { -1 };
+        let step_y: i64 = if dir_y > 0.0 { 1 } else { -1 };
+
+        let boundary = |cell: i64, step: i64, origin: f32, dir: f32| {
+            if dir == 0.0 {
+                f32::INFINITY
+            } else {
+                let edge = if step > 0 { (cell + 1) as f32 * g } else { cell as f32 * g };
+                (edge - origin) / dir
+            }
+        };
+
+        let mut t_max_x = boundary(col, step_x, x0, dir_x);
+        let mut t_max_y = boundary(row, step_y, y0, dir_y);
+        let t_delta_x = if dir_x == 0.0 { f32::INFINITY } else { g / dir_x.abs() };
+        let t_delta_y = if dir_y == 0.0 { f32::INFINITY } else { g / dir_y.abs() };
+
+        loop {
+            let (t, normal_x, normal_y) = if t_max_x < t_max_y {
+                col += step_x;
+                let t = t_max_x;
+                t_max_x += t_delta_x;
+                (t, -step_x as f32, 0.0)
+            } else {
+                row += step_y;
+                let t = t_max_y;
+                t_max_y += t_delta_y;
+                (t, 0.0, -step_y as f32)
+            };
+
+            if t > length {
+                return None;
+            }
+
+            if self.kind_i(col, row) == CellKind::Solid {
+                return Some(self.ray_hit(col, row, x0 + dir_x * t, y0 + dir_y * t, t, normal_x, normal_y));
+            }
+        }
+    }
+
+    /// Move a box by (dx, dy) and slide along solid cells. The box is
+    /// resolved horizontally first, then vertically. One-way cells
+    /// block only when falling onto them unless `drop_through` is set,
+    /// slopes carry the box by the center of its bottom edge.
+    pub fn move_and_slide(&self, rect: &Rect, dx: f32, dy: f32, drop_through: bool) -> MoveResult {
+
+        let g = self.grid_size;
+        let width = rect.size.x;
+        let height = rect.size.y;
+
+        let mut x = rect.pos.x;
+        let mut y = rect.pos.y;
+
+        let mut result = MoveResult::default();
+
+        let was_on_slope = self.slope_floor(x + width / 2.0, y + height).is_some();
+
+        // horizontal, the bottom cell row is walkable while on a slope
+        let foot_margin = if was_on_slope { g.min(height - EPSILON).max(0.0) } else { 0.0 };
+        let (new_x, hit_x) = self.sweep_x(x, y, width, height - foot_margin, dx);
+        x = new_x;
+        result.hit_x = hit_x;
+
+        if was_on_slope && self.kind_at(x + width / 2.0, y + height - EPSILON) == CellKind::Solid {
+            // stepped off the top of a slope onto a solid cell
+            y = (self.cell_coord(y + height - EPSILON) as f32) * g - height;
+        }
+
+        // vertical
+        let (new_y, hit_y) = self.sweep_y(x, y, width, height, dy, drop_through);
+        y = new_y;
+        result.hit_y = hit_y;
+        result.on_ground = hit_y && dy > 0.0;
+        result.on_ceiling = hit_y && dy < 0.0;
+
+        // slopes
+        if dy >= 0.0 && let Some(floor) = self.slope_floor(x + width / 2.0, y + height) {
+            let bottom = y + height;
+            let snap = if was_on_slope { dx.abs() + dy + EPSILON } else { 0.0 };
+            if bottom > floor || floor - bottom <= snap {
+                y = floor - height;
+                result.on_ground = true;
+                result.on_slope = true;
+                result.hit_y = dy > 0.0;
+            }
+        }
+
+        result.x = x;
+        result.y = y;
+        result.dx = x - rect.pos.x;
+        result.dy = y - rect.pos.y;
+
+        result
+    }
+
+    fn cell_coord(&self, v: f32) -> i64 {
+        (v / self.grid_size).floor() as i64
+    }
+
+    /// First and last cell covered by a span, the end is exclusive
+    fn cell_span(&self, start: f32, size: f32) -> (i64, i64) {
+        let first = self.cell_coord(start);
+        let last = self.cell_coord(start + size - EPSILON).max(first);
+        (first, last)
+    }
+
+    fn kind_i(&self, col: i64, row: i64) -> CellKind {
+        if col < 0 || row < 0 {
+            return CellKind::Empty;
+        }
+
+        self.kind(col as usize, row as usize)
+    }
+
+    fn kind_at(&self, x: f32, y: f32) -> CellKind {
+        self.kind_i(self.cell_coord(x), self.cell_coord(y))
+    }
+
+    fn ray_hit(&self, col: i64, row: i64, x: f32, y: f32, distance: f32, normal_x: f32, normal_y: f32) -> RayHit {
+        RayHit {
+            x,
+            y,
+            col: col as usize,
+            row: row as usize,
+            value: self.value(col as usize, row as usize),
+            distance,
+            normal_x,
+            normal_y
+        }
+    }
+
+    /// Floor height of the slope cell containing a bottom point
+    fn slope_floor(&self, x: f32, bottom: f32) -> Option<f32> {
+        let g = self.grid_size;
+        let col = self.cell_coord(x);
+        let row = self.cell_coord(bottom - EPSILON);
+        let kind = self.kind_i(col, row);
+
+        if !kind.is_slope() {
+            return None;
+        }
+
+        let local = ((x - col as f32 * g) / g).clamp(0.0, 1.0);
+        let top = row as f32 * g;
+
+        Some(match kind {
+            CellKind::SlopeUp => top + g * (1.0 - local),
+            _ => top + g * local
+        })
+    }
+
+    fn sweep_x(&self, x: f32, y: f32, width: f32, height: f32, dx: f32) -> (f32, bool) {
+
+        if dx == 0.0 {
+            return (x, false);
+        }
+
+        let g = self.grid_size;
+        let (row0, row1) = self.cell_span(y, height.max(EPSILON));
+
+        let blocked = |col: i64| (row0..=row1).any(|row| self.kind_i(col, row) == CellKind::Solid);
+
+        if dx > 0.0 {
+            let first = self.cell_coord(x + width - EPSILON) + 1;
+            let last = self.cell_coord(x + width + dx - EPSILON);
+            for col in first..=last {
+                if blocked(col) {
+                    return (col as f32 * g - width, true);
+                }
+            }
+        } else {
+            let first = self.cell_coord(x) - 1;
+            let last = self.cell_coord(x + dx);
+            for col in (last..=first).rev() {
+                if blocked(col) {
+                    return ((col + 1) as f32 * g, true);
+                }
+            }
+        }
+
+        (x + dx, false)
+    }
+
+    fn sweep_y(&self, x: f32, y: f32, width: f32, height: f32, dy: f32, drop_through: bool) -> (f32, bool) {
+
+        if dy == 0.0 {
+            return (y, false);
+        }
+
+        let g = self.grid_size;
+        let (col0, col1) = self.cell_span(x, width);
+
+        if dy > 0.0 {
+            // rows below the current bottom, one-way cells can be landed on
+            let blocked = |row: i64| (col0..=col1).any(|col| match self.kind_i(col, row) {
+                CellKind::Solid => true,
+                CellKind::OneWay => !drop_through,
+                _ => false
+            });
+
+            let first = self.cell_coord(y + height - EPSILON) + 1;
+            let last = self.cell_coord(y + height + dy - EPSILON);
+            for row in first..=last {
+                if blocked(row) {
+                    return (row as f32 * g - height, true);
+                }
+            }
+        } else {
+            let blocked = |row: i64| (col0..=col1).any(|col| self.kind_i(col, row) == CellKind::Solid);
+
+            let first = self.cell_coord(y) - 1;
+            let last = self.cell_coord(y + dy);
+            for row in (last..=first).rev() {
+                if blocked(row) {
+                    return ((row + 1) as f32 * g, true);
+                }
+            }
+        }
+
+        (y + dy, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 6x4 cells of 10px, floor on the last row, a wall at column 4,
+    // a one-way platform at (1, 1) and a slope rising to the right at (2, 2)
+    fn test_grid() -> IntGrid {
+        let values = vec![
+            0, 0, 0, 0, 1, 0,
+            0, 2, 0, 0, 1, 0,
+            0, 0, 3, 0, 1, 0,
+            1, 1, 1, 1, 1, 1
+        ];
+
+        let mut grid = IntGrid::new(6, 4, 10.0, values);
+        grid.set_cell_kind(2, CellKind::OneWay);
+        grid.set_cell_kind(3, CellKind::SlopeUp);
+        grid
+    }
+
+    #[test]
+    fn test_cell_queries() {
+        let grid = test_grid();
+
+        assert_eq!(grid.cell_at(15.0, 25.0), Some((1, 2)));
+        assert_eq!(grid.cell_at(-1.0, 0.0), None);
+        assert_eq!(grid.value_at(45.0, 5.0), 1);
+        assert!(grid.is_solid_at(5.0, 35.0));
+        assert!(!grid.is_solid_at(15.0, 15.0));
+        assert_eq!(grid.cell_kind(2), CellKind::OneWay);
+        assert_eq!(grid.cell_kind(7), CellKind::Solid);
+        assert_eq!(CellKind::from_identifier("Platform"), CellKind::OneWay);
+
+        assert!(grid.overlaps_solid(&Rect::new(35.0, 0.0, 6.0, 6.0)));
+        assert!(!grid.overlaps_solid(&Rect::new(30.0, 0.0, 10.0, 10.0)));
+        assert_eq!(grid.query_rect(&Rect::new(10.0, 10.0, 10.0, 10.0)), vec![(1, 1, 2)]);
+    }
+
+    #[test]
+    fn test_resize() {
+        let mut grid = test_grid();
+
+        grid.resize(3, 5);
+        assert_eq!((grid.cols(), grid.rows()), (3, 5));
+        assert_eq!(grid.value(1, 1), 2);
+        assert_eq!(grid.value(2, 3), 1);
+        assert_eq!(grid.value(0, 4), 0);
+        assert_eq!(grid.kind(1, 1), CellKind::OneWay);
+
+        grid.clear();
+        assert!(grid.values().iter().all(|v| *v == 0));
+    }
+
+    #[test]
+    fn test_raycast() {
+        let grid = test_grid();
+
+        let hit = grid.raycast(5.0, 5.0, 55.0, 5.0).unwrap();
+        assert_eq!((hit.col, hit.row), (4, 0));
+        assert_eq!(hit.x, 40.0);
+        assert_eq!(hit.normal_x, -1.0);
+
+        let hit = grid.raycast(5.0, 5.0, 5.0, 100.0).unwrap();
+        assert_eq!((hit.col, hit.row, hit.y), (0, 3, 30.0));
+
+        // one-way cells don't stop rays
+        assert!(grid.raycast(15.0, 5.0, 15.0, 25.0).is_none());
+    }
+
+    #[test]
+    fn test_move_and_slide() {
+        let grid = test_grid();
+
+        // fall onto the floor
+        let result = grid.move_and_slide(&Rect::new(0.0, 15.0, 8.0, 8.0), 0.0, 20.0, false);
+        assert_eq!(result.y, 22.0);
+        assert!(result.on_ground);
+
+        // slide against the wall
+        let result = grid.move_and_slide(&Rect::new(25.0, 0.0, 8.0, 8.0), 20.0, 0.0, false);
+        assert_eq!(result.x, 32.0);
+        assert!(result.hit_x);
+
+        // land on the one-way platform or drop through it
+        let result = grid.move_and_slide(&Rect::new(10.0, 0.0, 8.0, 8.0), 0.0, 5.0, false);
+        assert_eq!(result.y, 2.0);
+        assert!(result.on_ground);
+        let result = grid.move_and_slide(&Rect::new(10.0, 2.0, 8.0, 8.0), 0.0, 5.0, true);
+        assert_eq!(result.y, 7.0);
+
+        // jump up through the one-way platform
+        let result = grid.move_and_slide(&Rect::new(10.0, 21.0, 8.0, 8.0), 0.0, -15.0, false);
+        assert_eq!(result.y, 6.0);
+
+        // walk onto the slope, center at x = 25 is halfway up
+        let result = grid.move_and_slide(&Rect::new(11.0, 22.0, 8.0, 8.0), 10.0, 0.0, false);
+        assert_eq!(result.x, 21.0);
+        assert_eq!(result.y, 17.0);
+        assert!(result.on_slope);
+    }
+}
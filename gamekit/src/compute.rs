@@ -0,0 +1,422 @@
+//!
+//! Compute
+//!
+
+use std::sync::{Arc, Mutex};
+
+use ash::vk::{self, Handle};
+
+use crate::{api::{Disposable, LockRef}, buffer::{PushConstants, ShaderStorage, ShaderStorageBufferLockRef, Uniform, UniformBufferLockRef}, error::Error, shader::{ShaderLockRef, ShaderType}};
+
+const DEFAULT_SHADER_ENTRY_POINT: &str = "main";
+
+/// Compute shader with its storage buffers and uniforms.
+///
+/// Dispatches are recorded into the frame command buffer before the
+/// render pass starts, which requires the `deferred_render_pass` option,
+/// and are issued from `on_update`. Barriers between
+/// dispatches and towards the following draw calls are inserted
+/// automatically, so storage written here can be rendered directly.
+pub struct ComputeProgram {
+    invalidated: bool,
+    shader: Option<ShaderLockRef>,
+    entry_point: std::ffi::CString,
+    uniforms: Vec<UniformBufferLockRef>,
+    storage_buffers: Vec<ShaderStorageBufferLockRef>,
+    push_constant_ranges: Vec<vk::PushConstantRange>,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    pipeline_layout: vk::PipelineLayout,
+    compute_pipeline: vk::Pipeline,
+    descriptor_sets: Vec<vk::DescriptorSet>
+}
+
+pub type ComputeProgramLockRef = LockRef<ComputeProgram>;
+
+impl Disposable for ComputeProgram {
+    fn dispose(&mut self) {
+        self.free_descriptor_sets();
+        self.free_compute_pipeline();
+
+        self.shader = None;
+        self.uniforms.clear();
+        self.storage_buffers.clear();
+        self.push_constant_ranges.clear();
+    }
+}
+
+impl Default for ComputeProgram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ComputeProgram {
+    pub fn new() -> Self {
+        Self {
+            invalidated: true,
+            shader: None,
+            entry_point: std::ffi::CString::new(DEFAULT_SHADER_ENTRY_POINT).unwrap(),
+            uniforms: Vec::new(),
+            storage_buffers: Vec::new(),
+            push_constant_ranges: Vec::new(),
+            descriptor_pool: vk::DescriptorPool::null(),
+            descriptor_set_layout: vk::DescriptorSetLayout::null(),
+            pipeline_layout: vk::PipelineLayout::null(),
+            compute_pipeline: vk::Pipeline::null(),
+            descriptor_sets: Vec::new()
+        }
+    }
+
+    /// Program for a compute shader from the manifest
+    pub fn from_resource(shader_name: &str) -> Result<Self, Error> {
+        let resources = crate::globals::resources();
+        let shader_ref = resources.get_shader(shader_name);
+
+        let mut program = Self::new();
+        program.set_shader(shader_ref)?;
+        Ok(program)
+    }
+
+    pub fn to_lockref(program: Self) -> ComputeProgramLockRef {
+        Arc::new(Mutex::new(program))
+    }
+
+    pub fn set_shader(&mut self, shader: ShaderLockRef) -> Result<&mut Self, Error> {
+        if shader.lock().unwrap().shader_type != ShaderType::COMPUTE_SHADER {
+            return Err(Error::from("shader is not a compute shader"));
+        }
+
+        self.shader = Some(shader);
+        self.invalidated = true;
+        Ok(self)
+    }
+
+    pub fn add_push_constants<T: Default>(&mut self, push_constants: &PushConstants<T>) -> &mut Self {
+        let range = vk::PushConstantRange::default()
+            .offset(0)
+            .size(push_constants.size() as u32)
+            .stage_flags(vk::ShaderStageFlags::COMPUTE);
+        self.push_constant_ranges.push(range);
+        self.invalidated = true;
+        self
+    }
+
+    pub fn add_uniform<T: Default>(&mut self, uniform: &Uniform<T>) -> &mut Self {
+        self.uniforms.push(uniform.get_buffer_ref());
+        self.invalidated = true;
+        self
+    }
+
+    pub fn add_storage<T: Default + Clone>(&mut self, storage: &ShaderStorage<T>) -> &mut Self {
+        self.storage_buffers.push(storage.get_buffer_ref());
+        self.invalidated = true;
+        self
+    }
+
+    pub fn invalidate(&mut self) {
+        self.invalidated = true;
+    }
+
+    /// Record push constants for the next dispatch
+    pub fn push_constants<T: Default>(&mut self, push_constants: &PushConstants<T>) -> Result<(), Error> {
+        self.validate_pipeline()?;
+
+        let device = crate::globals::device();
+        let pipeline = crate::globals::pipeline();
+        let frame = pipeline.current_frame();
+
+        unsafe {
+            device.obj.cmd_push_constants(
+                frame.command_buffer.obj,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                push_constants.as_bytes()
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Record dispatch of work groups
+    pub fn dispatch(&mut self, group_count_x: u32, group_count_y: u32, group_count_z: u32) -> Result<(), Error> {
+
+        let pipeline = crate::globals::pipeline();
+        if pipeline.is_render_pass_active() || pipeline.is_offscreen_pass_active() {
+            return Err(Error::from("compute dispatch inside render pass, enable the deferred_render_pass option and dispatch during update"));
+        }
+
+        self.validate_pipeline()?;
+
+        let device = crate::globals::device();
+        let pipeline = crate::globals::pipeline_mut();
+        let frame = pipeline.current_frame();
+        let command_buffer = frame.command_buffer.obj;
+
+        // previous draws and dispatches have to finish with the storage
+        let memory_barriers = [
+            vk::MemoryBarrier::default()
+                .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+                .dst_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE)
+        ];
+
+        unsafe {
+            device.obj.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::VERTEX_INPUT | vk::PipelineStageFlags::VERTEX_SHADER | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::DependencyFlags::empty(),
+                &memory_barriers,
+                &[],
+                &[]
+            );
+
+            device.obj.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::COMPUTE, self.compute_pipeline);
+
+            if self.descriptor_sets.len() > frame.index as usize {
+                let dynamic_offsets: Vec<u32> = self.uniforms.iter()
+                    .map(|uniform_ref| uniform_ref.lock().unwrap())
+                    .filter(|uniform| uniform.is_dynamic())
+                    .map(|uniform| uniform.offset() as u32)
+                    .collect();
+
+                device.obj.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    vk::PipelineBindPoint::COMPUTE,
+                    self.pipeline_layout,
+                    0,
+                    &[self.descriptor_sets[frame.index as usize]],
+                    &dynamic_offsets
+                );
+            }
+
+            device.obj.cmd_dispatch(command_buffer, group_count_x.max(1), group_count_y.max(1), group_count_z.max(1));
+        }
+
+        pipeline.set_compute_pending();
+
+        Ok(())
+    }
+
+    /// Dispatch enough groups of `local_size` invocations for `num_elements`
+    pub fn dispatch_elements(&mut self, num_elements: usize, local_size: u32) -> Result<(), Error> {
+        let group_count = (num_elements as u32).div_ceil(local_size.max(1));
+        self.dispatch(group_count, 1, 1)
+    }
+
+    fn validate_pipeline(&mut self) -> Result<(), Error> {
+
+        if !self.invalidated {
+            return Ok(());
+        }
+
+        self.free_descriptor_sets();
+        self.free_compute_pipeline();
+
+        self.create_compute_pipeline()?;
+        self.create_descriptor_sets();
+
+        self.invalidated = false;
+
+        Ok(())
+    }
+
+    fn create_compute_pipeline(&mut self) -> Result<(), Error> {
+
+        let device = crate::globals::device();
+
+        let shader_ref = match &self.shader {
+            Some(shader_ref) => shader_ref.clone(),
+            None => {
+                return Err(Error::from("compute program has no shader"));
+            }
+        };
+
+        let mut descriptor_set_layout_bindings: Vec<vk::DescriptorSetLayoutBinding> = vec![];
+
+        for uniform_ref in &self.uniforms {
+            let uniform = uniform_ref.lock().unwrap();
+            let descriptor_type = if uniform.dynamic { vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC } else { vk::DescriptorType::UNIFORM_BUFFER };
+            descriptor_set_layout_bindings.push(vk::DescriptorSetLayoutBinding::default()
+                .descriptor_type(descriptor_type)
+                .binding(uniform.binding())
+                .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                .descriptor_count(1));
+        }
+
+        for storage_ref in &self.storage_buffers {
+            let storage = storage_ref.lock().unwrap();
+            descriptor_set_layout_bindings.push(vk::DescriptorSetLayoutBinding::default()
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .binding(storage.binding())
+                .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                .descriptor_count(1));
+        }
+
+        let mut descriptor_set_layouts: Vec<vk::DescriptorSetLayout> = Vec::new();
+
+        if !descriptor_set_layout_bindings.is_empty() {
+            let layout_info = vk::DescriptorSetLayoutCreateInfo::default()
+                .bindings(&descriptor_set_layout_bindings);
+
+            self.descriptor_set_layout = unsafe { match device.obj.create_descriptor_set_layout(&layout_info, None) {
+                Ok(obj) => obj,
+                Err(_) => { return Err(Error::from("failed to create descriptor set layout")); }
+            } };
+
+            descriptor_set_layouts.push(self.descriptor_set_layout);
+        }
+
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::default()
+            .set_layouts(&descriptor_set_layouts)
+            .push_constant_ranges(&self.push_constant_ranges);
+
+        self.pipeline_layout = unsafe { match device.obj.create_pipeline_layout(&pipeline_layout_info, None) {
+            Ok(obj) => obj,
+            Err(_) => { return Err(Error::from("failed to create pipeline layout")); }
+        } };
+
+        let shader = shader_ref.lock().unwrap();
+
+        let stage = vk::PipelineShaderStageCreateInfo::default()
+            .stage(vk::ShaderStageFlags::COMPUTE)
+            .module(shader.obj)
+            .name(self.entry_point.as_c_str());
+
+        let pipeline_infos = [
+            vk::ComputePipelineCreateInfo::default()
+                .stage(stage)
+                .layout(self.pipeline_layout)
+        ];
+
+        let compute_pipelines = unsafe { match device.obj.create_compute_pipelines(vk::PipelineCache::null(), &pipeline_infos, None) {
+            Ok(obj) => obj,
+            Err(_) => { return Err(Error::from("failed to create compute pipeline")); }
+        } };
+
+        self.compute_pipeline = compute_pipelines[0];
+
+        Ok(())
+    }
+
+    fn free_compute_pipeline(&mut self) {
+
+        let device = crate::globals::device();
+
+        unsafe {
+            if !self.compute_pipeline.is_null() {
+                device.obj.destroy_pipeline(self.compute_pipeline, None);
+                self.compute_pipeline = vk::Pipeline::null();
+            }
+
+            if !self.pipeline_layout.is_null() {
+                device.obj.destroy_pipeline_layout(self.pipeline_layout, None);
+                self.pipeline_layout = vk::PipelineLayout::null();
+            }
+
+            if !self.descriptor_set_layout.is_null() {
+                device.obj.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+                self.descriptor_set_layout = vk::DescriptorSetLayout::null();
+            }
+        }
+    }
+
+    fn create_descriptor_sets(&mut self) {
+
+        if self.descriptor_set_layout.is_null() {
+            return;
+        }
+
+        let device = crate::globals::device();
+        let pipeline = crate::globals::pipeline();
+
+        let num_frames = pipeline.frame_count();
+
+        let mut pool_sizes: Vec<vk::DescriptorPoolSize> = vec![];
+
+        let num_dynamic_uniforms = self.uniforms.iter().filter(|uniform_ref| uniform_ref.lock().unwrap().dynamic).count();
+        let num_static_uniforms = self.uniforms.len() - num_dynamic_uniforms;
+
+        if num_static_uniforms > 0 {
+            pool_sizes.push(vk::DescriptorPoolSize::default()
+                .ty(vk::DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count((num_frames * num_static_uniforms) as u32));
+        }
+
+        if num_dynamic_uniforms > 0 {
+            pool_sizes.push(vk::DescriptorPoolSize::default()
+                .ty(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+                .descriptor_count((num_frames * num_dynamic_uniforms) as u32));
+        }
+
+        if !self.storage_buffers.is_empty() {
+            pool_sizes.push(vk::DescriptorPoolSize::default()
+                .ty(vk::DescriptorType::STORAGE_BUFFER)
+                .descriptor_count((num_frames * self.storage_buffers.len()) as u32));
+        }
+
+        let descriptor_pool_create_info = vk::DescriptorPoolCreateInfo::default()
+            .pool_sizes(&pool_sizes)
+            .max_sets(num_frames as u32);
+
+        self.descriptor_pool = unsafe {
+            device.obj.create_descriptor_pool(&descriptor_pool_create_info, None).unwrap()
+        };
+
+        self.descriptor_sets.clear();
+
+        for frame_index in 0..num_frames {
+            let layouts = [ self.descriptor_set_layout ];
+
+            let alloc_info = vk::DescriptorSetAllocateInfo::default()
+                .descriptor_pool(self.descriptor_pool)
+                .set_layouts(&layouts);
+
+            let descriptor_set = unsafe { device.obj.allocate_descriptor_sets(&alloc_info).unwrap()[0] };
+
+            for uniform_ref in &self.uniforms {
+                let uniform = uniform_ref.lock().unwrap();
+                let buffer_infos = [uniform.get_buffer_info(frame_index)];
+                let descriptor_type = if uniform.dynamic { vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC } else { vk::DescriptorType::UNIFORM_BUFFER };
+
+                let descriptor_write = vk::WriteDescriptorSet::default()
+                    .dst_set(descriptor_set)
+                    .dst_binding(uniform.binding())
+                    .dst_array_element(0)
+                    .descriptor_type(descriptor_type)
+                    .buffer_info(&buffer_infos);
+
+                unsafe { device.obj.update_descriptor_sets(&[descriptor_write], &[]); }
+            }
+
+            for storage_ref in &self.storage_buffers {
+                let storage = storage_ref.lock().unwrap();
+                let buffer_infos = [storage.get_buffer_info(frame_index)];
+
+                let descriptor_write = vk::WriteDescriptorSet::default()
+                    .dst_set(descriptor_set)
+                    .dst_binding(storage.binding())
+                    .dst_array_element(0)
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                    .buffer_info(&buffer_infos);
+
+                unsafe { device.obj.update_descriptor_sets(&[descriptor_write], &[]); }
+            }
+
+            self.descriptor_sets.push(descriptor_set);
+        }
+    }
+
+    fn free_descriptor_sets(&mut self) {
+
+        if self.descriptor_pool.is_null() {
+            return;
+        }
+
+        let device = crate::globals::device();
+        unsafe { device.obj.destroy_descriptor_pool(self.descriptor_pool, None); }
+        self.descriptor_pool = vk::DescriptorPool::null();
+        self.descriptor_sets.clear();
+    }
+}
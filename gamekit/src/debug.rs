@@ -0,0 +1,447 @@
+//!
+//! Debug Draw
+//!
+
+use std::f32::consts::PI;
+use std::sync::{Arc, Mutex};
+
+use crate::{api::{Disposable, LockRef}, buffer::{PushConstants, Uniform}, error::Error, material::{Material, MaterialLockRef}, math::Vec2, primitives::{Color, LineQueue, VertexQueue}};
+
+const SHADER_PARAMS_BINDING: u32 = 0;
+const DEFAULT_CAPACITY: usize = 16384;
+const DEFAULT_LINE_WIDTH: f32 = 1.0;
+const ARROW_HEAD_SIZE: f32 = 6.0;
+
+#[repr(C)]
+#[derive(Default)]
+struct DebugDrawShaderParams {
+    window_width: f32,
+    window_height: f32,
+    view_width: f32,
+    view_height: f32,
+    view_x: f32,
+    view_y: f32,
+    view_scaling: f32
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct DebugDrawPushParams {
+    offset_x: f32,
+    offset_y: f32
+}
+
+#[derive(PartialEq, Default, Debug, Clone, Copy)]
+pub enum DebugDrawSpace {
+    /// View coordinates, moved by the draw offset (camera)
+    #[default]
+    World,
+    /// View coordinates, not affected by the draw offset
+    Screen
+}
+
+/// GPU resources, only created in builds with debug assertions
+struct DebugDrawRenderer {
+    world_queue: VertexQueue,
+    screen_queue: VertexQueue,
+    material: MaterialLockRef,
+    shader_params: Uniform::<DebugDrawShaderParams>,
+    push_params: PushConstants::<DebugDrawPushParams>
+}
+
+impl DebugDrawRenderer {
+
+    fn new(capacity: usize) -> Result<Self, Error> {
+
+        let shader_params = Uniform::<DebugDrawShaderParams>::new(SHADER_PARAMS_BINDING, 0)?;
+        let push_params = PushConstants::<DebugDrawPushParams>::new()?;
+
+        let resources = crate::globals::resources();
+
+        let mut material = Material::new();
+        material
+            .set_blending(true)
+            .add_shader(resources.get_shader("gamekit::debug_vertex_shader"))
+            .add_shader(resources.get_shader("gamekit::debug_fragment_shader"))
+            .add_uniform(&shader_params)
+            .add_push_constants(&push_params);
+
+        Ok(Self {
+            world_queue: VertexQueue::new(capacity.max(3)),
+            screen_queue: VertexQueue::new(capacity.max(3)),
+            material: Material::to_lockref(material),
+            shader_params,
+            push_params
+        })
+    }
+
+    fn queue(&mut self, space: DebugDrawSpace) -> &mut VertexQueue {
+        match space {
+            DebugDrawSpace::World => &mut self.world_queue,
+            DebugDrawSpace::Screen => &mut self.screen_queue
+        }
+    }
+
+    fn draw(&mut self, offset_x: f32, offset_y: f32) {
+
+        if self.world_queue.count() == 0 && self.screen_queue.count() == 0 {
+            return;
+        }
+
+        let renderer = crate::api::renderer_mut();
+        renderer.set_material(&self.material);
+
+        // window metrics
+        let metrics = crate::api::metrics();
+        let shader_params = self.shader_params.data_mut();
+        shader_params.window_width = metrics.window_width;
+        shader_params.window_height = metrics.window_height;
+        shader_params.view_width = metrics.view_width;
+        shader_params.view_height = metrics.view_height;
+        shader_params.view_x = metrics.view_x;
+        shader_params.view_y = metrics.view_y;
+        shader_params.view_scaling = metrics.view_scaling;
+
+        self.shader_params.update().unwrap();
+        self.material.lock().unwrap().bind_uniforms();
+
+        let push_params = self.push_params.data_mut();
+        push_params.offset_x = offset_x;
+        push_params.offset_y = offset_y;
+        let _ = self.push_params.update();
+
+        self.world_queue.draw();
+
+        let push_params = self.push_params.data_mut();
+        push_params.offset_x = 0.0;
+        push_params.offset_y = 0.0;
+        let _ = self.push_params.update();
+
+        self.screen_queue.draw();
+    }
+}
+
+impl Disposable for DebugDrawRenderer {
+    fn dispose(&mut self) {
+        self.world_queue.dispose();
+        self.screen_queue.dispose();
+        self.material.lock().unwrap().dispose();
+    }
+}
+
+/// Immediate mode shape drawing for debugging.
+///
+/// Shapes are collected between `begin()` and `end()` and rendered as
+/// triangles with a built-in material by `draw()`. In release builds no
+/// GPU resources are created, the debug shaders are not embedded and all
+/// calls are no-ops.
+pub struct DebugDraw {
+    enabled: bool,
+    space: DebugDrawSpace,
+    line_width: f32,
+    renderer: Option<DebugDrawRenderer>
+}
+
+pub type DebugDrawLockRef = LockRef<DebugDraw>;
+
+impl Disposable for DebugDraw {
+    fn dispose(&mut self) {
+        if let Some(renderer) = &mut self.renderer {
+            renderer.dispose();
+        }
+    }
+}
+
+impl DebugDraw {
+
+    /// Debug drawing is available in debug builds only
+    pub const AVAILABLE: bool = cfg!(debug_assertions);
+
+    pub fn new(capacity: usize) -> Result<Self, Error> {
+
+        let renderer = if Self::AVAILABLE {
+            Some(DebugDrawRenderer::new(capacity)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            enabled: Self::AVAILABLE,
+            space: DebugDrawSpace::World,
+            line_width: DEFAULT_LINE_WIDTH,
+            renderer
+        })
+    }
+
+    pub fn with_default_capacity() -> Result<Self, Error> {
+        Self::new(DEFAULT_CAPACITY)
+    }
+
+    pub fn to_lockref(debug_draw: Self) -> DebugDrawLockRef {
+        Arc::new(Mutex::new(debug_draw))
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled && Self::AVAILABLE;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        Self::AVAILABLE && self.enabled
+    }
+
+    /// Select coordinate space of subsequent shapes
+    pub fn set_space(&mut self, space: DebugDrawSpace) -> &mut Self {
+        self.space = space;
+        self
+    }
+
+    pub fn space(&self) -> DebugDrawSpace {
+        self.space
+    }
+
+    /// Set line width (view pixels) of subsequent outlines
+    pub fn set_line_width(&mut self, line_width: f32) -> &mut Self {
+        self.line_width = line_width.max(0.0);
+        self
+    }
+
+    /// Built-in material, not available in release builds
+    pub fn material(&self) -> Option<&MaterialLockRef> {
+        self.renderer.as_ref().map(|renderer| &renderer.material)
+    }
+
+    pub fn begin(&mut self) {
+        if let Some(renderer) = &mut self.renderer {
+            renderer.world_queue.begin();
+            renderer.screen_queue.begin();
+        }
+    }
+
+    pub fn end(&mut self) {
+        if let Some(renderer) = &mut self.renderer {
+            renderer.world_queue.end();
+            renderer.screen_queue.end();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        if let Some(renderer) = &mut self.renderer {
+            renderer.world_queue.clear();
+            renderer.screen_queue.clear();
+        }
+    }
+
+    /// Add triangle, dropped silently if the queue is full
+    fn push_triangle(&mut self, a: Vec2, b: Vec2, c: Vec2, color: &Color) {
+        let space = self.space;
+        let Some(renderer) = &mut self.renderer else {
+            return;
+        };
+
+        let queue = renderer.queue(space);
+        if queue.count() + 3 > queue.capacity() {
+            return;
+        }
+
+        for p in [a, b, c] {
+            queue.push(p.x, p.y, color.r, color.g, color.b, color.a, 0.0, 0.0, 0x0, 0x0);
+        }
+    }
+
+    pub fn line(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, color: &Color) {
+
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mut vertices = Vec::with_capacity(6);
+        LineQueue::extrude(x0, y0, x1, y1, self.line_width, 0.0, |v, _| vertices.push(v));
+
+        for triangle in vertices.chunks_exact(3) {
+            self.push_triangle(triangle[0], triangle[1], triangle[2], color);
+        }
+    }
+
+    pub fn arrow(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, color: &Color) {
+
+        if !self.is_enabled() {
+            return;
+        }
+
+        let Some([tip, left, right]) = Self::arrow_head(x0, y0, x1, y1, self.line_width) else {
+            return;
+        };
+
+        let base = (left + right) * 0.5;
+
+        self.line(x0, y0, base.x, base.y, color);
+        self.push_triangle(tip, left, right, color);
+    }
+
+    pub fn rect(&mut self, x: f32, y: f32, w: f32, h: f32, color: &Color) {
+
+        if !self.is_enabled() {
+            return;
+        }
+
+        self.polygon(&[
+            Vec2::new(x, y),
+            Vec2::new(x + w, y),
+            Vec2::new(x + w, y + h),
+            Vec2::new(x, y + h)
+        ], color);
+    }
+
+    pub fn fill_rect(&mut self, x: f32, y: f32, w: f32, h: f32, color: &Color) {
+
+        if !self.is_enabled() {
+            return;
+        }
+
+        self.push_triangle(Vec2::new(x, y), Vec2::new(x + w, y), Vec2::new(x + w, y + h), color);
+        self.push_triangle(Vec2::new(x, y), Vec2::new(x + w, y + h), Vec2::new(x, y + h), color);
+    }
+
+    /// Tip and base corners of an arrow head, scaled with the line width
+    fn arrow_head(x0: f32, y0: f32, x1: f32, y1: f32, line_width: f32) -> Option<[Vec2; 3]> {
+        let d = Vec2::new(x1 - x0, y1 - y0);
+        let len = (d.x * d.x + d.y * d.y).sqrt();
+        if len <= 0.0 {
+            return None;
+        }
+
+        let head_size = ARROW_HEAD_SIZE.max(line_width * 3.0).min(len);
+        let dir = Vec2::new(d.x / len, d.y / len);
+        let normal = Vec2::new(-dir.y, dir.x);
+
+        let tip = Vec2::new(x1, y1);
+        let base = tip - dir * head_size;
+
+        Some([tip, base + normal * (head_size * 0.5), base - normal * (head_size * 0.5)])
+    }
+
+    fn circle_points(x: f32, y: f32, radius: f32) -> Vec<Vec2> {
+        let num_segments = ((radius.abs().sqrt() * 4.0) as usize).clamp(12, 64);
+        (0..num_segments).map(|i| {
+            let angle = 2.0 * PI * (i as f32) / (num_segments as f32);
+            Vec2::new(x + angle.cos() * radius, y + angle.sin() * radius)
+        }).collect()
+    }
+
+    pub fn circle(&mut self, x: f32, y: f32, radius: f32, color: &Color) {
+
+        if !self.is_enabled() {
+            return;
+        }
+
+        let points = Self::circle_points(x, y, radius);
+        self.polygon(&points, color);
+    }
+
+    pub fn fill_circle(&mut self, x: f32, y: f32, radius: f32, color: &Color) {
+
+        if !self.is_enabled() {
+            return;
+        }
+
+        let points = Self::circle_points(x, y, radius);
+        self.fill_polygon(&points, color);
+    }
+
+    /// Closed polygon outline
+    pub fn polygon(&mut self, points: &[Vec2], color: &Color) {
+
+        if !self.is_enabled() || points.len() < 2 {
+            return;
+        }
+
+        for (index, p0) in points.iter().enumerate() {
+            let p1 = &points[(index + 1) % points.len()];
+            self.line(p0.x, p0.y, p1.x, p1.y, color);
+        }
+    }
+
+    /// Open line strip
+    pub fn polyline(&mut self, points: &[Vec2], color: &Color) {
+
+        if !self.is_enabled() {
+            return;
+        }
+
+        for segment in points.windows(2) {
+            self.line(segment[0].x, segment[0].y, segment[1].x, segment[1].y, color);
+        }
+    }
+
+    /// Filled convex polygon
+    pub fn fill_polygon(&mut self, points: &[Vec2], color: &Color) {
+
+        if !self.is_enabled() || points.len() < 3 {
+            return;
+        }
+
+        for index in 1..points.len() - 1 {
+            self.push_triangle(points[0], points[index], points[index + 1], color);
+        }
+    }
+
+    /// Render collected shapes, world space shapes are moved by the offset
+    pub fn draw(&mut self, offset_x: f32, offset_y: f32) {
+
+        if !self.is_enabled() {
+            return;
+        }
+
+        if let Some(renderer) = &mut self.renderer {
+            renderer.draw(offset_x, offset_y);
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arrow_head() {
+        assert!(DebugDraw::arrow_head(5.0, 5.0, 5.0, 5.0, 1.0).is_none());
+
+        let [tip, left, right] = DebugDraw::arrow_head(0.0, 0.0, 20.0, 0.0, 1.0).unwrap();
+        assert_eq!(tip, Vec2::new(20.0, 0.0));
+        assert_eq!(left, Vec2::new(14.0, 3.0));
+        assert_eq!(right, Vec2::new(14.0, -3.0));
+
+        // head grows with the line width, but never beyond the arrow length
+        let [_, left, _] = DebugDraw::arrow_head(0.0, 0.0, 20.0, 0.0, 4.0).unwrap();
+        assert_eq!(left, Vec2::new(8.0, 6.0));
+
+        let [_, left, _] = DebugDraw::arrow_head(0.0, 0.0, 4.0, 0.0, 1.0).unwrap();
+        assert_eq!(left, Vec2::new(0.0, 2.0));
+    }
+
+    #[test]
+    fn test_circle_points() {
+        let points = DebugDraw::circle_points(10.0, 20.0, 4.0);
+        assert_eq!(points.len(), 12);
+        assert_eq!(points[0], Vec2::new(14.0, 20.0));
+
+        for p in &points {
+            let d = Vec2::new(p.x - 10.0, p.y - 20.0);
+            assert!(((d.x * d.x + d.y * d.y).sqrt() - 4.0).abs() < 1e-4);
+        }
+
+        assert_eq!(DebugDraw::circle_points(0.0, 0.0, 100.0).len(), 40);
+        assert_eq!(DebugDraw::circle_points(0.0, 0.0, 10000.0).len(), 64);
+    }
+
+    #[test]
+    fn test_line_triangles() {
+        let mut vertices = Vec::new();
+        LineQueue::extrude(0.0, 0.0, 10.0, 0.0, 2.0, 0.0, |v, alpha| vertices.push((v, alpha)));
+
+        // two triangles covering the line width, no feathered edges
+        assert_eq!(vertices.len(), 6);
+        assert!(vertices.iter().all(|(v, alpha)| *alpha == 1.0 && v.y.abs() == 1.0));
+    }
+}
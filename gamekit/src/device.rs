@@ -34,7 +34,9 @@ pub fn required_device_extension_names() -> Vec<*const i8> {
 pub struct DeviceFeatures {
     dynamic_state: bool,
     dynamic_state_3: bool,
-    scalar_block_layout: bool
+    scalar_block_layout: bool,
+    wide_lines: bool,
+    triangle_fans: bool
 }
 
 impl DeviceFeatures {
@@ -49,6 +51,15 @@ impl DeviceFeatures {
     pub fn has_scalar_block_layout(&self) -> bool {
         self.scalar_block_layout
     }
+
+    pub fn has_wide_lines(&self) -> bool {
+        self.wide_lines
+    }
+
+    /// False on portability subset devices (MoltenVK) without triangle fan support
+    pub fn has_triangle_fans(&self) -> bool {
+        self.triangle_fans
+    }
 }
 
 pub struct PhysicalDeviceInfo {
@@ -57,7 +68,9 @@ pub struct PhysicalDeviceInfo {
     pub present_family_index: u32,
     pub mail_box_mode_support: bool,
     pub surface_format: ash::vk::SurfaceFormatKHR,
-    pub uniform_buffer_alignment: usize
+    pub uniform_buffer_alignment: usize,
+    pub line_width_range: [f32; 2],
+    pub portability_subset: bool
 }
 
 pub struct LogicalDeviceInfo {
@@ -73,7 +86,8 @@ pub struct CommandPoolInfo {
 }
 
 pub struct Limits {
-    pub uniform_buffer_alignment: usize
+    pub uniform_buffer_alignment: usize,
+    pub line_width_range: [f32; 2]
 }
 
 pub struct Device {
@@ -121,7 +135,8 @@ impl Device {
         let command_pool_info = Device::create_command_pool(&logical_device_info.obj, physical_device_info.graphics_family_index)?;
 
         let limits = Limits {
-            uniform_buffer_alignment: physical_device_info.uniform_buffer_alignment
+            uniform_buffer_alignment: physical_device_info.uniform_buffer_alignment,
+            line_width_range: physical_device_info.line_width_range
         };
 
         Ok(Self {
@@ -187,6 +202,12 @@ impl Device {
                 }
             }
 
+            // portability subset devices must enable the extension and report missing features
+            let portability_subset = device_extension_properties.iter().any(|device_extension| {
+                let extension_name_str = unsafe { std::ffi::CStr::from_ptr(device_extension.extension_name.as_ptr()) };
+                extension_name_str == ash::khr::portability_subset::NAME
+            });
+
             // check surface format
             let surface_formats = unsafe {
                 surface_loader.get_physical_device_surface_formats(physical_device, surface.obj).unwrap()
@@ -258,7 +279,9 @@ impl Device {
                 present_family_index: present_family_index as u32,
                 mail_box_mode_support,
                 surface_format,
-                uniform_buffer_alignment: properties.limits.min_uniform_buffer_offset_alignment as usize
+                uniform_buffer_alignment: properties.limits.min_uniform_buffer_offset_alignment as usize,
+                line_width_range: properties.limits.line_width_range,
+                portability_subset
             };
 
             let physical_device_name = String::from( unsafe { std::ffi::CStr::from_ptr(properties.device_name.as_ptr()) }.to_str().unwrap());
@@ -301,7 +324,10 @@ impl Device {
                 .collect::<Vec<_>>()
         };
 
-        let enabled_device_extension_names = required_device_extension_names();
+        let mut enabled_device_extension_names = required_device_extension_names();
+        if physical_device_info.portability_subset {
+            enabled_device_extension_names.push(ash::khr::portability_subset::NAME.as_ptr());
+        }
 
         let mut device_feature_selector = vk::PhysicalDeviceFeatures2::default();
         let mut feature_info_dynamic_state = vk::PhysicalDeviceExtendedDynamicStateFeaturesEXT::default();
@@ -322,6 +348,21 @@ impl Device {
             return Err(Error::from("feature 'dynamic state' not supported by device"));
         }
 
+        // optional, all supported core features are enabled
+        if device_feature_selector.features.wide_lines == vk::TRUE {
+            device_features.wide_lines = true;
+        }
+
+        // triangle fans are core, except on portability subset devices
+        let mut feature_info_portability = vk::PhysicalDevicePortabilitySubsetFeaturesKHR::default();
+        if physical_device_info.portability_subset {
+            let mut portability_selector = vk::PhysicalDeviceFeatures2::default().push_next(&mut feature_info_portability);
+            unsafe { instance.obj.get_physical_device_features2(physical_device, &mut portability_selector) };
+            device_features.triangle_fans = feature_info_portability.triangle_fans == vk::TRUE;
+        } else {
+            device_features.triangle_fans = true;
+        }
+
         if Constants::REQUIRE_EXTENDED_DYNAMIC_STATE3 {
             if feature_info_dynamic_state3.extended_dynamic_state3_color_blend_enable == vk::TRUE {
                 device_features.dynamic_state_3 = true;
@@ -343,11 +384,15 @@ impl Device {
             }
         }
 
-        let device_create_info = vk::DeviceCreateInfo::default()
+        let mut device_create_info = vk::DeviceCreateInfo::default()
             .queue_create_infos(&queue_create_infos)
             .enabled_extension_names(&enabled_device_extension_names)
             .push_next(&mut device_feature_selector);
 
+        if physical_device_info.portability_subset {
+            device_create_info = device_create_info.push_next(&mut feature_info_portability);
+        }
+
         let logical_device = unsafe { instance.obj.create_device(physical_device, &device_create_info, None).unwrap() };
         let graphics_queue = unsafe { logical_device.get_device_queue(physical_device_info.graphics_family_index, 0) };
         let present_queue = unsafe { logical_device.get_device_queue(physical_device_info.present_family_index, 0) };
@@ -0,0 +1,361 @@
+//!
+//! Map Entities
+//! LDtk entity instances with typed custom fields
+//!
+
+use crate::primitives::Color;
+use crate::thirdparty::LdtkJson::{EntityInstance, FieldInstance, TilesetRectangle};
+
+/// Tileset rectangle of an entity or tile field
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EntityTile {
+    pub tileset_uid: i64,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32
+}
+
+impl EntityTile {
+    fn from_rect(rect: &TilesetRectangle) -> Self {
+        Self {
+            tileset_uid: rect.tileset_uid,
+            x: rect.x as i32,
+            y: rect.y as i32,
+            width: rect.w as i32,
+            height: rect.h as i32
+        }
+    }
+
+    fn from_json(value: &serde_json::Value) -> Option<Self> {
+        let rect: TilesetRectangle = serde_json::from_value(value.clone()).ok()?;
+        Some(Self::from_rect(&rect))
+    }
+}
+
+/// Reference to another entity, resolved with `MapLevel::entity`
+/// when the target lives in the same level
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityRef {
+    pub entity_iid: String,
+    pub layer_iid: String,
+    pub level_iid: String,
+    pub world_iid: String
+}
+
+impl EntityRef {
+    fn from_json(value: &serde_json::Value) -> Option<Self> {
+        let field = |name: &str| value.get(name).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+
+        let entity_iid = field("entityIid");
+        if entity_iid.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            entity_iid,
+            layer_iid: field("layerIid"),
+            level_iid: field("levelIid"),
+            world_iid: field("worldIid")
+        })
+    }
+}
+
+/// Value of an entity field, arrays hold one value per element
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Null,
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    String(String),
+    Color(Color),
+    Point(i64, i64),
+    Enum(String),
+    EntityRef(EntityRef),
+    Tile(EntityTile),
+    Array(Vec<FieldValue>)
+}
+
+impl FieldValue {
+
+    /// Convert an LDtk `__value` according to the `__type` of the field
+    pub fn parse(field_type: &str, value: &serde_json::Value) -> Self {
+
+        if value.is_null() {
+            return FieldValue::Null;
+        }
+
+        if let Some(element_type) = field_type.strip_prefix("Array<").and_then(|t| t.strip_suffix('>')) {
+            return match value.as_array() {
+                Some(elements) => FieldValue::Array(
+                    elements.iter().map(|element| Self::parse(element_type, element)).collect()
+                ),
+                None => FieldValue::Null
+            };
+        }
+
+        let parsed = match field_type {
+            "Int" => value.as_i64().map(FieldValue::Int),
+            "Float" => value.as_f64().map(FieldValue::Float),
+            "Bool" => value.as_bool().map(FieldValue::Bool),
+            "String" | "Multilines" | "FilePath" => value.as_str().map(|s| FieldValue::String(s.to_string())),
+            "Color" => value.as_str().and_then(Color::parse).map(FieldValue::Color),
+            "Point" => {
+                let cx = value.get("cx").and_then(|v| v.as_i64());
+                let cy = value.get("cy").and_then(|v| v.as_i64());
+                cx.zip(cy).map(|(cx, cy)| FieldValue::Point(cx, cy))
+            },
+            "EntityRef" => EntityRef::from_json(value).map(FieldValue::EntityRef),
+            "Tile" => EntityTile::from_json(value).map(FieldValue::Tile),
+            _ if field_type.starts_with("LocalEnum.") || field_type.starts_with("ExternEnum.") => {
+                value.as_str().map(|s| FieldValue::Enum(s.to_string()))
+            },
+            _ => None
+        };
+
+        parsed.unwrap_or(FieldValue::Null)
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, FieldValue::Null)
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            FieldValue::Int(v) => Some(*v),
+            _ => None
+        }
+    }
+
+    /// Float value, integers are converted
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            FieldValue::Float(v) => Some(*v),
+            FieldValue::Int(v) => Some(*v as f64),
+            _ => None
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            FieldValue::Bool(v) => Some(*v),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            FieldValue::String(v) => Some(v.as_str()),
+            _ => None
+        }
+    }
+
+    pub fn as_color(&self) -> Option<&Color> {
+        match self {
+            FieldValue::Color(v) => Some(v),
+            _ => None
+        }
+    }
+
+    /// Grid coordinates (cx, cy)
+    pub fn as_point(&self) -> Option<(i64, i64)> {
+        match self {
+            FieldValue::Point(cx, cy) => Some((*cx, *cy)),
+            _ => None
+        }
+    }
+
+    pub fn as_enum(&self) -> Option<&str> {
+        match self {
+            FieldValue::Enum(v) => Some(v.as_str()),
+            _ => None
+        }
+    }
+
+    pub fn as_entity_ref(&self) -> Option<&EntityRef> {
+        match self {
+            FieldValue::EntityRef(v) => Some(v),
+            _ => None
+        }
+    }
+
+    pub fn as_tile(&self) -> Option<&EntityTile> {
+        match self {
+            FieldValue::Tile(v) => Some(v),
+            _ => None
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[FieldValue]> {
+        match self {
+            FieldValue::Array(v) => Some(v.as_slice()),
+            _ => None
+        }
+    }
+}
+
+/// Custom field of an entity
+#[derive(Debug, Clone)]
+pub struct EntityField {
+    pub identifier: String,
+    pub field_type: String,
+    pub value: FieldValue
+}
+
+impl EntityField {
+    fn from_instance(field: &FieldInstance) -> Self {
+        let value = match &field.value {
+            Some(value) => FieldValue::parse(&field.field_instance_type, value),
+            None => FieldValue::Null
+        };
+
+        Self {
+            identifier: field.identifier.clone(),
+            field_type: field.field_instance_type.clone(),
+            value
+        }
+    }
+}
+
+/// Entity instance of a level. The position is the pivot point in
+/// level pixel coordinates.
+#[derive(Debug, Clone)]
+pub struct MapEntity {
+    pub identifier: String,
+    pub iid: String,
+    pub layer: String,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub pivot_x: f32,
+    pub pivot_y: f32,
+    pub grid_x: i32,
+    pub grid_y: i32,
+    pub color: Color,
+    pub tags: Vec<String>,
+    pub tile: Option<EntityTile>,
+    pub fields: Vec<EntityField>
+}
+
+impl MapEntity {
+    pub(crate) fn from_instance(entity: &EntityInstance, layer: &str) -> Self {
+
+        let coord = |values: &[i64], index: usize| values.get(index).copied().unwrap_or_default();
+        let pivot = |index: usize| entity.pivot.get(index).copied().unwrap_or_default() as f32;
+
+        Self {
+            identifier: entity.identifier.clone(),
+            iid: entity.iid.clone(),
+            layer: layer.to_string(),
+            x: coord(&entity.px, 0) as f32,
+            y: coord(&entity.px, 1) as f32,
+            width: entity.width as f32,
+            height: entity.height as f32,
+            pivot_x: pivot(0),
+            pivot_y: pivot(1),
+            grid_x: coord(&entity.grid, 0) as i32,
+            grid_y: coord(&entity.grid, 1) as i32,
+            color: Color::parse(&entity.smart_color).unwrap_or(Color::white()),
+            tags: entity.tags.clone(),
+            tile: entity.tile.as_ref().map(EntityTile::from_rect),
+            fields: entity.field_instances.iter().map(EntityField::from_instance).collect()
+        }
+    }
+
+    /// Left edge of the bounds
+    pub fn left(&self) -> f32 {
+        self.x - self.pivot_x * self.width
+    }
+
+    /// Top edge of the bounds
+    pub fn top(&self) -> f32 {
+        self.y - self.pivot_y * self.height
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    pub fn field(&self, identifier: &str) -> Option<&FieldValue> {
+        self.fields.iter().find(|field| field.identifier == identifier).map(|field| &field.value)
+    }
+
+    pub fn get_int(&self, identifier: &str) -> Option<i64> {
+        self.field(identifier)?.as_int()
+    }
+
+    pub fn get_float(&self, identifier: &str) -> Option<f64> {
+        self.field(identifier)?.as_float()
+    }
+
+    pub fn get_bool(&self, identifier: &str) -> Option<bool> {
+        self.field(identifier)?.as_bool()
+    }
+
+    pub fn get_string(&self, identifier: &str) -> Option<&str> {
+        self.field(identifier)?.as_str()
+    }
+
+    pub fn get_color(&self, identifier: &str) -> Option<&Color> {
+        self.field(identifier)?.as_color()
+    }
+
+    pub fn get_point(&self, identifier: &str) -> Option<(i64, i64)> {
+        self.field(identifier)?.as_point()
+    }
+
+    pub fn get_enum(&self, identifier: &str) -> Option<&str> {
+        self.field(identifier)?.as_enum()
+    }
+
+    pub fn get_entity_ref(&self, identifier: &str) -> Option<&EntityRef> {
+        self.field(identifier)?.as_entity_ref()
+    }
+
+    pub fn get_tile(&self, identifier: &str) -> Option<&EntityTile> {
+        self.field(identifier)?.as_tile()
+    }
+
+    pub fn get_array(&self, identifier: &str) -> Option<&[FieldValue]> {
+        self.field(identifier)?.as_array()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_field_values() {
+        assert_eq!(FieldValue::parse("Int", &json!(42)), FieldValue::Int(42));
+        assert_eq!(FieldValue::parse("Float", &json!(1.5)).as_float(), Some(1.5));
+        assert_eq!(FieldValue::parse("Int", &json!(3)).as_float(), Some(3.0));
+        assert_eq!(FieldValue::parse("Bool", &json!(true)), FieldValue::Bool(true));
+        assert_eq!(FieldValue::parse("Multilines", &json!("a\nb")).as_str(), Some("a\nb"));
+        assert_eq!(FieldValue::parse("LocalEnum.Item", &json!("Key")).as_enum(), Some("Key"));
+        assert_eq!(FieldValue::parse("Point", &json!({"cx": 3, "cy": 7})).as_point(), Some((3, 7)));
+        assert_eq!(FieldValue::parse("Color", &json!("#ff0000")).as_color(), Some(&Color::rgb(1.0, 0.0, 0.0)));
+        assert!(FieldValue::parse("String", &serde_json::Value::Null).is_null());
+
+        // wrong type yields no value
+        assert_eq!(FieldValue::parse("Int", &json!(42)).as_str(), None);
+    }
+
+    #[test]
+    fn test_parse_field_arrays_and_refs() {
+        let value = FieldValue::parse("Array<Int>", &json!([1, 2, 3]));
+        let elements: Vec<i64> = value.as_array().unwrap().iter().filter_map(|v| v.as_int()).collect();
+        assert_eq!(elements, vec![1, 2, 3]);
+
+        let value = FieldValue::parse("EntityRef", &json!({
+            "entityIid": "e1", "layerIid": "l1", "levelIid": "v1", "worldIid": "w1"
+        }));
+        assert_eq!(value.as_entity_ref().map(|r| r.entity_iid.as_str()), Some("e1"));
+
+        let value = FieldValue::parse("Tile", &json!({"tilesetUid": 5, "x": 16, "y": 32, "w": 16, "h": 16}));
+        assert_eq!(value.as_tile(), Some(&EntityTile { tileset_uid: 5, x: 16, y: 32, width: 16, height: 16 }));
+    }
+}
@@ -188,6 +188,14 @@ impl <T: Application + Runnable + Disposable + 'static> Exec<T> {
                     }
 
                     Self::update(&self.application);
+
+                    {
+                        // no-op unless deferred, compute dispatches and
+                        // render targets are recorded during update
+                        let renderer = crate::globals::renderer_mut();
+                        renderer.begin_render_pass();
+                    }
+
                     Self::draw(&self.application);
                 }
 
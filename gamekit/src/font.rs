@@ -2,18 +2,52 @@
 //! Font
 //!
 
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
-use gamebuilder::manifest::StaticFontDescriptor;
+use gamebuilder::{bmfont::BmFont, manifest::{StaticFontDescriptor, name_from_path}};
 
 use crate::{api::{Disposable, LockRef}, error::Error, math::{Vec2, Vec4}, texture::TextureLockRef};
 
+/// Glyph metrics, offsets and advance are in pixels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Glyph {
+    pub rect: Vec4,     // texture coords (x, y, w, h)
+    pub size: Vec2,
+    pub offset: Vec2,   // from the pen position (top of the line)
+    pub advance: f32,
+    pub page: u32       // atlas page, selected by the texture mask
+}
+
+impl Glyph {
+    /// Texture mask of the glyph quads, bit n selects page n. Materials
+    /// bind the pages to consecutive bindings starting at the texture binding.
+    pub fn texture_mask(&self) -> u32 {
+        1 << self.page.min(31)
+    }
+}
+
+/// Fixed grid font or proportional BMFont font.
+///
+/// Grid fonts use `char_width` x `char_height` cells in rows and columns,
+/// the n-th character of the charset maps to the n-th cell. Without a
+/// charset, code points map to cells directly. BMFont fonts have per-glyph
+/// metrics, kerning pairs and may span several atlas pages. Characters
+/// not found use the fallback glyph, if any.
 #[derive(Default, Clone, Debug)]
 pub struct Font {
     charset: &'static str,
     char_width: u32,
     char_height: u32,
-    texture_width: u32
+    texture_width: u32,
+    texture_height: u32,
+    line_height: u32,
+    base: u32,
+    pages: Vec<String>,
+    char_map: HashMap<char, u32>,
+    fallback: Option<char>,
+    glyphs: HashMap<char, Glyph>,
+    kernings: HashMap<(char, char), f32>
 }
 
 pub type FontRef = std::sync::Arc<Font>;
@@ -27,14 +61,46 @@ impl Disposable for Font {
 impl Font {
     pub fn new(charset: &'static str, char_width: u32, char_height: u32, texture: &TextureLockRef) -> Result<Self, Error> {
 
-        let texture_width = texture.lock().unwrap().width;
+        let (texture_width, texture_height) = {
+            let texture = texture.lock().unwrap();
+            (texture.width, texture.height)
+        };
 
-        Ok(Self {
+        Ok(Self::with_grid(charset, char_width, char_height, texture_width, texture_height))
+    }
+
+    /// Grid font for a texture of the given size
+    pub fn with_grid(charset: &'static str, char_width: u32, char_height: u32, texture_width: u32, texture_height: u32) -> Self {
+
+        // first occurrence wins for duplicate characters
+        let mut char_map = HashMap::new();
+        for (index, c) in charset.chars().enumerate() {
+            char_map.entry(c).or_insert(index as u32);
+        }
+
+        Self {
             charset,
             char_width,
             char_height,
-            texture_width
-        })
+            texture_width,
+            texture_height,
+            line_height: char_height,
+            base: char_height,
+            char_map,
+            ..Default::default()
+        }
+    }
+
+    /// Proportional font with the given atlas size and page textures
+    pub fn with_metrics(line_height: u32, base: u32, texture_width: u32, texture_height: u32, pages: &[&str]) -> Self {
+        Self {
+            texture_width: texture_width.max(1),
+            texture_height: texture_height.max(1),
+            line_height,
+            base,
+            pages: pages.iter().map(|page| page.to_string()).collect(),
+            ..Default::default()
+        }
     }
 
     pub fn to_lockref(font: Self) -> FontLockRef {
@@ -42,9 +108,109 @@ impl Font {
     }
 
     pub fn from_resource(descriptor: &StaticFontDescriptor) -> Result<Self, Error> {
-        let resources = crate::globals::resources();
-        let texture = resources.get_texture(descriptor.texture);
-        Self::new(descriptor.charset, descriptor.char_width, descriptor.char_height, &texture)
+
+        let mut font = if descriptor.glyphs.is_empty() {
+            let resources = crate::globals::resources();
+            let texture = resources.get_texture(descriptor.texture);
+            Self::new(descriptor.charset, descriptor.char_width, descriptor.char_height, &texture)?
+        } else {
+            Self::with_metrics(descriptor.line_height, descriptor.base,
+                descriptor.texture_width, descriptor.texture_height, descriptor.pages)
+        };
+
+        font.set_fallback(descriptor.fallback.chars().next());
+
+        for g in descriptor.glyphs {
+            font.add_glyph(g.id, g.x, g.y, g.w, g.h, g.offset_x, g.offset_y, g.advance, g.page);
+        }
+
+        for k in descriptor.kernings {
+            font.add_kerning(k.first, k.second, k.amount);
+        }
+
+        Ok(font)
+    }
+
+    /// Load BMFont descriptor (text, XML or binary), page textures are
+    /// named after the page files unless given
+    pub fn from_bmfont(data: &[u8], pages: &[&str]) -> Result<Self, Error> {
+
+        let obj = match BmFont::parse(data) {
+            Ok(obj) => obj,
+            Err(e) => {
+                return Err(Error::from(e));
+            }
+        };
+
+        let page_names: Vec<String> = if pages.is_empty() {
+            obj.pages.iter().map(|page| name_from_path("", page)).collect()
+        } else {
+            pages.iter().map(|page| page.to_string()).collect()
+        };
+
+        let page_refs: Vec<&str> = page_names.iter().map(|page| page.as_str()).collect();
+        let mut font = Self::with_metrics(obj.line_height, obj.base, obj.scale_w, obj.scale_h, &page_refs);
+
+        for c in &obj.chars {
+            font.add_glyph(c.id, c.x, c.y, c.width, c.height, c.xoffset, c.yoffset, c.xadvance, c.page);
+        }
+
+        for k in &obj.kernings {
+            font.add_kerning(k.first, k.second, k.amount);
+        }
+
+        Ok(font)
+    }
+
+    pub fn from_bmfont_file(name: &str, pages: &[&str]) -> Result<Self, Error> {
+        let data = match std::fs::read(name) {
+            Ok(data) => data,
+            Err(_e) => {
+                return Err(Error::from(format!("failed to load font from file \"{name}\"")));
+            }
+        };
+
+        Self::from_bmfont(&data, pages)
+    }
+
+    /// Add glyph from atlas pixel coordinates, invalid code points are ignored
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_glyph(&mut self, id: u32, x: u32, y: u32, w: u32, h: u32, offset_x: i32, offset_y: i32, advance: i32, page: u32) {
+
+        let Some(c) = char::from_u32(id) else {
+            return;
+        };
+
+        let tw = self.texture_width.max(1) as f32;
+        let th = self.texture_height.max(1) as f32;
+
+        self.glyphs.insert(c, Glyph {
+            rect: Vec4::new(x as f32 / tw, y as f32 / th, w as f32 / tw, h as f32 / th),
+            size: Vec2::new(w as f32, h as f32),
+            offset: Vec2::new(offset_x as f32, offset_y as f32),
+            advance: advance as f32,
+            page
+        });
+    }
+
+    pub fn add_kerning(&mut self, first: u32, second: u32, amount: i32) {
+        if let (Some(a), Some(b)) = (char::from_u32(first), char::from_u32(second)) {
+            self.kernings.insert((a, b), amount as f32);
+        }
+    }
+
+    /// Set glyph used for characters missing from the font
+    pub fn set_fallback(&mut self, fallback: Option<char>) -> &mut Self {
+        self.fallback = fallback;
+        self
+    }
+
+    pub fn fallback(&self) -> Option<char> {
+        self.fallback
+    }
+
+    pub fn is_proportional(&self) -> bool {
+        !self.glyphs.is_empty()
     }
 
     pub fn char_width(&self) -> u32 {
@@ -55,31 +221,191 @@ impl Font {
         self.char_height
     }
 
+    /// Distance between two lines of text
+    pub fn line_height(&self) -> u32 {
+        if self.is_proportional() { self.line_height } else { self.char_height }
+    }
+
+    /// Distance from the top of the line to the baseline
+    pub fn base(&self) -> u32 {
+        if self.is_proportional() { self.base } else { self.char_height }
+    }
+
     pub fn charset(&self) -> &str {
         self.charset
     }
 
+    /// Page texture names of proportional fonts
+    pub fn pages(&self) -> &[String] {
+        &self.pages
+    }
+
+    /// Grid columns and rows
+    pub fn grid_size(&self) -> (u32, u32) {
+        (self.texture_width / self.char_width.max(1), self.texture_height / self.char_height.max(1))
+    }
+
+    /// Number of glyphs
     pub fn size(&self) -> usize {
-        (self.texture_width / self.char_width) as usize
+        if self.is_proportional() {
+            self.glyphs.len()
+        } else {
+            let (columns, rows) = self.grid_size();
+            (columns * rows) as usize
+        }
     }
 
     pub fn get_rect_by_idx(&self, idx: u32) -> Vec4 {
-        let w = (self.char_width as f32) / (self.texture_width as f32);
-        let h = 1.0f32;
-        let x = w * (idx as f32);
+        let columns = self.grid_size().0.max(1);
+        let w = (self.char_width as f32) / (self.texture_width.max(1) as f32);
+        let h = (self.char_height as f32) / (self.texture_height.max(1) as f32);
+        let x = w * ((idx % columns) as f32);
+        let y = h * ((idx / columns) as f32);
 
         Vec4::new(
-            x, 0.0, w, h
+            x, y, w, h
         )
     }
 
+    /// Grid cell of a character, the fallback or the first cell if not found
+    pub fn get_index(&self, c: char) -> u32 {
+        self.find_index(c)
+            .or_else(|| self.fallback.and_then(|fallback| self.find_index(fallback)))
+            .unwrap_or_default()
+    }
+
+    fn find_index(&self, c: char) -> Option<u32> {
+        if self.charset.is_empty() {
+            let idx = c as u32;
+            if (idx as usize) < self.size() { Some(idx) } else { None }
+        } else {
+            self.char_map.get(&c).copied()
+        }
+    }
+
     pub fn get_rect(&self, c: char) -> Vec4 {
-        let idx = self.charset.find(c).unwrap_or_default();
-        self.get_rect_by_idx(idx as u32)
+        if self.is_proportional() {
+            return self.glyph(c).map(|glyph| glyph.rect).unwrap_or(Vec4::new(0.0, 0.0, 0.0, 0.0));
+        }
+
+        self.get_rect_by_idx(self.get_index(c))
+    }
+
+    /// Glyph of a character, grid fonts map every character to a cell
+    pub fn glyph(&self, c: char) -> Option<Glyph> {
+        if self.is_proportional() {
+            return self.glyphs.get(&c)
+                .or_else(|| self.fallback.and_then(|fallback| self.glyphs.get(&fallback)))
+                .copied();
+        }
+
+        let w = self.char_width as f32;
+        let h = self.char_height as f32;
+
+        Some(Glyph {
+            rect: self.get_rect_by_idx(self.get_index(c)),
+            size: Vec2::new(w, h),
+            offset: Vec2::new(0.0, 0.0),
+            advance: w,
+            page: 0
+        })
+    }
+
+    /// Kerning adjustment between two characters (pixels)
+    pub fn kerning(&self, first: char, second: char) -> f32 {
+        self.kernings.get(&(first, second)).copied().unwrap_or_default()
     }
 
+    /// Size of a text block, lines are separated by '\n'
     pub fn get_text_extent(&self, text: &str) -> Vec2 {
-        Vec2::new((text.len() * self.char_width as usize) as f32, self.char_height as f32)
+
+        let mut width = 0.0f32;
+        let mut num_lines = 0;
+
+        for line in text.split('\n') {
+            let mut x = 0.0;
+            let mut prev: Option<char> = None;
+
+            for c in line.chars() {
+                if let Some(p) = prev {
+                    x += self.kerning(p, c);
+                }
+                x += self.glyph(c).map(|glyph| glyph.advance).unwrap_or_default();
+                prev = Some(c);
+            }
+
+            width = width.max(x);
+            num_lines += 1;
+        }
+
+        Vec2::new(width, (num_lines * self.line_height()) as f32)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grid() {
+        // 4 columns, 2 rows
+        let mut font = Font::with_grid("AB\u{c4}\u{416}\u{2665} AB", 8, 8, 32, 16);
+        assert_eq!(font.grid_size(), (4, 2));
+        assert_eq!(font.size(), 8);
+
+        assert_eq!(font.get_index('\u{416}'), 3);
+        assert_eq!(font.get_rect('\u{2665}'), Vec4::new(0.0, 0.5, 0.25, 0.5));
+        assert_eq!(font.get_index('B'), 1);
+
+        assert_eq!(font.get_index('?'), 0);
+        font.set_fallback(Some(' '));
+        assert_eq!(font.get_index('?'), 5);
+
+        // code points map to cells without a charset
+        let font = Font::with_grid("", 8, 8, 32, 16);
+        assert_eq!(font.get_index('\u{5}'), 5);
+        assert_eq!(font.get_index('A'), 0);
+    }
+
+    #[test]
+    fn test_text_extent() {
+        let fnt = "common lineHeight=18 base=14 scaleW=128 scaleH=64 pages=1\n\
+            page id=0 file=\"sans_0.png\"\n\
+            char id=65 x=0 y=0 width=10 height=12 xoffset=0 yoffset=2 xadvance=11 page=0\n\
+            char id=86 x=10 y=0 width=10 height=12 xoffset=-1 yoffset=2 xadvance=10 page=0\n\
+            char id=228 x=20 y=0 width=8 height=12 xoffset=0 yoffset=2 xadvance=9 page=0\n\
+            kerning first=65 second=86 amount=-2\n";
+
+        let font = Font::from_bmfont(fnt.as_bytes(), &[]).unwrap();
+        assert_eq!(font.pages(), &["sans_0"]);
+        assert_eq!(font.glyph('V').unwrap().rect, Vec4::new(10.0 / 128.0, 0.0, 10.0 / 128.0, 12.0 / 64.0));
+
+        // kerning applies to adjacent pairs only
+        assert_eq!(font.get_text_extent("AV"), Vec2::new(19.0, 18.0));
+        assert_eq!(font.get_text_extent("VA"), Vec2::new(21.0, 18.0));
+
+        // multi-byte characters count once
+        assert_eq!(font.get_text_extent("\u{e4}A\nA"), Vec2::new(20.0, 36.0));
     }
 
+    #[test]
+    fn test_multi_page() {
+        let fnt = "common lineHeight=18 base=14 scaleW=128 scaleH=64 pages=2\n\
+            page id=0 file=\"sans_0.png\"\n\
+            page id=1 file=\"sans_1.png\"\n\
+            char id=65 x=0 y=0 width=10 height=12 xoffset=0 yoffset=2 xadvance=11 page=0\n\
+            char id=86 x=10 y=0 width=10 height=12 xoffset=-1 yoffset=2 xadvance=10 page=1\n";
+
+        let font = Font::from_bmfont(fnt.as_bytes(), &[]).unwrap();
+        assert_eq!(font.pages(), &["sans_0", "sans_1"]);
+
+        // glyphs on page 1 sample the second page texture
+        assert_eq!(font.glyph('A').unwrap().texture_mask(), 0x1);
+        assert_eq!(font.glyph('V').unwrap().page, 1);
+        assert_eq!(font.glyph('V').unwrap().texture_mask(), 0x2);
+
+        // grid fonts have a single page
+        assert_eq!(Font::with_grid("AB", 8, 8, 16, 8).glyph('B').unwrap().texture_mask(), 0x1);
+    }
 }
@@ -46,6 +46,7 @@ impl Image {
 
     pub const PIXEL_BUFFER: u32 = 0x1;
     pub const DEPTH_BUFFER: u32 = 0x2;
+    pub const RENDER_TARGET: u32 = 0x3;
 
     pub fn new(image_type: u32, width: u32, height: u32, size: usize, format: vk::Format) -> Result<Self, Error> {
         Self::create(
@@ -131,6 +132,7 @@ impl Image {
         let usage_flags = match image_type {
             Self::DEPTH_BUFFER => vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
             Self::PIXEL_BUFFER => vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
+            Self::RENDER_TARGET => vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
             _ => { return Err(Error::from("unknown image_type")); }
         };
 
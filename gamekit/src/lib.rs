@@ -41,18 +41,31 @@ mod image;
 mod texture;
 mod shader;
 mod material;
+mod compute;
+mod target;
 mod primitives;
 mod random;
 mod logger;
 mod animator;
+mod animation;
+mod tween;
+mod particles;
+mod lighting;
 mod sprite;
+mod nineslice;
+mod text;
 mod bitmap;
 mod font;
 mod data;
 mod blitter;
+mod batch;
+mod debug;
 mod audio;
 mod input;
 mod maps;
+mod entities;
+mod collision;
+mod tiled;
 mod builtin;
 
 pub mod api;
@@ -0,0 +1,657 @@
+//!
+//! Lighting
+//!
+
+use std::sync::{Arc, Mutex};
+
+use crate::{api::{Disposable, LockRef}, buffer::{ShaderStorage, Uniform}, error::Error, maps::MapLayer, material::{BlendMode, Material, MaterialLockRef}, primitives::{Color, QuadQueue, ShaderInputType}, target::RenderTarget};
+
+const SHADER_PARAMS_BINDING: u32 = 0;
+const LIGHT_BUFFER_BINDING: u32 = 1;
+const OCCLUDER_BUFFER_BINDING: u32 = 2;
+const NORMAL_BUFFER_BINDING: u32 = 3;
+const LIGHT_MAP_BINDING: u32 = 1;
+
+const DEFAULT_MAX_LIGHTS: usize = 256;
+const DEFAULT_MAX_OCCLUDERS: usize = 4096;
+const DEFAULT_FALLOFF: f32 = 1.0;
+const DEFAULT_HEIGHT: f32 = 32.0;
+
+const LIGHT_FLAG_SPOT: u32 = 0x1;
+const LIGHT_FLAG_SHADOWS: u32 = 0x2;
+
+#[derive(PartialEq, Default, Debug, Clone, Copy)]
+pub enum LightType {
+    #[default]
+    Point,
+    /// Cone around `direction` with opening `angle`
+    Spot
+}
+
+#[derive(Clone, Debug)]
+pub struct Light {
+    pub light_type: LightType,
+    pub x: f32,
+    pub y: f32,
+    pub color: Color,
+    pub intensity: f32,
+    pub radius: f32,
+    /// Exponent of the attenuation curve, 1.0 = linear
+    pub falloff: f32,
+    /// Spot direction in degrees, 0 = right, 90 = down
+    pub direction: f32,
+    /// Spot cone opening in degrees
+    pub angle: f32,
+    /// Fraction of the cone fading out towards the edge
+    pub softness: f32,
+    /// Distance above the ground, used with normal maps
+    pub height: f32,
+    pub shadows: bool,
+    pub enabled: bool
+}
+
+impl Default for Light {
+    fn default() -> Self {
+        Self {
+            light_type: LightType::Point,
+            x: 0.0,
+            y: 0.0,
+            color: Color::white(),
+            intensity: 1.0,
+            radius: 0.0,
+            falloff: DEFAULT_FALLOFF,
+            direction: 0.0,
+            angle: 360.0,
+            softness: 0.0,
+            height: DEFAULT_HEIGHT,
+            shadows: true,
+            enabled: true
+        }
+    }
+}
+
+impl Light {
+    pub fn point(x: f32, y: f32, radius: f32, color: &Color) -> Self {
+        Self {
+            x,
+            y,
+            radius,
+            color: color.clone(),
+            ..Default::default()
+        }
+    }
+
+    pub fn spot(x: f32, y: f32, radius: f32, direction: f32, angle: f32, color: &Color) -> Self {
+        Self {
+            light_type: LightType::Spot,
+            x,
+            y,
+            radius,
+            direction,
+            angle,
+            softness: 0.25,
+            color: color.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Line segment blocking light
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct Occluder {
+    pub x0: f32,
+    pub y0: f32,
+    pub x1: f32,
+    pub y1: f32
+}
+
+impl Occluder {
+    pub fn new(x0: f32, y0: f32, x1: f32, y1: f32) -> Self {
+        Self { x0, y0, x1, y1 }
+    }
+
+    /// Shortest distance to a point
+    pub fn distance(&self, x: f32, y: f32) -> f32 {
+        let dx = self.x1 - self.x0;
+        let dy = self.y1 - self.y0;
+        let len_sq = dx * dx + dy * dy;
+
+        let t = if len_sq > 0.0 {
+            (((x - self.x0) * dx + (y - self.y0) * dy) / len_sq).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        let px = self.x0 + t * dx - x;
+        let py = self.y0 + t * dy - y;
+
+        (px * px + py * py).sqrt()
+    }
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct LightingShaderParams {
+    window_width: f32,
+    window_height: f32,
+    view_width: f32,
+    view_height: f32,
+    view_x: f32,
+    view_y: f32,
+    view_scaling: f32,
+    offset_x: f32,
+    offset_y: f32,
+    normal_mapping: u32,
+    num_lights: u32
+}
+
+#[repr(C)]
+#[derive(Default, Clone)]
+struct LightData {
+    x: f32,
+    y: f32,
+    radius: f32,
+    falloff: f32,
+    r: f32,
+    g: f32,
+    b: f32,
+    intensity: f32,
+    direction_x: f32,
+    direction_y: f32,
+    cos_outer: f32,
+    cos_inner: f32,
+    height: f32,
+    first_occluder: u32,
+    num_occluders: u32,
+    flags: u32
+}
+
+impl LightData {
+    fn new(light: &Light, first_occluder: usize, num_occluders: usize) -> Self {
+        let direction = light.direction.to_radians();
+        let half_angle = (light.angle * 0.5).clamp(0.0, 180.0).to_radians();
+        let softness = light.softness.clamp(0.0, 1.0);
+
+        let mut flags = 0x0;
+        if light.light_type == LightType::Spot { flags |= LIGHT_FLAG_SPOT; }
+        if num_occluders > 0 { flags |= LIGHT_FLAG_SHADOWS; }
+
+        Self {
+            x: light.x,
+            y: light.y,
+            radius: light.radius,
+            falloff: light.falloff.max(0.0),
+            r: light.color.r,
+            g: light.color.g,
+            b: light.color.b,
+            intensity: light.intensity,
+            direction_x: direction.cos(),
+            direction_y: direction.sin(),
+            cos_outer: half_angle.cos(),
+            cos_inner: (half_angle * (1.0 - softness)).cos(),
+            height: light.height,
+            first_occluder: first_occluder as u32,
+            num_occluders: num_occluders as u32,
+            flags
+        }
+    }
+}
+
+/// Outline of occupied grid cells. Edges between two occupied cells are
+/// skipped, adjacent edges on the same line are merged.
+pub fn grid_outline(occupied: &[bool], cols: usize, rows: usize, grid_size: f32, x: f32, y: f32) -> Vec<Occluder> {
+
+    let cell = |col: isize, row: isize| -> bool {
+        col >= 0 && row >= 0 && (col as usize) < cols && (row as usize) < rows && occupied[row as usize * cols + col as usize]
+    };
+
+    let mut segments = Vec::new();
+
+    let mut push_runs = |len: usize, edge: &dyn Fn(usize) -> bool, segment: &dyn Fn(usize, usize) -> Occluder| {
+        let mut start = None;
+        for index in 0..=len {
+            let present = index < len && edge(index);
+            match (start, present) {
+                (None, true) => { start = Some(index); },
+                (Some(first), false) => {
+                    segments.push(segment(first, index));
+                    start = None;
+                },
+                _ => {}
+            }
+        }
+    };
+
+    for row in 0..rows as isize {
+        let top = y + row as f32 * grid_size;
+        let bottom = top + grid_size;
+        push_runs(cols, &|col| cell(col as isize, row) && !cell(col as isize, row - 1),
+            &|c0, c1| Occluder::new(x + c0 as f32 * grid_size, top, x + c1 as f32 * grid_size, top));
+        push_runs(cols, &|col| cell(col as isize, row) && !cell(col as isize, row + 1),
+            &|c0, c1| Occluder::new(x + c0 as f32 * grid_size, bottom, x + c1 as f32 * grid_size, bottom));
+    }
+
+    for col in 0..cols as isize {
+        let left = x + col as f32 * grid_size;
+        let right = left + grid_size;
+        push_runs(rows, &|row| cell(col, row as isize) && !cell(col - 1, row as isize),
+            &|r0, r1| Occluder::new(left, y + r0 as f32 * grid_size, left, y + r1 as f32 * grid_size));
+        push_runs(rows, &|row| cell(col, row as isize) && !cell(col + 1, row as isize),
+            &|r0, r1| Occluder::new(right, y + r0 as f32 * grid_size, right, y + r1 as f32 * grid_size));
+    }
+
+    segments
+}
+
+/// 2D lights rendered into an offscreen light map, which is multiplied
+/// over the scene.
+///
+/// Per frame, during update: optionally draw normal mapped sprites and
+/// tiles between `begin_normals()` and `end_normals()`, then `render()`
+/// the light map. During draw, `draw()` darkens the scene by the light
+/// map, the ambient color is the light level without any light. Normal
+/// maps use the OpenGL convention (y up). Requires the
+/// `deferred_render_pass` option.
+pub struct Lighting {
+    ambient: Color,
+    normal_mapping: bool,
+    lights: Vec<(u32, Light)>,
+    next_id: u32,
+    occluders: Vec<Occluder>,
+    light_target: RenderTarget,
+    normal_target: RenderTarget,
+    light_queue: QuadQueue,
+    light_material: Option<MaterialLockRef>,
+    composite_material: Option<MaterialLockRef>,
+    shader_params: Uniform::<LightingShaderParams>,
+    light_buffer: ShaderStorage::<LightData>,
+    occluder_buffer: ShaderStorage::<Occluder>,
+    normals_drawn: bool,
+    rendered: bool
+}
+
+pub type LightingLockRef = LockRef<Lighting>;
+
+impl Disposable for Lighting {
+    fn dispose(&mut self) {
+        self.free_materials();
+        self.light_queue.dispose();
+        self.shader_params.dispose();
+        self.light_buffer.dispose();
+        self.occluder_buffer.dispose();
+        self.light_target.dispose();
+        self.normal_target.dispose();
+        self.lights.clear();
+        self.occluders.clear();
+    }
+}
+
+impl Lighting {
+
+    pub fn new(max_lights: usize, max_occluders: usize) -> Result<Self, Error> {
+
+        let max_lights = max_lights.max(1);
+
+        let shader_params = Uniform::<LightingShaderParams>::new(SHADER_PARAMS_BINDING, 0)?;
+        let light_buffer = ShaderStorage::<LightData>::new(LIGHT_BUFFER_BINDING, max_lights)?;
+        let occluder_buffer = ShaderStorage::<Occluder>::new(OCCLUDER_BUFFER_BINDING, max_occluders)?;
+
+        let light_target = RenderTarget::new()?;
+
+        // sampled texels are linearized, clear to what a flat normal map texel samples as
+        let flat = Self::srgb_to_linear(0.5);
+        let mut normal_target = RenderTarget::new()?;
+        normal_target.set_clear_color(&Color::rgba(flat, flat, 1.0, 1.0));
+
+        let mut lighting = Self {
+            ambient: Color::black(),
+            normal_mapping: false,
+            lights: Vec::new(),
+            next_id: 1,
+            occluders: Vec::new(),
+            light_target,
+            normal_target,
+            light_queue: QuadQueue::new(max_lights),
+            light_material: None,
+            composite_material: None,
+            shader_params,
+            light_buffer,
+            occluder_buffer,
+            normals_drawn: false,
+            rendered: false
+        };
+
+        lighting.create_materials();
+
+        Ok(lighting)
+    }
+
+    pub fn with_default_capacity() -> Result<Self, Error> {
+        Self::new(DEFAULT_MAX_LIGHTS, DEFAULT_MAX_OCCLUDERS)
+    }
+
+    pub fn to_lockref(lighting: Self) -> LightingLockRef {
+        Arc::new(Mutex::new(lighting))
+    }
+
+    fn srgb_to_linear(value: f32) -> f32 {
+        if value <= 0.04045 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) }
+    }
+
+    /// Light level of unlit areas
+    pub fn set_ambient(&mut self, color: &Color) -> &mut Self {
+        self.ambient = color.clone();
+        self
+    }
+
+    pub fn ambient(&self) -> &Color {
+        &self.ambient
+    }
+
+    /// Shade lights by the normal buffer
+    pub fn set_normal_mapping(&mut self, enabled: bool) -> &mut Self {
+        self.normal_mapping = enabled;
+        self
+    }
+
+    pub fn add_light(&mut self, light: Light) -> u32 {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1).max(1);
+        self.lights.push((id, light));
+        id
+    }
+
+    pub fn remove_light(&mut self, id: u32) {
+        self.lights.retain(|(light_id, _)| *light_id != id);
+    }
+
+    pub fn light(&self, id: u32) -> Option<&Light> {
+        self.lights.iter().find(|(light_id, _)| *light_id == id).map(|(_, light)| light)
+    }
+
+    pub fn light_mut(&mut self, id: u32) -> Option<&mut Light> {
+        self.lights.iter_mut().find(|(light_id, _)| *light_id == id).map(|(_, light)| light)
+    }
+
+    pub fn set_light_position(&mut self, id: u32, x: f32, y: f32) {
+        if let Some(light) = self.light_mut(id) {
+            light.x = x;
+            light.y = y;
+        }
+    }
+
+    pub fn clear_lights(&mut self) {
+        self.lights.clear();
+    }
+
+    pub fn num_lights(&self) -> usize {
+        self.lights.len()
+    }
+
+    pub fn add_occluder(&mut self, x0: f32, y0: f32, x1: f32, y1: f32) {
+        self.occluders.push(Occluder::new(x0, y0, x1, y1));
+    }
+
+    pub fn add_occluder_rect(&mut self, x: f32, y: f32, w: f32, h: f32) {
+        self.add_occluder(x, y, x + w, y);
+        self.add_occluder(x + w, y, x + w, y + h);
+        self.add_occluder(x + w, y + h, x, y + h);
+        self.add_occluder(x, y + h, x, y);
+    }
+
+    /// Let the non-transparent tiles of a map layer cast shadows, the
+    /// offset is the map position in world coordinates
+    pub fn add_layer_occluders(&mut self, layer: &MapLayer, offset_x: f32, offset_y: f32) {
+        let occupied = layer.occupancy();
+        let segments = grid_outline(&occupied, layer.cols(), layer.rows(), layer.grid_size() as f32, offset_x, offset_y);
+        self.occluders.extend(segments);
+    }
+
+    pub fn occluders(&self) -> &[Occluder] {
+        &self.occluders
+    }
+
+    pub fn clear_occluders(&mut self) {
+        self.occluders.clear();
+    }
+
+    /// Start drawing normal maps into the normal buffer, during update
+    pub fn begin_normals(&mut self) -> Result<(), Error> {
+        self.validate_targets();
+        self.normal_target.begin()?;
+        self.normals_drawn = true;
+        Ok(())
+    }
+
+    pub fn end_normals(&mut self) {
+        self.normal_target.end();
+    }
+
+    /// Render the light map, during update. Lights and occluders are in
+    /// world coordinates, moved by the draw offset (camera).
+    pub fn render(&mut self, offset_x: f32, offset_y: f32) -> Result<(), Error> {
+
+        self.validate_targets();
+
+        if !self.normals_drawn && self.normal_mapping {
+            // flat normals
+            self.normal_target.begin()?;
+            self.normal_target.end();
+            self.normals_drawn = true;
+        }
+
+        let num_lights = self.prepare_lights();
+
+        let metrics = crate::api::metrics();
+        let shader_params = self.shader_params.data_mut();
+        shader_params.window_width = metrics.window_width;
+        shader_params.window_height = metrics.window_height;
+        shader_params.view_width = metrics.view_width;
+        shader_params.view_height = metrics.view_height;
+        shader_params.view_x = metrics.view_x;
+        shader_params.view_y = metrics.view_y;
+        shader_params.view_scaling = metrics.view_scaling;
+        shader_params.offset_x = offset_x;
+        shader_params.offset_y = offset_y;
+        shader_params.normal_mapping = if self.normal_mapping && self.normals_drawn { 1 } else { 0 };
+        shader_params.num_lights = num_lights as u32;
+
+        self.shader_params.update()?;
+        self.light_buffer.update()?;
+        self.occluder_buffer.update()?;
+
+        self.light_target.set_clear_color(&Color::rgba(self.ambient.r, self.ambient.g, self.ambient.b, 1.0));
+        self.light_target.begin()?;
+
+        if num_lights > 0 {
+            let material = self.light_material.as_ref().unwrap();
+            let renderer = crate::api::renderer_mut();
+            let previous_material = renderer.material().clone();
+
+            renderer.set_material(material);
+            material.lock().unwrap().bind_uniforms();
+            self.light_queue.draw();
+
+            renderer.set_material(&previous_material);
+        }
+
+        self.light_target.end();
+
+        self.normals_drawn = false;
+        self.rendered = true;
+
+        Ok(())
+    }
+
+    /// Multiply the light map over the scene, during draw
+    pub fn draw(&mut self) {
+
+        if !self.rendered {
+            return;
+        }
+
+        self.rendered = false;
+
+        let material = self.composite_material.as_ref().unwrap();
+        let renderer = crate::api::renderer_mut();
+        let previous_material = renderer.material().clone();
+
+        renderer.set_material(material);
+
+        let pipeline = crate::globals::pipeline();
+        let frame = pipeline.current_frame();
+        let device = crate::globals::device();
+        unsafe { device.obj.cmd_draw(frame.command_buffer.obj, 3, 1, 0, 0) };
+
+        renderer.set_material(&previous_material);
+    }
+
+    /// Fill light and occluder buffers and the light quads, returns the
+    /// number of lights
+    fn prepare_lights(&mut self) -> usize {
+
+        let max_lights = self.light_buffer.len();
+        let max_occluders = self.occluder_buffer.len();
+
+        let light_data = self.light_buffer.data_mut();
+        let occluder_data = self.occluder_buffer.data_mut();
+
+        let mut num_lights = 0usize;
+        let mut num_occluders = 0usize;
+
+        self.light_queue.begin();
+
+        for (_, light) in &self.lights {
+
+            if !light.enabled || light.radius <= 0.0 {
+                continue;
+            }
+
+            if num_lights >= max_lights {
+                break;
+            }
+
+            // occluders within reach of the light
+            let first_occluder = num_occluders;
+            if light.shadows {
+                for occluder in &self.occluders {
+                    if num_occluders >= max_occluders {
+                        break;
+                    }
+                    if occluder.distance(light.x, light.y) < light.radius {
+                        occluder_data[num_occluders] = *occluder;
+                        num_occluders += 1;
+                    }
+                }
+            }
+
+            light_data[num_lights] = LightData::new(light, first_occluder, num_occluders - first_occluder);
+
+            let r = light.radius;
+            self.light_queue.push(
+                light.x - r, light.y - r, r * 2.0, r * 2.0,
+                1.0, 1.0, 1.0, 1.0,
+                0.0, 0.0, 1.0, 1.0,
+                0x0, num_lights as u32
+            );
+
+            num_lights += 1;
+        }
+
+        self.light_queue.end();
+
+        num_lights
+    }
+
+    /// Targets follow the window size, texture bindings are replaced.
+    /// The materials are kept since disposing them would also dispose
+    /// the shared uniform and storage buffers.
+    fn validate_targets(&mut self) {
+        if self.normal_target.validate().unwrap_or(false) && let Some(material) = &self.light_material {
+            material.lock().unwrap().set_texture(self.normal_target.texture(), NORMAL_BUFFER_BINDING, false);
+        }
+
+        if self.light_target.validate().unwrap_or(false) && let Some(material) = &self.composite_material {
+            material.lock().unwrap().set_texture(self.light_target.texture(), LIGHT_MAP_BINDING, false);
+        }
+    }
+
+    fn create_materials(&mut self) {
+
+        let resources = crate::globals::resources();
+
+        let mut light_material = Material::new();
+        light_material
+            .set_blending(true)
+            .set_blend_mode(BlendMode::ADDITIVE)
+            .add_shader(resources.get_shader("gamekit::light_vertex_shader"))
+            .add_shader(resources.get_shader("gamekit::light_fragment_shader"))
+            .add_uniform(&self.shader_params)
+            .add_storage(&self.light_buffer)
+            .add_storage(&self.occluder_buffer)
+            .add_texture(self.normal_target.texture(), NORMAL_BUFFER_BINDING, false);
+
+        let mut composite_material = Material::new();
+        composite_material
+            .set_blending(true)
+            .set_blend_mode(BlendMode::MULTIPLY)
+            .set_backface_culling(false)
+            .set_shader_input_type(ShaderInputType::None)
+            .add_shader(resources.get_shader("gamekit::light_composite_vertex_shader"))
+            .add_shader(resources.get_shader("gamekit::light_composite_fragment_shader"))
+            .add_texture(self.light_target.texture(), LIGHT_MAP_BINDING, false);
+
+        self.light_material = Some(Material::to_lockref(light_material));
+        self.composite_material = Some(Material::to_lockref(composite_material));
+    }
+
+    fn free_materials(&mut self) {
+        if let Some(material) = self.light_material.take() {
+            material.lock().unwrap().dispose();
+        }
+
+        if let Some(material) = self.composite_material.take() {
+            material.lock().unwrap().dispose();
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grid_outline() {
+        // single cell
+        let segments = grid_outline(&[true], 1, 1, 16.0, 0.0, 0.0);
+        assert_eq!(segments.len(), 4);
+        assert!(segments.contains(&Occluder::new(0.0, 0.0, 16.0, 0.0)));
+        assert!(segments.contains(&Occluder::new(16.0, 0.0, 16.0, 16.0)));
+
+        // horizontal bar, inner edges skipped and outer edges merged
+        let segments = grid_outline(&[true, true, true], 3, 1, 8.0, 10.0, 20.0);
+        assert_eq!(segments.len(), 4);
+        assert!(segments.contains(&Occluder::new(10.0, 20.0, 34.0, 20.0)));
+        assert!(segments.contains(&Occluder::new(10.0, 28.0, 34.0, 28.0)));
+
+        // L shape
+        let occupied = [
+            true, false,
+            true, true
+        ];
+        let segments = grid_outline(&occupied, 2, 2, 1.0, 0.0, 0.0);
+        assert_eq!(segments.len(), 6);
+
+        assert!(grid_outline(&[false; 4], 2, 2, 1.0, 0.0, 0.0).is_empty());
+    }
+
+    #[test]
+    fn test_occluder_distance() {
+        let occluder = Occluder::new(0.0, 0.0, 10.0, 0.0);
+        assert_eq!(occluder.distance(5.0, 3.0), 3.0);
+        assert_eq!(occluder.distance(-4.0, 3.0), 5.0);
+        assert_eq!(occluder.distance(10.0, 0.0), 0.0);
+    }
+}
@@ -10,17 +10,32 @@ use log::{*};
 
 use crate::animator::{Animator, AnimatorMode};
 use crate::api::{Disposable, LockRef};
-use crate::buffer::Uniform;
+use crate::buffer::{PushConstants, ShaderStorage, Uniform};
+use crate::collision::{CellKind, IntGrid};
+use crate::entities::{FieldValue, MapEntity};
 use crate::error::Error;
 
 use crate::material::MaterialLockRef;
-use crate::primitives::{TileQueue};
-use crate::thirdparty::LdtkJson::{LayerInstance, LdtkJson, Level, TilesetDefinition};
+use crate::texture::TextureLockRef;
+use crate::primitives::{Color, Tile, TileQueue};
+use crate::random::Random;
+use crate::tiled;
+use crate::thirdparty::LdtkJson::{self as ldtk_json, Definitions, LayerInstance, LdtkJson, Level, TileCustomMetadata, TilesetDefinition};
 
 use serde::Deserialize;
 
 const TRANSPARENT_TILE_ID: i32 = i32::MAX;
 
+/// First tileset texture binding of the map material, tileset `n`
+/// is bound to `TILESET_TEXTURE_BINDING + n`
+pub const TILESET_TEXTURE_BINDING: u32 = 1;
+
+/// Number of tileset textures the builtin tilemap shaders can sample
+pub const MAX_TILESETS: usize = 4;
+
+/// Storage buffer with the current frame of each animated tile
+pub const TILE_ANIMATION_BINDING: u32 = TILESET_TEXTURE_BINDING + MAX_TILESETS as u32;
+
 fn default_1_0f32() -> f32 { 1.0 }
 
 #[derive(Default, Deserialize, Debug, PartialEq)]
@@ -31,14 +46,26 @@ pub struct MapMetaData {
     #[serde(default = "default_1_0f32")]
     pub animation_step: f32,
 
-    pub animation_mode: String
+    pub animation_mode: String,
+
+    /// Start frame, relative to the first animation for grouped animations
+    pub animation_offset: f32,
+
+    /// Start at a random frame
+    pub animation_random_start: bool,
+
+    /// Sync group, animations of a tileset with the same group advance
+    /// with the first animation of the group
+    pub animation_group: Option<String>
 }
 
 pub struct TileAnimation {
     pub tile_id: u32,
     pub frames: Vec<u32>,
     pub animator: Animator,
-    pub current_frame: u32
+    pub current_frame: u32,
+    pub offset: f32,
+    pub group: Option<String>
 }
 
 impl TileAnimation {
@@ -53,20 +80,48 @@ impl TileAnimation {
             tile_id,
             frames: frames.to_vec(),
             animator,
-            current_frame
+            current_frame,
+            offset: 0.0,
+            group: None
         }
     }
 
+    /// Start frame, grouped animations keep the offset to the first
+    /// animation of the group
+    pub fn set_offset(&mut self, offset: f32) -> &mut Self {
+        if self.frames.is_empty() { return self; }
+        self.offset = offset.rem_euclid(self.frames.len() as f32);
+        self.animator.value = self.offset;
+        self.current_frame = self.frame_at(self.offset);
+        self
+    }
+
+    pub fn set_group(&mut self, group: &str) -> &mut Self {
+        self.group = Some(group.to_string());
+        self
+    }
+
     pub fn update(&mut self, delta: f32) {
         if self.frames.is_empty() { return; }
         self.animator.update(delta);
-        self.current_frame = self.frames[self.animator.value as usize];
+        self.current_frame = self.frame_at(self.animator.value);
+    }
+
+    fn follow(&mut self, value: f32) {
+        if self.frames.is_empty() { return; }
+        self.current_frame = self.frame_at(value + self.offset);
+    }
+
+    fn frame_at(&self, value: f32) -> u32 {
+        self.frames[value.max(0.0) as usize % self.frames.len()]
     }
 }
 
 pub struct TileAnimations {
     animations: Vec<TileAnimation>,
-    animation_map: HashMap<u32, usize>
+    animation_map: HashMap<u32, usize>,
+    /// First animation of the sync group, per animation
+    leaders: Vec<Option<usize>>
 }
 
 impl Default for TileAnimations {
@@ -79,14 +134,19 @@ impl TileAnimations {
     pub fn new() -> Self {
         Self {
             animations: Vec::<TileAnimation>::new(),
-            animation_map: HashMap::new()
+            animation_map: HashMap::new(),
+            leaders: Vec::new()
         }
     }
 
     pub fn push(&mut self, animation: TileAnimation) {
         let tile_id = animation.tile_id;
         let index = self.animations.len();
+        let leader = animation.group.as_ref().and_then(|group| {
+            self.animations.iter().position(|other| other.group.as_ref() == Some(group))
+        });
         self.animations.push(animation);
+        self.leaders.push(leader);
         self.animation_map.insert(tile_id, index);
     }
 
@@ -106,11 +166,124 @@ impl TileAnimations {
     }
 
     pub fn update(&mut self, delta: f32) {
-        for animation in &mut self.animations {
-            animation.update(delta);
+        // leaders come first, so they are already updated when followed
+        for index in 0..self.animations.len() {
+            match self.leaders[index] {
+                Some(leader) => {
+                    let value = self.animations[leader].animator.value;
+                    self.animations[index].follow(value);
+                },
+                None => self.animations[index].update(delta)
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.animations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.animations.is_empty()
+    }
+
+    fn from_tileset(tileset: &TilesetDefinition) -> Result<Self, Error> {
+
+        let mut animations = Self::new();
+
+        // process map meta data
+        let custom_data = &tileset.custom_data;
+        for meta_data in custom_data {
+            let tile_id = meta_data.tile_id;
+            let json = &meta_data.data;
+
+            if !json.is_empty() {
+                let meta: MapMetaData = match json5::from_str(json.as_str()) {
+                    Ok(manifest) => manifest,
+                    Err(e) => {
+                        trace!("failed to load custom map data: {}", e);
+                        return Err(Error::from("failed to load custom map data"));
+                    }
+                };
+
+                // processing animated tiles
+                if let Some(frames) = meta.animation_frames {
+                    trace!("adding animation for tile {}", tile_id);
+
+                    let mut animation = TileAnimation::new(
+                        tile_id as u32,
+                        &frames,
+                        AnimatorMode::from_string(&meta.animation_mode),
+                        meta.animation_step
+                    );
+
+                    let offset = if meta.animation_random_start {
+                        Random::get_float() * frames.len() as f32
+                    } else {
+                        meta.animation_offset
+                    };
+
+                    animation.set_offset(offset);
+
+                    if let Some(group) = &meta.animation_group {
+                        animation.set_group(group);
+                    }
+
+                    animations.push(animation);
+                }
+            }
+
+        }
+
+        // enum tags, custom data takes precedence
+        let num_tiles = (tileset.c_wid * tileset.c_hei).max(0) as u32;
+
+        for tag in &tileset.enum_tags {
+            let Some((num_frames, step)) = Self::parse_animation_tag(&tag.enum_value_id) else {
+                continue;
+            };
+
+            for tile_id in &tag.tile_ids {
+                let tile_id = *tile_id as u32;
+                if animations.contains_key(tile_id) {
+                    continue;
+                }
+
+                if tile_id + num_frames > num_tiles {
+                    warn!("animation {} of tile {} exceeds the tileset", tag.enum_value_id, tile_id);
+                    continue;
+                }
+
+                let frames: Vec<u32> = (tile_id..tile_id + num_frames).collect();
+                trace!("adding animation for tagged tile {}", tile_id);
+                animations.push(TileAnimation::new(tile_id, &frames, AnimatorMode::ForwardLoop, step));
+            }
+        }
+
+        Ok(animations)
+    }
+
+    /// Animation of an enum tag `Anim_<frames>` or `Anim_<frames>_<fps>`,
+    /// the tagged tile is followed by the frames in the tileset
+    fn parse_animation_tag(tag: &str) -> Option<(u32, f32)> {
+        let mut parts = tag.split('_');
+
+        if !parts.next()?.eq_ignore_ascii_case("anim") {
+            return None;
+        }
+
+        let num_frames = parts.next()?.parse::<u32>().ok().filter(|n| *n > 0)?;
+        let step = match parts.next() {
+            Some(fps) => fps.parse::<f32>().ok()?,
+            None => default_1_0f32()
+        };
+
+        if parts.next().is_some() {
+            return None;
         }
+
+        Some((num_frames, step))
     }
-    
+
     fn store_to(&self, buffer: &mut[u32]) {
         for (index, animation) in self.animations.iter().enumerate().take(buffer.len()) {
             buffer[index] = animation.current_frame;
@@ -167,31 +340,68 @@ impl MapShaderParams {
 
 }
 
+/// Per-layer parameters, pushed before each layer is drawn
 #[repr(C)]
-struct MapShaderTileLookupBuffer {
-    animation_frames: [u32; 256]
+#[derive(Default)]
+struct MapLayerParams {
+    offset_x: f32,
+    offset_y: f32,
+    texture_width: u32,
+    texture_height: u32,
+    tile_size: u32,
+    grid_size: u32,
+    cols: u32,
+    tileset: u32,
+    tint: [f32; 4],
+    padding: u32,
+    spacing: u32
 }
 
-impl Default for MapShaderTileLookupBuffer {
-    fn default() -> Self {
-        Self {
-            animation_frames: [0u32; 256]
-        }
-    }
+/// Number of tile columns of a tileset texture with padding at the
+/// borders and spacing between the tiles
+pub(crate) fn tileset_columns(texture_width: i64, tile_size: i64, padding: i64, spacing: i64) -> i64 {
+    (texture_width - 2 * padding + spacing).max(0) / (tile_size + spacing).max(1)
 }
 
-impl MapShaderTileLookupBuffer {
-    pub fn new() -> Result<Uniform::<Self>, Error> {
-        Uniform::<Self>::new(2, 0)
-    }
+/// Pixel position of a tile in the tileset texture
+pub(crate) fn tile_source(tileset: &TilesetDefinition, tile_id: i64) -> (i64, i64) {
+    let stride = tileset.tile_grid_size + tileset.spacing;
+    let cols = tileset.c_wid.max(1);
+    (tileset.padding + (tile_id % cols) * stride, tileset.padding + (tile_id / cols) * stride)
+}
+
+/// Layers of a level to draw, split at a layer identifier to draw
+/// sprites in between
+#[derive(Clone, Copy)]
+enum LayerSelection<'a> {
+    All,
+    /// Layers beneath the layer
+    Below(&'a str),
+    /// The layer and all layers above
+    From(&'a str)
 }
 
 pub struct MapLayer {
+    identifier: String,
     tile_queue: TileQueue,
+    int_grid: IntGrid,
     rows: usize,
     cols: usize,
     width: usize,
     height: usize,
+    grid_size: usize,
+    tileset: usize,
+    tile_size: usize,
+    texture_width: usize,
+    texture_height: usize,
+    padding: usize,
+    spacing: usize,
+    offset_x: f32,
+    offset_y: f32,
+    parallax_x: f32,
+    parallax_y: f32,
+    opacity: f32,
+    tint: Color,
     visible: bool,
     random_access: bool
 }
@@ -203,7 +413,7 @@ impl Disposable for MapLayer {
 }
 
 impl MapLayer {
-    fn new(layer: &LayerInstance, _tileset: &TilesetDefinition, animations: &TileAnimations, random_access: bool) -> Self {
+    fn new(layer: &LayerInstance, tileset_index: usize, tileset: &TilesetDefinition, animations: &TileAnimations, animation_base: usize, random_access: bool) -> Self {
 
         let data = if layer.layer_instance_type == "IntGrid" {
             &layer.auto_layer_tiles
@@ -231,10 +441,12 @@ impl MapLayer {
 
             for t in data {
 
+                // fully transparent tiles are skipped, their cells keep
+                // the transparent tile id
                 let alpha = t.a;
                 if alpha == 0.0 {
-                    continue; // transparent
-                } 
+                    continue;
+                }
 
                 let col = t.px[0] as usize / grid_size;
                 let row = t.px[1] as usize / grid_size;
@@ -242,38 +454,137 @@ impl MapLayer {
 
                 let map_tile_index = t.t as u32;
                 let tile_index = if let Some(animation_index) = animations.get_index(map_tile_index) {
-                    -(*animation_index as i32) - 1
+                    -((animation_base + *animation_index) as i32) - 1
                 } else {
                     map_tile_index as i32
                 };
 
+                // LDtk flip bits match the tile flags. LDtk has no rotation,
+                // so LDtk tiles are never rotated, only the Tiled importer
+                // stores quarter turns in the bits above
+                let flip = t.f as u32;
+                let rotation = (flip & Tile::ROTATION_MASK) >> Tile::ROTATION_SHIFT;
+                let flags = Tile::make_flags(flip & Tile::FLIP_X != 0, flip & Tile::FLIP_Y != 0, rotation, alpha as f32);
+
                 if random_access {
                     // write into buffer
                     tile_queue.set_value(pos_index, pos_index as u32, tile_index);
+                    tile_queue.set_flags(pos_index, flags);
                 } else {
                     // append to buffer
-                    tile_queue.push(pos_index as u32, tile_index);
+                    tile_queue.push_with_flags(pos_index as u32, tile_index, flags);
                 }
 
             }
 
         tile_queue.end();
 
+        let int_grid_values = layer.int_grid_csv.iter().map(|v| *v as i32).collect();
+        let int_grid = IntGrid::new(cols, rows, grid_size as f32, int_grid_values);
+
         Self {
+            identifier: layer.identifier.clone(),
             tile_queue,
+            int_grid,
             rows,
             cols,
             width,
             height,
+            grid_size,
+            tileset: tileset_index,
+            tile_size: tileset.tile_grid_size as usize,
+            texture_width: tileset.px_wid as usize,
+            texture_height: tileset.px_hei as usize,
+            padding: tileset.padding.max(0) as usize,
+            spacing: tileset.spacing.max(0) as usize,
+            offset_x: layer.px_total_offset_x as f32,
+            offset_y: layer.px_total_offset_y as f32,
+            parallax_x: 1.0,
+            parallax_y: 1.0,
+            opacity: layer.opacity as f32,
+            tint: Color::white(),
             visible: true,
             random_access
         }
     }
 
+    /// Layer of transparent tiles, for maps built at runtime
+    fn with_size(identifier: &str, cols: usize, rows: usize, grid_size: usize, tileset_index: usize, tileset: &TilesetDefinition) -> Self {
+
+        let num_tiles = cols * rows;
+        let mut tile_queue = TileQueue::new(num_tiles);
+
+        tile_queue.begin();
+        for pos_index in 0..num_tiles {
+            tile_queue.push(pos_index as u32, TRANSPARENT_TILE_ID);
+        }
+        tile_queue.end();
+
+        Self {
+            identifier: identifier.to_string(),
+            tile_queue,
+            int_grid: IntGrid::new(cols, rows, grid_size as f32, Vec::new()),
+            rows,
+            cols,
+            width: cols * grid_size,
+            height: rows * grid_size,
+            grid_size,
+            tileset: tileset_index,
+            tile_size: tileset.tile_grid_size as usize,
+            texture_width: tileset.px_wid as usize,
+            texture_height: tileset.px_hei as usize,
+            padding: tileset.padding.max(0) as usize,
+            spacing: tileset.spacing.max(0) as usize,
+            offset_x: 0.0,
+            offset_y: 0.0,
+            parallax_x: 1.0,
+            parallax_y: 1.0,
+            opacity: 1.0,
+            tint: Color::white(),
+            visible: true,
+            random_access: true
+        }
+    }
+
     pub fn draw(&mut self) {
         self.tile_queue.draw();
     }
 
+    /// Draw offset of the layer. Only the scroll offset is scaled by the
+    /// parallax factors, so the levels of a world stay aligned.
+    fn draw_offset(&self, origin_x: f32, origin_y: f32, scroll_x: f32, scroll_y: f32) -> (f32, f32) {
+        (
+            origin_x + scroll_x * self.parallax_x + self.offset_x,
+            origin_y + scroll_y * self.parallax_y + self.offset_y
+        )
+    }
+
+    /// Normal maps are drawn without tint and opacity
+    fn draw_with_params(&mut self, push_params: Option<&mut PushConstants<MapLayerParams>>, origin: (f32, f32), scroll: (f32, f32), tinted: bool) {
+        if let Some(push_params) = push_params {
+            let (offset_x, offset_y) = self.draw_offset(origin.0, origin.1, scroll.0, scroll.1);
+            let params = push_params.data_mut();
+            params.offset_x = offset_x;
+            params.offset_y = offset_y;
+            params.tint = if tinted {
+                [self.tint.r, self.tint.g, self.tint.b, self.tint.a * self.opacity]
+            } else {
+                [1.0; 4]
+            };
+            params.texture_width = self.texture_width as u32;
+            params.texture_height = self.texture_height as u32;
+            params.tile_size = self.tile_size as u32;
+            params.grid_size = self.grid_size as u32;
+            params.cols = self.cols as u32;
+            params.tileset = self.tileset as u32;
+            params.padding = self.padding as u32;
+            params.spacing = self.spacing as u32;
+            let _ = push_params.update();
+        }
+
+        self.tile_queue.draw();
+    }
+
     pub fn set_index(&mut self, vertex_index: usize, pos_index: usize, tile_index: i32) {
         self.tile_queue.set_value(vertex_index, pos_index as u32, tile_index);
     }
@@ -294,11 +605,295 @@ impl MapLayer {
         self.get_tile(y * self.cols + x)
     }
 
+    /// Flip, rotation and alpha of a tile, see `Tile::make_flags`
+    pub fn set_flags(&mut self, vertex_index: usize, flags: u32) {
+        self.tile_queue.set_flags(vertex_index, flags);
+    }
+
+    pub fn set_flags_xy(&mut self, x: usize, y: usize, flags: u32) {
+        self.set_flags(y * self.cols + x, flags);
+    }
+
+    pub fn get_flags(&self, vertex_index: usize) -> u32 {
+        self.tile_queue.get_flags(vertex_index)
+    }
+
+    pub fn get_flags_xy(&self, x: usize, y: usize) -> u32 {
+        self.get_flags(y * self.cols + x)
+    }
+
+    /// Make a cell transparent
+    pub fn clear_tile_xy(&mut self, x: usize, y: usize) {
+        self.set_tile_xy(x, y, TRANSPARENT_TILE_ID);
+    }
+
+    /// Set all tiles of the layer to the same tile index, see `MapLevel::fill`
+    fn fill(&mut self, tile_index: i32) {
+        for vertex_index in 0..self.tile_queue.count() {
+            self.tile_queue.set_tile(vertex_index, tile_index);
+            self.tile_queue.set_flags(vertex_index, Tile::OPAQUE);
+        }
+    }
+
+    /// Make all tiles transparent and clear the IntGrid values
+    pub fn clear(&mut self) {
+        if self.random_access {
+            for vertex_index in 0..self.tile_queue.count() {
+                self.tile_queue.set_tile(vertex_index, TRANSPARENT_TILE_ID);
+                self.tile_queue.set_flags(vertex_index, Tile::OPAQUE);
+            }
+        } else {
+            self.tile_queue.clear();
+        }
+
+        self.int_grid.clear();
+    }
+
+    /// Change the size in cells, tiles and IntGrid values of the
+    /// remaining cells are kept. Sparse layers become uncompressed.
+    /// Must not be called between drawing the layer and the end of
+    /// the frame.
+    pub fn resize(&mut self, cols: usize, rows: usize) {
+
+        // the buffer might still be in use by frames in flight
+        if !self.is_released() {
+            let device = crate::globals::device();
+            unsafe { let _ = device.obj.device_wait_idle(); }
+        }
+
+        self.resize_tiles(cols, rows);
+    }
+
+    /// Resize without waiting, the device has to be idle
+    fn resize_tiles(&mut self, cols: usize, rows: usize) {
+
+        let num_tiles = cols * rows;
+        let mut tile_queue = TileQueue::new(num_tiles);
+
+        tile_queue.begin();
+
+            for pos_index in 0..num_tiles {
+                tile_queue.push(pos_index as u32, TRANSPARENT_TILE_ID);
+            }
+
+            for vertex_index in 0..self.tile_queue.count() {
+                let pos_index = self.tile_queue.get_pos(vertex_index) as usize;
+                let col = pos_index % self.cols.max(1);
+                let row = pos_index / self.cols.max(1);
+
+                if col >= cols || row >= rows {
+                    continue;
+                }
+
+                let new_pos_index = row * cols + col;
+                tile_queue.set_value(new_pos_index, new_pos_index as u32, self.tile_queue.get_tile(vertex_index));
+                tile_queue.set_flags(new_pos_index, self.tile_queue.get_flags(vertex_index));
+            }
+
+        tile_queue.end();
+
+        self.tile_queue.dispose();
+        self.tile_queue = tile_queue;
+        self.int_grid.resize(cols, rows);
+
+        self.cols = cols;
+        self.rows = rows;
+        self.width = cols * self.grid_size;
+        self.height = rows * self.grid_size;
+        self.random_access = true;
+    }
+
+    pub fn identifier(&self) -> &str {
+        &self.identifier
+    }
+
+    pub fn set_visible(&mut self, visible: bool) -> &mut Self {
+        self.visible = visible;
+        self
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Offset in pixels, added after parallax scrolling
+    pub fn set_offset(&mut self, offset_x: f32, offset_y: f32) -> &mut Self {
+        self.offset_x = offset_x;
+        self.offset_y = offset_y;
+        self
+    }
+
+    pub fn offset(&self) -> (f32, f32) {
+        (self.offset_x, self.offset_y)
+    }
+
+    /// Scroll speed relative to the draw offset, 1.0 scrolls with the
+    /// level, smaller values for background layers
+    pub fn set_parallax(&mut self, parallax_x: f32, parallax_y: f32) -> &mut Self {
+        self.parallax_x = parallax_x;
+        self.parallax_y = parallax_y;
+        self
+    }
+
+    pub fn parallax(&self) -> (f32, f32) {
+        (self.parallax_x, self.parallax_y)
+    }
+
+    pub fn set_opacity(&mut self, opacity: f32) -> &mut Self {
+        self.opacity = opacity.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn opacity(&self) -> f32 {
+        self.opacity
+    }
+
+    /// Color multiplied with the tiles
+    pub fn set_tint(&mut self, tint: &Color) -> &mut Self {
+        self.tint = tint.clone();
+        self
+    }
+
+    pub fn tint(&self) -> &Color {
+        &self.tint
+    }
+
+    /// Apply a level field `<layer>_<property>` (parallax_x, parallax_y,
+    /// offset_x, offset_y, opacity, tint), returns false if not handled
+    fn apply_field(&mut self, property: &str, value: &FieldValue) -> bool {
+        match (property, value.as_float().map(|v| v as f32)) {
+            ("parallax_x", Some(v)) => { self.parallax_x = v; },
+            ("parallax_y", Some(v)) => { self.parallax_y = v; },
+            ("offset_x", Some(v)) => { self.offset_x = v; },
+            ("offset_y", Some(v)) => { self.offset_y = v; },
+            ("opacity", Some(v)) => { self.set_opacity(v); },
+            ("tint", _) => match value.as_color() {
+                Some(color) => { self.tint = color.clone(); },
+                None => return false
+            },
+            _ => return false
+        }
+
+        true
+    }
+
+    /// Free the GPU buffer, it is re-created when the layer is drawn
+    pub fn release(&mut self) {
+        self.tile_queue.release();
+    }
+
+    pub fn is_released(&self) -> bool {
+        self.tile_queue.is_released()
+    }
+
+    /// Raw IntGrid values with collision queries
+    pub fn int_grid(&self) -> &IntGrid {
+        &self.int_grid
+    }
+
+    pub fn int_grid_mut(&mut self) -> &mut IntGrid {
+        &mut self.int_grid
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn grid_size(&self) -> usize {
+        self.grid_size
+    }
+
+    /// Index of the tileset in the project definitions
+    pub fn tileset(&self) -> usize {
+        self.tileset
+    }
+
+    /// Non-transparent cells, row by row
+    pub fn occupancy(&self) -> Vec<bool> {
+        let mut occupied = vec![false; self.rows * self.cols];
+
+        for vertex_index in 0..self.tile_queue.count() {
+            let pos_index = self.tile_queue.get_pos(vertex_index) as usize;
+            if pos_index < occupied.len() && self.tile_queue.get_tile(vertex_index) != TRANSPARENT_TILE_ID {
+                occupied[pos_index] = true;
+            }
+        }
+
+        occupied
+    }
+
+}
+
+/// Direction of a neighbour level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NeighbourDirection {
+    North,
+    South,
+    East,
+    West,
+    NorthEast,
+    NorthWest,
+    SouthEast,
+    SouthWest,
+    /// Overlapping level in the same world depth
+    Overlap,
+    /// Level in a lower world depth
+    Below,
+    /// Level in a higher world depth
+    Above
+}
+
+impl NeighbourDirection {
+    fn from_dir(dir: &str) -> Option<Self> {
+        match dir {
+            "n" => Some(NeighbourDirection::North),
+            "s" => Some(NeighbourDirection::South),
+            "e" => Some(NeighbourDirection::East),
+            "w" => Some(NeighbourDirection::West),
+            "ne" => Some(NeighbourDirection::NorthEast),
+            "nw" => Some(NeighbourDirection::NorthWest),
+            "se" => Some(NeighbourDirection::SouthEast),
+            "sw" => Some(NeighbourDirection::SouthWest),
+            "o" => Some(NeighbourDirection::Overlap),
+            "<" => Some(NeighbourDirection::Below),
+            ">" => Some(NeighbourDirection::Above),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelNeighbour {
+    pub level_iid: String,
+    pub direction: NeighbourDirection
+}
+
+/// Arrangement of the levels in the world
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorldLayout {
+    Free,
+    GridVania,
+    LinearHorizontal,
+    LinearVertical
 }
 
 pub struct MapLevel {
+    pub identifier: String,
+    pub iid: String,
+    /// Position in world pixels
+    pub world_x: f32,
+    pub world_y: f32,
+    pub world_depth: i32,
+    pub neighbours: Vec<LevelNeighbour>,
     pub layers: Vec<MapLayer>,
-    pub animations: TileAnimations,
+    /// Animated tiles, one table per tileset
+    pub animations: Vec<TileAnimations>,
+    /// Entities of all entity layers
+    pub entities: Vec<MapEntity>,
     pub rows: usize,
     pub cols: usize,
     pub width: usize,
@@ -316,56 +911,87 @@ impl Disposable for MapLevel {
 
 impl MapLevel {
 
-    pub fn new(level: &Level, tileset: &TilesetDefinition, uncompressed_layers: &[&str]) -> Result<Self, Error> {
+    pub fn new(level: &Level, defs: &Definitions, uncompressed_layers: &[&str]) -> Result<Self, Error> {
+
+        let tilesets = &defs.tilesets;
 
         let mut rows: usize = 0;
         let mut cols: usize = 0;
         let mut width: usize = 0;
         let mut height: usize = 0;
         let mut map_layers = Vec::new();
-        let mut animations = TileAnimations::default();
-
-        // process map meta data
-        let custom_data = &tileset.custom_data;
-        for meta_data in custom_data {
-            let tile_id = meta_data.tile_id;
-            let json = &meta_data.data;
-
-            if !json.is_empty() {
-                let meta: MapMetaData = match json5::from_str(json.as_str()) {
-                    Ok(manifest) => manifest,
-                    Err(e) => {
-                        trace!("failed to load custom map data: {}", e);
-                        return Err(Error::from("failed to load custom map data"));
-                    }
-                };
-
-                // processing animated tiles
-                if let Some(frames) = meta.animation_frames {
-                    trace!("adding animation for tile {}", tile_id);
-                    animations.push(
-                        TileAnimation::new(
-                            tile_id as u32,
-                            &frames,
-                            AnimatorMode::from_string(&meta.animation_mode),
-                            meta.animation_step
-                        )
-                    );
-                }
-            }
-
+        let mut entities = Vec::new();
+        let mut animations = Vec::new();
+        let mut animation_bases = Vec::new();
+        let mut num_animations: usize = 0;
+
+        for tileset in tilesets {
+            let tileset_animations = TileAnimations::from_tileset(tileset)?;
+            animation_bases.push(num_animations);
+            num_animations += tileset_animations.len();
+            animations.push(tileset_animations);
         }
 
-        let layers = level.layer_instances.as_ref().unwrap();
+        let Some(layers) = level.layer_instances.as_ref() else {
+            return Err(Error::from(format!("layers of level \"{}\" not loaded, external level files are not resolved", level.identifier)));
+        };
 
         for layer in layers {
 
             let identifier = &layer.identifier;
+
+            for entity in &layer.entity_instances {
+                entities.push(MapEntity::from_instance(entity, identifier));
+            }
+
             let uncompressed = uncompressed_layers.contains(&identifier.as_str());
 
-            let mut map_layer = MapLayer::new(layer, tileset, &animations, uncompressed);
+            let tileset_index = Self::resolve_tileset(layer, tilesets)?;
+            let tileset = &tilesets[tileset_index];
+
+            let mut map_layer = MapLayer::new(
+                layer,
+                tileset_index,
+                tileset,
+                &animations[tileset_index],
+                animation_bases[tileset_index],
+                uncompressed
+            );
             map_layer.visible = layer.visible;
 
+            if let Some(layer_def) = defs.layers.iter().find(|def| def.uid == layer.layer_def_uid) {
+
+                // collision kinds by IntGrid value identifiers
+                for value_def in &layer_def.int_grid_values {
+                    if let Some(value_identifier) = &value_def.identifier {
+                        map_layer.int_grid.set_cell_kind(value_def.value as i32, CellKind::from_identifier(value_identifier));
+                    }
+                }
+
+                // LDtk factors slow the layer down, 0.0 scrolls with the level
+                map_layer.set_parallax(
+                    1.0 - layer_def.parallax_factor_x as f32,
+                    1.0 - layer_def.parallax_factor_y as f32
+                );
+            }
+
+            // level fields override the layer settings
+            let prefix = format!("{identifier}_");
+            for field in &level.field_instances {
+                let Some(property) = field.identifier.strip_prefix(&prefix) else {
+                    continue;
+                };
+
+                let value = match &field.value {
+                    Some(value) => FieldValue::parse(&field.field_instance_type, value),
+                    None => continue
+                };
+
+                if !map_layer.apply_field(property, &value) {
+                    warn!("unsupported layer field {}", field.identifier);
+                }
+            }
+
             rows = rows.max(map_layer.rows);
             cols = cols.max(map_layer.cols);
 
@@ -375,22 +1001,80 @@ impl MapLevel {
             map_layers.push(map_layer);
         }
 
+        let neighbours = level.neighbours.iter()
+            .filter_map(|neighbour| {
+                NeighbourDirection::from_dir(&neighbour.dir).map(|direction| LevelNeighbour {
+                    level_iid: neighbour.level_iid.clone(),
+                    direction
+                })
+            })
+            .collect();
+
         Ok(Self {
+            identifier: level.identifier.clone(),
+            iid: level.iid.clone(),
+            world_x: level.world_x as f32,
+            world_y: level.world_y as f32,
+            world_depth: level.world_depth as i32,
+            neighbours,
             layers: map_layers,
             animations,
+            entities,
             rows,
             cols,
-            width,
-            height
+            width: width.max(level.px_wid.max(0) as usize),
+            height: height.max(level.px_hei.max(0) as usize)
         })
     }
 
-    pub fn get_layer(&self, layer: usize) -> Result<&MapLayer, Error> {
-        if layer >= self.layers.len() {
-            return Err(Error::from("layer index out of range"));
-        }
+    /// Level without layers, for maps built at runtime
+    fn with_size(identifier: &str, cols: usize, rows: usize, grid_size: usize, defs: &Definitions) -> Result<Self, Error> {
 
-        Ok(&self.layers[layer])
+        let animations = defs.tilesets.iter()
+            .map(TileAnimations::from_tileset)
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(Self {
+            identifier: identifier.to_string(),
+            iid: identifier.to_string(),
+            world_x: 0.0,
+            world_y: 0.0,
+            world_depth: 0,
+            neighbours: Vec::new(),
+            layers: Vec::new(),
+            animations,
+            entities: Vec::new(),
+            rows,
+            cols,
+            width: cols * grid_size,
+            height: rows * grid_size
+        })
+    }
+
+    /// Layers without tiles (entities, plain IntGrid) use the first tileset
+    fn resolve_tileset(layer: &LayerInstance, tilesets: &[TilesetDefinition]) -> Result<usize, Error> {
+
+        if tilesets.is_empty() {
+            return Err(Error::from("map has no tilesets"));
+        }
+
+        let Some(uid) = layer.tileset_def_uid else {
+            return Ok(0);
+        };
+
+        match tilesets.iter().position(|tileset| tileset.uid == uid) {
+            Some(index) if index < MAX_TILESETS => Ok(index),
+            Some(_) => Err(Error::from(format!("layer \"{}\" uses more than {MAX_TILESETS} tilesets", layer.identifier))),
+            None => Err(Error::from(format!("tileset {uid} of layer \"{}\" not found", layer.identifier)))
+        }
+    }
+
+    pub fn get_layer(&self, layer: usize) -> Result<&MapLayer, Error> {
+        if layer >= self.layers.len() {
+            return Err(Error::from("layer index out of range"));
+        }
+
+        Ok(&self.layers[layer])
     }    
 
     pub fn get_layer_mut(&mut self, layer: usize) -> Result<&mut MapLayer, Error> {
@@ -399,32 +1083,305 @@ impl MapLevel {
         }
 
         Ok(&mut self.layers[layer])
+    }
+
+    /// Tile index as stored in the layers for a tile of a tileset,
+    /// animated tiles refer to their animation
+    pub fn tile_index(&self, tileset: usize, tile_id: u32) -> i32 {
+        let animation_base: usize = self.animations.iter().take(tileset).map(|animations| animations.len()).sum();
+
+        match self.animations.get(tileset).and_then(|animations| animations.get_index(tile_id)) {
+            Some(animation_index) => -((animation_base + *animation_index) as i32) - 1,
+            None => tile_id as i32
+        }
+    }
+
+    /// Set a cell of a layer to a tile of the layer tileset
+    pub fn set_tile_xy(&mut self, layer: usize, x: usize, y: usize, tile_id: u32) -> Result<(), Error> {
+        let map_layer = self.get_layer(layer)?;
+        if x >= map_layer.cols || y >= map_layer.rows {
+            return Err(Error::from("tile position out of range"));
+        }
+
+        let tile_index = self.tile_index(map_layer.tileset, tile_id);
+        self.layers[layer].set_tile_xy(x, y, tile_index);
+
+        Ok(())
+    }
+
+    /// Set all cells of a layer to a tile of the layer tileset,
+    /// flips and rotations are reset
+    pub fn fill(&mut self, layer: usize, tile_id: u32) -> Result<(), Error> {
+        let tileset = self.get_layer(layer)?.tileset;
+        let tile_index = self.tile_index(tileset, tile_id);
+        self.layers[layer].fill(tile_index);
+
+        Ok(())
+    }
+
+    /// Resize all layers, see `MapLayer::resize`
+    pub fn resize(&mut self, cols: usize, rows: usize) {
+
+        // buffers might still be in use by frames in flight
+        if self.is_loaded() {
+            let device = crate::globals::device();
+            unsafe { let _ = device.obj.device_wait_idle(); }
+        }
+
+        for layer in &mut self.layers {
+            layer.resize_tiles(cols, rows);
+        }
+
+        if self.cols > 0 && self.rows > 0 {
+            self.width = cols * (self.width / self.cols);
+            self.height = rows * (self.height / self.rows);
+        }
+
+        self.cols = cols;
+        self.rows = rows;
+    }
+
+    /// Layer by LDtk identifier
+    pub fn find_layer(&self, identifier: &str) -> Option<&MapLayer> {
+        self.layers.iter().find(|layer| layer.identifier == identifier)
+    }
+
+    pub fn find_layer_mut(&mut self, identifier: &str) -> Option<&mut MapLayer> {
+        self.layers.iter_mut().find(|layer| layer.identifier == identifier)
     }    
 
+    /// Entities with the given definition identifier
+    pub fn find_entities<'a>(&'a self, identifier: &'a str) -> impl Iterator<Item = &'a MapEntity> {
+        self.entities.iter().filter(move |entity| entity.identifier == identifier)
+    }
+
+    /// First entity with the given definition identifier, e.g. a spawn point
+    pub fn find_entity(&self, identifier: &str) -> Option<&MapEntity> {
+        self.entities.iter().find(|entity| entity.identifier == identifier)
+    }
+
+    /// Entity by instance id, resolves `EntityRef` fields
+    pub fn entity(&self, iid: &str) -> Option<&MapEntity> {
+        self.entities.iter().find(|entity| entity.iid == iid)
+    }
+
     pub fn animate(&mut self, delta: f32) {
-        self.animations.update(delta);
+        for animations in &mut self.animations {
+            animations.update(delta);
+        }
     }
 
     pub fn draw(&mut self) {
-        for layer in &mut self.layers.iter_mut().rev() {
+        self.draw_with_params(None, (0.0, 0.0), (0.0, 0.0), LayerSelection::All, true);
+    }
+
+    /// Layers are stored top to bottom as in LDtk and drawn bottom up.
+    /// Without the split layer, all layers count as below it.
+    fn layer_range(&self, selection: LayerSelection) -> std::ops::Range<usize> {
+
+        let split = |identifier: &str| {
+            let index = self.layers.iter().position(|layer| layer.identifier == identifier);
+            if index.is_none() {
+                warn!("layer {} not found in level {}, drawing all layers below it", identifier, self.identifier);
+            }
+            index
+        };
+
+        match selection {
+            LayerSelection::All => 0..self.layers.len(),
+            LayerSelection::Below(identifier) => split(identifier).map_or(0, |index| index + 1)..self.layers.len(),
+            LayerSelection::From(identifier) => 0..split(identifier).map_or(0, |index| index + 1)
+        }
+    }
+
+    /// The origin is the level position on screen without scrolling, the
+    /// scroll offset is scaled by the layer parallax
+    fn draw_with_params(&mut self, mut push_params: Option<&mut PushConstants<MapLayerParams>>, origin: (f32, f32), scroll: (f32, f32), selection: LayerSelection, tinted: bool) {
+
+        let range = self.layer_range(selection);
+
+        for layer in self.layers[range].iter_mut().rev() {
             if layer.visible {
-                layer.draw();
+                layer.draw_with_params(push_params.as_deref_mut(), origin, scroll, tinted);
             }
         }
     }
-    
+
+    /// Upload the tile buffers ahead of drawing
+    pub fn load(&mut self) {
+        for layer in &mut self.layers {
+            layer.tile_queue.update();
+        }
+    }
+
+    /// Free the tile buffers, map data stays available. Must not be
+    /// called between drawing the level and the end of the frame.
+    pub fn unload(&mut self) {
+        if !self.is_loaded() {
+            return;
+        }
+
+        // buffers might still be in use by frames in flight
+        let device = crate::globals::device();
+        unsafe { let _ = device.obj.device_wait_idle(); }
+
+        self.release();
+    }
+
+    /// Free the tile buffers, the device has to be idle
+    fn release(&mut self) {
+        for layer in &mut self.layers {
+            layer.release();
+        }
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.layers.iter().any(|layer| !layer.is_released())
+    }
+
+    /// True if the level overlaps a world rectangle
+    pub fn intersects(&self, x: f32, y: f32, width: f32, height: f32) -> bool {
+        self.world_x < x + width && x < self.world_x + self.width as f32 &&
+        self.world_y < y + height && y < self.world_y + self.height as f32
+    }
+
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.world_x && x < self.world_x + self.width as f32 &&
+        y >= self.world_y && y < self.world_y + self.height as f32
+    }
+
     fn store_animations_to(&mut self, buffer: &mut [u32]) {
-        self.animations.store_to(buffer);
+        let mut offset: usize = 0;
+        for animations in &self.animations {
+            let start = offset.min(buffer.len());
+            animations.store_to(&mut buffer[start..]);
+            offset += animations.len();
+        }
     }
 
 }
 
+/// Tileset of a map built at runtime. The texture is looked up by
+/// identifier when the material is set, as for LDtk tilesets.
+pub struct MapTileset {
+    pub identifier: String,
+    pub texture_width: usize,
+    pub texture_height: usize,
+    pub tile_size: usize,
+    /// Animated tiles as tile id, frames and frames per second
+    pub animations: Vec<(u32, Vec<u32>, f32)>
+}
+
+impl MapTileset {
+    pub fn new(identifier: &str, texture_width: usize, texture_height: usize, tile_size: usize) -> Self {
+        Self {
+            identifier: identifier.to_string(),
+            texture_width,
+            texture_height,
+            tile_size,
+            animations: Vec::new()
+        }
+    }
+
+    pub fn add_animation(&mut self, tile_id: u32, frames: &[u32], step: f32) -> &mut Self {
+        self.animations.push((tile_id, frames.to_vec(), step));
+        self
+    }
+
+    /// Animations are stored as custom tile data, as in LDtk
+    fn to_definition(&self, uid: i64) -> TilesetDefinition {
+
+        let tile_size = self.tile_size.max(1);
+
+        let custom_data = self.animations.iter()
+            .map(|(tile_id, frames, step)| TileCustomMetadata {
+                data: serde_json::json!({ "animation_frames": frames, "animation_step": step }).to_string(),
+                tile_id: *tile_id as i64
+            })
+            .collect();
+
+        TilesetDefinition {
+            c_hei: (self.texture_height / tile_size) as i64,
+            c_wid: (self.texture_width / tile_size) as i64,
+            cached_pixel_data: None,
+            custom_data,
+            embed_atlas: None,
+            enum_tags: Vec::new(),
+            identifier: self.identifier.clone(),
+            padding: 0,
+            px_hei: self.texture_height as i64,
+            px_wid: self.texture_width as i64,
+            rel_path: None,
+            saved_selections: Vec::new(),
+            spacing: 0,
+            tags: Vec::new(),
+            tags_source_enum_uid: None,
+            tile_grid_size: tile_size as i64,
+            uid
+        }
+    }
+}
+
+/// LDtk project without levels, definitions are added by the caller
+pub(crate) fn empty_project(grid_size: i64) -> LdtkJson {
+    LdtkJson {
+        forced_refs: None,
+        app_build_id: 0.0,
+        backup_limit: 0,
+        backup_on_save: false,
+        backup_rel_path: None,
+        bg_color: String::new(),
+        custom_commands: Vec::new(),
+        default_entity_height: grid_size,
+        default_entity_width: grid_size,
+        default_grid_size: grid_size,
+        default_level_bg_color: String::new(),
+        default_level_height: None,
+        default_level_width: None,
+        default_pivot_x: 0.0,
+        default_pivot_y: 0.0,
+        defs: Definitions {
+            entities: Vec::new(),
+            enums: Vec::new(),
+            external_enums: Vec::new(),
+            layers: Vec::new(),
+            level_fields: Vec::new(),
+            tilesets: Vec::new()
+        },
+        dummy_world_iid: String::new(),
+        export_level_bg: false,
+        export_png: None,
+        export_tiled: false,
+        external_levels: false,
+        flags: Vec::new(),
+        identifier_style: ldtk_json::IdentifierStyle::Free,
+        iid: String::new(),
+        image_export_mode: ldtk_json::ImageExportMode::None,
+        json_version: String::new(),
+        level_name_pattern: String::new(),
+        levels: Vec::new(),
+        minify_json: false,
+        next_uid: 0,
+        png_file_pattern: None,
+        simplified_export: false,
+        toc: Vec::new(),
+        tutorial_desc: None,
+        world_grid_height: None,
+        world_grid_width: None,
+        world_layout: Some(ldtk_json::WorldLayout::Free),
+        worlds: Vec::new()
+    }
+}
+
 pub struct Map {
     pub obj: LdtkJson,
     pub levels: Vec<MapLevel>,
     pub material: Option<MaterialLockRef>,
+    pub normal_material: Option<MaterialLockRef>,
     shader_params: Uniform::<MapShaderParams>,
-    shader_lookup_buffer: Uniform::<MapShaderTileLookupBuffer>
+    animation_buffer: ShaderStorage::<u32>,
+    layer_params: PushConstants::<MapLayerParams>,
+    world_layout: WorldLayout
 }
 
 unsafe impl Send for Map {}
@@ -458,8 +1415,23 @@ impl Map {
         Ok(&mut self.levels[level])
     }
 
+    /// Load an LDtk project or a Tiled map (.tmx, .tmj)
     pub fn from_file(name: &str, uncompressed_layers: &[&str]) -> Result<Self, Error> {
 
+        // external tilesets are relative to the map file
+        if tiled::is_tiled_path(name) {
+            let data = std::fs::read(name)
+                .map_err(|_e| Error::from(format!("failed to load map from file \"{name}\"")))?;
+
+            let base_dir = std::path::Path::new(name).parent().unwrap_or(std::path::Path::new(""));
+            let obj = tiled::to_ldtk(&data, |path| {
+                std::fs::read(base_dir.join(path))
+                    .map_err(|_e| Error::from(format!("failed to load tileset from file \"{path}\"")))
+            })?;
+
+            return Self::from_ldtk(obj, uncompressed_layers);
+        }
+
         let file = match std::fs::File::open(name) {
             Ok(file) => file,
             Err(_e) => {
@@ -469,18 +1441,48 @@ impl Map {
 
         let reader = std::io::BufReader::new(file);
 
-        let obj: LdtkJson = match serde_json::from_reader(reader) {
+        let mut obj: LdtkJson = match serde_json::from_reader(reader) {
             Ok(obj) => obj,
             Err(_e) => {
                 return Err(Error::from("failed to load map"));
             }
         };
 
+        // external level files are relative to the project file
+        let base_dir = std::path::Path::new(name).parent().unwrap_or(std::path::Path::new(""));
+        Self::load_external_levels(&mut obj, |path| {
+            std::fs::read(base_dir.join(path))
+                .map_err(|_e| Error::from(format!("failed to load map level from file \"{path}\"")))
+        })?;
+
         Self::from_ldtk(obj, uncompressed_layers)
     }
 
     pub fn from_resource(descriptor: &StaticMapDescriptor) -> Result<Self, Error> {
-        let mut map = Self::from_memory(descriptor.data, descriptor.uncompressed_layers)?;
+
+        // external level and tileset files are embedded by the manifest compiler
+        let load = |path: &str| {
+            match descriptor.levels.iter().find(|level| level.path == path) {
+                Some(level) => Ok(level.data.to_vec()),
+                None => Err(Error::from(format!("map file \"{path}\" not found in \"{}\"", descriptor.name)))
+            }
+        };
+
+        let obj = if descriptor.format == "tiled" {
+            tiled::to_ldtk(descriptor.data, load)?
+        } else {
+            let mut obj: LdtkJson = match serde_json::from_slice(descriptor.data) {
+                Ok(obj) => obj,
+                Err(_e) => {
+                    return Err(Error::from("failed to load map"));
+                }
+            };
+
+            Self::load_external_levels(&mut obj, load)?;
+            obj
+        };
+
+        let mut map = Self::from_ldtk(obj, descriptor.uncompressed_layers)?;
 
         if !descriptor.material.is_empty() {
             let materials = crate::api::materials();
@@ -488,11 +1490,23 @@ impl Map {
             map.set_material(&material_ref);
         }
 
+        if !descriptor.normal_material.is_empty() {
+            let materials = crate::api::materials();
+            let material_ref = materials.get(descriptor.normal_material);
+            map.set_normal_material(&material_ref);
+        }
+
         Ok(map)
     }
 
+    /// Load an LDtk project or a Tiled map with embedded tilesets
     pub fn from_memory(data: &[u8], uncompressed_layers: &[&str]) -> Result<Self, Error> {
 
+        if tiled::is_tiled_data(data) {
+            let obj = tiled::to_ldtk(data, |path| Err(Error::from(format!("external tileset \"{path}\" not supported"))))?;
+            return Self::from_ldtk(obj, uncompressed_layers);
+        }
+
         let obj: LdtkJson = match serde_json::from_slice(data) {
             Ok(obj) => obj,
             Err(_e) => {
@@ -503,15 +1517,155 @@ impl Map {
         Self::from_ldtk(obj, uncompressed_layers)
     }
 
+    /// Map without levels for levels built at runtime, e.g. generated
+    /// roguelike or puzzle levels. The tilesets are fixed, levels and
+    /// layers are added with `add_level` and `add_layer`.
+    pub fn new(grid_size: usize, tilesets: &[MapTileset]) -> Result<Self, Error> {
+
+        let mut ldtk = empty_project(grid_size as i64);
+
+        ldtk.defs.tilesets = tilesets.iter()
+            .enumerate()
+            .map(|(index, tileset)| tileset.to_definition(index as i64 + 1))
+            .collect();
+
+        Self::from_ldtk(ldtk, &[])
+    }
+
+    /// Add a level without layers, returns the level index. The level
+    /// is placed at the world origin, see `MapLevel::world_x`.
+    pub fn add_level(&mut self, identifier: &str, cols: usize, rows: usize) -> Result<usize, Error> {
+        let grid_size = self.obj.default_grid_size.max(1) as usize;
+        let level = MapLevel::with_size(identifier, cols, rows, grid_size, &self.obj.defs)?;
+        self.levels.push(level);
+        Ok(self.levels.len() - 1)
+    }
+
+    /// Add a layer of transparent tiles on top of the layers of a level,
+    /// the layer has the size of the level
+    pub fn add_layer(&mut self, level_index: usize, identifier: &str, tileset: &str) -> Result<&mut MapLayer, Error> {
+
+        let tilesets = &self.obj.defs.tilesets;
+        let tileset_index = match tilesets.iter().position(|def| def.identifier == tileset) {
+            Some(index) if index < MAX_TILESETS => index,
+            _ => { return Err(Error::from(format!("tileset \"{tileset}\" not found"))); }
+        };
+
+        let grid_size = self.obj.default_grid_size.max(1) as usize;
+        let level = self.levels.get_mut(level_index).ok_or_else(|| Error::from("level index out of range"))?;
+
+        let layer = MapLayer::with_size(identifier, level.cols, level.rows, grid_size, tileset_index, &tilesets[tileset_index]);
+
+        // layers are stored top to bottom
+        level.layers.insert(0, layer);
+
+        Ok(&mut level.layers[0])
+    }
+
+    /// Replace levels saved to separate files (`externalRelPath`) by
+    /// the level data returned from the loader
+    fn load_external_levels<F>(obj: &mut LdtkJson, load: F) -> Result<(), Error>
+    where
+        F: Fn(&str) -> Result<Vec<u8>, Error>
+    {
+        let world_levels = obj.worlds.iter_mut().flat_map(|world| world.levels.iter_mut());
+
+        for level in obj.levels.iter_mut().chain(world_levels) {
+
+            if level.layer_instances.is_some() {
+                continue;
+            }
+
+            let Some(path) = level.external_rel_path.clone() else {
+                continue;
+            };
+
+            let data = load(&path)?;
+
+            *level = match serde_json::from_slice(&data) {
+                Ok(level) => level,
+                Err(_e) => {
+                    return Err(Error::from(format!("failed to load map level \"{path}\"")));
+                }
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Tileset textures are looked up by tileset identifier or image
+    /// file name, the texture of the material is used as fallback
     pub fn set_material(&mut self, material_ref: &MaterialLockRef) {
-        material_ref.lock().unwrap().add_uniform(&self.shader_params);
-        material_ref.lock().unwrap().add_uniform(&self.shader_lookup_buffer);
+        self.bind_material(material_ref, "");
         self.material = Some(material_ref.clone());
     }
 
+    /// Material drawing the normal map tileset, see `Lighting`.
+    /// Normal textures are looked up with a `_normals` suffix.
+    pub fn set_normal_material(&mut self, material_ref: &MaterialLockRef) {
+        self.bind_material(material_ref, "_normals");
+        self.normal_material = Some(material_ref.clone());
+    }
+
+    /// Override the texture of a tileset, by tileset identifier
+    pub fn set_tileset_texture(&mut self, identifier: &str, texture_ref: &TextureLockRef) -> Result<(), Error> {
+
+        let tilesets = &self.obj.defs.tilesets;
+        let index = match tilesets.iter().position(|tileset| tileset.identifier == identifier) {
+            Some(index) if index < MAX_TILESETS => index,
+            _ => { return Err(Error::from(format!("tileset \"{identifier}\" not found"))); }
+        };
+
+        if let Some(material_ref) = &self.material {
+            material_ref.lock().unwrap().set_texture(texture_ref, TILESET_TEXTURE_BINDING + index as u32, false);
+        }
+
+        Ok(())
+    }
+
+    fn bind_material(&self, material_ref: &MaterialLockRef, suffix: &str) {
+
+        let resources = crate::globals::resources();
+        let mut material = material_ref.lock().unwrap();
+
+        material.add_uniform(&self.shader_params);
+        material.add_storage(&self.animation_buffer);
+        material.add_push_constants(&self.layer_params);
+
+        let fallback = material.texture(TILESET_TEXTURE_BINDING).cloned();
+        let tilesets = &self.obj.defs.tilesets;
+
+        // the builtin shaders declare all slots, unused ones repeat the fallback
+        for slot in 0..MAX_TILESETS {
+
+            let texture_ref = tilesets.get(slot)
+                .and_then(|tileset| Self::find_tileset_texture(resources, tileset, suffix))
+                .or_else(|| fallback.clone());
+
+            if let Some(texture_ref) = texture_ref {
+                material.set_texture(&texture_ref, TILESET_TEXTURE_BINDING + slot as u32, false);
+            }
+        }
+    }
+
+    fn find_tileset_texture(resources: &crate::resources::Resources, tileset: &TilesetDefinition, suffix: &str) -> Option<TextureLockRef> {
+
+        if let Some(texture_ref) = resources.find_texture(&format!("{}{suffix}", tileset.identifier)) {
+            return Some(texture_ref);
+        }
+
+        let stem = std::path::Path::new(tileset.rel_path.as_deref()?).file_stem()?.to_str()?;
+        resources.find_texture(&format!("{stem}{suffix}"))
+    }
+
     pub fn from_ldtk(ldtk: LdtkJson, uncompressed_layers: &[&str]) -> Result<Self, Error> {
 
         let tilesets = &ldtk.defs.tilesets;
+        if tilesets.is_empty() {
+            return Err(Error::from("map has no tilesets"));
+        }
+
+        // defaults for custom shaders, the builtin shaders use the layer parameters
         let tileset = &tilesets[0];
 
         let shader_params = MapShaderParams::new(
@@ -520,25 +1674,125 @@ impl Map {
             tileset.tile_grid_size as u32
         )?;
 
-        let shader_lookup_buffer = MapShaderTileLookupBuffer::new()?;
+        let layer_params = PushConstants::<MapLayerParams>::new()?;
 
         let mut map_levels = Vec::new();
 
-        let levels = &ldtk.levels;
+        // multi-world projects store levels per world, the first world is used
+        let (levels, world_layout) = match ldtk.worlds.first() {
+            Some(world) if ldtk.levels.is_empty() => (&world.levels, &world.world_layout),
+            _ => (&ldtk.levels, &ldtk.world_layout)
+        };
+
+        let world_layout = match world_layout {
+            Some(ldtk_json::WorldLayout::GridVania) => WorldLayout::GridVania,
+            Some(ldtk_json::WorldLayout::LinearHorizontal) => WorldLayout::LinearHorizontal,
+            Some(ldtk_json::WorldLayout::LinearVertical) => WorldLayout::LinearVertical,
+            _ => WorldLayout::Free
+        };
+
         for level in levels {
-            let map_level = MapLevel::new(level, tileset, uncompressed_layers)?;
+            let map_level = MapLevel::new(level, &ldtk.defs, uncompressed_layers)?;
             map_levels.push(map_level);
         }
 
+        // all levels share the tilesets and their animations, levels
+        // might be added later
+        let num_animations: usize = match map_levels.first() {
+            Some(level) => level.animations.iter().map(|animations| animations.len()).sum(),
+            None => tilesets.iter()
+                .map(|tileset| TileAnimations::from_tileset(tileset).map(|animations| animations.len()))
+                .sum::<Result<usize, Error>>()?
+        };
+
+        let animation_buffer = ShaderStorage::<u32>::new(TILE_ANIMATION_BINDING, num_animations)?;
+
+        line_up_levels(&mut map_levels, world_layout);
+
         Ok(Self {
             obj: ldtk,
             levels: map_levels,
             material: None,
+            normal_material: None,
             shader_params,
-            shader_lookup_buffer
+            animation_buffer,
+            layer_params,
+            world_layout
         })
     }
 
+    pub fn world_layout(&self) -> WorldLayout {
+        self.world_layout
+    }
+
+    /// Level index by LDtk identifier
+    pub fn find_level(&self, identifier: &str) -> Option<usize> {
+        self.levels.iter().position(|level| level.identifier == identifier)
+    }
+
+    /// Level index by LDtk instance id
+    pub fn find_level_by_iid(&self, iid: &str) -> Option<usize> {
+        self.levels.iter().position(|level| level.iid == iid)
+    }
+
+    /// Topmost level containing a world position
+    pub fn level_at(&self, x: f32, y: f32) -> Option<usize> {
+        self.levels.iter()
+            .enumerate()
+            .filter(|(_, level)| level.contains(x, y))
+            .max_by_key(|(_, level)| level.world_depth)
+            .map(|(index, _)| index)
+    }
+
+    /// Levels overlapping a world rectangle, ordered by world depth
+    pub fn levels_in_rect(&self, x: f32, y: f32, width: f32, height: f32) -> Vec<usize> {
+        levels_in_rect(&self.levels, x, y, width, height)
+    }
+
+    /// First neighbour of a level in a direction, for room transitions
+    pub fn neighbour(&self, level_index: usize, direction: NeighbourDirection) -> Option<usize> {
+        neighbour(&self.levels, level_index, direction)
+    }
+
+    pub fn load_level(&mut self, level_index: usize) {
+        if let Some(level) = self.levels.get_mut(level_index) {
+            level.load();
+        }
+    }
+
+    pub fn unload_level(&mut self, level_index: usize) {
+        if let Some(level) = self.levels.get_mut(level_index) {
+            level.unload();
+        }
+    }
+
+    /// Keep GPU buffers only for levels near a world rectangle. Levels
+    /// within `margin` are loaded, levels further than twice the margin
+    /// are unloaded. Call during update, before the levels are drawn.
+    pub fn stream(&mut self, x: f32, y: f32, width: f32, height: f32, margin: f32) {
+        let mut unload = Vec::new();
+
+        for level in &mut self.levels {
+            if level.intersects(x - margin, y - margin, width + margin * 2.0, height + margin * 2.0) {
+                level.load();
+            } else if level.is_loaded() && !level.intersects(x - margin * 2.0, y - margin * 2.0, width + margin * 4.0, height + margin * 4.0) {
+                unload.push(level);
+            }
+        }
+
+        if unload.is_empty() {
+            return;
+        }
+
+        // buffers might still be in use by frames in flight
+        let device = crate::globals::device();
+        unsafe { let _ = device.obj.device_wait_idle(); }
+
+        for level in unload {
+            level.release();
+        }
+    }
+
     pub fn animate(&mut self, level_index: usize, delta: f32) {
         if level_index >= self.levels.len() {
             return;
@@ -548,25 +1802,113 @@ impl Map {
         level.animate(delta);
     }
 
+    /// Animate all levels, keeps animations in sync for `draw_world`
+    pub fn animate_world(&mut self, delta: f32) {
+        for level in &mut self.levels {
+            level.animate(delta);
+        }
+    }
+
     pub fn draw(&mut self, level_index: usize, offset_x: f32, offset_y: f32) {
+        let material = self.material.clone();
+        self.draw_with_material(material, &[(level_index, 0.0, 0.0)], (offset_x, offset_y), LayerSelection::All, true);
+    }
 
-        if level_index >= self.levels.len() {
+    /// Draw the layers beneath a layer, e.g. before drawing the player
+    /// between "Ground" and "Foreground"
+    pub fn draw_layers_below(&mut self, level_index: usize, identifier: &str, offset_x: f32, offset_y: f32) {
+        let material = self.material.clone();
+        self.draw_with_material(material, &[(level_index, 0.0, 0.0)], (offset_x, offset_y), LayerSelection::Below(identifier), true);
+    }
+
+    /// Draw a layer and all layers above, completes `draw_layers_below`
+    pub fn draw_layers_from(&mut self, level_index: usize, identifier: &str, offset_x: f32, offset_y: f32) {
+        let material = self.material.clone();
+        self.draw_with_material(material, &[(level_index, 0.0, 0.0)], (offset_x, offset_y), LayerSelection::From(identifier), true);
+    }
+
+    /// Draw normal map tiles into the normal buffer of the lighting
+    pub fn draw_normals(&mut self, level_index: usize, offset_x: f32, offset_y: f32) {
+        if self.normal_material.is_none() {
             return;
         }
 
-        let level = &mut self.levels[level_index];
+        let material = self.normal_material.clone();
+        self.draw_with_material(material, &[(level_index, 0.0, 0.0)], (offset_x, offset_y), LayerSelection::All, false);
+    }
+
+    /// Draw all levels visible from a camera, the camera position is
+    /// the world position at the top left of the view
+    pub fn draw_world(&mut self, camera_x: f32, camera_y: f32) {
+        let material = self.material.clone();
+        let visible = self.visible_levels(camera_x, camera_y);
+        self.draw_with_material(material, &visible, (-camera_x, -camera_y), LayerSelection::All, true);
+    }
+
+    /// Draw the layers beneath a layer of all visible levels
+    pub fn draw_world_below(&mut self, camera_x: f32, camera_y: f32, identifier: &str) {
+        let material = self.material.clone();
+        let visible = self.visible_levels(camera_x, camera_y);
+        self.draw_with_material(material, &visible, (-camera_x, -camera_y), LayerSelection::Below(identifier), true);
+    }
+
+    /// Draw a layer and all layers above of all visible levels
+    pub fn draw_world_from(&mut self, camera_x: f32, camera_y: f32, identifier: &str) {
+        let material = self.material.clone();
+        let visible = self.visible_levels(camera_x, camera_y);
+        self.draw_with_material(material, &visible, (-camera_x, -camera_y), LayerSelection::From(identifier), true);
+    }
+
+    /// Draw normal map tiles of all levels visible from a camera
+    pub fn draw_world_normals(&mut self, camera_x: f32, camera_y: f32) {
+        if self.normal_material.is_none() {
+            return;
+        }
+
+        let material = self.normal_material.clone();
+        let visible = self.visible_levels(camera_x, camera_y);
+        self.draw_with_material(material, &visible, (-camera_x, -camera_y), LayerSelection::All, false);
+    }
+
+    /// Visible levels with their world position
+    fn visible_levels(&self, camera_x: f32, camera_y: f32) -> Vec<(usize, f32, f32)> {
+        let metrics = crate::api::metrics();
+
+        self.levels_in_rect(camera_x, camera_y, metrics.view_width, metrics.view_height)
+            .into_iter()
+            .map(|index| {
+                let level = &self.levels[index];
+                (index, level.world_x, level.world_y)
+            })
+            .collect()
+    }
+
+    /// Draw levels at their origin, shifted by the scroll offset. Shared
+    /// parameters are taken from the first level.
+    fn draw_with_material(&mut self, material: Option<MaterialLockRef>, levels: &[(usize, f32, f32)], scroll: (f32, f32), selection: LayerSelection, tinted: bool) {
+
+        let levels: Vec<(usize, f32, f32)> = levels.iter()
+            .copied()
+            .filter(|(level_index, _, _)| *level_index < self.levels.len())
+            .collect();
+
+        let Some(&(first_index, origin_x, origin_y)) = levels.first() else {
+            return;
+        };
 
         let renderer = crate::api::renderer_mut();
 
-        if self.material.is_some() {
-            renderer.set_material(self.material.as_ref().unwrap());
+        if material.is_some() {
+            renderer.set_material(material.as_ref().unwrap());
         }
 
+        let first_level = &mut self.levels[first_index];
+
         let shader_params = self.shader_params.data_mut();
-        shader_params.offset_left = offset_x;
-        shader_params.offset_top = offset_y;
-        shader_params.map_rows = level.rows as u32;
-        shader_params.map_cols = level.cols as u32;
+        shader_params.offset_left = origin_x + scroll.0;
+        shader_params.offset_top = origin_y + scroll.1;
+        shader_params.map_rows = first_level.rows as u32;
+        shader_params.map_cols = first_level.cols as u32;
 
         // window metrics
         let metrics = crate::api::metrics();
@@ -580,16 +1922,320 @@ impl Map {
 
         self.shader_params.update().unwrap();
 
-        let shader_lookup_buffer = self.shader_lookup_buffer.data_mut();
-        level.store_animations_to(&mut shader_lookup_buffer.animation_frames);
-        self.shader_lookup_buffer.update().unwrap();
+        first_level.store_animations_to(self.animation_buffer.data_mut());
+        self.animation_buffer.update().unwrap();
+
+        let Some(material) = material else {
+            for (level_index, _, _) in levels {
+                self.levels[level_index].draw_with_params(None, (0.0, 0.0), (0.0, 0.0), selection, tinted);
+            }
+            return;
+        };
+
+        material.lock().unwrap().bind_uniforms();
 
-        if self.material.is_some() {
-            self.material.as_ref().unwrap().lock().unwrap().bind_uniforms();
+        for (level_index, origin_x, origin_y) in levels {
+            self.levels[level_index].draw_with_params(Some(&mut self.layer_params), (origin_x, origin_y), scroll, selection, tinted);
         }
 
-        level.draw();
+    }
+
+}
+
+/// Levels overlapping a world rectangle, ordered by world depth
+fn levels_in_rect(levels: &[MapLevel], x: f32, y: f32, width: f32, height: f32) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..levels.len())
+        .filter(|index| levels[*index].intersects(x, y, width, height))
+        .collect();
+
+    indices.sort_by_key(|index| levels[*index].world_depth);
+    indices
+}
 
+/// First neighbour of a level in a direction
+fn neighbour(levels: &[MapLevel], level_index: usize, direction: NeighbourDirection) -> Option<usize> {
+    let level = levels.get(level_index)?;
+    level.neighbours.iter()
+        .filter(|neighbour| neighbour.direction == direction)
+        .find_map(|neighbour| levels.iter().position(|level| level.iid == neighbour.level_iid))
+}
+
+/// Linear layouts have no world coordinates, levels are lined up
+fn line_up_levels(levels: &mut [MapLevel], world_layout: WorldLayout) {
+    let mut pos: f32 = 0.0;
+    for level in levels {
+        match world_layout {
+            WorldLayout::LinearHorizontal => {
+                level.world_x = pos;
+                level.world_y = 0.0;
+                pos += level.width as f32;
+            },
+            WorldLayout::LinearVertical => {
+                level.world_x = 0.0;
+                level.world_y = pos;
+                pos += level.height as f32;
+            },
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_animation_tag() {
+        assert_eq!(TileAnimations::parse_animation_tag("Anim_4"), Some((4, 1.0)));
+        assert_eq!(TileAnimations::parse_animation_tag("anim_3_8"), Some((3, 8.0)));
+        assert_eq!(TileAnimations::parse_animation_tag("Anim_0"), None);
+        assert_eq!(TileAnimations::parse_animation_tag("Water"), None);
+        assert_eq!(TileAnimations::parse_animation_tag("Anim_4_8_x"), None);
+    }
+
+    #[test]
+    fn test_tile_source() {
+        assert_eq!(tileset_columns(32, 8, 0, 0), 4);
+        assert_eq!(tileset_columns(39, 8, 2, 1), 4);
+
+        let mut tileset = MapTileset::new("tiles", 32, 32, 8).to_definition(1);
+        assert_eq!(tile_source(&tileset, 5), (8, 8));
+
+        tileset.padding = 2;
+        tileset.spacing = 1;
+        assert_eq!(tile_source(&tileset, 5), (11, 11));
+    }
+
+    #[test]
+    fn test_grouped_animations() {
+        let mut animations = TileAnimations::new();
+
+        let mut leader = TileAnimation::new(0, &[10, 11, 12, 13], AnimatorMode::ForwardLoop, 1.0);
+        leader.set_group("water");
+        animations.push(leader);
+
+        let mut follower = TileAnimation::new(1, &[20, 21, 22, 23], AnimatorMode::ForwardLoop, 5.0);
+        follower.set_group("water").set_offset(1.0);
+        animations.push(follower);
+
+        let mut other = TileAnimation::new(2, &[30, 31], AnimatorMode::ForwardLoop, 1.0);
+        other.set_offset(3.0);
+        animations.push(other);
+
+        animations.update(1.5);
+
+        // follower keeps its offset to the leader, not its own step
+        assert_eq!(animations.get(0).unwrap().current_frame, 11);
+        assert_eq!(animations.get(1).unwrap().current_frame, 22);
+        // offset wraps to the frame count
+        assert_eq!(animations.get(2).unwrap().current_frame, 30);
+    }
+
+    #[test]
+    fn test_layer_tilesets() {
+        let tmx = r#"<map orientation="orthogonal" width="2" height="1" tilewidth="8" tileheight="8">
+ <tileset firstgid="1" name="ground" tilewidth="8" tileheight="8" tilecount="16" columns="4">
+  <image source="ground.png" width="32" height="32"/>
+ </tileset>
+ <tileset firstgid="17" name="items" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="items.png" width="32" height="32"/>
+ </tileset>
+ <layer name="Ground" width="2" height="1"><data encoding="csv">1,18</data></layer>
+ <objectgroup name="Entities"/>
+</map>"#;
+
+        let mut ldtk = tiled::to_ldtk(tmx.as_bytes(), |path| Err(Error::from(path))).unwrap();
+        let level = MapLevel::new(&ldtk.levels[0], &ldtk.defs, &[]).unwrap();
+
+        // tiles of the second tileset are moved to their own layer
+        let ground = level.find_layer("Ground").unwrap();
+        let items = level.find_layer("Ground_items").unwrap();
+        assert_eq!((ground.tileset(), ground.tile_size), (0, 8));
+        assert_eq!((items.tileset(), items.tile_size), (1, 16));
+
+        // layers without tileset use the first one
+        assert_eq!(level.find_layer("Entities").unwrap().tileset(), 0);
+
+        let layers = ldtk.levels[0].layer_instances.as_mut().unwrap();
+        layers[0].tileset_def_uid = Some(99);
+        assert!(MapLevel::resolve_tileset(&layers[0], &ldtk.defs.tilesets).is_err());
+        assert!(MapLevel::resolve_tileset(&layers[0], &[]).is_err());
+    }
+
+    #[test]
+    fn test_external_levels() {
+        let tmx = r#"<map orientation="orthogonal" width="2" height="1" tilewidth="8" tileheight="8">
+ <tileset firstgid="1" name="ground" tilewidth="8" tileheight="8" tilecount="16" columns="4">
+  <image source="ground.png" width="32" height="32"/>
+ </tileset>
+ <layer name="Ground" width="2" height="1"><data encoding="csv">1,2</data></layer>
+</map>"#;
+
+        // project saved with separate level files
+        let mut ldtk = tiled::to_ldtk(tmx.as_bytes(), |path| Err(Error::from(path))).unwrap();
+        let level_data = serde_json::to_vec(&ldtk.levels[0]).unwrap();
+        ldtk.levels[0].layer_instances = None;
+        ldtk.levels[0].external_rel_path = Some("world/Level_0.ldtkl".to_string());
+
+        let load = |path: &str| match path {
+            "world/Level_0.ldtkl" => Ok(level_data.clone()),
+            "world/Broken.ldtkl" => Ok(b"{".to_vec()),
+            _ => Err(Error::from(format!("file not found: {path}")))
+        };
+
+        let mut project = ldtk.clone();
+        Map::load_external_levels(&mut project, load).unwrap();
+        let level = MapLevel::new(&project.levels[0], &project.defs, &[]).unwrap();
+        assert_eq!(level.find_layer("Ground").unwrap().get_tile_xy(1, 0), 1);
+
+        let mut project = ldtk.clone();
+        project.levels[0].external_rel_path = Some("world/Missing.ldtkl".to_string());
+        assert!(Map::load_external_levels(&mut project, load).is_err());
+
+        let mut project = ldtk.clone();
+        project.levels[0].external_rel_path = Some("world/Broken.ldtkl".to_string());
+        assert!(Map::load_external_levels(&mut project, load).is_err());
+    }
+
+    #[test]
+    fn test_runtime_level() {
+        let mut tileset = MapTileset::new("tiles", 64, 32, 8);
+        tileset.add_animation(5, &[5, 6, 7], 4.0);
+
+        let mut defs = empty_project(8).defs;
+        defs.tilesets.push(tileset.to_definition(1));
+        assert_eq!((defs.tilesets[0].c_wid, defs.tilesets[0].c_hei), (8, 4));
+
+        let mut level = MapLevel::with_size("Generated", 4, 3, 8, &defs).unwrap();
+        level.layers.push(MapLayer::with_size("Ground", 4, 3, 8, 0, &defs.tilesets[0]));
+        assert_eq!((level.width, level.height), (32, 24));
+
+        // animated tiles refer to the animation table
+        assert_eq!(level.tile_index(0, 2), 2);
+        assert_eq!(level.tile_index(0, 5), -1);
+
+        level.set_tile_xy(0, 1, 2, 9).unwrap();
+        level.set_tile_xy(0, 3, 0, 5).unwrap();
+        assert!(level.set_tile_xy(0, 4, 0, 1).is_err());
+
+        let layer = level.get_layer_mut(0).unwrap();
+        layer.int_grid_mut().set_value(1, 2, 1);
+        assert_eq!(layer.occupancy().iter().filter(|occupied| **occupied).count(), 2);
+
+        // cells outside the new size are dropped
+        level.resize(2, 4);
+        let layer = level.get_layer_mut(0).unwrap();
+        assert_eq!((layer.cols(), layer.rows()), (2, 4));
+        assert_eq!(layer.get_tile_xy(1, 2), 9);
+        assert_eq!(layer.int_grid().value(1, 2), 1);
+        assert_eq!(layer.occupancy().iter().filter(|occupied| **occupied).count(), 1);
+
+        layer.clear();
+        assert!(layer.occupancy().iter().all(|occupied| !*occupied));
+        assert_eq!(layer.int_grid().value(1, 2), 0);
+        assert_eq!((level.width, level.height), (16, 32));
+
+        // fill resolves animations and resets flips
+        let layer = level.get_layer_mut(0).unwrap();
+        layer.set_flags_xy(1, 1, Tile::make_flags(true, false, 1, 0.5));
+        level.fill(0, 5).unwrap();
+        let layer = level.get_layer(0).unwrap();
+        assert!((0..4).all(|y| (0..2).all(|x| layer.get_tile_xy(x, y) == -1 && layer.get_flags_xy(x, y) == Tile::OPAQUE)));
+
+        level.fill(0, 3).unwrap();
+        assert_eq!(level.get_layer(0).unwrap().get_tile_xy(1, 3), 3);
+        assert!(level.fill(1, 3).is_err());
     }
 
+    #[test]
+    fn test_layer_range() {
+        let tileset = MapTileset::new("tiles", 64, 32, 8);
+        let mut defs = empty_project(8).defs;
+        defs.tilesets.push(tileset.to_definition(1));
+
+        // stored top to bottom
+        let mut level = MapLevel::with_size("Level_0", 4, 3, 8, &defs).unwrap();
+        for identifier in ["Foreground", "Ground", "Background"] {
+            level.layers.push(MapLayer::with_size(identifier, 4, 3, 8, 0, &defs.tilesets[0]));
+        }
+
+        assert_eq!(level.layer_range(LayerSelection::All), 0..3);
+        assert_eq!(level.layer_range(LayerSelection::Below("Foreground")), 1..3);
+        assert_eq!(level.layer_range(LayerSelection::From("Foreground")), 0..1);
+        assert_eq!(level.layer_range(LayerSelection::Below("Background")), 3..3);
+
+        // unknown layers count as topmost
+        assert_eq!(level.layer_range(LayerSelection::Below("Sky")), 0..3);
+        assert_eq!(level.layer_range(LayerSelection::From("Sky")), 0..0);
+    }
+
+    #[test]
+    fn test_layer_fields() {
+        let tileset = MapTileset::new("tiles", 64, 32, 8);
+        let mut defs = empty_project(8).defs;
+        defs.tilesets.push(tileset.to_definition(1));
+
+        let mut layer = MapLayer::with_size("Background", 4, 3, 8, 0, &defs.tilesets[0]);
+
+        assert!(layer.apply_field("parallax_x", &FieldValue::Float(0.5)));
+        assert!(layer.apply_field("parallax_y", &FieldValue::Int(0)));
+        assert!(layer.apply_field("offset_y", &FieldValue::Float(-4.0)));
+        assert!(layer.apply_field("opacity", &FieldValue::Float(2.0)));
+        assert!(layer.apply_field("tint", &FieldValue::Color(Color::rgb(1.0, 0.5, 0.0))));
+        assert!(!layer.apply_field("tint", &FieldValue::Float(1.0)));
+        assert!(!layer.apply_field("parallax_x", &FieldValue::String("fast".to_string())));
+        assert!(!layer.apply_field("depth", &FieldValue::Float(1.0)));
+
+        assert_eq!(layer.parallax(), (0.5, 0.0));
+        assert_eq!(layer.opacity(), 1.0);
+        assert_eq!(layer.tint(), &Color::rgb(1.0, 0.5, 0.0));
+
+        // neighbouring levels keep their distance, only scrolling is scaled
+        assert_eq!(layer.draw_offset(0.0, 0.0, -100.0, -40.0), (-50.0, -4.0));
+        assert_eq!(layer.draw_offset(256.0, 0.0, -100.0, -40.0), (206.0, -4.0));
+    }
+
+    #[test]
+    fn test_world_levels() {
+        let defs = empty_project(8).defs;
+
+        // 32x24, 64x24 and 16x40 pixels
+        let mut levels = vec![
+            MapLevel::with_size("Start", 4, 3, 8, &defs).unwrap(),
+            MapLevel::with_size("Hall", 8, 3, 8, &defs).unwrap(),
+            MapLevel::with_size("Shaft", 2, 5, 8, &defs).unwrap()
+        ];
+
+        line_up_levels(&mut levels, WorldLayout::LinearHorizontal);
+        assert_eq!(levels.iter().map(|level| level.world_x).collect::<Vec<_>>(), vec![0.0, 32.0, 96.0]);
+        assert!(levels.iter().all(|level| level.world_y == 0.0));
+
+        line_up_levels(&mut levels, WorldLayout::LinearVertical);
+        assert_eq!(levels.iter().map(|level| level.world_y).collect::<Vec<_>>(), vec![0.0, 24.0, 48.0]);
+        assert!(levels.iter().all(|level| level.world_x == 0.0));
+
+        // free layouts keep the world coordinates
+        levels[2].world_x = 20.0;
+        line_up_levels(&mut levels, WorldLayout::Free);
+        assert_eq!(levels[2].world_x, 20.0);
+
+        line_up_levels(&mut levels, WorldLayout::LinearHorizontal);
+
+        // edges touching the rectangle don't count, results are ordered by depth
+        levels[0].world_depth = 1;
+        assert_eq!(levels_in_rect(&levels, 0.0, 0.0, 32.0, 10.0), vec![0]);
+        assert_eq!(levels_in_rect(&levels, 30.0, 0.0, 70.0, 10.0), vec![1, 2, 0]);
+        assert!(levels_in_rect(&levels, 10.0, 30.0, 40.0, 10.0).is_empty());
+        assert_eq!(levels_in_rect(&levels, 100.0, 30.0, 4.0, 4.0), vec![2]);
+
+        levels[0].neighbours.push(LevelNeighbour { level_iid: "Missing".to_string(), direction: NeighbourDirection::East });
+        levels[0].neighbours.push(LevelNeighbour { level_iid: "Hall".to_string(), direction: NeighbourDirection::East });
+        levels[1].neighbours.push(LevelNeighbour { level_iid: "Start".to_string(), direction: NeighbourDirection::West });
+
+        // unknown levels are skipped
+        assert_eq!(neighbour(&levels, 0, NeighbourDirection::East), Some(1));
+        assert_eq!(neighbour(&levels, 1, NeighbourDirection::West), Some(0));
+        assert_eq!(neighbour(&levels, 1, NeighbourDirection::East), None);
+        assert_eq!(neighbour(&levels, 5, NeighbourDirection::East), None);
+    }
 }
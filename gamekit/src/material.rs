@@ -8,7 +8,7 @@ use std::{collections::HashMap, sync::{Arc, Mutex, MutexGuard}};
 
 use ash::vk::{self, Handle};
 
-use crate::{api::{Disposable, LockRef, Point}, buffer::{PushConstants, Uniform, UniformBufferLockRef}, error::Error, font::{Font, FontLockRef}, primitives::{ShaderInputType, Tile, Vertex}, shader::{ShaderLockRef, ShaderType}, texture::{Texture, TextureBinding, TextureLockRef}};
+use crate::{api::{Disposable, LockRef, Point}, buffer::{PushConstants, ShaderStorage, ShaderStorageBufferLockRef, Uniform, UniformBufferLockRef}, error::Error, font::{Font, FontLockRef}, primitives::{QuadInstance, ShaderInputType, Tile, Vertex}, shader::{ShaderLockRef, ShaderType}, sprite::SpriteSheetLockRef, texture::{Texture, TextureBinding, TextureLockRef}};
 
 const DEFAULT_SHADER_ENTRY_POINT: &str = "main";
 
@@ -33,6 +33,10 @@ pub struct Topology {}
 impl Topology {
     pub const TRIANGLE_LIST: u32 = 0x1;
     pub const POINT_LIST: u32 = 0x2;
+    pub const LINE_LIST: u32 = 0x3;
+    pub const LINE_STRIP: u32 = 0x4;
+    pub const TRIANGLE_STRIP: u32 = 0x5;
+    pub const TRIANGLE_FAN: u32 = 0x6; // requires the 'triangleFans' feature on portability subset devices
 
     pub fn from_string(blend_mode: &str) -> u32 {
         match blend_mode {
@@ -40,9 +44,29 @@ impl Topology {
             "triangle_list" => Topology::TRIANGLE_LIST,
             "points" => Topology::POINT_LIST,
             "point_list" => Topology::POINT_LIST,
+            "lines" => Topology::LINE_LIST,
+            "line_list" => Topology::LINE_LIST,
+            "line_strip" => Topology::LINE_STRIP,
+            "triangle_strip" => Topology::TRIANGLE_STRIP,
+            "triangle_fan" => Topology::TRIANGLE_FAN,
             _ => Topology::TRIANGLE_LIST
         }
     }
+
+    /// Vulkan topology, fails for triangle fans if the device lacks support
+    pub fn to_primitive_topology(topology: u32, triangle_fans: bool) -> Result<vk::PrimitiveTopology, Error> {
+        match topology {
+            Topology::POINT_LIST => Ok(vk::PrimitiveTopology::POINT_LIST),
+            Topology::LINE_LIST => Ok(vk::PrimitiveTopology::LINE_LIST),
+            Topology::LINE_STRIP => Ok(vk::PrimitiveTopology::LINE_STRIP),
+            Topology::TRIANGLE_STRIP => Ok(vk::PrimitiveTopology::TRIANGLE_STRIP),
+            Topology::TRIANGLE_FAN if !triangle_fans => Err(Error::from(
+                "topology 'triangle_fan' not supported by device (portability subset without 'triangleFans' feature)"
+            )),
+            Topology::TRIANGLE_FAN => Ok(vk::PrimitiveTopology::TRIANGLE_FAN),
+            _ => Ok(vk::PrimitiveTopology::TRIANGLE_LIST)
+        }
+    }
 }
 
 pub struct RenderState {
@@ -205,12 +229,15 @@ pub struct Material {
     invalidated: bool,
     shader_input_type: ShaderInputType,
     topology: u32,
+    line_width: f32,
     render_state: RenderState,
     textures: Vec<TextureBinding>,
     shaders: Vec<ShaderInfo>,
     uniforms: Vec<UniformBufferLockRef>,
+    storage_buffers: Vec<ShaderStorageBufferLockRef>,
     push_constant_ranges: Vec<vk::PushConstantRange>,
     font: FontLockRef,
+    sprite_sheet: Option<SpriteSheetLockRef>,
     descriptor_pool: vk::DescriptorPool,
     descriptor_set_layout: vk::DescriptorSetLayout,
     pub pipeline_layout: vk::PipelineLayout,
@@ -240,6 +267,11 @@ impl Disposable for Material {
         }
         self.uniforms.clear();
 
+        for element in &mut self.storage_buffers {
+            element.lock().unwrap().dispose();
+        }
+        self.storage_buffers.clear();
+
         self.push_constant_ranges.clear();
     }
 }
@@ -250,13 +282,16 @@ impl Material {
             invalidated: true,
             shader_input_type: ShaderInputType::Vertex,
             topology: Topology::TRIANGLE_LIST,
+            line_width: 1.0,
             render_state: RenderState::default(),
 
             textures: Vec::new(),
             shaders: Vec::new(),
             uniforms: Vec::new(),
+            storage_buffers: Vec::new(),
             push_constant_ranges: Vec::new(),
             font: Arc::new(Mutex::new(Font::default())),
+            sprite_sheet: None,
 
             descriptor_pool: vk::DescriptorPool::null(),
             descriptor_set_layout: vk::DescriptorSetLayout::null(),
@@ -274,6 +309,7 @@ impl Material {
         let mut material = Self::new();
 
         material.set_topology(Topology::from_string(descriptor.topology));
+        material.set_line_width(descriptor.line_width);
         material.set_blending(descriptor.blending);
         material.set_blend_mode(BlendMode::from_string(descriptor.blend_mode));
         material.set_backface_culling(descriptor.backface_culling);
@@ -286,6 +322,10 @@ impl Material {
                 ShaderInputType::Point
             } else if descriptor.shader_input_type.eq_ignore_ascii_case("tile") {
                 ShaderInputType::Tile
+            } else if descriptor.shader_input_type.eq_ignore_ascii_case("instance") {
+                ShaderInputType::Instance
+            } else if descriptor.shader_input_type.eq_ignore_ascii_case("none") {
+                ShaderInputType::None
             } else {
                 ShaderInputType::Vertex
             }
@@ -294,6 +334,20 @@ impl Material {
         if !descriptor.font.is_empty() {
             let font_ref = &resources.get_font(descriptor.font);
             material.set_font(font_ref);
+
+            // bind BMFont pages to consecutive bindings unless a texture is given
+            if descriptor.texture.is_empty() {
+                let font = font_ref.lock().unwrap();
+                for (index, page) in font.pages().iter().enumerate() {
+                    let texture_ref = &resources.get_texture(page);
+                    material.add_texture(texture_ref, descriptor.texture_binding + index as u32, descriptor.texture_filtering);
+                }
+            }
+        }
+
+        if !descriptor.sprite_sheet.is_empty() {
+            let sprite_sheet_ref = &resources.get_sprite_sheet(descriptor.sprite_sheet);
+            material.set_sprite_sheet(sprite_sheet_ref);
         }
 
         if !descriptor.texture.is_empty() {
@@ -318,7 +372,8 @@ impl Material {
         Arc::new(Mutex::new(material))
     }
 
-    pub fn set_topology(&mut self, val: u32) -> &mut Self { self.topology = val; self }
+    pub fn set_topology(&mut self, val: u32) -> &mut Self { self.topology = val; self.invalidated = true; self }
+    pub fn set_line_width(&mut self, val: f32) -> &mut Self { self.line_width = val; self.invalidated = true; self }
     pub fn set_blending(&mut self, val: bool) -> &mut Self { self.render_state.set_blending(val); self }
     pub fn set_blend_mode(&mut self, val: u32) -> &mut Self { self.render_state.set_blend_mode(val); self }
     pub fn set_backface_culling(&mut self, val: bool) -> &mut Self { self.render_state.set_backface_culling(val); self }
@@ -361,6 +416,17 @@ impl Material {
         self
     }
 
+    pub fn add_storage<T: Default + Clone>(&mut self, storage: &ShaderStorage<T>) -> &mut Self {
+        let storage_buffer = storage.get_buffer_ref();
+        self.storage_buffers.push(storage_buffer);
+        self.invalidated = true;
+        self
+    }
+
+    pub fn invalidate(&mut self) {
+        self.invalidated = true;
+    }
+
     pub fn add_texture(&mut self, texture_ref: &TextureLockRef, binding: u32, filtering: bool) -> &mut Self {
         let texture_binding = Texture::get_binding(texture_ref, binding, filtering);
         self.textures.push(texture_binding);
@@ -368,6 +434,24 @@ impl Material {
         self
     }
 
+    /// Replace the texture at a binding, or add it if the binding is unused
+    pub fn set_texture(&mut self, texture_ref: &TextureLockRef, binding: u32, filtering: bool) -> &mut Self {
+        let texture_binding = Texture::get_binding(texture_ref, binding, filtering);
+        if let Some(existing) = self.textures.iter_mut().find(|element| element.binding() == binding) {
+            existing.dispose();
+            *existing = texture_binding;
+        } else {
+            self.textures.push(texture_binding);
+        }
+        self.invalidated = true;
+        self
+    }
+
+    /// Texture bound at a binding, if any
+    pub fn texture(&self, binding: u32) -> Option<&TextureLockRef> {
+        self.textures.iter().find(|element| element.binding() == binding).map(|element| &element.texture)
+    }
+
     pub fn set_font(&mut self, font_ref: &FontLockRef) -> &mut Self {
         self.font = font_ref.clone();
         self
@@ -377,10 +461,26 @@ impl Material {
         &self.font
     }
 
+    pub fn set_sprite_sheet(&mut self, sprite_sheet_ref: &SpriteSheetLockRef) -> &mut Self {
+        self.sprite_sheet = Some(sprite_sheet_ref.clone());
+        self
+    }
+
+    pub fn sprite_sheet(&self) -> Option<&SpriteSheetLockRef> {
+        self.sprite_sheet.as_ref()
+    }
+
     fn compile(&mut self) {
         self.validate_pipeline();
     }
 
+    /// Check that the device supports the material settings
+    pub fn validate(&self) -> Result<(), Error> {
+        let device = crate::globals::device();
+        Topology::to_primitive_topology(self.topology, device.features.has_triangle_fans())?;
+        Ok(())
+    }
+
     pub fn bind(&mut self) {
         self.validate_pipeline();
         self.bind_pipeline();
@@ -482,12 +582,19 @@ impl Material {
         let tile_binding_descriptions = [ Tile::get_binding_description() ];
         let tile_attribute_descriptions = Tile::get_attribute_descriptions();
 
+        let instance_binding_descriptions = QuadInstance::get_binding_descriptions();
+        let instance_attribute_descriptions = QuadInstance::get_attribute_descriptions();
+
         let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::default()
             .vertex_binding_descriptions(
                 if self.shader_input_type == ShaderInputType::Point {
                     &point_binding_descriptions
                 } else if self.shader_input_type == ShaderInputType::Tile {
                     &tile_binding_descriptions
+                } else if self.shader_input_type == ShaderInputType::Instance {
+                    &instance_binding_descriptions
+                } else if self.shader_input_type == ShaderInputType::None {
+                    &[]
                 } else {
                     &vertex_binding_descriptions
                 }
@@ -497,14 +604,25 @@ impl Material {
                     &point_attribute_descriptions
                 } else if self.shader_input_type == ShaderInputType::Tile {
                     &tile_attribute_descriptions
+                } else if self.shader_input_type == ShaderInputType::Instance {
+                    &instance_attribute_descriptions
+                } else if self.shader_input_type == ShaderInputType::None {
+                    &[]
                 } else {
                     &vertex_attribute_descriptions
                 }
             );
 
-        let topology = match self.topology {
-            Topology::POINT_LIST => vk::PrimitiveTopology::POINT_LIST,
-            _ => vk::PrimitiveTopology::TRIANGLE_LIST
+        // materials from the manifest are validated by Materials::build
+        let topology = Topology::to_primitive_topology(self.topology, device.features.has_triangle_fans())
+            .unwrap_or_else(|e| panic!("failed to create graphics pipeline: {}", e.message()));
+
+        // without wide lines support, only 1.0 is valid (use LineQueue instead)
+        let line_width = if device.features.has_wide_lines() {
+            let range = device.limits.line_width_range;
+            self.line_width.clamp(range[0], range[1])
+        } else {
+            1.0
         };
 
         let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::default()
@@ -518,7 +636,7 @@ impl Material {
             .depth_clamp_enable(false)
             .rasterizer_discard_enable(false)
             .polygon_mode(vk::PolygonMode::FILL)
-            .line_width(1.0)
+            .line_width(line_width)
             .cull_mode(cull_mode)
             .front_face(front_face)
             .depth_bias_enable(false)
@@ -603,6 +721,15 @@ impl Material {
                 .descriptor_count(1));
         }
 
+        for storage_ref in &self.storage_buffers {
+            let storage = storage_ref.lock().unwrap();
+            descriptor_set_layout_bindings.push(vk::DescriptorSetLayoutBinding::default()
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .binding(storage.binding())
+                .stage_flags(vk::ShaderStageFlags::ALL_GRAPHICS)
+                .descriptor_count(1));
+        }
+
         for texture_info in &self.textures {
             descriptor_set_layout_bindings.push(vk::DescriptorSetLayoutBinding::default()
                 .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
@@ -640,6 +767,10 @@ impl Material {
 
         for shader_info in &self.shaders {
             let shader = shader_info.shader.lock().unwrap();
+            if shader.shader_type == ShaderType::COMPUTE_SHADER {
+                // dispatched separately through ComputeProgram
+                continue;
+            }
             let stage = if shader.shader_type == ShaderType::FRAGMENT_SHADER { vk::ShaderStageFlags::FRAGMENT } else { vk::ShaderStageFlags::VERTEX };
 
             shader_stages.push(vk::PipelineShaderStageCreateInfo::default()
@@ -735,6 +866,14 @@ impl Material {
             );
         }
 
+        let num_storage_buffers = self.storage_buffers.len();
+        if num_storage_buffers > 0 {
+            pool_sizes.push(vk::DescriptorPoolSize::default()
+                .ty(vk::DescriptorType::STORAGE_BUFFER)
+                .descriptor_count((num_frames * num_storage_buffers) as u32)
+            );
+        }
+
         let num_textures = self.textures.len();
         if num_textures > 0 {
             pool_sizes.push(vk::DescriptorPoolSize::default()
@@ -791,6 +930,22 @@ impl Material {
             unsafe { device.obj.update_descriptor_sets(&[descriptor_write], &[]); }
         }
 
+        for storage_ref in &self.storage_buffers {
+            let storage = storage_ref.lock().unwrap();
+
+            let buffer_info = storage.get_buffer_info(frame_index);
+            let buffer_infos = &[buffer_info];
+
+            let descriptor_write = vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(storage.binding())
+                .dst_array_element(0)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .buffer_info(buffer_infos);
+
+            unsafe { device.obj.update_descriptor_sets(&[descriptor_write], &[]); }
+        }
+
         for texture_info in &self.textures {
             let binding = texture_info.binding();
             if binding == u32::MAX {
@@ -857,6 +1012,9 @@ impl Materials {
 
         for descriptor in descriptors {
             let material = Material::from_static(descriptor);
+            if let Err(e) = material.validate() {
+                return Err(Error::from(format!("material '{}': {}", descriptor.name, e.message())));
+            }
             materials.add_material(descriptor.name, material);
         }
 
@@ -902,3 +1060,20 @@ impl Materials {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topology() {
+        assert_eq!(Topology::from_string("line_strip"), Topology::LINE_STRIP);
+        assert_eq!(Topology::from_string("triangle_fan"), Topology::TRIANGLE_FAN);
+        assert_eq!(Topology::from_string("unknown"), Topology::TRIANGLE_LIST);
+
+        assert_eq!(Topology::to_primitive_topology(Topology::LINE_LIST, false).unwrap(), vk::PrimitiveTopology::LINE_LIST);
+        assert_eq!(Topology::to_primitive_topology(Topology::TRIANGLE_FAN, true).unwrap(), vk::PrimitiveTopology::TRIANGLE_FAN);
+        assert!(Topology::to_primitive_topology(Topology::TRIANGLE_FAN, false).is_err());
+        assert_eq!(Topology::to_primitive_topology(0x0, false).unwrap(), vk::PrimitiveTopology::TRIANGLE_LIST);
+    }
+}
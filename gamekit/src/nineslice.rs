@@ -0,0 +1,224 @@
+//!
+//! Nine-Slice
+//!
+
+use std::sync::{Arc, Mutex};
+
+use gamebuilder::manifest::StaticNineSliceDescriptor;
+
+use crate::{api::{Disposable, LockRef}, error::Error, math::{Vec2, Vec4}};
+
+#[derive(PartialEq, Default, Debug, Clone, Copy)]
+pub enum NineSliceMode {
+    #[default]
+    Stretch,
+    Tile
+}
+
+impl NineSliceMode {
+    pub fn from_string(mode: &str) -> NineSliceMode {
+        match mode {
+            "tile" => NineSliceMode::Tile,
+            _ => NineSliceMode::Stretch
+        }
+    }
+}
+
+/// Single quad of a nine-slice panel
+#[derive(Debug, Clone, PartialEq)]
+pub struct NineSlicePatch {
+    pub rect: Vec4,     // x, y, w, h (view coordinates)
+    pub texcoords: Vec4 // u, v, w, h (normalized)
+}
+
+/// Scalable panel from a texture region split by four insets.
+///
+/// Corners keep their size, edges and centre are stretched or tiled.
+/// If the panel is smaller than the corners, the corners shrink.
+#[derive(Debug, Clone)]
+pub struct NineSlice {
+    texture_size: Vec2,
+    rect: Vec4,     // texture region x, y, w, h (pixels)
+    insets: Vec4,   // left, top, right, bottom (pixels)
+    edge_mode: NineSliceMode,
+    center_mode: NineSliceMode
+}
+
+pub type NineSliceLockRef = LockRef<NineSlice>;
+
+impl Disposable for NineSlice {
+    fn dispose(&mut self) {
+    }
+}
+
+impl NineSlice {
+    pub fn new(texture_width: u32, texture_height: u32, rect: Vec4, insets: Vec4) -> Self {
+        Self {
+            texture_size: Vec2::new(texture_width.max(1) as f32, texture_height.max(1) as f32),
+            rect,
+            insets,
+            edge_mode: NineSliceMode::Stretch,
+            center_mode: NineSliceMode::Stretch
+        }
+    }
+
+    pub fn from_resource(descriptor: &StaticNineSliceDescriptor) -> Result<Self, Error> {
+        let resources = crate::globals::resources();
+        let texture_ref = resources.get_texture(descriptor.texture);
+
+        let (width, height) = {
+            let texture = texture_ref.lock().unwrap();
+            (texture.width, texture.height)
+        };
+
+        let w = if descriptor.w > 0 { descriptor.w } else { width.saturating_sub(descriptor.x) };
+        let h = if descriptor.h > 0 { descriptor.h } else { height.saturating_sub(descriptor.y) };
+
+        if descriptor.left + descriptor.right > w || descriptor.top + descriptor.bottom > h {
+            return Err(Error::from(format!("nine-slice \"{}\" insets exceed region", descriptor.name)));
+        }
+
+        let mut nine_slice = Self::new(width, height,
+            Vec4::new(descriptor.x as f32, descriptor.y as f32, w as f32, h as f32),
+            Vec4::new(descriptor.left as f32, descriptor.top as f32, descriptor.right as f32, descriptor.bottom as f32)
+        );
+
+        nine_slice.set_edge_mode(NineSliceMode::from_string(descriptor.edge_mode));
+        nine_slice.set_center_mode(NineSliceMode::from_string(descriptor.center_mode));
+
+        Ok(nine_slice)
+    }
+
+    pub fn to_lockref(nine_slice: Self) -> NineSliceLockRef {
+        Arc::new(Mutex::new(nine_slice))
+    }
+
+    pub fn set_edge_mode(&mut self, mode: NineSliceMode) -> &mut Self {
+        self.edge_mode = mode;
+        self
+    }
+
+    pub fn set_center_mode(&mut self, mode: NineSliceMode) -> &mut Self {
+        self.center_mode = mode;
+        self
+    }
+
+    pub fn edge_mode(&self) -> NineSliceMode {
+        self.edge_mode
+    }
+
+    pub fn center_mode(&self) -> NineSliceMode {
+        self.center_mode
+    }
+
+    pub fn rect(&self) -> &Vec4 {
+        &self.rect
+    }
+
+    pub fn insets(&self) -> &Vec4 {
+        &self.insets
+    }
+
+    /// Split a span into pieces (start, length, fraction of source)
+    fn split(start: f32, length: f32, source_length: f32, mode: NineSliceMode) -> Vec<(f32, f32, f32)> {
+
+        if length <= 0.0 {
+            return Vec::new();
+        }
+
+        if mode == NineSliceMode::Stretch || source_length <= 0.0 {
+            return vec![(start, length, 1.0)];
+        }
+
+        let mut pieces = Vec::new();
+        let mut pos = 0.0;
+        while pos < length {
+            let piece = source_length.min(length - pos);
+            pieces.push((start + pos, piece, piece / source_length));
+            pos += source_length;
+        }
+
+        pieces
+    }
+
+    /// Corner sizes, scaled down if the target is too small
+    fn fit(size: f32, a: f32, b: f32) -> (f32, f32) {
+        if a + b > size && a + b > 0.0 {
+            let scale = size.max(0.0) / (a + b);
+            (a * scale, b * scale)
+        } else {
+            (a, b)
+        }
+    }
+
+    /// Quads for a panel at the given view rectangle
+    pub fn patches(&self, rect: &Vec4) -> Vec<NineSlicePatch> {
+
+        let (left, right) = Self::fit(rect.z, self.insets.x, self.insets.z);
+        let (top, bottom) = Self::fit(rect.w, self.insets.y, self.insets.w);
+
+        let src_x = [self.rect.x, self.rect.x + self.insets.x, self.rect.x + self.rect.z - self.insets.z];
+        let src_w = [self.insets.x, self.rect.z - self.insets.x - self.insets.z, self.insets.z];
+        let src_y = [self.rect.y, self.rect.y + self.insets.y, self.rect.y + self.rect.w - self.insets.w];
+        let src_h = [self.insets.y, self.rect.w - self.insets.y - self.insets.w, self.insets.w];
+
+        let dst_x = [rect.x, rect.x + left, rect.x + rect.z - right];
+        let dst_w = [left, rect.z - left - right, right];
+        let dst_y = [rect.y, rect.y + top, rect.y + rect.w - bottom];
+        let dst_h = [top, rect.w - top - bottom, bottom];
+
+        let tw = self.texture_size.x;
+        let th = self.texture_size.y;
+
+        let mut patches = Vec::new();
+
+        for row in 0..3 {
+            for col in 0..3 {
+
+                let h_mode = if col != 1 { NineSliceMode::Stretch } else if row == 1 { self.center_mode } else { self.edge_mode };
+                let v_mode = if row != 1 { NineSliceMode::Stretch } else if col == 1 { self.center_mode } else { self.edge_mode };
+
+                for (y, h, fy) in Self::split(dst_y[row], dst_h[row], src_h[row], v_mode) {
+                    for (x, w, fx) in Self::split(dst_x[col], dst_w[col], src_w[col], h_mode) {
+                        patches.push(NineSlicePatch {
+                            rect: Vec4::new(x, y, w, h),
+                            texcoords: Vec4::new(
+                                src_x[col] / tw, src_y[row] / th,
+                                src_w[col] * fx / tw, src_h[row] * fy / th
+                            )
+                        });
+                    }
+                }
+            }
+        }
+
+        patches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patches() {
+        let mut nine_slice = NineSlice::new(64, 64, Vec4::new(0.0, 0.0, 24.0, 24.0), Vec4::new(8.0, 8.0, 8.0, 8.0));
+
+        let patches = nine_slice.patches(&Vec4::new(10.0, 10.0, 100.0, 50.0));
+        assert_eq!(patches.len(), 9);
+        assert_eq!(patches[4].rect, Vec4::new(18.0, 18.0, 84.0, 34.0));
+        assert_eq!(patches[8].texcoords, Vec4::new(0.25, 0.25, 0.125, 0.125));
+
+        // 20 pixels of edge with 8 pixel source: 8 + 8 + 4
+        nine_slice.set_edge_mode(NineSliceMode::Tile);
+        let patches = nine_slice.patches(&Vec4::new(0.0, 0.0, 36.0, 16.0));
+        assert_eq!(patches.len(), 5 + 5);
+        assert_eq!(patches[3].rect, Vec4::new(24.0, 0.0, 4.0, 8.0));
+        assert_eq!(patches[3].texcoords.z, 4.0 / 64.0);
+
+        // corners shrink when the panel is too small
+        let patches = nine_slice.patches(&Vec4::new(0.0, 0.0, 8.0, 8.0));
+        assert_eq!(patches.len(), 4);
+        assert_eq!(patches[0].rect, Vec4::new(0.0, 0.0, 4.0, 4.0));
+    }
+}
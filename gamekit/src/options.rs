@@ -43,7 +43,8 @@ pub struct Options {
     pub queue_size: usize,
     pub headless: bool,
     pub enable_validation_layer: bool,
-    pub enable_api_dump_layer: bool
+    pub enable_api_dump_layer: bool,
+    pub deferred_render_pass: bool    // start the render pass after update, for compute and offscreen passes
 }
 
 impl Default for Options {
@@ -62,7 +63,8 @@ impl Default for Options {
             queue_size: Constants::DEFAULT_BLITTER_BATCH_CAPACITY,
             headless: false,
             enable_validation_layer: Constants::ENABLE_VALIDATION_LAYER,
-            enable_api_dump_layer: Constants::ENABLE_API_DUMP_LAYER
+            enable_api_dump_layer: Constants::ENABLE_API_DUMP_LAYER,
+            deferred_render_pass: false
         }
     }
 }
@@ -87,7 +89,8 @@ impl Options {
             queue_size: if descriptor.queue_size > 0 { descriptor.queue_size } else { Constants::DEFAULT_BLITTER_BATCH_CAPACITY },
             headless: descriptor.headless,
             enable_validation_layer: descriptor.enable_validation_layer,
-            enable_api_dump_layer: descriptor.enable_api_dump_layer
+            enable_api_dump_layer: descriptor.enable_api_dump_layer,
+            deferred_render_pass: descriptor.deferred_render_pass
         }
     }
 
@@ -134,4 +137,48 @@ impl Options {
         self
     }
 
+    /// Start the render pass after update instead of in begin_frame, so
+    /// compute dispatches and render targets can be recorded during update
+    pub fn set_deferred_render_pass(&mut self, deferred_render_pass: bool) -> &mut Self {
+        self.deferred_render_pass = deferred_render_pass;
+        self
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deferred_render_pass() {
+        // the render pass starts in begin_frame unless deferred
+        assert!(!Options::default().deferred_render_pass);
+
+        static DESCRIPTOR: StaticOptionsDescriptor = StaticOptionsDescriptor {
+            title: "test",
+            window_x: 0,
+            window_y: 0,
+            window_width: 320,
+            window_height: 200,
+            view_width: 0,
+            view_height: 0,
+            scaling_mode: "zoom",
+            fps: 60,
+            show_statistics: false,
+            queue_size: 0,
+            headless: true,
+            enable_validation_layer: false,
+            enable_api_dump_layer: false,
+            deferred_render_pass: true
+        };
+
+        let mut options = Options::from_static(&DESCRIPTOR);
+        assert!(options.deferred_render_pass);
+        assert_eq!(options.scaling_mode, ScalingMode::ZOOM);
+        assert_eq!(options.queue_size, Constants::DEFAULT_BLITTER_BATCH_CAPACITY);
+
+        options.set_deferred_render_pass(false);
+        assert!(!options.deferred_render_pass);
+    }
 }
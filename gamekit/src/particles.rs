@@ -0,0 +1,583 @@
+//!
+//! Particles
+//!
+
+use std::{collections::HashMap, sync::{Arc, Mutex}};
+
+use cgmath::InnerSpace;
+use gamebuilder::manifest::StaticParticleEffectDescriptor;
+
+use crate::{api::{Disposable, LockRef}, error::Error, math::{Vec2, Vec4}, primitives::{Color, QuadQueue}, random::Random, task::TaskTime, tween::Tweenable};
+
+const DEFAULT_SIZE: f32 = 8.0;
+
+/// Area new particles are spawned in, relative to the emitter position
+#[derive(PartialEq, Default, Debug, Clone, Copy)]
+pub enum EmitterShape {
+    #[default]
+    Point,
+    Line(f32, f32),     // segment from -(dx, dy)/2 to (dx, dy)/2
+    Circle(f32),        // radius
+    Rect(f32, f32)      // width, height (centered)
+}
+
+impl EmitterShape {
+    pub fn from_string(shape: &str, width: f32, height: f32, radius: f32) -> EmitterShape {
+        match shape {
+            "line" => EmitterShape::Line(width, height),
+            "circle" => EmitterShape::Circle(radius),
+            "rect" | "rectangle" => EmitterShape::Rect(width, height),
+            _ => EmitterShape::Point
+        }
+    }
+
+    /// Random spawn offset within the shape
+    pub fn sample(&self) -> Vec2 {
+        match *self {
+            EmitterShape::Point => Vec2::new(0.0, 0.0),
+            EmitterShape::Line(dx, dy) => {
+                let t = Random::get_float() - 0.5;
+                Vec2::new(dx * t, dy * t)
+            },
+            EmitterShape::Circle(radius) => {
+                // uniform distribution over the disc area
+                let angle = Random::get_float_range(0.0, 2.0 * std::f32::consts::PI);
+                let r = radius * Random::get_float().sqrt();
+                Vec2::new(angle.cos() * r, angle.sin() * r)
+            },
+            EmitterShape::Rect(width, height) => {
+                Vec2::new(width * (Random::get_float() - 0.5), height * (Random::get_float() - 0.5))
+            }
+        }
+    }
+}
+
+/// Value over the normalized particle lifetime (0..1), linearly
+/// interpolated between keys
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParticleCurve<T: Tweenable> {
+    keys: Vec<(f32, T)>
+}
+
+impl<T: Tweenable> ParticleCurve<T> {
+    /// Constant curve
+    pub fn new(value: T) -> Self {
+        Self { keys: vec![(0.0, value)] }
+    }
+
+    /// Keys evenly spaced over the lifetime, constant default if empty
+    pub fn from_values(values: &[T], default: T) -> Self {
+        if values.is_empty() {
+            return Self::new(default);
+        }
+
+        let step = if values.len() > 1 { 1.0 / (values.len() - 1) as f32 } else { 0.0 };
+        let keys = values.iter().enumerate().map(|(i, v)| (i as f32 * step, v.clone())).collect();
+        Self { keys }
+    }
+
+    /// Add key at time (0..1), replacing a key at the same time
+    pub fn add_key(&mut self, time: f32, value: T) -> &mut Self {
+        let time = time.clamp(0.0, 1.0);
+        match self.keys.iter().position(|(t, _)| *t >= time) {
+            Some(index) if self.keys[index].0 == time => self.keys[index].1 = value,
+            Some(index) => self.keys.insert(index, (time, value)),
+            None => self.keys.push((time, value))
+        }
+        self
+    }
+
+    pub fn sample(&self, time: f32) -> T {
+        let index = self.keys.iter().position(|(t, _)| *t > time).unwrap_or(self.keys.len());
+
+        if index == 0 {
+            return self.keys[0].1.clone();
+        }
+
+        if index == self.keys.len() {
+            return self.keys[index - 1].1.clone();
+        }
+
+        let (t0, v0) = &self.keys[index - 1];
+        let (t1, v1) = &self.keys[index];
+        v0.lerp(v1, (time - t0) / (t1 - t0))
+    }
+}
+
+/// Emitter settings, defined in code or loaded from the manifest.
+///
+/// Emitters spawn `burst` particles when started (and on every loop)
+/// plus `rate` particles per second. A duration of zero emits until
+/// stopped, or only once if there is no continuous rate.
+#[derive(Debug, Clone)]
+pub struct ParticleEffect {
+    pub shape: EmitterShape,
+    pub rate: f32,              // particles per second
+    pub burst: u32,
+    pub duration: f32,          // seconds, 0 = infinite
+    pub looping: bool,
+    pub lifetime: Vec2,         // min, max (seconds)
+    pub speed: Vec2,            // min, max (pixels per second)
+    pub direction: f32,         // degrees, 0 = right, 90 = down
+    pub spread: f32,            // degrees, full cone angle
+    pub gravity: Vec2,          // pixels per second squared
+    pub drag: f32,              // velocity loss per second (0..1)
+    pub colors: ParticleCurve<Color>,
+    pub sizes: ParticleCurve<f32>,
+    pub velocities: ParticleCurve<f32>, // speed factor
+    pub texcoords: Vec4,        // u, v, w, h (normalized)
+    pub texture_mask: u32,
+    pub flags: u32
+}
+
+pub type ParticleEffectLockRef = LockRef<ParticleEffect>;
+
+impl Disposable for ParticleEffect {
+    fn dispose(&mut self) {
+    }
+}
+
+impl Default for ParticleEffect {
+    fn default() -> Self {
+        Self {
+            shape: EmitterShape::Point,
+            rate: 0.0,
+            burst: 0,
+            duration: 0.0,
+            looping: false,
+            lifetime: Vec2::new(1.0, 1.0),
+            speed: Vec2::new(0.0, 0.0),
+            direction: 0.0,
+            spread: 360.0,
+            gravity: Vec2::new(0.0, 0.0),
+            drag: 0.0,
+            colors: ParticleCurve::new(Color::white()),
+            sizes: ParticleCurve::new(DEFAULT_SIZE),
+            velocities: ParticleCurve::new(1.0),
+            texcoords: Vec4::new(0.0, 0.0, 1.0, 1.0),
+            texture_mask: 0x1,
+            flags: 0x0
+        }
+    }
+}
+
+impl ParticleEffect {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_resource(descriptor: &StaticParticleEffectDescriptor) -> Result<Self, Error> {
+
+        let mut colors = Vec::with_capacity(descriptor.colors.len());
+        for color in descriptor.colors {
+            match Color::parse(color) {
+                Some(color) => colors.push(color),
+                None => {
+                    return Err(Error::from(format!("particle effect \"{}\" has invalid color \"{}\"", descriptor.name, color)));
+                }
+            }
+        }
+
+        let lifetime_max = if descriptor.lifetime_max > 0.0 { descriptor.lifetime_max } else { descriptor.lifetime_min };
+        let speed_max = if descriptor.speed_max > 0.0 { descriptor.speed_max } else { descriptor.speed_min };
+        let texture_w = if descriptor.texture_w > 0.0 { descriptor.texture_w } else { 1.0 };
+        let texture_h = if descriptor.texture_h > 0.0 { descriptor.texture_h } else { 1.0 };
+
+        Ok(Self {
+            shape: EmitterShape::from_string(descriptor.shape, descriptor.width, descriptor.height, descriptor.radius),
+            rate: descriptor.rate,
+            burst: descriptor.burst,
+            duration: descriptor.duration,
+            looping: descriptor.looping,
+            lifetime: Vec2::new(descriptor.lifetime_min, lifetime_max),
+            speed: Vec2::new(descriptor.speed_min, speed_max),
+            direction: descriptor.direction,
+            spread: descriptor.spread,
+            gravity: Vec2::new(descriptor.gravity_x, descriptor.gravity_y),
+            drag: descriptor.drag,
+            colors: ParticleCurve::from_values(&colors, Color::white()),
+            sizes: ParticleCurve::from_values(descriptor.sizes, DEFAULT_SIZE),
+            velocities: ParticleCurve::from_values(descriptor.velocities, 1.0),
+            texcoords: Vec4::new(descriptor.texture_x, descriptor.texture_y, texture_w, texture_h),
+            texture_mask: descriptor.texture_mask,
+            flags: descriptor.flags
+        })
+    }
+
+    pub fn to_lockref(effect: Self) -> ParticleEffectLockRef {
+        ParticleEffectLockRef::new(Mutex::new(effect))
+    }
+}
+
+/// Point force acting on all particles. Negative strength repels,
+/// a radius of zero means unlimited range.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParticleAttractor {
+    pub position: Vec2,
+    pub strength: f32,  // pixels per second squared
+    pub radius: f32
+}
+
+impl ParticleAttractor {
+    pub fn new(x: f32, y: f32, strength: f32, radius: f32) -> Self {
+        Self { position: Vec2::new(x, y), strength, radius }
+    }
+
+    fn acceleration(&self, position: Vec2) -> Vec2 {
+        let delta = self.position - position;
+        let distance = delta.magnitude();
+
+        if distance < 1.0 || (self.radius > 0.0 && distance >= self.radius) {
+            return Vec2::new(0.0, 0.0);
+        }
+
+        let falloff = if self.radius > 0.0 { 1.0 - distance / self.radius } else { 1.0 };
+        delta * (self.strength * falloff / distance)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Particle {
+    effect: Arc<ParticleEffect>,
+    position: Vec2,
+    velocity: Vec2,
+    age: f32,
+    lifetime: f32
+}
+
+impl Particle {
+    fn progress(&self) -> f32 {
+        (self.age / self.lifetime).clamp(0.0, 1.0)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ParticleEmitter {
+    id: u32,
+    effect: Arc<ParticleEffect>,
+    position: Vec2,
+    elapsed: f32,
+    accumulator: f32,
+    started: bool,
+    active: bool
+}
+
+/// Particle and emitter pool without the render queue
+#[derive(Debug, Default)]
+struct ParticlePool {
+    capacity: usize,
+    particles: Vec<Particle>,
+    emitters: Vec<ParticleEmitter>,
+    attractors: Vec<ParticleAttractor>,
+    next_id: u32
+}
+
+impl ParticlePool {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            particles: Vec::with_capacity(capacity),
+            next_id: 1,
+            ..Default::default()
+        }
+    }
+
+    fn play(&mut self, effect: Arc<ParticleEffect>, position: Vec2) -> u32 {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1).max(1);
+
+        self.emitters.push(ParticleEmitter {
+            id,
+            effect,
+            position,
+            elapsed: 0.0,
+            accumulator: 0.0,
+            started: false,
+            active: true
+        });
+
+        id
+    }
+
+    /// Spawn particles, dropped if the pool is full
+    fn emit(&mut self, effect: &Arc<ParticleEffect>, position: Vec2, count: usize) {
+        let count = count.min(self.capacity - self.particles.len());
+
+        for _ in 0..count {
+            let angle = (effect.direction + Random::get_float_range(-0.5, 0.5) * effect.spread).to_radians();
+            let speed = Random::get_float_range(effect.speed.x, effect.speed.y);
+            let lifetime = Random::get_float_range(effect.lifetime.x, effect.lifetime.y);
+
+            self.particles.push(Particle {
+                effect: effect.clone(),
+                position: position + effect.shape.sample(),
+                velocity: Vec2::new(angle.cos() * speed, angle.sin() * speed),
+                age: 0.0,
+                lifetime: lifetime.max(f32::EPSILON)
+            });
+        }
+    }
+
+    fn update(&mut self, step: f32) {
+
+        let mut emitters = std::mem::take(&mut self.emitters);
+
+        for emitter in emitters.iter_mut() {
+            let effect = emitter.effect.clone();
+
+            if !emitter.started {
+                emitter.started = true;
+                self.emit(&effect, emitter.position, effect.burst as usize);
+            }
+
+            if !emitter.active {
+                continue;
+            }
+
+            emitter.elapsed += step;
+
+            if effect.rate > 0.0 {
+                emitter.accumulator += effect.rate * step;
+                let count = emitter.accumulator.floor();
+                emitter.accumulator -= count;
+                self.emit(&effect, emitter.position, count as usize);
+            }
+
+            if effect.duration > 0.0 {
+                if emitter.elapsed >= effect.duration {
+                    if effect.looping {
+                        emitter.elapsed -= effect.duration;
+                        self.emit(&effect, emitter.position, effect.burst as usize);
+                    } else {
+                        emitter.active = false;
+                    }
+                }
+            } else if effect.rate <= 0.0 {
+                // single burst
+                emitter.active = false;
+            }
+        }
+
+        emitters.retain(|emitter| emitter.active);
+        emitters.append(&mut self.emitters);
+        self.emitters = emitters;
+
+        let attractors = &self.attractors;
+
+        self.particles.retain_mut(|particle| {
+            particle.age += step;
+            if particle.age >= particle.lifetime {
+                return false;
+            }
+
+            let effect = &particle.effect;
+
+            let mut acceleration = effect.gravity;
+            for attractor in attractors {
+                acceleration += attractor.acceleration(particle.position);
+            }
+
+            particle.velocity += acceleration * step;
+            particle.velocity *= (1.0 - effect.drag * step).max(0.0);
+
+            let factor = effect.velocities.sample(particle.progress());
+            particle.position += particle.velocity * (factor * step);
+
+            true
+        });
+    }
+
+    fn stop(&mut self, id: u32) {
+        for emitter in self.emitters.iter_mut().filter(|emitter| emitter.id == id) {
+            emitter.started = true;
+            emitter.active = false;
+        }
+    }
+}
+
+/// Pooled particles from any number of emitters, rendered as a single
+/// quad queue with the current material.
+///
+/// Emitters are started with `play` and stop by themselves once their
+/// duration is over, particles live on until their lifetime ends.
+pub struct ParticleSystem {
+    pool: ParticlePool,
+    effects: HashMap<String, Arc<ParticleEffect>>,
+    queue: QuadQueue
+}
+
+impl Disposable for ParticleSystem {
+    fn dispose(&mut self) {
+        self.queue.dispose();
+    }
+}
+
+impl ParticleSystem {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            pool: ParticlePool::new(capacity),
+            effects: HashMap::new(),
+            queue: QuadQueue::new(capacity)
+        }
+    }
+
+    /// Start emitter with a particle effect from the manifest, returns the emitter id
+    pub fn play(&mut self, name: &str, x: f32, y: f32) -> u32 {
+        let effect = self.get_effect(name);
+        self.pool.play(effect, Vec2::new(x, y))
+    }
+
+    /// Start emitter with a particle effect defined in code, returns the emitter id
+    pub fn play_effect(&mut self, effect: &ParticleEffect, x: f32, y: f32) -> u32 {
+        self.pool.play(Arc::new(effect.clone()), Vec2::new(x, y))
+    }
+
+    /// Spawn particles of a manifest effect without an emitter
+    pub fn burst(&mut self, name: &str, x: f32, y: f32, count: usize) {
+        let effect = self.get_effect(name);
+        self.pool.emit(&effect, Vec2::new(x, y), count);
+    }
+
+    /// Stop emitting, existing particles are kept
+    pub fn stop(&mut self, id: u32) {
+        self.pool.stop(id);
+    }
+
+    /// Stop all emitters and remove all particles
+    pub fn clear(&mut self) {
+        self.pool.emitters.clear();
+        self.pool.particles.clear();
+    }
+
+    pub fn is_playing(&self, id: u32) -> bool {
+        self.pool.emitters.iter().any(|emitter| emitter.id == id && emitter.active)
+    }
+
+    /// Move emitter, particles already spawned are not affected
+    pub fn set_position(&mut self, id: u32, x: f32, y: f32) {
+        for emitter in self.pool.emitters.iter_mut().filter(|emitter| emitter.id == id) {
+            emitter.position = Vec2::new(x, y);
+        }
+    }
+
+    /// Add attractor, returns its index
+    pub fn add_attractor(&mut self, attractor: ParticleAttractor) -> usize {
+        self.pool.attractors.push(attractor);
+        self.pool.attractors.len() - 1
+    }
+
+    pub fn attractor_mut(&mut self, index: usize) -> Option<&mut ParticleAttractor> {
+        self.pool.attractors.get_mut(index)
+    }
+
+    pub fn clear_attractors(&mut self) {
+        self.pool.attractors.clear();
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.pool.capacity
+    }
+
+    pub fn count(&self) -> usize {
+        self.pool.particles.len()
+    }
+
+    /// Simulate and write the particle quads into the queue
+    pub fn update(&mut self, step: f32) {
+        self.pool.update(step);
+
+        let queue = &mut self.queue;
+        queue.begin();
+        for particle in &self.pool.particles {
+            let effect = &particle.effect;
+            let t = particle.progress();
+            let size = effect.sizes.sample(t);
+            let color = effect.colors.sample(t);
+            let texcoords = &effect.texcoords;
+
+            queue.push(
+                particle.position.x - size * 0.5, particle.position.y - size * 0.5,
+                size, size,
+                color.r, color.g, color.b, color.a,
+                texcoords.x, texcoords.y, texcoords.z, texcoords.w,
+                effect.texture_mask, effect.flags
+            );
+        }
+        queue.end();
+    }
+
+    /// Update by the task time step
+    pub fn step(&mut self, time: &TaskTime) {
+        self.update(time.step);
+    }
+
+    /// Draw with the current material
+    pub fn draw(&mut self) {
+        self.queue.draw();
+    }
+
+    fn get_effect(&mut self, name: &str) -> Arc<ParticleEffect> {
+        self.effects.entry(name.to_string()).or_insert_with(|| {
+            let resources = crate::globals::resources();
+            let effect_ref = resources.get_particle_effect(name);
+            let effect = effect_ref.lock().unwrap();
+            Arc::new(effect.clone())
+        }).clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simulation() {
+
+        let mut curve = ParticleCurve::from_values(&[0.0, 10.0, 0.0], 1.0);
+        assert_eq!(curve.sample(0.25), 5.0);
+        assert_eq!(curve.sample(0.5), 10.0);
+        assert_eq!(curve.sample(1.5), 0.0);
+        curve.add_key(0.75, 20.0);
+        assert_eq!(curve.sample(0.625), 15.0);
+        assert_eq!(ParticleCurve::from_values(&[], 3.0).sample(0.5), 3.0);
+
+        // burst limited by pool capacity, removed after lifetime
+        let effect = Arc::new(ParticleEffect { burst: 20, ..Default::default() });
+        let mut pool = ParticlePool::new(16);
+        pool.play(effect, Vec2::new(0.0, 0.0));
+        pool.update(0.5);
+        assert_eq!(pool.particles.len(), 16);
+        assert!(pool.emitters.is_empty());
+        pool.update(0.6);
+        assert!(pool.particles.is_empty());
+
+        // continuous emission over the duration
+        let effect = Arc::new(ParticleEffect { rate: 10.0, duration: 1.0, lifetime: Vec2::new(5.0, 5.0), ..Default::default() });
+        let mut pool = ParticlePool::new(100);
+        let id = pool.play(effect, Vec2::new(0.0, 0.0));
+        for _ in 0..8 {
+            pool.update(0.25);
+        }
+        assert_eq!(pool.particles.len(), 10);
+        assert!(!pool.emitters.iter().any(|emitter| emitter.id == id));
+
+        // gravity, drag and attractor
+        let effect = Arc::new(ParticleEffect {
+            burst: 1,
+            shape: EmitterShape::Point,
+            gravity: Vec2::new(0.0, 10.0),
+            drag: 0.5,
+            lifetime: Vec2::new(10.0, 10.0),
+            ..Default::default()
+        });
+        let mut pool = ParticlePool::new(1);
+        pool.play(effect, Vec2::new(0.0, 0.0));
+        pool.update(1.0);
+        assert_eq!(pool.particles[0].velocity, Vec2::new(0.0, 5.0));
+        assert_eq!(pool.particles[0].position, Vec2::new(0.0, 5.0));
+
+        pool.attractors.push(ParticleAttractor::new(0.0, 105.0, 20.0, 200.0));
+        pool.update(1.0);
+        assert_eq!(pool.particles[0].velocity, Vec2::new(0.0, 12.5));
+    }
+}
@@ -51,7 +51,10 @@ pub struct Pipeline {
     pub frame_index: usize,
 
     image_index: u32,
-    need_reinit: bool
+    need_reinit: bool,
+    render_pass_active: bool,
+    offscreen_pass_active: bool,
+    compute_pending: bool
 }
 
 impl Disposable for Pipeline {
@@ -91,7 +94,10 @@ impl Pipeline {
             frame_count,
             frame_index: 0,
             image_index: 0,
-            need_reinit: false
+            need_reinit: false,
+            render_pass_active: false,
+            offscreen_pass_active: false,
+            compute_pending: false
         })
 
     }
@@ -124,6 +130,9 @@ impl Pipeline {
         self.frame_index = 0;
         self.image_index = 0;
         self.need_reinit = false;
+        self.render_pass_active = false;
+        self.offscreen_pass_active = false;
+        self.compute_pending = false;
 
         Ok(())
     }
@@ -229,16 +238,31 @@ impl Pipeline {
     }
 
     fn create_render_pass(device_context: &Device, swapchain_info: &SwapChain, depth_buffer_format: vk::Format) -> Result<RenderPassInfo, Error> {
+        let render_pass = Self::create_render_pass_ex(&device_context.obj, swapchain_info.format.format, depth_buffer_format, vk::ImageLayout::PRESENT_SRC_KHR)?;
+
+        Ok(RenderPassInfo {
+            render_pass
+        })
+    }
+
+    /// Render pass compatible with the main render pass, only the final
+    /// color layout differs (e.g. for sampling offscreen targets)
+    pub(crate) fn create_compatible_render_pass(&self, color_final_layout: vk::ImageLayout) -> Result<vk::RenderPass, Error> {
+        let device = crate::globals::device();
+        Self::create_render_pass_ex(&device.obj, self.swapchain.format.format, self.depth_image.format, color_final_layout)
+    }
+
+    fn create_render_pass_ex(device: &ash::Device, color_format: vk::Format, depth_buffer_format: vk::Format, color_final_layout: vk::ImageLayout) -> Result<vk::RenderPass, Error> {
 
         let color_attachment = vk::AttachmentDescription::default()
-            .format(swapchain_info.format.format)
+            .format(color_format)
             .samples(vk::SampleCountFlags::TYPE_1)
             .load_op(vk::AttachmentLoadOp::CLEAR)
             .store_op(vk::AttachmentStoreOp::STORE)
             .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
             .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
             .initial_layout(vk::ImageLayout::UNDEFINED)
-            .final_layout(vk::ImageLayout::PRESENT_SRC_KHR);
+            .final_layout(color_final_layout);
 
 
         let depth_attachment = vk::AttachmentDescription::default()
@@ -272,11 +296,14 @@ impl Pipeline {
 
         let subpasses = vec![subpass];
 
+        // offscreen targets are shared by all frames in flight, the pass
+        // waits for the previous frame sampling the target. The same
+        // dependency is used for all passes to keep them identical.
         let dependency = vk::SubpassDependency::default()
             .src_subpass(vk::SUBPASS_EXTERNAL)
             .dst_subpass(0u32)
-            .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS)
-            .src_access_mask(vk::AccessFlags::NONE)
+            .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS | vk::PipelineStageFlags::FRAGMENT_SHADER)
+            .src_access_mask(vk::AccessFlags::SHADER_READ)
             .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS)
             .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE);
 
@@ -287,11 +314,12 @@ impl Pipeline {
             .subpasses(&subpasses)
             .dependencies(&dependencies);
 
-        let render_pass = unsafe { device_context.obj.create_render_pass(&render_pass_create_info, None).unwrap() };
+        let render_pass = unsafe { match device.create_render_pass(&render_pass_create_info, None) {
+            Ok(obj) => obj,
+            Err(_) => { return Err(Error::from("failed to create render pass")); }
+        } };
 
-        Ok(RenderPassInfo {
-            render_pass
-        })
+        Ok(render_pass)
     }
 
     fn destroy_render_pass(&mut self) {
@@ -389,7 +417,6 @@ impl Pipeline {
             reinitialized = true;
         }
 
-        let swapchain =  &self.swapchain;
         let frame = self.current_frame();
 
         let mut needs_reinit = false;
@@ -425,6 +452,42 @@ impl Pipeline {
             return Err(Error::from("pipeline needs to be reinitialized"))
         }
 
+        let command_buffer = &frame.command_buffer;
+        command_buffer.reset();
+        command_buffer.begin();
+
+        self.image_index = image_index;
+        self.render_pass_active = false;
+        self.offscreen_pass_active = false;
+        self.compute_pending = false;
+
+        // deferred, the render pass starts after update or with the first draw
+        if !crate::globals::options().deferred_render_pass {
+            self.begin_render_pass();
+        }
+
+        Ok(reinitialized)
+
+    }
+
+    /// Start the render pass of the current frame, called by begin_frame
+    /// unless the render pass is deferred. Compute dispatches and offscreen
+    /// passes have to be recorded before, their writes are made visible to
+    /// the vertex input and shader stages here.
+    pub fn begin_render_pass(&mut self) {
+
+        if self.render_pass_active {
+            return;
+        }
+
+        self.end_offscreen_pass();
+        self.flush_compute();
+
+        let swapchain = &self.swapchain;
+        let frame = self.current_frame();
+        let device = crate::globals::device();
+        let command_buffer = &frame.command_buffer;
+
         let clear_values = [
             vk::ClearValue { color: vk::ClearColorValue { float32: [0.0, 0.0, 0.0, 1.0] } },
             vk::ClearValue { depth_stencil: vk::ClearDepthStencilValue { depth: 1.0, stencil: 0 } },
@@ -432,7 +495,7 @@ impl Pipeline {
 
         let render_pass_info = vk::RenderPassBeginInfo::default()
             .render_pass(self.render_pass)
-            .framebuffer(self.frame_buffers[image_index as usize].obj)
+            .framebuffer(self.frame_buffers[self.image_index as usize].obj)
             .render_area(vk::Rect2D {
                 offset: vk::Offset2D{x:0,y:0},
                 extent: vk::Extent2D{
@@ -443,24 +506,130 @@ impl Pipeline {
             .clear_values(&clear_values);
 
         unsafe {
+            device.obj.cmd_begin_render_pass(
+                command_buffer.obj,
+                &render_pass_info,
+                vk::SubpassContents::INLINE);
+        };
 
-            let device = crate::globals::device();
+        self.render_pass_active = true;
+    }
+
+    /// Start an offscreen render pass, has to be recorded before the
+    /// render pass of the current frame
+    pub(crate) fn begin_offscreen_pass(&mut self, render_pass: vk::RenderPass, framebuffer: vk::Framebuffer, extent: vk::Extent2D, clear_values: &[vk::ClearValue]) -> Result<(), Error> {
 
-            let command_buffer = &frame.command_buffer;
-            command_buffer.reset();
-            command_buffer.begin();
+        if self.render_pass_active {
+            return Err(Error::from("offscreen pass inside render pass, enable the deferred_render_pass option and draw render targets during update"));
+        }
+
+        if self.offscreen_pass_active {
+            return Err(Error::from("offscreen pass already active"));
+        }
 
+        self.flush_compute();
+
+        let frame = self.current_frame();
+        let device = crate::globals::device();
+
+        let render_pass_info = vk::RenderPassBeginInfo::default()
+            .render_pass(render_pass)
+            .framebuffer(framebuffer)
+            .render_area(vk::Rect2D {
+                offset: vk::Offset2D{x:0,y:0},
+                extent
+            })
+            .clear_values(clear_values);
+
+        unsafe {
             device.obj.cmd_begin_render_pass(
-                command_buffer.obj,
+                frame.command_buffer.obj,
                 &render_pass_info,
                 vk::SubpassContents::INLINE);
-
         };
 
-        self.image_index = image_index;
+        self.offscreen_pass_active = true;
 
-        Ok(reinitialized)
+        Ok(())
+    }
+
+    /// End offscreen render pass, its color output can be sampled by
+    /// subsequent fragment shaders
+    pub(crate) fn end_offscreen_pass(&mut self) {
 
+        if !self.offscreen_pass_active {
+            return;
+        }
+
+        let frame = self.current_frame();
+        let device = crate::globals::device();
+        let command_buffer = &frame.command_buffer;
+
+        let memory_barriers = [
+            vk::MemoryBarrier::default()
+                .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .dst_access_mask(vk::AccessFlags::SHADER_READ)
+        ];
+
+        unsafe {
+            device.obj.cmd_end_render_pass(command_buffer.obj);
+
+            device.obj.cmd_pipeline_barrier(
+                command_buffer.obj,
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                &memory_barriers,
+                &[],
+                &[]
+            );
+        }
+
+        self.offscreen_pass_active = false;
+    }
+
+    pub fn is_offscreen_pass_active(&self) -> bool {
+        self.offscreen_pass_active
+    }
+
+    /// Make pending compute writes visible to subsequent draw calls
+    fn flush_compute(&mut self) {
+
+        if !self.compute_pending {
+            return;
+        }
+
+        let frame = self.current_frame();
+        let device = crate::globals::device();
+
+        let memory_barriers = [
+            vk::MemoryBarrier::default()
+                .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+                .dst_access_mask(vk::AccessFlags::VERTEX_ATTRIBUTE_READ | vk::AccessFlags::SHADER_READ | vk::AccessFlags::INDIRECT_COMMAND_READ)
+        ];
+
+        unsafe {
+            device.obj.cmd_pipeline_barrier(
+                frame.command_buffer.obj,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::DRAW_INDIRECT | vk::PipelineStageFlags::VERTEX_INPUT | vk::PipelineStageFlags::VERTEX_SHADER | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                &memory_barriers,
+                &[],
+                &[]
+            );
+        }
+
+        self.compute_pending = false;
+    }
+
+    pub fn is_render_pass_active(&self) -> bool {
+        self.render_pass_active
+    }
+
+    /// Mark compute work recorded for the current frame
+    pub(crate) fn set_compute_pending(&mut self) {
+        self.compute_pending = true;
     }
 
     pub fn end_frame(&mut self) -> Result<(), Error> {
@@ -469,6 +638,10 @@ impl Pipeline {
             return Err(Error::from("pipeline needs to be reinitialized"));
         }
 
+        // clear and present even if nothing was drawn
+        self.begin_render_pass();
+        self.render_pass_active = false;
+
         let swapchain: &_ = &self.swapchain;
         let frame = self.current_frame();
         let command_buffer = &frame.command_buffer;
@@ -19,26 +19,53 @@ const DEFAULT_QUAD_INDICES: [IndexBufferElementType; 6] = [2,1,0,0,3,2];
 pub enum ShaderInputType {
     Vertex,
     Point,
-    Tile
+    Tile,
+    Instance,
+    None
 }
 
 #[repr(C)]
 #[derive(Clone)]
 pub struct Tile {
     pos_index: u32,
-    tile_index: i32
+    tile_index: i32,
+    flags: u32
 }
 
 impl Tile {
-    pub const NUM_ATTRIBUTES: usize = 2;
+    pub const NUM_ATTRIBUTES: usize = 3;
+
+    /// Mirror horizontally, same bit as LDtk flip bits
+    pub const FLIP_X: u32 = 0x1;
+    /// Mirror vertically, same bit as LDtk flip bits
+    pub const FLIP_Y: u32 = 0x2;
+    /// Clockwise quarter turns (0-3), applied after flipping
+    pub const ROTATION_MASK: u32 = 0xc;
+    pub const ROTATION_SHIFT: u32 = 2;
+    /// Alpha (0-255)
+    pub const ALPHA_MASK: u32 = 0xff00;
+    pub const ALPHA_SHIFT: u32 = 8;
+    /// Default flags, no transformation and fully opaque
+    pub const OPAQUE: u32 = Self::ALPHA_MASK;
 
     pub fn new() -> Self {
         Self {
             pos_index: 0,
-            tile_index: 0
+            tile_index: 0,
+            flags: Self::OPAQUE
         }
     }
 
+    /// Encode flip, rotation (quarter turns) and alpha (0.0-1.0) flags
+    pub fn make_flags(flip_x: bool, flip_y: bool, rotation: u32, alpha: f32) -> u32 {
+        let mut flags = 0u32;
+        if flip_x { flags |= Self::FLIP_X; }
+        if flip_y { flags |= Self::FLIP_Y; }
+        flags |= (rotation % 4) << Self::ROTATION_SHIFT;
+        flags |= ((alpha.clamp(0.0, 1.0) * 255.0).round() as u32) << Self::ALPHA_SHIFT;
+        flags
+    }
+
     pub fn set_data(&mut self, pos_index: u32, tile_index: i32) -> &mut Self {
         self.pos_index = pos_index;
         self.tile_index = tile_index;
@@ -59,6 +86,19 @@ impl Tile {
         self.tile_index
     }
 
+    pub fn get_pos(&self) -> u32 {
+        self.pos_index
+    }
+
+    pub fn set_flags(&mut self, flags: u32) -> &mut Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn get_flags(&self) -> u32 {
+        self.flags
+    }
+
     pub fn get_binding_description() -> vk::VertexInputBindingDescription {
 
         let stride = core::mem::size_of::<Tile>();
@@ -80,7 +120,12 @@ impl Tile {
                 .binding(0)
                 .location(1)
                 .format(vk::Format::R32_SINT)
-                .offset(offset_of!(Tile, tile_index) as _)
+                .offset(offset_of!(Tile, tile_index) as _),
+            vk::VertexInputAttributeDescription::default()
+                .binding(0)
+                .location(2)
+                .format(vk::Format::R32_UINT)
+                .offset(offset_of!(Tile, flags) as _)
         ]
     }
 }
@@ -91,12 +136,12 @@ pub struct TileQueue {
     modified: bool,
     count: usize,
     vertices: Vec<Tile>,
-    vertex_buffer: VertexBuffer
+    vertex_buffer: Option<VertexBuffer>
 }
 
 impl Disposable for TileQueue {
     fn dispose(&mut self) {
-        self.vertex_buffer.dispose();
+        self.release();
         self.clear();
     }
 }
@@ -110,15 +155,14 @@ impl TileQueue {
         let mut vertices: Vec<Tile> = Vec::new();
         vertices.resize(num_vertices, Tile::new());
 
-        let vertex_buffer = VertexBuffer::new(num_vertices * std::mem::size_of::<Tile>());
-
+        // the vertex buffer is created with the first update
         Self {
             capacity,
             reserved: 0,
             modified: false,
             count: 0,
             vertices,
-            vertex_buffer
+            vertex_buffer: None
         }
     }
 
@@ -166,20 +210,38 @@ impl TileQueue {
         index
     }
 
+    /// Free the vertex buffer, tiles are kept and uploaded again
+    /// with the next update
+    pub fn release(&mut self) {
+        if let Some(mut vertex_buffer) = self.vertex_buffer.take() {
+            vertex_buffer.dispose();
+            self.modified = true;
+        }
+    }
+
+    pub fn is_released(&self) -> bool {
+        self.vertex_buffer.is_none()
+    }
+
     pub fn update(&mut self) {
         let num = self.count + self.reserved;
 
-        if !self.modified || 0 == num {
+        if 0 == num || (!self.modified && self.vertex_buffer.is_some()) {
             return;
         }
 
         self.modified = false;
 
         let num_vertices = num;
+        let capacity = self.capacity;
+
+        let vertex_buffer = self.vertex_buffer.get_or_insert_with(|| {
+            VertexBuffer::new(capacity * std::mem::size_of::<Tile>())
+        });
 
         let vertex_data = self.vertices.as_ptr() as *const std::ffi::c_void;
         let vertex_data_size = num_vertices * std::mem::size_of::<Tile>();
-        self.vertex_buffer.copy_region(vertex_data, 0, vertex_data_size).unwrap();
+        vertex_buffer.copy_region(vertex_data, 0, vertex_data_size).unwrap();
     }
 
     pub fn draw(&mut self) {
@@ -191,10 +253,14 @@ impl TileQueue {
             return;
         }
 
+        let Some(vertex_buffer) = &self.vertex_buffer else {
+            return;
+        };
+
         let num_vertices = num;
         let pipeline = crate::globals::pipeline();
         let frame = pipeline.current_frame();
-        self.vertex_buffer.bind(frame).unwrap();
+        vertex_buffer.bind(frame).unwrap();
 
         let command_buffer = frame.command_buffer.obj;
 
@@ -242,9 +308,28 @@ impl TileQueue {
         self.vertices[vertex_index].get_tile()
     }
 
+    /// Flip, rotation and alpha, see `Tile::make_flags`
+    pub fn set_flags(&mut self, vertex_index: usize, flags: u32) {
+        self.vertices[vertex_index].set_flags(flags);
+        self.modified = true;
+    }
+
+    pub fn get_flags(&self, vertex_index: usize) -> u32 {
+        self.vertices[vertex_index].get_flags()
+    }
+
+    pub fn get_pos(&self, vertex_index: usize) -> u32 {
+        self.vertices[vertex_index].get_pos()
+    }
+
     pub fn push(&mut self, pos_index: u32, tile_index: i32) -> usize {
+        self.push_with_flags(pos_index, tile_index, Tile::OPAQUE)
+    }
+
+    pub fn push_with_flags(&mut self, pos_index: u32, tile_index: i32, flags: u32) -> usize {
         let vertex_index = self.count + self.reserved;
         self.store(vertex_index, pos_index, tile_index);
+        self.set_flags(vertex_index, flags);
         self.count += 1;
         vertex_index
     }
@@ -738,6 +823,161 @@ impl VertexQueue {
 
 }
 
+/// Thick, anti-aliased lines emitted as triangles into a vertex queue.
+///
+/// Use with a `TRIANGLE_LIST` material. Devices lacking the `wideLines`
+/// feature only rasterize native lines with a width of 1.0, this works
+/// everywhere. Edges fade out over the feather distance.
+pub struct LineQueue {
+    vertex_queue: VertexQueue,
+    line_width: f32,
+    feather: f32
+}
+
+impl Disposable for LineQueue {
+    fn dispose(&mut self) {
+        self.vertex_queue.dispose();
+    }
+}
+
+impl LineQueue {
+
+    pub const DEFAULT_LINE_WIDTH: f32 = 1.0;
+    pub const DEFAULT_FEATHER: f32 = 0.5;
+
+    /// Create queue for the given number of line segments
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            vertex_queue: VertexQueue::new(capacity * Self::vertices_per_segment(Self::DEFAULT_FEATHER)),
+            line_width: Self::DEFAULT_LINE_WIDTH,
+            feather: Self::DEFAULT_FEATHER
+        }
+    }
+
+    fn vertices_per_segment(feather: f32) -> usize {
+        if feather > 0.0 { 18 } else { 6 }
+    }
+
+    pub fn realloc(&mut self, capacity: usize) {
+        self.vertex_queue.realloc(capacity * Self::vertices_per_segment(Self::DEFAULT_FEATHER));
+    }
+
+    pub fn begin(&mut self) {
+        self.vertex_queue.begin();
+    }
+
+    pub fn end(&mut self) {
+        self.vertex_queue.end();
+    }
+
+    pub fn clear(&mut self) {
+        self.vertex_queue.clear();
+    }
+
+    pub fn update(&mut self) {
+        self.vertex_queue.update();
+    }
+
+    pub fn draw(&mut self) {
+        self.vertex_queue.draw();
+    }
+
+    /// Capacity in vertices
+    pub fn capacity(&self) -> usize {
+        self.vertex_queue.capacity()
+    }
+
+    /// Number of queued vertices
+    pub fn count(&self) -> usize {
+        self.vertex_queue.count()
+    }
+
+    pub fn set_line_width(&mut self, line_width: f32) -> &mut Self {
+        self.line_width = line_width.max(0.0);
+        self
+    }
+
+    pub fn line_width(&self) -> f32 {
+        self.line_width
+    }
+
+    /// Set width of the anti-aliased edge, 0 disables anti-aliasing
+    pub fn set_feather(&mut self, feather: f32) -> &mut Self {
+        self.feather = feather.max(0.0);
+        self
+    }
+
+    pub fn feather(&self) -> f32 {
+        self.feather
+    }
+
+    /// Triangle vertices of a line segment extruded along its normal,
+    /// with the opacity of each vertex. Feathered edges fade out to 0.0.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn extrude<F: FnMut(Vec2, f32)>(
+        x0: f32, y0: f32, x1: f32, y1: f32,
+        line_width: f32, feather: f32,
+        mut emit: F) {
+
+        let d = Vec2::new(x1 - x0, y1 - y0);
+        let len = (d.x * d.x + d.y * d.y).sqrt();
+        if len <= 0.0 {
+            return;
+        }
+
+        let n = Vec2::new(-d.y / len, d.x / len);
+        let p0 = Vec2::new(x0, y0);
+        let p1 = Vec2::new(x1, y1);
+
+        let mut push_band = |ofs_a: f32, alpha_a: f32, ofs_b: f32, alpha_b: f32| {
+            let a0 = p0 + n * ofs_a;
+            let a1 = p1 + n * ofs_a;
+            let b0 = p0 + n * ofs_b;
+            let b1 = p1 + n * ofs_b;
+
+            for (v, alpha) in [(a0, alpha_a), (a1, alpha_a), (b1, alpha_b), (a0, alpha_a), (b1, alpha_b), (b0, alpha_b)] {
+                emit(v, alpha);
+            }
+        };
+
+        let half_width = line_width * 0.5;
+
+        push_band(half_width, 1.0, -half_width, 1.0);
+
+        if feather > 0.0 {
+            push_band(half_width + feather, 0.0, half_width, 1.0);
+            push_band(-half_width, 1.0, -half_width - feather, 0.0);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn push_line(&mut self,
+        x0: f32, y0: f32, x1: f32, y1: f32,
+        r: f32, g: f32, b: f32, a: f32) {
+
+        let vertex_queue = &mut self.vertex_queue;
+
+        Self::extrude(x0, y0, x1, y1, self.line_width, self.feather, |v, alpha| {
+            vertex_queue.push(v.x, v.y, r, g, b, a * alpha, 0.0, 0.0, 0x0, DEFAULT_FLAGS);
+        });
+    }
+
+    /// Connected line segments, optionally closed
+    pub fn push_strip(&mut self, points: &[Vec2], closed: bool, r: f32, g: f32, b: f32, a: f32) {
+
+        for segment in points.windows(2) {
+            self.push_line(segment[0].x, segment[0].y, segment[1].x, segment[1].y, r, g, b, a);
+        }
+
+        if closed && points.len() > 2 {
+            let first = points[0];
+            let last = points[points.len() - 1];
+            self.push_line(last.x, last.y, first.x, first.y, r, g, b, a);
+        }
+    }
+
+}
+
 pub struct Quad {
     vertices: [Vertex; 4],
     indices: [IndexBufferElementType; 6],
@@ -1198,6 +1438,346 @@ impl QuadQueue {
 
 #[repr(C)]
 #[derive(Clone)]
+pub struct QuadInstance {
+    pos: Vec2,
+    size: Vec2,
+    pivot: Vec2,
+    rotation: f32,
+    color: Vec4,
+    frame: u32
+}
+
+impl QuadInstance {
+    pub const NUM_BINDINGS: usize = 2;
+    pub const NUM_ATTRIBUTES: usize = 7;
+
+    pub fn new() -> Self {
+        Self {
+            pos: Vec2::zero(),
+            size: Vec2::zero(),
+            pivot: Vec2::zero(),
+            rotation: 0.0,
+            color: DEFAULT_COLOR,
+            frame: 0
+        }
+    }
+
+    pub fn set_pos(&mut self, x: f32, y: f32) -> &mut Self {
+        self.pos.x = x;
+        self.pos.y = y;
+        self
+    }
+
+    pub fn set_size(&mut self, w: f32, h: f32) -> &mut Self {
+        self.size.x = w;
+        self.size.y = h;
+        self
+    }
+
+    pub fn set_pivot(&mut self, x: f32, y: f32) -> &mut Self {
+        self.pivot.x = x;
+        self.pivot.y = y;
+        self
+    }
+
+    pub fn set_rotation(&mut self, val: f32) -> &mut Self {
+        self.rotation = val;
+        self
+    }
+
+    pub fn set_color(&mut self, r: f32, g: f32, b: f32, a: f32) -> &mut Self {
+        self.color.x = r;
+        self.color.y = g;
+        self.color.z = b;
+        self.color.w = a;
+        self
+    }
+
+    pub fn set_frame(&mut self, val: u32) -> &mut Self {
+        self.frame = val;
+        self
+    }
+
+    /// Binding 0 holds the unit quad corners (per vertex),
+    /// binding 1 the instance data (per instance)
+    pub fn get_binding_descriptions() -> [vk::VertexInputBindingDescription; Self::NUM_BINDINGS] {
+
+        let stride = core::mem::size_of::<QuadInstance>();
+
+        [
+            Point::get_binding_description(),
+
+            vk::VertexInputBindingDescription::default()
+                .binding(1)
+                .stride(stride as u32)
+                .input_rate(vk::VertexInputRate::INSTANCE)
+        ]
+    }
+
+    pub fn get_attribute_descriptions() -> [vk::VertexInputAttributeDescription; Self::NUM_ATTRIBUTES] {
+        [
+            vk::VertexInputAttributeDescription::default()
+                .binding(0)
+                .location(0)
+                .format(vk::Format::R32G32B32_SFLOAT)
+                .offset(offset_of!(Point, pos) as _),
+
+            vk::VertexInputAttributeDescription::default()
+                .binding(1)
+                .location(1)
+                .format(vk::Format::R32G32_SFLOAT)
+                .offset(offset_of!(QuadInstance, pos) as _),
+
+            vk::VertexInputAttributeDescription::default()
+                .binding(1)
+                .location(2)
+                .format(vk::Format::R32G32_SFLOAT)
+                .offset(offset_of!(QuadInstance, size) as _),
+
+            vk::VertexInputAttributeDescription::default()
+                .binding(1)
+                .location(3)
+                .format(vk::Format::R32G32_SFLOAT)
+                .offset(offset_of!(QuadInstance, pivot) as _),
+
+            vk::VertexInputAttributeDescription::default()
+                .binding(1)
+                .location(4)
+                .format(vk::Format::R32_SFLOAT)
+                .offset(offset_of!(QuadInstance, rotation) as _),
+
+            vk::VertexInputAttributeDescription::default()
+                .binding(1)
+                .location(5)
+                .format(vk::Format::R32G32B32A32_SFLOAT)
+                .offset(offset_of!(QuadInstance, color) as _),
+
+            vk::VertexInputAttributeDescription::default()
+                .binding(1)
+                .location(6)
+                .format(vk::Format::R32_UINT)
+                .offset(offset_of!(QuadInstance, frame) as _)
+        ]
+    }
+}
+
+/// Queue of quad instances drawn with a single instanced draw call
+pub struct InstanceQueue {
+    capacity: usize,
+    reserved: usize,
+    modified: bool,
+    count: usize,
+    instances: Vec<QuadInstance>,
+    vertex_buffer: VertexBuffer,
+    instance_buffer: VertexBuffer
+}
+
+impl Disposable for InstanceQueue {
+    fn dispose(&mut self) {
+        self.vertex_buffer.dispose();
+        self.instance_buffer.dispose();
+        self.clear();
+    }
+}
+
+impl InstanceQueue {
+
+    const NUM_QUAD_VERTICES: usize = 6;
+
+    pub fn new(capacity: usize) -> Self {
+
+        // unit quad, triangulated the same way as QuadQueue
+        let corners = [(0.0f32, 0.0f32), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let mut vertices: Vec<Point> = Vec::with_capacity(Self::NUM_QUAD_VERTICES);
+        for index in DEFAULT_QUAD_INDICES {
+            let (x, y) = corners[index as usize];
+            let mut point = Point::new();
+            point.set_pos(x, y, 0.0);
+            vertices.push(point);
+        }
+
+        let vertex_buffer = VertexBuffer::new(Self::NUM_QUAD_VERTICES * std::mem::size_of::<Point>());
+        vertex_buffer.copy(vertices.as_ptr() as *const std::ffi::c_void).unwrap();
+
+        let mut instances: Vec<QuadInstance> = Vec::new();
+        instances.resize(capacity, QuadInstance::new());
+
+        let instance_buffer = VertexBuffer::new(capacity * std::mem::size_of::<QuadInstance>());
+
+        Self {
+            capacity,
+            reserved: 0,
+            modified: false,
+            count: 0,
+            instances,
+            vertex_buffer,
+            instance_buffer
+        }
+    }
+
+    pub fn realloc(&mut self, capacity: usize) {
+
+        self.dispose();
+
+        let q = Self::new(capacity);
+
+        self.capacity = capacity;
+        self.reserved = 0;
+        self.modified = false;
+        self.count = 0;
+
+        self.instances = q.instances;
+        self.vertex_buffer = q.vertex_buffer;
+        self.instance_buffer = q.instance_buffer;
+
+    }
+
+    pub fn begin(&mut self) {
+        self.count = 0;
+    }
+
+    pub fn end(&mut self) {
+    }
+
+    pub fn clear(&mut self) {
+        self.count = 0;
+        self.reserved = 0;
+    }
+
+    pub fn reserve(&mut self, reserved_capacity: usize) -> usize {
+
+        if self.count > 0 {
+            panic!("cannot reserve after dynamic push");
+        }
+
+        if self.count + self.reserved + reserved_capacity > self.capacity {
+            panic!("queue overflow");
+        }
+
+        let index = self.reserved;
+
+        self.reserved += reserved_capacity;
+
+        index
+    }
+
+    pub fn update(&mut self) {
+        let num = self.count + self.reserved;
+
+        if !self.modified || 0 == num {
+            return;
+        }
+
+        self.modified = false;
+
+        let instance_data = self.instances.as_ptr() as *const std::ffi::c_void;
+        let instance_data_size = num * std::mem::size_of::<QuadInstance>();
+        self.instance_buffer.copy_region(instance_data, 0, instance_data_size).unwrap();
+
+    }
+
+    pub fn draw(&mut self) {
+        self.update();
+
+        let num = self.count + self.reserved;
+
+        if 0 == num {
+            return;
+        }
+
+        let pipeline = crate::globals::pipeline();
+        let frame = pipeline.current_frame();
+        self.vertex_buffer.bind_to(frame, 0).unwrap();
+        self.instance_buffer.bind_to(frame, 1).unwrap();
+
+        let command_buffer = frame.command_buffer.obj;
+
+        let device = crate::globals::device();
+        unsafe { device.obj.cmd_draw(
+            command_buffer,
+            Self::NUM_QUAD_VERTICES as u32,
+            num as u32,
+            0, 0
+        ) };
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn set_coords(&mut self, index: usize, x: f32, y: f32, w: f32, h: f32) {
+        self.instances[index].set_pos(x, y).set_size(w, h);
+        self.modified = true;
+    }
+
+    pub fn set_pivot(&mut self, index: usize, x: f32, y: f32) {
+        self.instances[index].set_pivot(x, y);
+        self.modified = true;
+    }
+
+    pub fn set_rotation(&mut self, index: usize, rotation: f32) {
+        self.instances[index].set_rotation(rotation);
+        self.modified = true;
+    }
+
+    pub fn set_color(&mut self, index: usize, r: f32, g: f32, b: f32, a: f32) {
+        self.instances[index].set_color(r, g, b, a);
+        self.modified = true;
+    }
+
+    pub fn set_frame(&mut self, index: usize, frame: u32) {
+        self.instances[index].set_frame(frame);
+        self.modified = true;
+    }
+
+    pub fn push(&mut self,
+        x: f32, y: f32, w: f32, h: f32,
+        px: f32, py: f32, rotation: f32,
+        r: f32, g: f32, b: f32, a: f32,
+        frame: u32) {
+
+        let index = self.count + self.reserved;
+
+        self.store(index,
+            x, y, w, h,
+            px, py, rotation,
+            r, g, b, a,
+            frame
+        );
+
+        self.count += 1;
+    }
+
+    pub fn store(&mut self, index: usize,
+        x: f32, y: f32, w: f32, h: f32,
+        px: f32, py: f32, rotation: f32,
+        r: f32, g: f32, b: f32, a: f32,
+        frame: u32) {
+
+        if index >= self.capacity {
+            panic!("queue overflow");
+        }
+
+        self.instances[index]
+            .set_pos(x, y)
+            .set_size(w, h)
+            .set_pivot(px, py)
+            .set_rotation(rotation)
+            .set_color(r, g, b, a)
+            .set_frame(frame);
+
+        self.modified = true;
+
+    }
+
+}
+
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Color {
     pub r: f32,
     pub g: f32,
@@ -1244,4 +1824,114 @@ impl Color {
         self.a = color.a;
     }
 
+    /// Parse a color name or "#rrggbb" / "#rrggbbaa"
+    pub fn parse(value: &str) -> Option<Color> {
+        match value {
+            "white" => Some(Color::white()),
+            "black" => Some(Color::black()),
+            "red" => Some(Color::rgb(1.0, 0.0, 0.0)),
+            "green" => Some(Color::rgb(0.0, 1.0, 0.0)),
+            "blue" => Some(Color::rgb(0.0, 0.0, 1.0)),
+            "yellow" => Some(Color::rgb(1.0, 1.0, 0.0)),
+            "cyan" => Some(Color::rgb(0.0, 1.0, 1.0)),
+            "magenta" => Some(Color::rgb(1.0, 0.0, 1.0)),
+            "gray" => Some(Color::rgb(0.5, 0.5, 0.5)),
+            _ => {
+                let hex = value.strip_prefix('#')?;
+                if hex.len() != 6 && hex.len() != 8 {
+                    return None;
+                }
+                let v = u32::from_str_radix(hex, 16).ok()?;
+                let v = if hex.len() == 6 { (v << 8) | 0xff } else { v };
+                let channel = |shift: u32| ((v >> shift) & 0xff) as f32 / 255.0;
+                Some(Color::rgba(channel(24), channel(16), channel(8), channel(0)))
+            }
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tile_flags() {
+        assert_eq!(Tile::make_flags(false, false, 0, 1.0), Tile::OPAQUE);
+
+        for flip_x in [false, true] {
+            for flip_y in [false, true] {
+                for rotation in 0..4 {
+                    for alpha in [0.0, 0.5, 1.0] {
+                        let flags = Tile::make_flags(flip_x, flip_y, rotation, alpha);
+                        assert_eq!(flags & Tile::FLIP_X != 0, flip_x);
+                        assert_eq!(flags & Tile::FLIP_Y != 0, flip_y);
+                        assert_eq!((flags & Tile::ROTATION_MASK) >> Tile::ROTATION_SHIFT, rotation);
+                        assert_eq!((flags & Tile::ALPHA_MASK) >> Tile::ALPHA_SHIFT, (alpha * 255.0_f32).round() as u32);
+                        assert_eq!(flags & !(Tile::FLIP_X | Tile::FLIP_Y | Tile::ROTATION_MASK | Tile::ALPHA_MASK), 0);
+                    }
+                }
+            }
+        }
+
+        // rotation wraps, alpha is clamped
+        assert_eq!(Tile::make_flags(false, false, 5, 2.0), (1 << Tile::ROTATION_SHIFT) | Tile::OPAQUE);
+        assert_eq!(Tile::make_flags(true, false, 0, -1.0), Tile::FLIP_X);
+    }
+
+    #[test]
+    fn test_line_extrusion() {
+        let extrude = |line_width: f32, feather: f32| {
+            let mut vertices = Vec::new();
+            LineQueue::extrude(2.0, 3.0, 2.0, 13.0, line_width, feather, |v, alpha| vertices.push((v.x, v.y, alpha)));
+            vertices
+        };
+
+        // degenerate segments produce nothing
+        let mut count = 0;
+        LineQueue::extrude(1.0, 1.0, 1.0, 1.0, 4.0, 1.0, |_, _| count += 1);
+        assert_eq!(count, 0);
+
+        // vertical line, the normal points to -x
+        let vertices = extrude(4.0, 0.0);
+        assert_eq!(vertices, vec![
+            (0.0, 3.0, 1.0), (0.0, 13.0, 1.0), (4.0, 13.0, 1.0),
+            (0.0, 3.0, 1.0), (4.0, 13.0, 1.0), (4.0, 3.0, 1.0)
+        ]);
+
+        // feathered edges fade out over one extra band per side
+        let vertices = extrude(4.0, 1.0);
+        assert_eq!(vertices.len(), 18);
+        assert_eq!(&vertices[..6], &extrude(4.0, 0.0)[..]);
+        assert_eq!(vertices[6], (-1.0, 3.0, 0.0));
+        assert_eq!(vertices[8], (0.0, 13.0, 1.0));
+        assert_eq!(vertices[12], (4.0, 3.0, 1.0));
+        assert_eq!(vertices[14], (5.0, 13.0, 0.0));
+
+        for (x, _, alpha) in &vertices {
+            assert!((0.0..=4.0).contains(x) || *alpha == 0.0);
+        }
+    }
+
+    #[test]
+    fn test_instance_layout() {
+        let [quad, instance] = QuadInstance::get_binding_descriptions();
+        assert_eq!(quad.input_rate, vk::VertexInputRate::VERTEX);
+        assert_eq!(instance.binding, 1);
+        assert_eq!(instance.input_rate, vk::VertexInputRate::INSTANCE);
+        assert_eq!(instance.stride as usize, std::mem::size_of::<QuadInstance>());
+
+        // unit quad corners come from binding 0, everything else per instance
+        let attributes = QuadInstance::get_attribute_descriptions();
+        for (location, attribute) in attributes.iter().enumerate() {
+            assert_eq!(attribute.location as usize, location);
+            assert_eq!(attribute.binding, if location == 0 { 0 } else { 1 });
+        }
+
+        let mut instance = QuadInstance::new();
+        instance.set_pos(1.0, 2.0).set_size(3.0, 4.0).set_pivot(0.5, 1.0).set_rotation(0.25).set_color(0.1, 0.2, 0.3, 0.4).set_frame(7);
+        assert_eq!(attributes[6].offset as usize, offset_of!(QuadInstance, frame));
+        assert_eq!((instance.pos, instance.size, instance.pivot), (Vec2::new(1.0, 2.0), Vec2::new(3.0, 4.0), Vec2::new(0.5, 1.0)));
+        assert_eq!((instance.rotation, instance.color, instance.frame), (0.25, Vec4::new(0.1, 0.2, 0.3, 0.4), 7));
+    }
 }
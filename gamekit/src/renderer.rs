@@ -10,7 +10,10 @@ use crate::error::Error;
 use crate::font::{Font, FontLockRef};
 use crate::material::{Material, MaterialLockRef};
 use crate::math::Vec4;
-use crate::sprite::{Sprite, SpriteData};
+use crate::nineslice::NineSlice;
+use crate::primitives::Color;
+use crate::sprite::{Sprite, SpriteData, SpriteSheetLockRef};
+use crate::text::TextLayout;
 
 pub struct Renderer {
     valid: bool,
@@ -69,6 +72,7 @@ impl Renderer {
         if self.pipeline_active {
             let mut material_lock = self.material.lock().unwrap();
             self.font = material_lock.font().lock().unwrap().clone();
+            self.blitter.set_sprite_sheet(material_lock.sprite_sheet());
             material_lock.bind();
         }
 
@@ -161,6 +165,13 @@ impl Renderer {
         Ok(pipeline_reinitialized)
     }
 
+    /// Start drawing if the render pass is deferred, compute work has to
+    /// be dispatched before
+    pub fn begin_render_pass(&mut self) {
+        let pipeline = crate::globals::pipeline_mut();
+        pipeline.begin_render_pass();
+    }
+
     pub fn end_frame(&mut self) -> Result<(), Error> {
 
         let pipeline = crate::globals::pipeline_mut();
@@ -191,6 +202,10 @@ impl Renderer {
         self.blitter.draw_sprite(sprite);
     }
 
+    pub fn draw_nine_slice(&mut self, nine_slice: &NineSlice, rect: &Vec4, color: &Color) {
+        self.blitter.draw_nine_slice(nine_slice, rect, color);
+    }
+
     pub fn draw_char_by_index(&mut self, x: f32, y: f32, idx: u32) {
         self.blitter.draw_char_by_index(&self.font, x, y, idx);
     }
@@ -203,6 +218,10 @@ impl Renderer {
         self.blitter.draw_text_rect(&self.font, rect, text);
     }
 
+    pub fn draw_text_layout(&mut self, layout: &mut TextLayout, x: f32, y: f32) {
+        self.blitter.draw_text_layout(&self.font, layout, x, y);
+    }
+
     pub fn draw_text_scaled(&mut self, x: f32, y: f32, scale_x: f32, scale_y: f32, text: &str) {
         self.blitter.draw_text_scaled(&self.font, x, y, scale_x, scale_y, text);
     }
@@ -211,4 +230,8 @@ impl Renderer {
         self.blitter.generate_sprite_sheet(width, height, tile_width, tile_height);
     }
 
+    pub fn set_sprite_sheet(&mut self, sprite_sheet: &SpriteSheetLockRef) {
+        self.blitter.set_sprite_sheet(Some(sprite_sheet));
+    }
+
 }
@@ -6,18 +6,21 @@ use gamebuilder::manifest::ApplicationDescriptorTable;
 use log::{*};
 use std::{collections::HashMap, sync::Mutex};
 
-use crate::{api::Disposable, audio::{Music, MusicLockRef, Sample, SampleLockRef}, bitmap::{Bitmap, BitmapLockRef}, data::{StaticData, StaticDataLockRef}, error::Error, font::{Font, FontLockRef}, maps::{Map, MapLockRef}, shader::{Shader, ShaderLockRef}, texture::{Texture, TextureLockRef}};
+use crate::{api::Disposable, audio::{Music, MusicLockRef, Sample, SampleLockRef}, bitmap::{Bitmap, BitmapLockRef}, data::{StaticData, StaticDataLockRef}, error::Error, font::{Font, FontLockRef}, maps::{Map, MapLockRef}, nineslice::{NineSlice, NineSliceLockRef}, particles::{ParticleEffect, ParticleEffectLockRef}, shader::{Shader, ShaderLockRef}, sprite::{SpriteSheet, SpriteSheetLockRef}, texture::{Texture, TextureLockRef}};
 
 #[derive(Default)]
 pub struct Resources {
     bitmaps: HashMap<String, BitmapLockRef>,
     textures: HashMap<String, TextureLockRef>,
+    nine_slices: HashMap<String, NineSliceLockRef>,
     shaders: HashMap<String, ShaderLockRef>,
     data: HashMap<String, StaticDataLockRef>,
     fonts: HashMap<String, FontLockRef>,
+    sprite_sheets: HashMap<String, SpriteSheetLockRef>,
     music: HashMap<String, MusicLockRef>,
     samples: HashMap<String, SampleLockRef>,
-    maps: HashMap<String, MapLockRef>
+    maps: HashMap<String, MapLockRef>,
+    particle_effects: HashMap<String, ParticleEffectLockRef>
 }
 
 impl Disposable for Resources {
@@ -35,6 +38,16 @@ impl Disposable for Resources {
         }
         self.fonts.clear();
 
+        for element in self.sprite_sheets.values_mut() {
+            element.lock().unwrap().dispose();
+        }
+        self.sprite_sheets.clear();
+
+        for element in self.nine_slices.values_mut() {
+            element.lock().unwrap().dispose();
+        }
+        self.nine_slices.clear();
+
         for element in self.textures.values_mut() {
             element.lock().unwrap().dispose();
         }
@@ -65,6 +78,11 @@ impl Disposable for Resources {
         }
         self.maps.clear();
 
+        for element in self.particle_effects.values_mut() {
+            element.lock().unwrap().dispose();
+        }
+        self.particle_effects.clear();
+
     }
 }
 
@@ -94,12 +112,24 @@ impl Resources {
                 resources.textures.insert(String::from(descriptor.name), res_ref);
             }
 
+            for descriptor in descriptors.nine_slices {
+                let res = NineSlice::from_resource(descriptor)?;
+                let res_ref = NineSliceLockRef::new(Mutex::new(res));
+                resources.nine_slices.insert(String::from(descriptor.name), res_ref);
+            }
+
             for descriptor in descriptors.fonts {
                 let res = Font::from_resource(descriptor)?;
                 let res_ref = FontLockRef::new(Mutex::new(res));
                 resources.fonts.insert(String::from(descriptor.name), res_ref);
             }
 
+            for descriptor in descriptors.sprite_sheets {
+                let res = SpriteSheet::from_resource(descriptor)?;
+                let res_ref = SpriteSheetLockRef::new(Mutex::new(res));
+                resources.sprite_sheets.insert(String::from(descriptor.name), res_ref);
+            }
+
             for descriptor in descriptors.shaders {
                 let res = Shader::from_resource(descriptor)?;
                 let res_ref = ShaderLockRef::new(Mutex::new(res));
@@ -118,6 +148,12 @@ impl Resources {
                 let res_ref = SampleLockRef::new(Mutex::new(res));
                 resources.samples.insert(String::from(descriptor.name), res_ref);
             }
+
+            for descriptor in descriptors.particle_effects {
+                let res = ParticleEffect::from_resource(descriptor)?;
+                let res_ref = ParticleEffectLockRef::new(Mutex::new(res));
+                resources.particle_effects.insert(String::from(descriptor.name), res_ref);
+            }
         } else if 1 == stage {
 
             // depends on materials to be built
@@ -149,11 +185,25 @@ impl Resources {
         res_ref.clone()
     }
 
+    pub fn find_texture(&self, id: &str) -> Option<TextureLockRef> {
+        self.textures.get(id).cloned()
+    }
+
+    pub fn get_nine_slice(&self, id: &str) -> NineSliceLockRef {
+        let res_ref = self.nine_slices.get(id).unwrap_or_else(|| panic!("nine-slice not found: \"{id}\""));
+        res_ref.clone()
+    }
+
     pub fn get_font(&self, id: &str) -> FontLockRef {
         let res_ref = self.fonts.get(id).unwrap_or_else(|| panic!("font not found: \"{id}\""));
         res_ref.clone()
     }
 
+    pub fn get_sprite_sheet(&self, id: &str) -> SpriteSheetLockRef {
+        let res_ref = self.sprite_sheets.get(id).unwrap_or_else(|| panic!("sprite sheet not found: \"{id}\""));
+        res_ref.clone()
+    }
+
     pub fn get_data(&self, id: &str) -> StaticDataLockRef {
         let res_ref = self.data.get(id).unwrap_or_else(|| panic!("data not found: \"{id}\""));
         res_ref.clone()
@@ -174,4 +224,9 @@ impl Resources {
         res_ref.clone()
     }
 
+    pub fn get_particle_effect(&self, id: &str) -> ParticleEffectLockRef {
+        let res_ref = self.particle_effects.get(id).unwrap_or_else(|| panic!("particle effect not found: \"{id}\""));
+        res_ref.clone()
+    }
+
 }
@@ -17,6 +17,15 @@ impl ShaderType {
     pub const UNKNOWN: u32 = 0x0;
     pub const VERTEX_SHADER: u32 = 0x1;
     pub const FRAGMENT_SHADER: u32 = 0x2;
+    pub const COMPUTE_SHADER: u32 = 0x3;
+
+    pub fn from_string(format: &str) -> u32 {
+        match format {
+            "vertex" => ShaderType::VERTEX_SHADER,
+            "compute" => ShaderType::COMPUTE_SHADER,
+            _ => ShaderType::FRAGMENT_SHADER
+        }
+    }
 }
 
 pub struct ShaderDescriptor {
@@ -65,7 +74,7 @@ impl Shader {
         let data_ptr = descriptor.data.as_ptr() as *const std::ffi::c_uint;
         let num_code_words = descriptor.data.len() / 4;
         let code = unsafe { core::slice::from_raw_parts(data_ptr, num_code_words) }.to_vec();
-        let shader_type = if descriptor.format == "vertex" { ShaderType::VERTEX_SHADER } else { ShaderType::FRAGMENT_SHADER };
+        let shader_type = ShaderType::from_string(descriptor.format);
         Self::new(&code, shader_type)
     }
 
@@ -2,18 +2,23 @@
 //! Sprite
 //!
 
-use std::sync::{Arc, Mutex};
+use std::{collections::HashMap, sync::{Arc, Mutex}};
 
 use cgmath::Zero;
+use gamebuilder::{aseprite::AsepriteJson, manifest::StaticSpriteSheetDescriptor};
 
-use crate::{api::{Disposable, LockRef, SpriteMeta}, math::{Vec2, Vec4}, primitives::Color};
+use crate::{animation::AnimationClip, api::{Disposable, LockRef, SpriteMeta}, error::Error, math::{Vec2, Vec4}, primitives::Color};
 
 pub struct SpriteData {
     pub position: Vec2,
     pub pivot: Vec2,
     pub size: Vec2,
+    pub rotation: f32,
     pub color: Color,
-    pub frame: f32
+    pub frame: f32,
+    pub fade_frame: f32,    // frame blended out while crossfading
+    pub fade_weight: f32,   // opacity of the fade frame, 0 = no crossfade
+    pub sheet: Option<SpriteSheetLockRef>   // None = use sheet of current material
 }
 
 impl Default for SpriteData {
@@ -22,8 +27,12 @@ impl Default for SpriteData {
             position: Vec2::zero(),
             pivot: Vec2::zero(),
             size: Vec2::zero(),
+            rotation: 0.0,
             color: Color::white(),
-            frame: 0.0
+            frame: 0.0,
+            fade_frame: 0.0,
+            fade_weight: 0.0,
+            sheet: None
         }
     }
 }
@@ -45,14 +54,60 @@ impl SpriteData {
         self.size.y = h;
     }
 
+    pub fn set_rotation(&mut self, rotation: f32) {
+        self.rotation = rotation;
+    }
+
     pub fn set_frame(&mut self, frame: f32) {
         self.frame = frame;
     }
 
+    /// Blend the given frame over the current frame, the current frame
+    /// is drawn with the remaining weight
+    pub fn set_fade(&mut self, frame: f32, weight: f32) {
+        self.fade_frame = frame;
+        self.fade_weight = weight.clamp(0.0, 1.0);
+    }
+
     pub fn set_color(&mut self, color: &Color) {
         self.color.set(color);
     }
 
+    pub fn set_sheet(&mut self, sheet: &SpriteSheetLockRef) {
+        self.sheet = Some(sheet.clone());
+    }
+
+    /// Set frame from animation clip at the given playback time
+    pub fn set_frame_from_clip(&mut self, clip: &AnimationClip, time: f32) {
+        self.frame = clip.frame_at(time) as f32;
+    }
+
+    /// Set pivot from a named slice, requires a sprite sheet to be assigned
+    pub fn set_pivot_from_slice(&mut self, name: &str) -> bool {
+        let pivot = match &self.sheet {
+            Some(sheet) => sheet.lock().unwrap().pivot(name),
+            None => None
+        };
+
+        match pivot {
+            Some(pivot) => { self.pivot = pivot; true },
+            None => false
+        }
+    }
+
+    /// Set frame by name, requires a sprite sheet to be assigned
+    pub fn set_frame_by_name(&mut self, name: &str) -> bool {
+        let index = match &self.sheet {
+            Some(sheet) => sheet.lock().unwrap().find(name),
+            None => None
+        };
+
+        match index {
+            Some(index) => { self.frame = index as f32; true },
+            None => false
+        }
+    }
+
 }
 
 
@@ -109,6 +164,10 @@ impl <T: Default + SpriteMeta> Sprite<T> {
         &self.data.size
     }
 
+    pub fn rotation(&self) -> f32 {
+        self.data.rotation
+    }
+
     pub fn frame(&self) -> f32 {
         self.data.frame
     }
@@ -132,6 +191,10 @@ impl <T: Default + SpriteMeta> Sprite<T> {
         self.data.size.y = h;
     }
 
+    pub fn set_rotation(&mut self, rotation: f32) {
+        self.data.rotation = rotation;
+    }
+
     pub fn set_frame(&mut self, frame: f32) {
         self.data.frame = frame;
     }
@@ -139,10 +202,67 @@ impl <T: Default + SpriteMeta> Sprite<T> {
     pub fn set_color(&mut self, color: &Color) {
         self.data.color.set(color);
     }
+
+    pub fn sheet(&self) -> Option<&SpriteSheetLockRef> {
+        self.data.sheet.as_ref()
+    }
+
+    pub fn set_sheet(&mut self, sheet: &SpriteSheetLockRef) {
+        self.data.set_sheet(sheet);
+    }
+
+    pub fn set_frame_by_name(&mut self, name: &str) -> bool {
+        self.data.set_frame_by_name(name)
+    }
+
+    pub fn set_frame_from_clip(&mut self, clip: &AnimationClip, time: f32) {
+        self.data.set_frame_from_clip(clip, time);
+    }
+
+    pub fn set_pivot_from_slice(&mut self, name: &str) -> bool {
+        self.data.set_pivot_from_slice(name)
+    }
+}
+
+#[derive(Clone)]
+pub struct SpriteFrame {
+    pub rect: Vec4,         // texture coords (x, y, w, h)
+    pub offset: Vec2,       // trim offset within the source frame (pixels)
+    pub size: Vec2,         // frame size (pixels)
+    pub source_size: Vec2,  // untrimmed frame size (pixels)
+    pub duration: f32       // seconds, 0 = undefined
+}
+
+impl SpriteFrame {
+    pub fn is_trimmed(&self) -> bool {
+        self.size != self.source_size
+    }
+}
+
+/// Full texture frame, used for sheets without frames
+static DEFAULT_FRAME: SpriteFrame = SpriteFrame {
+    rect: Vec4::new(0.0, 0.0, 1.0, 1.0),
+    offset: Vec2::new(0.0, 0.0),
+    size: Vec2::new(1.0, 1.0),
+    source_size: Vec2::new(1.0, 1.0),
+    duration: 0.0
+};
+
+#[derive(Clone)]
+pub struct SpriteSlice {
+    pub name: String,
+    pub frame: usize,
+    pub bounds: Vec4,           // x, y, w, h (pixels, relative to frame)
+    pub pivot: Option<Vec2>     // relative to bounds
 }
 
 pub struct SpriteSheet {
-    coords: Vec<Vec4>
+    width: usize,
+    height: usize,
+    frames: Vec<SpriteFrame>,
+    names: HashMap<String, usize>,
+    clips: Vec<AnimationClip>,
+    slices: Vec<SpriteSlice>
 }
 
 pub type SpriteSheetRef = std::sync::Arc<SpriteSheet>;
@@ -150,14 +270,22 @@ pub type SpriteSheetLockRef = LockRef<SpriteSheet>;
 
 impl Disposable for SpriteSheet {
     fn dispose(&mut self) {
-        self.coords.clear()
+        self.frames.clear();
+        self.names.clear();
+        self.clips.clear();
+        self.slices.clear();
     }
 }
 
 impl Default for SpriteSheet {
     fn default() -> Self {
         Self {
-            coords: vec!( Vec4::new(0.0, 0.0, 1.0, 1.0) )
+            width: 1,
+            height: 1,
+            frames: vec!(DEFAULT_FRAME.clone()),
+            names: HashMap::new(),
+            clips: Vec::new(),
+            slices: Vec::new()
         }
     }
 }
@@ -165,39 +293,364 @@ impl Default for SpriteSheet {
 impl SpriteSheet {
 
     pub fn new(width: usize, height: usize, tile_width: usize, tile_height: usize) -> Self {
-        let mut sheet = Self { coords: Vec::new() };
+        let mut sheet = Self::with_size(width, height);
         sheet.alloc(width, height, tile_width, tile_height);
         sheet
     }
 
+    /// Create an empty sprite sheet for a texture of the given size
+    pub fn with_size(width: usize, height: usize) -> Self {
+        Self {
+            width: width.max(1),
+            height: height.max(1),
+            frames: Vec::new(),
+            names: HashMap::new(),
+            clips: Vec::new(),
+            slices: Vec::new()
+        }
+    }
+
+    pub fn from_resource(descriptor: &StaticSpriteSheetDescriptor) -> Result<Self, Error> {
+        let resources = crate::globals::resources();
+        let texture_ref = resources.get_texture(descriptor.texture);
+
+        let (width, height) = {
+            let texture = texture_ref.lock().unwrap();
+            (texture.width as usize, texture.height as usize)
+        };
+
+        let mut sheet = Self::with_size(width, height);
+
+        if descriptor.tile_width > 0 && descriptor.tile_height > 0 {
+            sheet.alloc(width, height, descriptor.tile_width as usize, descriptor.tile_height as usize);
+        }
+
+        for f in descriptor.frames {
+            let index = sheet.add_trimmed_frame(f.name,
+                f.x as f32, f.y as f32, f.w as f32, f.h as f32,
+                f.offset_x as f32, f.offset_y as f32,
+                f.source_width as f32, f.source_height as f32
+            );
+            sheet.frames[index].duration = (f.duration as f32) / 1000.0;
+        }
+
+        if sheet.is_empty() {
+            return Err(Error::from(format!("sprite sheet \"{}\" has no frames", descriptor.name)));
+        }
+
+        for t in descriptor.tags {
+            sheet.add_tag(t.name, t.from as usize, t.to as usize, t.direction, t.repeat);
+        }
+
+        for s in descriptor.slices {
+            let pivot = if s.has_pivot { Some(Vec2::new(s.pivot_x as f32, s.pivot_y as f32)) } else { None };
+            sheet.add_slice(s.name, s.frame as usize,
+                Vec4::new(s.x as f32, s.y as f32, s.w as f32, s.h as f32),
+                pivot
+            );
+        }
+
+        Ok(sheet)
+    }
+
+    /// Load sprite sheet from Aseprite JSON export (hash or array layout)
+    pub fn from_aseprite(json: &str) -> Result<Self, Error> {
+
+        let obj = match AsepriteJson::parse(json) {
+            Ok(obj) => obj,
+            Err(e) => {
+                return Err(Error::from(e));
+            }
+        };
+
+        let mut sheet = Self::with_size(obj.meta.size.w as usize, obj.meta.size.h as usize);
+
+        for f in obj.frames() {
+            let index = sheet.add_trimmed_frame(&f.filename,
+                f.frame.x as f32, f.frame.y as f32, f.frame.w as f32, f.frame.h as f32,
+                f.sprite_source_size.x as f32, f.sprite_source_size.y as f32,
+                f.source_size.w as f32, f.source_size.h as f32
+            );
+            sheet.frames[index].duration = (f.duration as f32) / 1000.0;
+        }
+
+        if sheet.is_empty() {
+            return Err(Error::from("aseprite sprite sheet has no frames"));
+        }
+
+        for t in &obj.meta.frame_tags {
+            sheet.add_tag(&t.name, t.from as usize, t.to as usize, &t.direction, t.repeat.count());
+        }
+
+        for s in &obj.meta.slices {
+            for key in &s.keys {
+                let b = &key.bounds;
+                let pivot = key.pivot.map(|p| Vec2::new(p.x as f32, p.y as f32));
+                sheet.add_slice(&s.name, key.frame as usize,
+                    Vec4::new(b.x as f32, b.y as f32, b.w as f32, b.h as f32),
+                    pivot
+                );
+            }
+        }
+
+        Ok(sheet)
+    }
+
+    pub fn from_aseprite_file(name: &str) -> Result<Self, Error> {
+        let json = match std::fs::read_to_string(name) {
+            Ok(json) => json,
+            Err(_e) => {
+                return Err(Error::from(format!("failed to load sprite sheet from file \"{name}\"")));
+            }
+        };
+
+        Self::from_aseprite(&json)
+    }
+
     pub fn to_lockref(sprite_sheet: Self) -> SpriteSheetLockRef {
         Arc::new(Mutex::new(sprite_sheet))
     }
 
     pub fn alloc(&mut self, width: usize, height: usize, tile_width: usize, tile_height: usize) {
-        self.coords.clear();
+        self.frames.clear();
+        self.names.clear();
+        self.clips.clear();
+        self.slices.clear();
+
+        self.width = width.max(1);
+        self.height = height.max(1);
 
         let cols = width / tile_width;
         let rows = height / tile_height;
 
         let count = rows * cols;
-        self.coords.reserve(count);
-
-        let w = (tile_width as f32) / (width as f32);
-        let h = (tile_height as f32) / (height as f32);
+        self.frames.reserve(count);
 
         for r in 0..rows {
-            let y = (r as f32) / (rows as f32);
             for c in 0..cols {
-                let x = (c as f32) / (cols as f32);
-                self.coords.push(Vec4::new(x, y, w, h));
+                self.add_frame("",
+                    (c * tile_width) as f32, (r * tile_height) as f32,
+                    tile_width as f32, tile_height as f32
+                );
             }
         }
     }
 
+    /// Add frame by pixel rectangle, returns the frame index
+    pub fn add_frame(&mut self, name: &str, x: f32, y: f32, w: f32, h: f32) -> usize {
+        self.add_trimmed_frame(name, x, y, w, h, 0.0, 0.0, w, h)
+    }
+
+    /// Add trimmed frame by pixel rectangle, offset and untrimmed source size,
+    /// returns the frame index
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_trimmed_frame(&mut self, name: &str,
+        x: f32, y: f32, w: f32, h: f32,
+        offset_x: f32, offset_y: f32,
+        source_width: f32, source_height: f32) -> usize {
+
+        let tw = self.width as f32;
+        let th = self.height as f32;
+
+        let source_width = if source_width > 0.0 { source_width } else { w };
+        let source_height = if source_height > 0.0 { source_height } else { h };
+
+        let index = self.frames.len();
+
+        self.frames.push(SpriteFrame {
+            rect: Vec4::new(x / tw, y / th, w / tw, h / th),
+            offset: Vec2::new(offset_x, offset_y),
+            size: Vec2::new(w, h),
+            source_size: Vec2::new(source_width, source_height),
+            duration: 0.0
+        });
+
+        if !name.is_empty() {
+            self.names.insert(name.to_string(), index);
+        }
+
+        index
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
     pub fn rect(&self, index: usize) -> &Vec4 {
-        let i = if index >= self.coords.len() { 0 } else { index };
-        &self.coords[i]
+        &self.frame(index).rect
+    }
+
+    /// Frame by index, falls back to the first frame or the full texture
+    pub fn frame(&self, index: usize) -> &SpriteFrame {
+        find_frame(&self.frames, index)
     }
 
+    pub fn frames(&self) -> &[SpriteFrame] {
+        &self.frames
+    }
+
+    /// Get frame index by name
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.names.get(name).copied()
+    }
+
+    /// Add animation clip from a frame range, using the frame durations.
+    /// Ranges outside of the sheet are ignored.
+    pub fn add_tag(&mut self, name: &str, from: usize, to: usize, direction: &str, repeat: u32) -> Option<&AnimationClip> {
+
+        if self.frames.is_empty() {
+            return None;
+        }
+
+        let last = to.min(self.frames.len() - 1);
+        if from > last {
+            return None;
+        }
+
+        let mut clip = AnimationClip::from_direction(name, direction, repeat);
+
+        for index in from..=last {
+            clip.add_frame(index, self.frames[index].duration);
+        }
+
+        if direction == "pingpong_reverse" {
+            clip.reverse();
+        }
+
+        Some(self.add_clip(clip))
+    }
+
+    pub fn add_clip(&mut self, clip: AnimationClip) -> &AnimationClip {
+        self.clips.push(clip);
+        self.clips.last().unwrap()
+    }
+
+    pub fn clip(&self, name: &str) -> Option<&AnimationClip> {
+        self.clips.iter().find(|clip| clip.name() == name)
+    }
+
+    pub fn clips(&self) -> &[AnimationClip] {
+        &self.clips
+    }
+
+    pub fn add_slice(&mut self, name: &str, frame: usize, bounds: Vec4, pivot: Option<Vec2>) {
+        self.slices.push(SpriteSlice {
+            name: name.to_string(),
+            frame,
+            bounds,
+            pivot
+        });
+    }
+
+    /// Get slice by name (first key)
+    pub fn slice(&self, name: &str) -> Option<&SpriteSlice> {
+        self.slices.iter().find(|slice| slice.name == name)
+    }
+
+    pub fn slices(&self) -> &[SpriteSlice] {
+        &self.slices
+    }
+
+    /// Get pivot of a named slice relative to the frame origin
+    pub fn pivot(&self, name: &str) -> Option<Vec2> {
+        let slice = self.slice(name)?;
+        let pivot = slice.pivot?;
+        Some(Vec2::new(slice.bounds.x + pivot.x, slice.bounds.y + pivot.y))
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+}
+
+/// Frame by index, falls back to the first frame or the full texture
+pub(crate) fn find_frame(frames: &[SpriteFrame], index: usize) -> &SpriteFrame {
+    frames.get(index).or(frames.first()).unwrap_or(&DEFAULT_FRAME)
+}
+
+/// Screen rectangle (x, y, w, h) of a sprite frame. Each axis of the
+/// sprite size defaults to the untrimmed frame size, trimmed frames are
+/// placed and scaled relative to the source frame.
+pub(crate) fn sprite_rect(data: &SpriteData, frame: &SpriteFrame) -> Vec4 {
+
+    let size = Vec2::new(
+        if data.size.x != 0.0 { data.size.x } else { frame.source_size.x },
+        if data.size.y != 0.0 { data.size.y } else { frame.source_size.y }
+    );
+
+    let sx = size.x / frame.source_size.x;
+    let sy = size.y / frame.source_size.y;
+
+    Vec4::new(
+        data.position.x - data.pivot.x + frame.offset.x * sx,
+        data.position.y - data.pivot.y + frame.offset.y * sy,
+        frame.size.x * sx,
+        frame.size.y * sy
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_tag() {
+
+        // tags of an empty sheet are ignored
+        let mut sheet = SpriteSheet::with_size(64, 32);
+        assert!(sheet.add_tag("walk", 0, 3, "forward", 0).is_none());
+        assert!(sheet.clips().is_empty());
+
+        let mut sheet = SpriteSheet::new(64, 32, 16, 16);
+        assert_eq!(sheet.frames().len(), 8);
+
+        // ranges are clamped to the last frame
+        let clip = sheet.add_tag("walk", 6, 10, "forward", 0).unwrap();
+        assert_eq!(clip.frames(), &[6, 7]);
+
+        assert!(sheet.add_tag("jump", 8, 10, "forward", 0).is_none());
+        assert!(sheet.add_tag("fall", 5, 4, "forward", 0).is_none());
+        assert_eq!(sheet.clips().len(), 1);
+    }
+
+    #[test]
+    fn test_sprite_rect() {
+
+        let mut sheet = SpriteSheet::with_size(128, 64);
+
+        // 20x24 pixels trimmed from a 32x32 source frame at (6, 4)
+        let trimmed = sheet.add_trimmed_frame("", 0.0, 0.0, 20.0, 24.0, 6.0, 4.0, 32.0, 32.0);
+        let frame = sheet.frame(trimmed);
+
+        let mut data = SpriteData::default();
+        data.set_position(100.0, 50.0);
+        data.set_pivot(16.0, 16.0);
+
+        // untrimmed size by default
+        assert_eq!(sprite_rect(&data, frame), Vec4::new(90.0, 38.0, 20.0, 24.0));
+
+        // offset and size scale with the sprite
+        data.set_size(64.0, 16.0);
+        assert_eq!(sprite_rect(&data, frame), Vec4::new(96.0, 36.0, 40.0, 12.0));
+
+        // a single axis set keeps the source size of the other
+        data.set_size(64.0, 0.0);
+        assert_eq!(sprite_rect(&data, frame), Vec4::new(96.0, 38.0, 40.0, 24.0));
+
+        data.set_size(0.0, 16.0);
+        assert_eq!(sprite_rect(&data, frame), Vec4::new(90.0, 36.0, 20.0, 12.0));
+
+        // empty sheets use the full texture
+        let sheet = SpriteSheet::with_size(128, 64);
+        assert_eq!(sheet.frame(3).rect, Vec4::new(0.0, 0.0, 1.0, 1.0));
+    }
 }
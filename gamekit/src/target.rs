@@ -0,0 +1,196 @@
+//!
+//! Render Target
+//!
+
+use std::sync::{Arc, Mutex};
+
+use ash::vk::{self, Handle};
+
+use crate::{api::{Disposable, LockRef}, error::Error, image::{Image, ImageView}, primitives::Color, texture::{Texture, TextureLockRef}, types::Framebuffer};
+
+/// Offscreen color buffer with the size of the window.
+///
+/// The render pass is compatible with the main render pass, so any
+/// material can draw into the target. Like compute dispatches, targets
+/// are drawn during update, before the render pass of the frame starts,
+/// which requires the `deferred_render_pass` option.
+/// The color output is sampled through `texture()`.
+pub struct RenderTarget {
+    width: u32,
+    height: u32,
+    clear_color: Color,
+    texture: Option<TextureLockRef>,
+    depth_image: Option<Image>,
+    depth_image_view: Option<ImageView>,
+    render_pass: vk::RenderPass,
+    framebuffer: Option<Framebuffer>
+}
+
+pub type RenderTargetLockRef = LockRef<RenderTarget>;
+
+impl Disposable for RenderTarget {
+    fn dispose(&mut self) {
+        self.destroy_buffers();
+
+        if !self.render_pass.is_null() {
+            let device = crate::globals::device();
+            unsafe { device.obj.destroy_render_pass(self.render_pass, None); }
+            self.render_pass = vk::RenderPass::null();
+        }
+    }
+}
+
+impl RenderTarget {
+
+    pub fn new() -> Result<Self, Error> {
+
+        let pipeline = crate::globals::pipeline();
+        let render_pass = pipeline.create_compatible_render_pass(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)?;
+
+        let mut target = Self {
+            width: 0,
+            height: 0,
+            clear_color: Color::black(),
+            texture: None,
+            depth_image: None,
+            depth_image_view: None,
+            render_pass,
+            framebuffer: None
+        };
+
+        target.validate()?;
+
+        Ok(target)
+    }
+
+    pub fn to_lockref(target: Self) -> RenderTargetLockRef {
+        Arc::new(Mutex::new(target))
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn set_clear_color(&mut self, color: &Color) -> &mut Self {
+        self.clear_color = color.clone();
+        self
+    }
+
+    pub fn clear_color(&self) -> &Color {
+        &self.clear_color
+    }
+
+    /// Color output, recreated when the window size changes
+    pub fn texture(&self) -> &TextureLockRef {
+        self.texture.as_ref().expect("render target not created")
+    }
+
+    /// Follow the window size, returns true if the buffers were
+    /// recreated and texture bindings have to be updated
+    pub fn validate(&mut self) -> Result<bool, Error> {
+
+        let pipeline = crate::globals::pipeline();
+        let extent = pipeline.swapchain.extent;
+
+        if self.texture.is_some() && extent.width == self.width && extent.height == self.height {
+            return Ok(false);
+        }
+
+        self.destroy_buffers();
+        self.create_buffers(extent.width.max(1), extent.height.max(1))?;
+
+        Ok(true)
+    }
+
+    /// Start drawing into the target, clears to the clear color
+    pub fn begin(&mut self) -> Result<(), Error> {
+
+        self.validate()?;
+
+        let clear_values = [
+            vk::ClearValue { color: vk::ClearColorValue { float32: [self.clear_color.r, self.clear_color.g, self.clear_color.b, self.clear_color.a] } },
+            vk::ClearValue { depth_stencil: vk::ClearDepthStencilValue { depth: 1.0, stencil: 0 } },
+        ];
+
+        let extent = vk::Extent2D {
+            width: self.width,
+            height: self.height
+        };
+
+        let framebuffer = self.framebuffer.as_ref().unwrap().obj;
+
+        let pipeline = crate::globals::pipeline_mut();
+        pipeline.begin_offscreen_pass(self.render_pass, framebuffer, extent, &clear_values)
+    }
+
+    pub fn end(&mut self) {
+        let pipeline = crate::globals::pipeline_mut();
+        pipeline.end_offscreen_pass();
+    }
+
+    fn create_buffers(&mut self, width: u32, height: u32) -> Result<(), Error> {
+
+        let device = crate::globals::device();
+        let pipeline = crate::globals::pipeline();
+
+        let size = (width * height * 4) as usize;
+
+        let color_image = Image::create(Image::RENDER_TARGET, width, height, size, pipeline.swapchain.format.format)?;
+        let texture = Texture::from_image(color_image)?;
+
+        let depth_image = Image::create(Image::DEPTH_BUFFER, width, height, size, pipeline.depth_image.format)?;
+        let depth_image_view = ImageView::new(&depth_image);
+
+        let framebuffer = Framebuffer::new(
+            &device.obj,
+            self.render_pass,
+            texture.image_view().obj,
+            depth_image_view.obj,
+            width,
+            height
+        )?;
+
+        self.width = width;
+        self.height = height;
+        self.texture = Some(TextureLockRef::new(Mutex::new(texture)));
+        self.depth_image = Some(depth_image);
+        self.depth_image_view = Some(depth_image_view);
+        self.framebuffer = Some(framebuffer);
+
+        Ok(())
+    }
+
+    fn destroy_buffers(&mut self) {
+
+        if self.texture.is_none() {
+            return;
+        }
+
+        // buffers might still be in use by frames in flight
+        let device = crate::globals::device();
+        unsafe { let _ = device.obj.device_wait_idle(); }
+
+        if let Some(mut framebuffer) = self.framebuffer.take() {
+            framebuffer.dispose();
+        }
+
+        if let Some(mut depth_image_view) = self.depth_image_view.take() {
+            depth_image_view.dispose();
+        }
+
+        if let Some(mut depth_image) = self.depth_image.take() {
+            depth_image.dispose();
+        }
+
+        if let Some(texture) = self.texture.take() {
+            texture.lock().unwrap().dispose();
+        }
+
+        self.width = 0;
+        self.height = 0;
+    }
+}
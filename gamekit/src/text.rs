@@ -0,0 +1,474 @@
+//!
+//! Text
+//!
+
+use crate::{font::Font, math::Vec2, primitives::Color, task::TaskTime};
+
+const WAVE_AMPLITUDE: f32 = 0.15;   // fraction of the line height
+const WAVE_FREQUENCY: f32 = 6.0;    // radians per second
+const WAVE_PHASE: f32 = 0.6;        // radians per character
+const SHAKE_AMPLITUDE: f32 = 1.0;   // pixels
+const SHAKE_RATE: f32 = 20.0;       // changes per second
+
+#[derive(PartialEq, Default, Debug, Clone, Copy)]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+    Justify
+}
+
+impl TextAlign {
+    pub fn from_string(align: &str) -> TextAlign {
+        match align {
+            "center" => TextAlign::Center,
+            "right" => TextAlign::Right,
+            "justify" => TextAlign::Justify,
+            _ => TextAlign::Left
+        }
+    }
+}
+
+#[derive(PartialEq, Default, Debug, Clone, Copy)]
+pub enum TextEffect {
+    #[default]
+    None,
+    Wave,
+    Shake
+}
+
+impl TextEffect {
+    /// Animated glyph displacement (pixels)
+    pub fn offset(&self, time: f32, index: usize, line_height: f32) -> Vec2 {
+        match self {
+            TextEffect::None => Vec2::new(0.0, 0.0),
+            TextEffect::Wave => {
+                let angle = time * WAVE_FREQUENCY - (index as f32) * WAVE_PHASE;
+                Vec2::new(0.0, angle.sin() * WAVE_AMPLITUDE * line_height)
+            },
+            TextEffect::Shake => {
+                let seed = (index as u32).wrapping_mul(0x9e37_79b9) ^ ((time * SHAKE_RATE) as u32);
+                let hash = |v: u32| -> f32 {
+                    let v = (v ^ (v >> 16)).wrapping_mul(0x7feb_352d);
+                    let v = (v ^ (v >> 15)).wrapping_mul(0x846c_a68b);
+                    ((v ^ (v >> 16)) as f32 / u32::MAX as f32) * 2.0 - 1.0
+                };
+                Vec2::new(hash(seed) * SHAKE_AMPLITUDE, hash(seed ^ 0x5bd1_e995) * SHAKE_AMPLITUDE)
+            }
+        }
+    }
+}
+
+/// Positioned character of a text layout
+#[derive(Clone)]
+pub struct TextGlyph {
+    pub c: char,
+    pub x: f32,             // pen position relative to the layout origin
+    pub y: f32,             // top of the line
+    pub color: Color,
+    pub effect: TextEffect,
+    pub index: usize        // reveal order
+}
+
+#[derive(Clone)]
+struct StyledChar {
+    c: char,
+    color: Color,
+    effect: TextEffect
+}
+
+struct TextLine {
+    start: usize,
+    end: usize,             // exclusive, trailing spaces trimmed
+    last: bool              // last line of a paragraph
+}
+
+/// Word wrapped, aligned text with optional markup and typewriter reveal.
+///
+/// Markup tags are `[color=#rrggbb]`, `[color=#rrggbbaa]` or `[color=name]`,
+/// `[wave]` and `[shake]`, closed by `[/color]`, `[/wave]` and `[/shake]`.
+/// A literal bracket is written as `[[`. The layout is recomputed on the
+/// next draw after any change, call `invalidate()` when switching fonts.
+pub struct TextLayout {
+    text: String,
+    markup: bool,
+    width: f32,             // wrap width, 0 = no wrapping
+    align: TextAlign,
+    line_spacing: f32,      // line height multiplier
+    scale: f32,
+    color: Color,
+    glyphs: Vec<TextGlyph>,
+    size: Vec2,
+    num_lines: usize,
+    dirty: bool,
+    time: f32,
+    reveal: f32,
+    reveal_speed: f32       // characters per second, 0 = no typewriter
+}
+
+impl TextLayout {
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_owned(),
+            markup: true,
+            width: 0.0,
+            align: TextAlign::Left,
+            line_spacing: 1.0,
+            scale: 1.0,
+            color: Color::white(),
+            glyphs: Vec::new(),
+            size: Vec2::new(0.0, 0.0),
+            num_lines: 0,
+            dirty: true,
+            time: 0.0,
+            reveal: 0.0,
+            reveal_speed: 0.0
+        }
+    }
+
+    pub fn set_text(&mut self, text: &str) -> &mut Self {
+        if self.text != text {
+            self.text = text.to_owned();
+            self.reveal = 0.0;
+            self.dirty = true;
+        }
+        self
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Enable or disable markup parsing (enabled by default)
+    pub fn set_markup(&mut self, markup: bool) -> &mut Self {
+        self.markup = markup;
+        self.dirty = true;
+        self
+    }
+
+    /// Set wrap width (view pixels), 0 disables wrapping
+    pub fn set_width(&mut self, width: f32) -> &mut Self {
+        self.width = width.max(0.0);
+        self.dirty = true;
+        self
+    }
+
+    pub fn set_align(&mut self, align: TextAlign) -> &mut Self {
+        self.align = align;
+        self.dirty = true;
+        self
+    }
+
+    pub fn set_line_spacing(&mut self, line_spacing: f32) -> &mut Self {
+        self.line_spacing = line_spacing;
+        self.dirty = true;
+        self
+    }
+
+    pub fn set_scale(&mut self, scale: f32) -> &mut Self {
+        self.scale = scale;
+        self.dirty = true;
+        self
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// Set default color of unmarked text
+    pub fn set_color(&mut self, color: &Color) -> &mut Self {
+        self.color = color.clone();
+        self.dirty = true;
+        self
+    }
+
+    /// Reveal characters over time, 0 shows all characters at once
+    pub fn set_typewriter(&mut self, chars_per_second: f32) -> &mut Self {
+        self.reveal_speed = chars_per_second.max(0.0);
+        self.reveal = 0.0;
+        self
+    }
+
+    pub fn reveal_all(&mut self) {
+        self.reveal = self.glyphs.len().max(self.text.chars().count()) as f32;
+    }
+
+    pub fn visible_count(&self) -> usize {
+        if self.reveal_speed > 0.0 {
+            (self.reveal as usize).min(self.glyphs.len())
+        } else {
+            self.glyphs.len()
+        }
+    }
+
+    pub fn is_revealed(&self) -> bool {
+        !self.dirty && self.visible_count() >= self.glyphs.len()
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Advance effects and typewriter
+    pub fn update(&mut self, step: f32) {
+        self.time += step;
+        if self.reveal_speed > 0.0 {
+            self.reveal += step * self.reveal_speed;
+            if !self.dirty {
+                self.reveal = self.reveal.min(self.glyphs.len() as f32);
+            }
+        }
+    }
+
+    pub fn step(&mut self, time: &TaskTime) {
+        self.update(time.step);
+    }
+
+    pub fn invalidate(&mut self) {
+        self.dirty = true;
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn glyphs(&self) -> &[TextGlyph] {
+        &self.glyphs
+    }
+
+    /// Size of the laid out text block
+    pub fn size(&self) -> Vec2 {
+        self.size
+    }
+
+    pub fn num_lines(&self) -> usize {
+        self.num_lines
+    }
+
+    /// Split text into styled characters, unknown tags are kept as text
+    fn parse(&self) -> Vec<StyledChar> {
+
+        let mut chars = Vec::new();
+        let mut colors = vec![self.color.clone()];
+        let mut effects = vec![TextEffect::None];
+
+        let mut rest = self.text.as_str();
+        while let Some(c) = rest.chars().next() {
+
+            if self.markup && c == '[' {
+                if rest.starts_with("[[") {
+                    rest = &rest[2..];
+                    chars.push(StyledChar { c: '[', color: colors.last().unwrap().clone(), effect: *effects.last().unwrap() });
+                    continue;
+                }
+
+                if let Some(end) = rest.find(']') {
+                    let tag = &rest[1..end];
+                    let handled = match tag {
+                        "wave" => { effects.push(TextEffect::Wave); true },
+                        "shake" => { effects.push(TextEffect::Shake); true },
+                        "/wave" | "/shake" => { if effects.len() > 1 { effects.pop(); } true },
+                        "/color" => { if colors.len() > 1 { colors.pop(); } true },
+                        _ => match tag.strip_prefix("color=").and_then(Color::parse) {
+                            Some(color) => { colors.push(color); true },
+                            None => false
+                        }
+                    };
+
+                    if handled {
+                        rest = &rest[end + 1..];
+                        continue;
+                    }
+                }
+            }
+
+            chars.push(StyledChar { c, color: colors.last().unwrap().clone(), effect: *effects.last().unwrap() });
+            rest = &rest[c.len_utf8()..];
+        }
+
+        chars
+    }
+
+    fn measure(font: &Font, chars: &[StyledChar], scale: f32) -> f32 {
+        let mut width = 0.0;
+        let mut prev: Option<char> = None;
+        for s in chars {
+            if let Some(p) = prev {
+                width += font.kerning(p, s.c) * scale;
+            }
+            width += font.glyph(s.c).map(|glyph| glyph.advance).unwrap_or_default() * scale;
+            prev = Some(s.c);
+        }
+        width
+    }
+
+    /// Greedy line breaking at spaces, words longer than a line are split
+    fn break_lines(&self, font: &Font, chars: &[StyledChar]) -> Vec<TextLine> {
+
+        let mut lines = Vec::new();
+        let wrap = self.width > 0.0;
+
+        let mut start = 0;
+        while start <= chars.len() {
+            let end = chars[start..].iter().position(|s| s.c == '\n').map(|p| start + p).unwrap_or(chars.len());
+
+            let mut line_start = start;
+            let mut last_space: Option<usize> = None;
+            let mut i = start;
+
+            while i < end {
+                if chars[i].c == ' ' {
+                    last_space = Some(i);
+                    i += 1;
+                    continue;
+                }
+
+                if wrap && i > line_start && Self::measure(font, &chars[line_start..=i], self.scale) > self.width {
+                    let break_at = match last_space {
+                        Some(space) if space > line_start => space,
+                        _ => i
+                    };
+
+                    lines.push(TextLine { start: line_start, end: break_at, last: false });
+
+                    line_start = break_at;
+                    while line_start < end && chars[line_start].c == ' ' {
+                        line_start += 1;
+                    }
+                    last_space = None;
+                    continue;
+                }
+
+                i += 1;
+            }
+
+            lines.push(TextLine { start: line_start, end, last: true });
+            start = end + 1;
+        }
+
+        for line in &mut lines {
+            while line.end > line.start && chars[line.end - 1].c == ' ' {
+                line.end -= 1;
+            }
+        }
+
+        lines
+    }
+
+    /// Compute glyph positions for a font
+    pub fn layout(&mut self, font: &Font) {
+
+        let chars = self.parse();
+        let lines = self.break_lines(font, &chars);
+
+        let widths: Vec<f32> = lines.iter().map(|line| Self::measure(font, &chars[line.start..line.end], self.scale)).collect();
+        let max_width = widths.iter().copied().fold(0.0, f32::max);
+        let box_width = if self.width > 0.0 { self.width } else { max_width };
+        let line_advance = font.line_height() as f32 * self.scale * self.line_spacing;
+
+        self.glyphs.clear();
+
+        for (line_index, line) in lines.iter().enumerate() {
+
+            let free = (box_width - widths[line_index]).max(0.0);
+            let num_spaces = chars[line.start..line.end].iter().filter(|s| s.c == ' ').count();
+
+            let mut x = match self.align {
+                TextAlign::Left | TextAlign::Justify => 0.0,
+                TextAlign::Center => free * 0.5,
+                TextAlign::Right => free
+            };
+
+            let gap = if self.align == TextAlign::Justify && !line.last && num_spaces > 0 {
+                free / num_spaces as f32
+            } else {
+                0.0
+            };
+
+            let y = line_index as f32 * line_advance;
+            let mut prev: Option<char> = None;
+
+            for s in &chars[line.start..line.end] {
+                if let Some(p) = prev {
+                    x += font.kerning(p, s.c) * self.scale;
+                }
+
+                self.glyphs.push(TextGlyph {
+                    c: s.c,
+                    x,
+                    y,
+                    color: s.color.clone(),
+                    effect: s.effect,
+                    index: self.glyphs.len()
+                });
+
+                x += font.glyph(s.c).map(|glyph| glyph.advance).unwrap_or_default() * self.scale;
+                if s.c == ' ' {
+                    x += gap;
+                }
+                prev = Some(s.c);
+            }
+        }
+
+        self.num_lines = lines.len();
+        self.size = Vec2::new(box_width, lines.len() as f32 * line_advance);
+        self.dirty = false;
+
+        if self.reveal_speed > 0.0 {
+            self.reveal = self.reveal.min(self.glyphs.len() as f32);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_font() -> Font {
+        let mut font = Font::with_metrics(10, 8, 128, 128, &["font"]);
+        for c in "[]abcdefghijklmnopqrstuvwxyz ".chars() {
+            font.add_glyph(c as u32, 0, 0, 8, 8, 0, 0, 10, 0);
+        }
+        font
+    }
+
+    #[test]
+    fn test_layout() {
+        let font = test_font();
+
+        let mut layout = TextLayout::new("ab [color=#ff0000]cd[/color] [[x [wave]ef[/wave]");
+        layout.layout(&font);
+
+        let text: String = layout.glyphs().iter().map(|g| g.c).collect();
+        assert_eq!(text, "ab cd [x ef");
+        assert_eq!(layout.glyphs()[3].color.r, 1.0);
+        assert_eq!(layout.glyphs()[3].color.g, 0.0);
+        assert_eq!(layout.glyphs()[9].effect, TextEffect::Wave);
+
+        // wrap at spaces, 5 characters per line
+        layout.set_text("aaa bb cccccccc d").set_width(50.0);
+        layout.layout(&font);
+        assert_eq!(layout.num_lines(), 4);
+        let line_starts: Vec<(char, f32, f32)> = layout.glyphs().iter().filter(|g| g.x == 0.0).map(|g| (g.c, g.x, g.y)).collect();
+        assert_eq!(line_starts, vec![('a', 0.0, 0.0), ('b', 0.0, 10.0), ('c', 0.0, 20.0), ('c', 0.0, 30.0)]);
+
+        // justify stretches all but the last line of a paragraph
+        layout.set_text("aa bb cc dd ee").set_width(100.0).set_align(TextAlign::Justify);
+        layout.layout(&font);
+        assert_eq!(layout.glyphs()[6].x, 80.0);
+        assert_eq!(layout.glyphs()[11].x, 30.0);
+
+        layout.set_align(TextAlign::Right);
+        layout.layout(&font);
+        assert_eq!(layout.glyphs()[0].x, 20.0);
+        assert_eq!(layout.size(), Vec2::new(100.0, 20.0));
+
+        // typewriter
+        layout.set_typewriter(10.0);
+        layout.update(0.35);
+        assert_eq!(layout.visible_count(), 3);
+        layout.update(10.0);
+        assert!(layout.is_revealed());
+    }
+}
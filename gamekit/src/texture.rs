@@ -66,6 +66,10 @@ impl Texture {
         Self::from_image(image)
     }
 
+    pub(crate) fn image_view(&self) -> &ImageView {
+        &self.image_view
+    }
+
     pub fn get_binding(texture_ref: &TextureLockRef, binding: u32, filtering: bool) -> TextureBinding {
 
         let t = texture_ref.clone();
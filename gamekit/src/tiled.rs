@@ -0,0 +1,1185 @@
+//!
+//! Tiled Maps
+//! Imports Tiled (https://www.mapeditor.org) maps into the LDtk model
+//!
+
+use std::io::Read;
+
+use base64::Engine;
+use log::{*};
+use serde_json::{json, Value};
+
+use crate::error::Error;
+use crate::maps::{tile_source, tileset_columns};
+use crate::primitives::Tile;
+use crate::thirdparty::LdtkJson::{
+    EntityInstance, EnumTagValue, FieldInstance, LayerDefinition, LayerInstance, LdtkJson, Level, TileCustomMetadata,
+    TileInstance, TilesetDefinition, TilesetRectangle, Type
+};
+
+const FLIPPED_HORIZONTALLY: u32 = 0x80000000;
+const FLIPPED_VERTICALLY: u32 = 0x40000000;
+const FLIPPED_DIAGONALLY: u32 = 0x20000000;
+const ROTATED_HEXAGONAL: u32 = 0x10000000;
+const GID_MASK: u32 = !(FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY | FLIPPED_DIAGONALLY | ROTATED_HEXAGONAL);
+
+const LEVEL_IDENTIFIER: &str = "Level_0";
+
+/// Tile properties passed to the animation meta data
+const ANIMATION_PROPERTIES: [&str; 4] = ["animation_mode", "animation_offset", "animation_random_start", "animation_group"];
+
+/// True for Tiled map files (.tmx, .tmj)
+pub fn is_tiled_path(path: &str) -> bool {
+    let extension = std::path::Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or_default();
+    extension.eq_ignore_ascii_case("tmx") || extension.eq_ignore_ascii_case("tmj")
+}
+
+/// True for Tiled map data, XML or JSON with a Tiled version
+pub fn is_tiled_data(data: &[u8]) -> bool {
+    if is_xml(data) {
+        return true;
+    }
+
+    serde_json::from_slice::<Value>(data)
+        .map(|value| value.get("tiledversion").is_some() || value.get("type").and_then(Value::as_str) == Some("map"))
+        .unwrap_or(false)
+}
+
+/// Convert a Tiled map (XML or JSON) to the LDtk model, external
+/// tilesets are read through the loader, paths are relative to the map
+pub fn to_ldtk<F>(data: &[u8], loader: F) -> Result<LdtkJson, Error>
+where
+    F: Fn(&str) -> Result<Vec<u8>, Error>
+{
+    let mut map = if is_xml(data) { xml::parse_map(data)? } else { json::parse_map(data)? };
+
+    if map.orientation != "orthogonal" {
+        return Err(Error::from(format!("unsupported Tiled map orientation \"{}\"", map.orientation)));
+    }
+
+    if map.tile_width != map.tile_height || map.tile_width <= 0 {
+        return Err(Error::from("Tiled maps need square tiles"));
+    }
+
+    for tileset in &mut map.tilesets {
+        let Some(source) = tileset.source.clone() else {
+            continue;
+        };
+
+        let data = loader(&source)?;
+        let mut external = if is_xml(&data) { xml::parse_tileset(&data)? } else { json::parse_tileset(&data)? };
+
+        // image paths are relative to the tileset file
+        let base_dir = std::path::Path::new(&source).parent().unwrap_or(std::path::Path::new(""));
+        external.image = base_dir.join(&external.image).to_str().unwrap_or_default().replace('\\', "/");
+        external.first_gid = tileset.first_gid;
+
+        *tileset = external;
+    }
+
+    Converter::new(&map)?.convert()
+}
+
+fn is_xml(data: &[u8]) -> bool {
+    data.iter().find(|c| !c.is_ascii_whitespace()) == Some(&b'<')
+}
+
+struct TiledProperty {
+    name: String,
+    property_type: String,
+    value: Value
+}
+
+struct TiledChunk {
+    x: i64,
+    y: i64,
+    width: i64,
+    height: i64,
+    data: Vec<u32>
+}
+
+struct TiledObject {
+    id: i64,
+    name: String,
+    class: String,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    gid: u32,
+    properties: Vec<TiledProperty>
+}
+
+enum TiledLayerContent {
+    Tiles(Vec<TiledChunk>),
+    Objects(Vec<TiledObject>),
+    Group(Vec<TiledLayer>),
+    Other
+}
+
+struct TiledLayer {
+    name: String,
+    offset_x: f64,
+    offset_y: f64,
+    opacity: f64,
+    visible: bool,
+    parallax_x: f64,
+    parallax_y: f64,
+    tint: String,
+    properties: Vec<TiledProperty>,
+    content: TiledLayerContent
+}
+
+struct TiledTile {
+    id: u32,
+    class: String,
+    /// Frames as (tile id, duration in milliseconds)
+    animation: Vec<(u32, u32)>,
+    properties: Vec<TiledProperty>
+}
+
+#[derive(Default)]
+struct TiledTileset {
+    first_gid: u32,
+    source: Option<String>,
+    name: String,
+    image: String,
+    image_width: i64,
+    image_height: i64,
+    tile_width: i64,
+    tile_height: i64,
+    tile_count: i64,
+    columns: i64,
+    margin: i64,
+    spacing: i64,
+    tiles: Vec<TiledTile>
+}
+
+struct TiledMap {
+    orientation: String,
+    width: i64,
+    height: i64,
+    tile_width: i64,
+    tile_height: i64,
+    layers: Vec<TiledLayer>,
+    tilesets: Vec<TiledTileset>,
+    properties: Vec<TiledProperty>
+}
+
+/// Tiled flip bits as tile flags. The diagonal flip (applied first in
+/// Tiled) is a quarter turn with a flip.
+fn tile_flags(gid: u32) -> i64 {
+    let h = gid & FLIPPED_HORIZONTALLY != 0;
+    let v = gid & FLIPPED_VERTICALLY != 0;
+
+    let (flip_x, flip_y, rotation) = if gid & FLIPPED_DIAGONALLY != 0 {
+        (v, !h, 1)
+    } else {
+        (h, v, 0)
+    };
+
+    let flags = Tile::make_flags(flip_x, flip_y, rotation, 1.0);
+    (flags & (Tile::FLIP_X | Tile::FLIP_Y | Tile::ROTATION_MASK)) as i64
+}
+
+/// Tiled colors are #AARRGGBB
+fn convert_color(color: &str) -> Option<String> {
+    let hex = color.strip_prefix('#')?;
+    match hex.len() {
+        6 => Some(format!("#{hex}")),
+        8 => Some(format!("#{}{}", &hex[2..], &hex[..2])),
+        _ => None
+    }
+}
+
+fn decode_tile_data(text: &str, encoding: &str, compression: &str) -> Result<Vec<u32>, Error> {
+    match encoding {
+        "base64" => {
+            let raw = base64::engine::general_purpose::STANDARD.decode(text.trim())
+                .map_err(|e| Error::from(format!("invalid Tiled tile data: {e}")))?;
+
+            let bytes = match compression {
+                "" => raw,
+                "zlib" => {
+                    let mut bytes = Vec::new();
+                    flate2::read::ZlibDecoder::new(raw.as_slice()).read_to_end(&mut bytes)
+                        .map_err(|e| Error::from(format!("invalid Tiled tile data: {e}")))?;
+                    bytes
+                },
+                "gzip" => {
+                    let mut bytes = Vec::new();
+                    flate2::read::GzDecoder::new(raw.as_slice()).read_to_end(&mut bytes)
+                        .map_err(|e| Error::from(format!("invalid Tiled tile data: {e}")))?;
+                    bytes
+                },
+                _ => return Err(Error::from(format!("unsupported Tiled tile compression \"{compression}\"")))
+            };
+
+            Ok(bytes.chunks_exact(4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect())
+        },
+        "csv" | "" => {
+            text.split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(|s| s.parse::<u32>().map_err(|_e| Error::from(format!("invalid Tiled tile \"{s}\""))))
+                .collect()
+        },
+        _ => Err(Error::from(format!("unsupported Tiled tile encoding \"{encoding}\"")))
+    }
+}
+
+/// Tiled JSON (.tmj, .tsj)
+mod json {
+    use super::*;
+
+    fn get_str(value: &Value, key: &str) -> String {
+        value.get(key).and_then(Value::as_str).unwrap_or_default().to_string()
+    }
+
+    fn get_i64(value: &Value, key: &str, default: i64) -> i64 {
+        value.get(key).and_then(Value::as_i64).unwrap_or(default)
+    }
+
+    fn get_f64(value: &Value, key: &str, default: f64) -> f64 {
+        value.get(key).and_then(Value::as_f64).unwrap_or(default)
+    }
+
+    fn get_bool(value: &Value, key: &str, default: bool) -> bool {
+        value.get(key).and_then(Value::as_bool).unwrap_or(default)
+    }
+
+    fn get_array<'a>(value: &'a Value, key: &str) -> &'a [Value] {
+        value.get(key).and_then(Value::as_array).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Tiled 1.9 renamed "type" to "class"
+    fn get_class(value: &Value) -> String {
+        let class = get_str(value, "class");
+        if class.is_empty() { get_str(value, "type") } else { class }
+    }
+
+    fn parse_json(data: &[u8]) -> Result<Value, Error> {
+        serde_json::from_slice(data).map_err(|e| Error::from(format!("failed to parse Tiled file: {e}")))
+    }
+
+    pub(super) fn parse_map(data: &[u8]) -> Result<TiledMap, Error> {
+        let map = parse_json(data)?;
+
+        Ok(TiledMap {
+            orientation: get_str(&map, "orientation"),
+            width: get_i64(&map, "width", 0),
+            height: get_i64(&map, "height", 0),
+            tile_width: get_i64(&map, "tilewidth", 0),
+            tile_height: get_i64(&map, "tileheight", 0),
+            layers: parse_layers(&map)?,
+            tilesets: get_array(&map, "tilesets").iter().map(parse_tileset_value).collect(),
+            properties: parse_properties(&map)
+        })
+    }
+
+    pub(super) fn parse_tileset(data: &[u8]) -> Result<TiledTileset, Error> {
+        Ok(parse_tileset_value(&parse_json(data)?))
+    }
+
+    fn parse_tileset_value(tileset: &Value) -> TiledTileset {
+        let tiles = get_array(tileset, "tiles").iter().map(|tile| TiledTile {
+            id: get_i64(tile, "id", 0) as u32,
+            class: get_class(tile),
+            animation: get_array(tile, "animation").iter()
+                .map(|frame| (get_i64(frame, "tileid", 0) as u32, get_i64(frame, "duration", 0) as u32))
+                .collect(),
+            properties: parse_properties(tile)
+        }).collect();
+
+        TiledTileset {
+            first_gid: get_i64(tileset, "firstgid", 1) as u32,
+            source: tileset.get("source").and_then(Value::as_str).map(str::to_string),
+            name: get_str(tileset, "name"),
+            image: get_str(tileset, "image"),
+            image_width: get_i64(tileset, "imagewidth", 0),
+            image_height: get_i64(tileset, "imageheight", 0),
+            tile_width: get_i64(tileset, "tilewidth", 0),
+            tile_height: get_i64(tileset, "tileheight", 0),
+            tile_count: get_i64(tileset, "tilecount", 0),
+            columns: get_i64(tileset, "columns", 0),
+            margin: get_i64(tileset, "margin", 0),
+            spacing: get_i64(tileset, "spacing", 0),
+            tiles
+        }
+    }
+
+    fn parse_layers(parent: &Value) -> Result<Vec<TiledLayer>, Error> {
+        get_array(parent, "layers").iter().map(parse_layer).collect()
+    }
+
+    fn parse_layer(layer: &Value) -> Result<TiledLayer, Error> {
+
+        let content = match get_str(layer, "type").as_str() {
+            "tilelayer" => {
+                let encoding = get_str(layer, "encoding");
+                let compression = get_str(layer, "compression");
+
+                let tile_data = |data: &Value| match data {
+                    Value::Array(values) => Ok(values.iter().map(|v| v.as_u64().unwrap_or(0) as u32).collect()),
+                    Value::String(text) => decode_tile_data(text, &encoding, &compression),
+                    _ => Ok(Vec::new())
+                };
+
+                // infinite maps store chunks
+                let chunks = match layer.get("chunks").and_then(Value::as_array) {
+                    Some(chunks) => chunks.iter().map(|chunk| -> Result<TiledChunk, Error> { Ok(TiledChunk {
+                        x: get_i64(chunk, "x", 0),
+                        y: get_i64(chunk, "y", 0),
+                        width: get_i64(chunk, "width", 0),
+                        height: get_i64(chunk, "height", 0),
+                        data: tile_data(&chunk["data"])?
+                    }) }).collect::<Result<Vec<_>, Error>>()?,
+                    None => vec![TiledChunk {
+                        x: get_i64(layer, "x", 0),
+                        y: get_i64(layer, "y", 0),
+                        width: get_i64(layer, "width", 0),
+                        height: get_i64(layer, "height", 0),
+                        data: tile_data(&layer["data"])?
+                    }]
+                };
+
+                TiledLayerContent::Tiles(chunks)
+            },
+            "objectgroup" => TiledLayerContent::Objects(
+                get_array(layer, "objects").iter().map(|object| TiledObject {
+                    id: get_i64(object, "id", 0),
+                    name: get_str(object, "name"),
+                    class: get_class(object),
+                    x: get_f64(object, "x", 0.0),
+                    y: get_f64(object, "y", 0.0),
+                    width: get_f64(object, "width", 0.0),
+                    height: get_f64(object, "height", 0.0),
+                    gid: get_i64(object, "gid", 0) as u32,
+                    properties: parse_properties(object)
+                }).collect()
+            ),
+            "group" => TiledLayerContent::Group(parse_layers(layer)?),
+            _ => TiledLayerContent::Other
+        };
+
+        Ok(TiledLayer {
+            name: get_str(layer, "name"),
+            offset_x: get_f64(layer, "offsetx", 0.0),
+            offset_y: get_f64(layer, "offsety", 0.0),
+            opacity: get_f64(layer, "opacity", 1.0),
+            visible: get_bool(layer, "visible", true),
+            parallax_x: get_f64(layer, "parallaxx", 1.0),
+            parallax_y: get_f64(layer, "parallaxy", 1.0),
+            tint: get_str(layer, "tintcolor"),
+            properties: parse_properties(layer),
+            content
+        })
+    }
+
+    fn parse_properties(value: &Value) -> Vec<TiledProperty> {
+        get_array(value, "properties").iter().map(|property| {
+            let property_type = get_str(property, "type");
+            TiledProperty {
+                name: get_str(property, "name"),
+                property_type: if property_type.is_empty() { "string".to_string() } else { property_type },
+                value: property.get("value").cloned().unwrap_or(Value::Null)
+            }
+        }).collect()
+    }
+}
+
+/// Tiled XML (.tmx, .tsx)
+mod xml {
+    use super::*;
+    use roxmltree::{Document, Node};
+
+    fn attr_str(node: &Node, name: &str) -> String {
+        node.attribute(name).unwrap_or_default().to_string()
+    }
+
+    fn attr_i64(node: &Node, name: &str, default: i64) -> i64 {
+        node.attribute(name).and_then(|v| v.parse().ok()).unwrap_or(default)
+    }
+
+    fn attr_f64(node: &Node, name: &str, default: f64) -> f64 {
+        node.attribute(name).and_then(|v| v.parse().ok()).unwrap_or(default)
+    }
+
+    fn attr_bool(node: &Node, name: &str, default: bool) -> bool {
+        node.attribute(name).map(|v| v == "1" || v == "true").unwrap_or(default)
+    }
+
+    /// Tiled 1.9 renamed "type" to "class"
+    fn attr_class(node: &Node) -> String {
+        let class = attr_str(node, "class");
+        if class.is_empty() { attr_str(node, "type") } else { class }
+    }
+
+    fn elements<'a, 'input>(node: &Node<'a, 'input>, name: &'static str) -> impl Iterator<Item = Node<'a, 'input>> {
+        node.children().filter(move |child| child.has_tag_name(name))
+    }
+
+    fn parse_document<'input>(data: &'input [u8], root: &str) -> Result<Document<'input>, Error> {
+        let text = std::str::from_utf8(data).map_err(|e| Error::from(format!("failed to parse Tiled file: {e}")))?;
+        let document = Document::parse(text).map_err(|e| Error::from(format!("failed to parse Tiled file: {e}")))?;
+
+        if !document.root_element().has_tag_name(root) {
+            return Err(Error::from(format!("Tiled file has no <{root}> element")));
+        }
+
+        Ok(document)
+    }
+
+    pub(super) fn parse_map(data: &[u8]) -> Result<TiledMap, Error> {
+        let document = parse_document(data, "map")?;
+        let map = document.root_element();
+
+        Ok(TiledMap {
+            orientation: attr_str(&map, "orientation"),
+            width: attr_i64(&map, "width", 0),
+            height: attr_i64(&map, "height", 0),
+            tile_width: attr_i64(&map, "tilewidth", 0),
+            tile_height: attr_i64(&map, "tileheight", 0),
+            layers: parse_layers(&map)?,
+            tilesets: elements(&map, "tileset").map(|tileset| parse_tileset_node(&tileset)).collect(),
+            properties: parse_properties(&map)
+        })
+    }
+
+    pub(super) fn parse_tileset(data: &[u8]) -> Result<TiledTileset, Error> {
+        let document = parse_document(data, "tileset")?;
+        Ok(parse_tileset_node(&document.root_element()))
+    }
+
+    fn parse_tileset_node(tileset: &Node) -> TiledTileset {
+        let image = elements(tileset, "image").next();
+
+        let tiles = elements(tileset, "tile").map(|tile| TiledTile {
+            id: attr_i64(&tile, "id", 0) as u32,
+            class: attr_class(&tile),
+            animation: elements(&tile, "animation")
+                .flat_map(|animation| elements(&animation, "frame")
+                    .map(|frame| (attr_i64(&frame, "tileid", 0) as u32, attr_i64(&frame, "duration", 0) as u32))
+                    .collect::<Vec<_>>())
+                .collect(),
+            properties: parse_properties(&tile)
+        }).collect();
+
+        TiledTileset {
+            first_gid: attr_i64(tileset, "firstgid", 1) as u32,
+            source: tileset.attribute("source").map(str::to_string),
+            name: attr_str(tileset, "name"),
+            image: image.map(|image| attr_str(&image, "source")).unwrap_or_default(),
+            image_width: image.map(|image| attr_i64(&image, "width", 0)).unwrap_or_default(),
+            image_height: image.map(|image| attr_i64(&image, "height", 0)).unwrap_or_default(),
+            tile_width: attr_i64(tileset, "tilewidth", 0),
+            tile_height: attr_i64(tileset, "tileheight", 0),
+            tile_count: attr_i64(tileset, "tilecount", 0),
+            columns: attr_i64(tileset, "columns", 0),
+            margin: attr_i64(tileset, "margin", 0),
+            spacing: attr_i64(tileset, "spacing", 0),
+            tiles
+        }
+    }
+
+    fn parse_layers(parent: &Node) -> Result<Vec<TiledLayer>, Error> {
+        parent.children()
+            .filter(|child| child.is_element() && child.tag_name().name() != "properties" && child.tag_name().name() != "tileset")
+            .map(|child| parse_layer(&child))
+            .collect::<Result<Vec<_>, Error>>()
+            .map(|layers| layers.into_iter().flatten().collect())
+    }
+
+    /// Tile data without encoding is a list of <tile> elements
+    fn parse_tile_data(node: &Node, encoding: &str, compression: &str) -> Result<Vec<u32>, Error> {
+        if encoding.is_empty() {
+            return Ok(elements(node, "tile").map(|tile| attr_i64(&tile, "gid", 0) as u32).collect());
+        }
+
+        let text: String = node.children().filter(|child| child.is_text()).filter_map(|child| child.text()).collect();
+        decode_tile_data(&text, encoding, compression)
+    }
+
+    fn parse_layer(layer: &Node) -> Result<Option<TiledLayer>, Error> {
+
+        let content = match layer.tag_name().name() {
+            "layer" => {
+                let Some(data) = elements(layer, "data").next() else {
+                    return Ok(None);
+                };
+
+                let encoding = attr_str(&data, "encoding");
+                let compression = attr_str(&data, "compression");
+
+                // infinite maps store chunks
+                let chunks: Vec<TiledChunk> = elements(&data, "chunk").map(|chunk| -> Result<TiledChunk, Error> { Ok(TiledChunk {
+                    x: attr_i64(&chunk, "x", 0),
+                    y: attr_i64(&chunk, "y", 0),
+                    width: attr_i64(&chunk, "width", 0),
+                    height: attr_i64(&chunk, "height", 0),
+                    data: parse_tile_data(&chunk, &encoding, &compression)?
+                }) }).collect::<Result<Vec<_>, Error>>()?;
+
+                let chunks = if chunks.is_empty() {
+                    vec![TiledChunk {
+                        x: attr_i64(layer, "x", 0),
+                        y: attr_i64(layer, "y", 0),
+                        width: attr_i64(layer, "width", 0),
+                        height: attr_i64(layer, "height", 0),
+                        data: parse_tile_data(&data, &encoding, &compression)?
+                    }]
+                } else {
+                    chunks
+                };
+
+                TiledLayerContent::Tiles(chunks)
+            },
+            "objectgroup" => TiledLayerContent::Objects(
+                elements(layer, "object").map(|object| TiledObject {
+                    id: attr_i64(&object, "id", 0),
+                    name: attr_str(&object, "name"),
+                    class: attr_class(&object),
+                    x: attr_f64(&object, "x", 0.0),
+                    y: attr_f64(&object, "y", 0.0),
+                    width: attr_f64(&object, "width", 0.0),
+                    height: attr_f64(&object, "height", 0.0),
+                    gid: attr_i64(&object, "gid", 0) as u32,
+                    properties: parse_properties(&object)
+                }).collect()
+            ),
+            "group" => TiledLayerContent::Group(parse_layers(layer)?),
+            "imagelayer" => TiledLayerContent::Other,
+            _ => return Ok(None)
+        };
+
+        Ok(Some(TiledLayer {
+            name: attr_str(layer, "name"),
+            offset_x: attr_f64(layer, "offsetx", 0.0),
+            offset_y: attr_f64(layer, "offsety", 0.0),
+            opacity: attr_f64(layer, "opacity", 1.0),
+            visible: attr_bool(layer, "visible", true),
+            parallax_x: attr_f64(layer, "parallaxx", 1.0),
+            parallax_y: attr_f64(layer, "parallaxy", 1.0),
+            tint: attr_str(layer, "tintcolor"),
+            properties: parse_properties(layer),
+            content
+        }))
+    }
+
+    fn parse_properties(node: &Node) -> Vec<TiledProperty> {
+        elements(node, "properties")
+            .flat_map(|properties| elements(&properties, "property").collect::<Vec<_>>())
+            .map(|property| {
+                let property_type = attr_str(&property, "type");
+                let property_type = if property_type.is_empty() { "string".to_string() } else { property_type };
+
+                // multi-line strings are stored as text
+                let text = match property.attribute("value") {
+                    Some(value) => value.to_string(),
+                    None => property.text().unwrap_or_default().to_string()
+                };
+
+                let value = match property_type.as_str() {
+                    "int" | "object" => text.parse::<i64>().map(Value::from).unwrap_or(Value::Null),
+                    "float" => text.parse::<f64>().map(Value::from).unwrap_or(Value::Null),
+                    "bool" => Value::Bool(text == "true"),
+                    "class" => Value::Null,
+                    _ => Value::String(text)
+                };
+
+                TiledProperty {
+                    name: attr_str(&property, "name"),
+                    property_type,
+                    value
+                }
+            })
+            .collect()
+    }
+}
+
+/// Inherited state of group layers
+#[derive(Clone, Copy)]
+struct LayerState {
+    offset_x: f64,
+    offset_y: f64,
+    opacity: f64,
+    visible: bool,
+    parallax_x: f64,
+    parallax_y: f64
+}
+
+struct Converter<'a> {
+    map: &'a TiledMap,
+    grid_size: i64,
+    /// Level bounds in cells
+    min_x: i64,
+    min_y: i64,
+    cols: i64,
+    rows: i64,
+    tilesets: Vec<TilesetDefinition>,
+    layer_defs: Vec<LayerDefinition>,
+    layers: Vec<LayerInstance>,
+    level_fields: Vec<FieldInstance>
+}
+
+impl<'a> Converter<'a> {
+
+    fn new(map: &'a TiledMap) -> Result<Self, Error> {
+
+        let mut chunks = Vec::new();
+        Self::collect_chunks(&map.layers, &mut chunks);
+
+        // infinite maps grow into any direction
+        let (min_x, min_y, max_x, max_y) = if chunks.is_empty() {
+            (0, 0, map.width, map.height)
+        } else {
+            chunks.iter().fold((i64::MAX, i64::MAX, i64::MIN, i64::MIN), |(min_x, min_y, max_x, max_y), chunk| (
+                min_x.min(chunk.x),
+                min_y.min(chunk.y),
+                max_x.max(chunk.x + chunk.width),
+                max_y.max(chunk.y + chunk.height)
+            ))
+        };
+
+        let tilesets = map.tilesets.iter()
+            .enumerate()
+            .map(|(index, tileset)| Self::convert_tileset(index, tileset))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(Self {
+            map,
+            grid_size: map.tile_width,
+            min_x,
+            min_y,
+            cols: (max_x - min_x).max(0),
+            rows: (max_y - min_y).max(0),
+            tilesets,
+            layer_defs: Vec::new(),
+            layers: Vec::new(),
+            level_fields: Vec::new()
+        })
+    }
+
+    fn collect_chunks<'b>(layers: &'b [TiledLayer], chunks: &mut Vec<&'b TiledChunk>) {
+        for layer in layers {
+            match &layer.content {
+                TiledLayerContent::Tiles(layer_chunks) => chunks.extend(layer_chunks.iter()),
+                TiledLayerContent::Group(group) => Self::collect_chunks(group, chunks),
+                _ => {}
+            }
+        }
+    }
+
+    fn convert(mut self) -> Result<LdtkJson, Error> {
+
+        let state = LayerState {
+            offset_x: 0.0,
+            offset_y: 0.0,
+            opacity: 1.0,
+            visible: true,
+            parallax_x: 1.0,
+            parallax_y: 1.0
+        };
+
+        let map = self.map;
+        self.convert_layers(&map.layers, state);
+
+        for property in &map.properties {
+            self.level_fields.push(Self::field_instance(&property.name, property));
+        }
+
+        // Tiled lists layers bottom up, LDtk top down
+        self.layers.reverse();
+
+        let level = Level {
+            bg_color: String::new(),
+            bg_pos: None,
+            neighbours: Vec::new(),
+            smart_color: String::new(),
+            level_bg_color: None,
+            bg_pivot_x: 0.0,
+            bg_pivot_y: 0.0,
+            level_bg_pos: None,
+            bg_rel_path: None,
+            external_rel_path: None,
+            field_instances: self.level_fields,
+            identifier: LEVEL_IDENTIFIER.to_string(),
+            iid: LEVEL_IDENTIFIER.to_string(),
+            layer_instances: Some(self.layers),
+            px_hei: self.rows * self.grid_size,
+            px_wid: self.cols * self.grid_size,
+            uid: 0,
+            use_auto_identifier: false,
+            world_depth: 0,
+            world_x: self.min_x * self.grid_size,
+            world_y: self.min_y * self.grid_size
+        };
+
+        let mut ldtk = crate::maps::empty_project(self.grid_size);
+        ldtk.defs.layers = self.layer_defs;
+        ldtk.defs.tilesets = self.tilesets;
+        ldtk.levels = vec![level];
+
+        Ok(ldtk)
+    }
+
+    fn convert_tileset(index: usize, tileset: &TiledTileset) -> Result<TilesetDefinition, Error> {
+
+        if tileset.image.is_empty() {
+            return Err(Error::from(format!("Tiled tileset \"{}\" has no image, image collections are not supported", tileset.name)));
+        }
+
+        if tileset.tile_width != tileset.tile_height || tileset.tile_width <= 0 {
+            return Err(Error::from(format!("Tiled tileset \"{}\" needs square tiles", tileset.name)));
+        }
+
+        let columns = if tileset.columns > 0 {
+            tileset.columns
+        } else {
+            tileset_columns(tileset.image_width, tileset.tile_width, tileset.margin, tileset.spacing)
+        };
+        let rows = if tileset.tile_count > 0 && columns > 0 {
+            (tileset.tile_count + columns - 1) / columns
+        } else {
+            tileset_columns(tileset.image_height, tileset.tile_height, tileset.margin, tileset.spacing)
+        };
+
+        let mut custom_data = Vec::new();
+        let mut classes: Vec<(String, Vec<i64>)> = Vec::new();
+
+        for tile in &tileset.tiles {
+
+            if !tile.class.is_empty() {
+                match classes.iter_mut().find(|(class, _)| *class == tile.class) {
+                    Some((_, tile_ids)) => tile_ids.push(tile.id as i64),
+                    None => classes.push((tile.class.clone(), vec![tile.id as i64]))
+                }
+            }
+
+            if tile.animation.is_empty() {
+                continue;
+            }
+
+            // the animator runs at a fixed rate, use the average duration
+            if tile.animation.iter().any(|(_, duration)| *duration != tile.animation[0].1) {
+                warn!("animation of tile {} in tileset \"{}\" has varying frame durations, using the average", tile.id, tileset.name);
+            }
+
+            let duration: u32 = tile.animation.iter().map(|(_, duration)| *duration).sum();
+            let average = (duration as f64 / tile.animation.len() as f64).max(1.0);
+
+            let mut meta = serde_json::Map::new();
+            meta.insert("animation_frames".to_string(), json!(tile.animation.iter().map(|(id, _)| *id).collect::<Vec<_>>()));
+            meta.insert("animation_step".to_string(), json!(1000.0 / average));
+
+            for property in &tile.properties {
+                if ANIMATION_PROPERTIES.contains(&property.name.as_str()) {
+                    meta.insert(property.name.clone(), property.value.clone());
+                }
+            }
+
+            custom_data.push(TileCustomMetadata {
+                data: Value::Object(meta).to_string(),
+                tile_id: tile.id as i64
+            });
+        }
+
+        // tile classes work like LDtk enum tags
+        let enum_tags = classes.into_iter()
+            .map(|(enum_value_id, tile_ids)| EnumTagValue { enum_value_id, tile_ids })
+            .collect();
+
+        Ok(TilesetDefinition {
+            c_hei: rows,
+            c_wid: columns,
+            cached_pixel_data: None,
+            custom_data,
+            embed_atlas: None,
+            enum_tags,
+            identifier: tileset.name.clone(),
+            padding: tileset.margin,
+            px_hei: tileset.image_height,
+            px_wid: tileset.image_width,
+            rel_path: Some(tileset.image.clone()),
+            saved_selections: Vec::new(),
+            spacing: tileset.spacing,
+            tags: Vec::new(),
+            tags_source_enum_uid: None,
+            tile_grid_size: tileset.tile_width,
+            uid: index as i64 + 1
+        })
+    }
+
+    /// Index of the tileset of a global tile id
+    fn find_tileset(&self, gid: u32) -> Option<usize> {
+        self.map.tilesets.iter()
+            .enumerate()
+            .filter(|(_, tileset)| tileset.first_gid <= gid)
+            .max_by_key(|(_, tileset)| tileset.first_gid)
+            .map(|(index, _)| index)
+    }
+
+    fn convert_layers(&mut self, layers: &[TiledLayer], parent: LayerState) {
+        for layer in layers {
+
+            let state = LayerState {
+                offset_x: parent.offset_x + layer.offset_x,
+                offset_y: parent.offset_y + layer.offset_y,
+                opacity: parent.opacity * layer.opacity,
+                visible: parent.visible && layer.visible,
+                parallax_x: parent.parallax_x * layer.parallax_x,
+                parallax_y: parent.parallax_y * layer.parallax_y
+            };
+
+            match &layer.content {
+                TiledLayerContent::Tiles(chunks) => self.convert_tile_layer(layer, chunks, state),
+                TiledLayerContent::Objects(objects) => self.convert_object_layer(layer, objects, state),
+                TiledLayerContent::Group(group) => self.convert_layers(group, state),
+                TiledLayerContent::Other => trace!("skipping Tiled layer \"{}\"", layer.name)
+            }
+        }
+    }
+
+    /// Layers use a single tileset, tiles of further tilesets are moved
+    /// to additional layers named `<layer>_<tileset>`
+    fn convert_tile_layer(&mut self, layer: &TiledLayer, chunks: &[TiledChunk], state: LayerState) {
+
+        let mut tiles_by_tileset: Vec<(usize, Vec<TileInstance>)> = Vec::new();
+
+        for chunk in chunks {
+            for (index, gid) in chunk.data.iter().enumerate() {
+
+                let id = gid & GID_MASK;
+                if id == 0 || chunk.width <= 0 {
+                    continue;
+                }
+
+                let Some(tileset_index) = self.find_tileset(id) else {
+                    warn!("tile {} of Tiled layer \"{}\" has no tileset", id, layer.name);
+                    continue;
+                };
+
+                let tileset = &self.tilesets[tileset_index];
+                let tile_id = (id - self.map.tilesets[tileset_index].first_gid) as i64;
+
+                let cx = chunk.x + index as i64 % chunk.width - self.min_x;
+                let cy = chunk.y + index as i64 / chunk.width - self.min_y;
+
+                let (src_x, src_y) = tile_source(tileset, tile_id);
+
+                let tile = TileInstance {
+                    a: 1.0,
+                    d: vec![cy * self.cols + cx],
+                    f: tile_flags(*gid),
+                    px: vec![cx * self.grid_size, cy * self.grid_size],
+                    src: vec![src_x, src_y],
+                    t: tile_id
+                };
+
+                match tiles_by_tileset.iter_mut().find(|(index, _)| *index == tileset_index) {
+                    Some((_, tiles)) => tiles.push(tile),
+                    None => tiles_by_tileset.push((tileset_index, vec![tile]))
+                }
+            }
+        }
+
+        if tiles_by_tileset.is_empty() {
+            self.push_layer(&layer.name, layer, Type::Tiles, None, Vec::new(), Vec::new(), state);
+            return;
+        }
+
+        for (position, (tileset_index, tiles)) in tiles_by_tileset.into_iter().enumerate() {
+            let identifier = if position == 0 {
+                layer.name.clone()
+            } else {
+                format!("{}_{}", layer.name, self.tilesets[tileset_index].identifier)
+            };
+
+            let tileset_uid = self.tilesets[tileset_index].uid;
+            self.push_layer(&identifier, layer, Type::Tiles, Some(tileset_uid), tiles, Vec::new(), state);
+        }
+    }
+
+    fn convert_object_layer(&mut self, layer: &TiledLayer, objects: &[TiledObject], state: LayerState) {
+
+        let origin_x = (self.min_x * self.grid_size) as f64 - state.offset_x;
+        let origin_y = (self.min_y * self.grid_size) as f64 - state.offset_y;
+
+        let entities = objects.iter().map(|object| {
+
+            let x = (object.x - origin_x).round() as i64;
+            let y = (object.y - origin_y).round() as i64;
+
+            // tile objects are anchored at the bottom left
+            let id = object.gid & GID_MASK;
+            let tile = if id != 0 { self.find_tileset(id) } else { None }.map(|tileset_index| {
+                let tileset = &self.tilesets[tileset_index];
+                let tile_id = (id - self.map.tilesets[tileset_index].first_gid) as i64;
+                let (x, y) = tile_source(tileset, tile_id);
+                TilesetRectangle {
+                    h: tileset.tile_grid_size,
+                    tileset_uid: tileset.uid,
+                    w: tileset.tile_grid_size,
+                    x,
+                    y
+                }
+            });
+
+            let pivot_y = if tile.is_some() { 1.0 } else { 0.0 };
+
+            let mut fields = Vec::new();
+            if !object.name.is_empty() {
+                fields.push(Self::field_instance("name", &TiledProperty {
+                    name: "name".to_string(),
+                    property_type: "string".to_string(),
+                    value: Value::String(object.name.clone())
+                }));
+            }
+
+            for property in &object.properties {
+                fields.push(Self::field_instance(&property.name, property));
+            }
+
+            EntityInstance {
+                grid: vec![x.div_euclid(self.grid_size), y.div_euclid(self.grid_size)],
+                identifier: if object.class.is_empty() { object.name.clone() } else { object.class.clone() },
+                pivot: vec![0.0, pivot_y],
+                smart_color: "#ffffff".to_string(),
+                tags: Vec::new(),
+                tile,
+                world_x: None,
+                world_y: None,
+                def_uid: 0,
+                field_instances: fields,
+                height: object.height.round() as i64,
+                iid: object.id.to_string(),
+                px: vec![x, y],
+                width: object.width.round() as i64
+            }
+        }).collect();
+
+        self.push_layer(&layer.name, layer, Type::Entities, None, Vec::new(), entities, state);
+    }
+
+    fn push_layer(&mut self, identifier: &str, layer: &TiledLayer, layer_type: Type, tileset_uid: Option<i64>, tiles: Vec<TileInstance>, entities: Vec<EntityInstance>, state: LayerState) {
+
+        let uid = self.layer_defs.len() as i64 + 1;
+        let type_name = match layer_type {
+            Type::Entities => "Entities",
+            _ => "Tiles"
+        };
+
+        // entity positions already include the offsets
+        let (offset_x, offset_y) = match layer_type {
+            Type::Entities => (0, 0),
+            _ => (state.offset_x.round() as i64, state.offset_y.round() as i64)
+        };
+
+        self.layer_defs.push(LayerDefinition {
+            layer_definition_type: type_name.to_string(),
+            auto_rule_groups: Vec::new(),
+            auto_source_layer_def_uid: None,
+            auto_tileset_def_uid: None,
+            auto_tiles_killed_by_other_layer_uid: None,
+            biome_field_uid: None,
+            can_select_when_inactive: true,
+            display_opacity: 1.0,
+            doc: None,
+            excluded_tags: Vec::new(),
+            grid_size: self.grid_size,
+            guide_grid_hei: 0,
+            guide_grid_wid: 0,
+            hide_fields_when_inactive: false,
+            hide_in_list: false,
+            identifier: identifier.to_string(),
+            inactive_opacity: 1.0,
+            int_grid_values: Vec::new(),
+            int_grid_values_groups: Vec::new(),
+            // LDtk factors slow layers down, Tiled factors scale the scrolling
+            parallax_factor_x: 1.0 - state.parallax_x,
+            parallax_factor_y: 1.0 - state.parallax_y,
+            parallax_scaling: false,
+            px_offset_x: 0,
+            px_offset_y: 0,
+            render_in_world_view: true,
+            required_tags: Vec::new(),
+            tile_pivot_x: 0.0,
+            tile_pivot_y: 0.0,
+            tileset_def_uid: tileset_uid,
+            purple_type: layer_type,
+            ui_color: None,
+            uid,
+            ui_filter_tags: Vec::new(),
+            use_async_render: false
+        });
+
+        self.layers.push(LayerInstance {
+            c_hei: self.rows,
+            c_wid: self.cols,
+            grid_size: self.grid_size,
+            identifier: identifier.to_string(),
+            opacity: state.opacity,
+            px_total_offset_x: offset_x,
+            px_total_offset_y: offset_y,
+            tileset_def_uid: tileset_uid,
+            tileset_rel_path: None,
+            layer_instance_type: type_name.to_string(),
+            auto_layer_tiles: Vec::new(),
+            entity_instances: entities,
+            grid_tiles: tiles,
+            iid: format!("{LEVEL_IDENTIFIER}_{identifier}"),
+            int_grid: None,
+            int_grid_csv: Vec::new(),
+            layer_def_uid: uid,
+            level_id: 0,
+            optional_rules: Vec::new(),
+            override_tileset_uid: None,
+            px_offset_x: offset_x,
+            px_offset_y: offset_y,
+            seed: 0,
+            visible: state.visible
+        });
+
+        // tint and custom properties as level fields `<layer>_<name>`
+        if let Some(tint) = convert_color(&layer.tint) {
+            self.level_fields.push(FieldInstance {
+                identifier: format!("{identifier}_tint"),
+                tile: None,
+                field_instance_type: "Color".to_string(),
+                value: Some(Value::String(tint)),
+                def_uid: 0,
+                real_editor_values: Vec::new()
+            });
+        }
+
+        for property in &layer.properties {
+            self.level_fields.push(Self::field_instance(&format!("{identifier}_{}", property.name), property));
+        }
+    }
+
+    /// Custom property as LDtk field, object references become
+    /// entity references
+    fn field_instance(identifier: &str, property: &TiledProperty) -> FieldInstance {
+
+        let (field_type, value) = match property.property_type.as_str() {
+            "int" => ("Int", property.value.clone()),
+            "float" => ("Float", property.value.clone()),
+            "bool" => ("Bool", property.value.clone()),
+            "file" => ("FilePath", property.value.clone()),
+            "color" => ("Color", property.value.as_str().and_then(convert_color).map(Value::String).unwrap_or(Value::Null)),
+            "object" => ("EntityRef", match property.value.as_i64() {
+                Some(id) if id != 0 => json!({
+                    "entityIid": id.to_string(),
+                    "layerIid": "",
+                    "levelIid": LEVEL_IDENTIFIER,
+                    "worldIid": ""
+                }),
+                _ => Value::Null
+            }),
+            "class" => ("String", Value::Null),
+            _ => ("String", property.value.clone())
+        };
+
+        FieldInstance {
+            identifier: identifier.to_string(),
+            tile: None,
+            field_instance_type: field_type.to_string(),
+            value: Some(value),
+            def_uid: 0,
+            real_editor_values: Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TMX: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="4" height="2" tilewidth="8" tileheight="8" infinite="0">
+ <properties>
+  <property name="music" value="cave"/>
+ </properties>
+ <tileset firstgid="1" name="tiles" tilewidth="8" tileheight="8" tilecount="16" columns="4">
+  <image source="tiles.png" width="32" height="32"/>
+  <tile id="2" type="Water">
+   <animation>
+    <frame tileid="2" duration="100"/>
+    <frame tileid="3" duration="100"/>
+   </animation>
+  </tile>
+ </tileset>
+ <layer id="1" name="Ground" width="4" height="2" opacity="0.5">
+  <data encoding="csv">1,0,3,2147483650,0,0,0,0</data>
+ </layer>
+ <objectgroup id="2" name="Entities">
+  <object id="7" name="start" type="Spawn" x="12" y="4" width="8" height="8">
+   <properties>
+    <property name="health" type="int" value="3"/>
+    <property name="tint" type="color" value="#80ff0000"/>
+   </properties>
+  </object>
+ </objectgroup>
+</map>"##;
+
+    #[test]
+    fn test_tile_flags() {
+        assert_eq!(tile_flags(1), 0);
+        assert_eq!(tile_flags(1 | FLIPPED_HORIZONTALLY) as u32, Tile::FLIP_X);
+        assert_eq!(tile_flags(1 | FLIPPED_VERTICALLY) as u32, Tile::FLIP_Y);
+
+        // horizontal + diagonal flip is a clockwise quarter turn
+        assert_eq!(tile_flags(1 | FLIPPED_HORIZONTALLY | FLIPPED_DIAGONALLY) as u32, 1 << Tile::ROTATION_SHIFT);
+    }
+
+    #[test]
+    fn test_decode_tile_data() {
+        assert_eq!(decode_tile_data("1, 2,\n3", "csv", "").unwrap(), vec![1, 2, 3]);
+
+        // 1, 2 as little endian u32
+        assert_eq!(decode_tile_data("AQAAAAIAAAA=", "base64", "").unwrap(), vec![1, 2]);
+        assert!(decode_tile_data("AQAAAAIAAAA=", "base64", "zstd").is_err());
+        assert_eq!(convert_color("#80ff0000").as_deref(), Some("#ff000080"));
+    }
+
+    #[test]
+    fn test_tileset_spacing() {
+        let tmx = TMX.replace(r#"tilecount="16" columns="4""#, r#"tilecount="16" columns="4" margin="2" spacing="1""#);
+        let ldtk = to_ldtk(tmx.as_bytes(), |path| Err(Error::from(path))).unwrap();
+
+        let tileset = &ldtk.defs.tilesets[0];
+        assert_eq!((tileset.padding, tileset.spacing), (2, 1));
+
+        // tile 1 is the second column, after the margin and one spacing
+        let tiles = &ldtk.levels[0].layer_instances.as_ref().unwrap()[1].grid_tiles;
+        assert_eq!((tiles[2].t, tiles[2].src[0], tiles[2].src[1]), (1, 11, 2));
+    }
+
+    #[test]
+    fn test_convert_tmx() {
+        let ldtk = to_ldtk(TMX.as_bytes(), |path| Err(Error::from(path))).unwrap();
+
+        let tileset = &ldtk.defs.tilesets[0];
+        assert_eq!((tileset.c_wid, tileset.c_hei, tileset.tile_grid_size), (4, 4, 8));
+        assert_eq!(tileset.custom_data[0].tile_id, 2);
+        assert_eq!(tileset.enum_tags[0].enum_value_id, "Water");
+
+        let level = &ldtk.levels[0];
+        assert_eq!((level.px_wid, level.px_hei), (32, 16));
+        assert_eq!(level.field_instances[0].identifier, "music");
+
+        // LDtk order, topmost layer first
+        let layers = level.layer_instances.as_ref().unwrap();
+        assert_eq!(layers[0].identifier, "Entities");
+        assert_eq!(layers[1].identifier, "Ground");
+        assert_eq!(layers[1].opacity, 0.5);
+
+        let tiles = &layers[1].grid_tiles;
+        assert_eq!(tiles.len(), 3);
+        assert_eq!((tiles[2].t, tiles[2].px[0], tiles[2].f as u32), (1, 24, Tile::FLIP_X));
+
+        let entity = &layers[0].entity_instances[0];
+        assert_eq!((entity.identifier.as_str(), entity.px[0], entity.px[1]), ("Spawn", 12, 4));
+        assert_eq!(entity.field_instances.len(), 3);
+    }
+}
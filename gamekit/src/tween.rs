@@ -0,0 +1,637 @@
+//!
+//! Tween
+//!
+
+use std::f32::consts::PI;
+
+use crate::{math::{Vec2, Vec3, Vec4}, primitives::Color, task::TaskTime};
+
+#[derive(PartialEq, Default, Debug, Clone, Copy)]
+pub enum Easing {
+    #[default]
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    BackIn,
+    BackOut,
+    BackInOut,
+    ElasticIn,
+    ElasticOut,
+    ElasticInOut,
+    BounceIn,
+    BounceOut,
+    BounceInOut
+}
+
+impl Easing {
+    pub fn from_string(easing: &str) -> Easing {
+        match easing {
+            "QuadIn" => Easing::QuadIn,
+            "QuadOut" => Easing::QuadOut,
+            "QuadInOut" => Easing::QuadInOut,
+            "CubicIn" => Easing::CubicIn,
+            "CubicOut" => Easing::CubicOut,
+            "CubicInOut" => Easing::CubicInOut,
+            "BackIn" => Easing::BackIn,
+            "BackOut" => Easing::BackOut,
+            "BackInOut" => Easing::BackInOut,
+            "ElasticIn" => Easing::ElasticIn,
+            "ElasticOut" => Easing::ElasticOut,
+            "ElasticInOut" => Easing::ElasticInOut,
+            "BounceIn" => Easing::BounceIn,
+            "BounceOut" => Easing::BounceOut,
+            "BounceInOut" => Easing::BounceInOut,
+            _ => Easing::Linear
+        }
+    }
+
+    /// Map linear progress (0..1) to eased progress
+    pub fn apply(&self, t: f32) -> f32 {
+
+        const C1: f32 = 1.70158;
+        const C2: f32 = C1 * 1.525;
+        const C3: f32 = C1 + 1.0;
+        const C4: f32 = (2.0 * PI) / 3.0;
+        const C5: f32 = (2.0 * PI) / 4.5;
+
+        let t = t.clamp(0.0, 1.0);
+
+        match self {
+            Easing::Linear => t,
+            Easing::QuadIn => t * t,
+            Easing::QuadOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::QuadInOut => {
+                if t < 0.5 { 2.0 * t * t } else { 1.0 - (-2.0 * t + 2.0).powi(2) / 2.0 }
+            },
+            Easing::CubicIn => t * t * t,
+            Easing::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Easing::CubicInOut => {
+                if t < 0.5 { 4.0 * t * t * t } else { 1.0 - (-2.0 * t + 2.0).powi(3) / 2.0 }
+            },
+            Easing::BackIn => C3 * t * t * t - C1 * t * t,
+            Easing::BackOut => 1.0 + C3 * (t - 1.0).powi(3) + C1 * (t - 1.0).powi(2),
+            Easing::BackInOut => {
+                if t < 0.5 {
+                    ((2.0 * t).powi(2) * ((C2 + 1.0) * 2.0 * t - C2)) / 2.0
+                } else {
+                    ((2.0 * t - 2.0).powi(2) * ((C2 + 1.0) * (t * 2.0 - 2.0) + C2) + 2.0) / 2.0
+                }
+            },
+            Easing::ElasticIn => {
+                if t <= 0.0 || t >= 1.0 { t } else { -(2.0f32).powf(10.0 * t - 10.0) * ((10.0 * t - 10.75) * C4).sin() }
+            },
+            Easing::ElasticOut => {
+                if t <= 0.0 || t >= 1.0 { t } else { (2.0f32).powf(-10.0 * t) * ((10.0 * t - 0.75) * C4).sin() + 1.0 }
+            },
+            Easing::ElasticInOut => {
+                if t <= 0.0 || t >= 1.0 {
+                    t
+                } else if t < 0.5 {
+                    -((2.0f32).powf(20.0 * t - 10.0) * ((20.0 * t - 11.125) * C5).sin()) / 2.0
+                } else {
+                    ((2.0f32).powf(-20.0 * t + 10.0) * ((20.0 * t - 11.125) * C5).sin()) / 2.0 + 1.0
+                }
+            },
+            Easing::BounceIn => 1.0 - Self::bounce_out(1.0 - t),
+            Easing::BounceOut => Self::bounce_out(t),
+            Easing::BounceInOut => {
+                if t < 0.5 {
+                    (1.0 - Self::bounce_out(1.0 - 2.0 * t)) / 2.0
+                } else {
+                    (1.0 + Self::bounce_out(2.0 * t - 1.0)) / 2.0
+                }
+            }
+        }
+    }
+
+    fn bounce_out(t: f32) -> f32 {
+        const N1: f32 = 7.5625;
+        const D1: f32 = 2.75;
+
+        if t < 1.0 / D1 {
+            N1 * t * t
+        } else if t < 2.0 / D1 {
+            let t = t - 1.5 / D1;
+            N1 * t * t + 0.75
+        } else if t < 2.5 / D1 {
+            let t = t - 2.25 / D1;
+            N1 * t * t + 0.9375
+        } else {
+            let t = t - 2.625 / D1;
+            N1 * t * t + 0.984375
+        }
+    }
+}
+
+/// Value that can be interpolated by a tween
+pub trait Tweenable: Clone + Send + 'static {
+    fn lerp(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Tweenable for f32 {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Tweenable for Vec2 {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Tweenable for Vec3 {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Tweenable for Vec4 {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Tweenable for Color {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Color::rgba(
+            self.r.lerp(&other.r, t),
+            self.g.lerp(&other.g, t),
+            self.b.lerp(&other.b, t),
+            self.a.lerp(&other.a, t)
+        )
+    }
+}
+
+/// Anything that advances over time: tweens, delays, sequences and
+/// parallel groups.
+pub trait Tweening: Send {
+    /// Advance by `step` seconds, returns the time left over after finishing
+    fn advance(&mut self, step: f32) -> f32;
+
+    fn is_finished(&self) -> bool;
+
+    /// Rewind to the start
+    fn reset(&mut self);
+
+    /// Advance by the task time step
+    fn step(&mut self, time: &TaskTime) {
+        self.advance(time.step);
+    }
+}
+
+type TweenUpdateCallback<T> = Box<dyn FnMut(&T) + Send>;
+type TweenCompleteCallback = Box<dyn FnMut() + Send>;
+
+/// Repeat count for infinite repetition
+pub const REPEAT_INFINITE: i32 = -1;
+
+/// Interpolates a value between two end points with easing.
+///
+/// A repeat count of `n` plays the tween `n + 1` times, `REPEAT_INFINITE`
+/// repeats forever. With yoyo enabled every other repetition plays
+/// backwards.
+pub struct Tween<T: Tweenable> {
+    from: T,
+    to: T,
+    value: T,
+    duration: f32,
+    delay: f32,
+    easing: Easing,
+    repeat: i32,
+    yoyo: bool,
+    elapsed: f32,
+    finished: bool,
+    on_update: Option<TweenUpdateCallback<T>>,
+    on_complete: Option<TweenCompleteCallback>
+}
+
+impl<T: Tweenable> Tween<T> {
+    pub fn new(from: T, to: T, duration: f32, easing: Easing) -> Self {
+        Self {
+            value: from.clone(),
+            from,
+            to,
+            duration,
+            delay: 0.0,
+            easing,
+            repeat: 0,
+            yoyo: false,
+            elapsed: 0.0,
+            finished: false,
+            on_update: None,
+            on_complete: None
+        }
+    }
+
+    pub fn set_delay(&mut self, delay: f32) -> &mut Self {
+        self.delay = delay.max(0.0);
+        self
+    }
+
+    pub fn set_repeat(&mut self, repeat: i32) -> &mut Self {
+        self.repeat = repeat;
+        self
+    }
+
+    pub fn set_yoyo(&mut self, yoyo: bool) -> &mut Self {
+        self.yoyo = yoyo;
+        self
+    }
+
+    pub fn set_easing(&mut self, easing: Easing) -> &mut Self {
+        self.easing = easing;
+        self
+    }
+
+    /// Set callback invoked with the new value on every update
+    pub fn on_update<F: FnMut(&T) + Send + 'static>(&mut self, callback: F) -> &mut Self {
+        self.on_update = Some(Box::new(callback));
+        self
+    }
+
+    /// Set callback invoked once the tween has finished
+    pub fn on_complete<F: FnMut() + Send + 'static>(&mut self, callback: F) -> &mut Self {
+        self.on_complete = Some(Box::new(callback));
+        self
+    }
+
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    pub fn from(&self) -> &T {
+        &self.from
+    }
+
+    pub fn to(&self) -> &T {
+        &self.to
+    }
+
+    /// Total duration in seconds including delay and repetitions
+    pub fn total_duration(&self) -> f32 {
+        if self.repeat < 0 {
+            f32::INFINITY
+        } else {
+            self.delay + self.duration * ((self.repeat + 1) as f32)
+        }
+    }
+
+    pub fn update(&mut self, step: f32) -> &T {
+        self.advance(step);
+        &self.value
+    }
+}
+
+impl<T: Tweenable> Tweening for Tween<T> {
+    fn advance(&mut self, step: f32) -> f32 {
+
+        if self.finished {
+            return step;
+        }
+
+        self.elapsed += step;
+
+        let t = self.elapsed - self.delay;
+        if t < 0.0 {
+            return 0.0;
+        }
+
+        let mut left_over = 0.0;
+
+        let (cycle, progress) = if self.duration <= 0.0 || t >= self.total_duration() - self.delay {
+            self.finished = true;
+            if self.duration > 0.0 {
+                left_over = t - (self.total_duration() - self.delay);
+            }
+            (self.repeat.max(0), 1.0)
+        } else {
+            let cycle = (t / self.duration).floor();
+            (cycle as i32, (t - cycle * self.duration) / self.duration)
+        };
+
+        let progress = if self.yoyo && cycle % 2 == 1 { 1.0 - progress } else { progress };
+        self.value = self.from.lerp(&self.to, self.easing.apply(progress));
+
+        if let Some(callback) = &mut self.on_update {
+            callback(&self.value);
+        }
+
+        if self.finished && let Some(callback) = &mut self.on_complete {
+            callback();
+        }
+
+        left_over
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn reset(&mut self) {
+        self.elapsed = 0.0;
+        self.finished = false;
+        self.value = self.from.clone();
+    }
+}
+
+/// Waits for a given time, used in sequences
+pub struct TweenDelay {
+    duration: f32,
+    elapsed: f32
+}
+
+impl TweenDelay {
+    pub fn new(duration: f32) -> Self {
+        Self {
+            duration: duration.max(0.0),
+            elapsed: 0.0
+        }
+    }
+}
+
+impl Tweening for TweenDelay {
+    fn advance(&mut self, step: f32) -> f32 {
+        let remaining = (self.duration - self.elapsed).max(0.0);
+        self.elapsed += step.min(remaining);
+        (step - remaining).max(0.0)
+    }
+
+    fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    fn reset(&mut self) {
+        self.elapsed = 0.0;
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum TweenGroupMode {
+    Sequence,
+    Parallel
+}
+
+/// Plays tweens one after another or all at once
+pub struct TweenGroup {
+    mode: TweenGroupMode,
+    items: Vec<Box<dyn Tweening>>,
+    current: usize,
+    repeat: i32,
+    cycle: i32,
+    finished: bool,
+    on_complete: Option<TweenCompleteCallback>
+}
+
+impl TweenGroup {
+    pub fn new(mode: TweenGroupMode) -> Self {
+        Self {
+            mode,
+            items: Vec::new(),
+            current: 0,
+            repeat: 0,
+            cycle: 0,
+            finished: false,
+            on_complete: None
+        }
+    }
+
+    pub fn sequence() -> Self {
+        Self::new(TweenGroupMode::Sequence)
+    }
+
+    pub fn parallel() -> Self {
+        Self::new(TweenGroupMode::Parallel)
+    }
+
+    pub fn add<T: Tweening + 'static>(&mut self, item: T) -> &mut Self {
+        self.items.push(Box::new(item));
+        self
+    }
+
+    pub fn delay(&mut self, duration: f32) -> &mut Self {
+        self.add(TweenDelay::new(duration))
+    }
+
+    pub fn set_repeat(&mut self, repeat: i32) -> &mut Self {
+        self.repeat = repeat;
+        self
+    }
+
+    pub fn on_complete<F: FnMut() + Send + 'static>(&mut self, callback: F) -> &mut Self {
+        self.on_complete = Some(Box::new(callback));
+        self
+    }
+
+    pub fn mode(&self) -> TweenGroupMode {
+        self.mode
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    fn advance_cycle(&mut self, step: f32) -> f32 {
+        match self.mode {
+            TweenGroupMode::Sequence => {
+                let mut remaining = step;
+                while self.current < self.items.len() {
+                    remaining = self.items[self.current].advance(remaining);
+                    if !self.items[self.current].is_finished() {
+                        return 0.0;
+                    }
+                    self.current += 1;
+                }
+                remaining
+            },
+            TweenGroupMode::Parallel => {
+                let mut remaining = step;
+                let mut all_finished = true;
+                for item in self.items.iter_mut() {
+                    if !item.is_finished() {
+                        remaining = remaining.min(item.advance(step));
+                        all_finished &= item.is_finished();
+                    }
+                }
+                if all_finished { remaining } else { 0.0 }
+            }
+        }
+    }
+}
+
+impl Tweening for TweenGroup {
+    fn advance(&mut self, step: f32) -> f32 {
+
+        if self.finished {
+            return step;
+        }
+
+        let mut remaining = step;
+
+        loop {
+            let cycle_start = remaining;
+            remaining = self.advance_cycle(remaining);
+
+            let cycle_finished = match self.mode {
+                TweenGroupMode::Sequence => self.current >= self.items.len(),
+                TweenGroupMode::Parallel => self.items.iter().all(|item| item.is_finished())
+            };
+
+            if !cycle_finished {
+                return 0.0;
+            }
+
+            if self.repeat >= 0 && self.cycle >= self.repeat {
+                break;
+            }
+
+            self.cycle += 1;
+            self.current = 0;
+            for item in self.items.iter_mut() {
+                item.reset();
+            }
+
+            // zero length cycles would loop forever
+            if remaining <= 0.0 || remaining >= cycle_start {
+                return 0.0;
+            }
+        }
+
+        self.finished = true;
+
+        if let Some(callback) = &mut self.on_complete {
+            callback();
+        }
+
+        remaining
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn reset(&mut self) {
+        self.current = 0;
+        self.cycle = 0;
+        self.finished = false;
+        for item in self.items.iter_mut() {
+            item.reset();
+        }
+    }
+}
+
+/// Runs independent tweens and drops them once finished
+#[derive(Default)]
+pub struct Tweens {
+    items: Vec<Box<dyn Tweening>>
+}
+
+impl Tweens {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add<T: Tweening + 'static>(&mut self, item: T) -> &mut Self {
+        self.items.push(Box::new(item));
+        self
+    }
+
+    pub fn update(&mut self, step: f32) {
+        for item in self.items.iter_mut() {
+            item.advance(step);
+        }
+        self.items.retain(|item| !item.is_finished());
+    }
+
+    /// Advance all tweens by the task time step
+    pub fn step(&mut self, time: &TaskTime) {
+        self.update(time.step);
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    #[test]
+    fn test_easing() {
+        let easings = [
+            Easing::Linear, Easing::QuadIn, Easing::QuadOut, Easing::QuadInOut,
+            Easing::CubicIn, Easing::CubicOut, Easing::CubicInOut,
+            Easing::BackIn, Easing::BackOut, Easing::BackInOut,
+            Easing::ElasticIn, Easing::ElasticOut, Easing::ElasticInOut,
+            Easing::BounceIn, Easing::BounceOut, Easing::BounceInOut
+        ];
+
+        for easing in easings {
+            assert!(easing.apply(0.0).abs() < 0.001, "{:?}", easing);
+            assert!((easing.apply(1.0) - 1.0).abs() < 0.001, "{:?}", easing);
+        }
+
+        assert!(Easing::BackIn.apply(0.2) < 0.0);
+        assert!(Easing::QuadIn.apply(0.5) < 0.5);
+    }
+
+    #[test]
+    fn test_tween() {
+        let mut tween = Tween::new(0.0f32, 10.0, 1.0, Easing::Linear);
+        tween.set_delay(0.5).set_repeat(1).set_yoyo(true);
+
+        assert_eq!(*tween.update(0.5), 0.0);
+        assert_eq!(*tween.update(0.5), 5.0);
+        assert_eq!(*tween.update(0.75), 7.5);
+        assert!(!tween.is_finished());
+        assert_eq!(tween.advance(1.0), 0.25);
+        assert!(tween.is_finished());
+        assert_eq!(*tween.value(), 0.0);
+    }
+
+    #[test]
+    fn test_sequence() {
+        let completed = Arc::new(Mutex::new(0));
+        let counter = completed.clone();
+
+        let mut group = TweenGroup::sequence();
+        group
+            .add(Tween::new(Vec2::new(0.0, 0.0), Vec2::new(2.0, 4.0), 1.0, Easing::Linear))
+            .delay(0.5)
+            .add(Tween::new(0.0f32, 1.0, 1.0, Easing::QuadOut))
+            .set_repeat(1)
+            .on_complete(move || { *counter.lock().unwrap() += 1; });
+
+        assert_eq!(group.advance(2.0), 0.0);
+        assert!(!group.is_finished());
+        assert_eq!(group.advance(2.0), 0.0);
+        assert!(!group.is_finished());
+        assert_eq!(group.advance(1.5), 0.5);
+        assert!(group.is_finished());
+        assert_eq!(*completed.lock().unwrap(), 1);
+
+        let mut tweens = Tweens::new();
+        tweens.add(TweenGroup::parallel());
+        tweens.update(0.1);
+        assert!(tweens.is_empty());
+    }
+}